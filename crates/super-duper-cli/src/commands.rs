@@ -1,4 +1,5 @@
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Debug, Parser)]
 #[command(name = "super-duper")]
@@ -6,18 +7,60 @@ use clap::{Parser, Subcommand};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
+
+    /// Read this configuration file instead of Config.toml in the working directory
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Scan this directory instead of the configured root_paths (repeatable)
+    #[arg(long = "root", global = true, value_name = "PATH")]
+    pub roots: Vec<String>,
+
+    /// Use this glob instead of the configured ignore_patterns (repeatable)
+    #[arg(long = "ignore", global = true, value_name = "GLOB")]
+    pub ignores: Vec<String>,
 }
 
 #[derive(Debug, Subcommand)]
 pub enum Commands {
     /// Perform full duplicate detection process on configured paths
-    Process,
+    Process {
+        /// Byte-compare duplicate candidates before recording them (overrides config)
+        #[arg(long)]
+        verify: bool,
+        /// Rescan everything instead of updating the previous scan of the same roots
+        #[arg(long)]
+        full: bool,
+        /// Resume a cancelled or aborted scan session, reusing the work it checkpointed
+        #[arg(long, value_name = "SESSION")]
+        resume: Option<i64>,
+    },
     /// Build directory fingerprints and compute similarity
     AnalyzeDirectories,
     /// Display the number of keys in the hash cache
     CountHashCache,
-    /// Print configuration values
+    /// Display hash cache size and the hit/miss ratio of the last scan
+    HashCacheStats,
+    /// Remove hash cache entries for missing or changed files, plus any over the limits
+    EvictHashCache {
+        /// Also remove entries stored more than this many days ago
+        #[arg(long)]
+        max_age_days: Option<u64>,
+        /// Remove the oldest entries until the cache holds at most this many megabytes
+        #[arg(long)]
+        max_size_mb: Option<u64>,
+        /// Compact the cache afterwards to reclaim disk space
+        #[arg(long)]
+        compact: bool,
+    },
+    /// Compact the hash cache to reclaim disk space
+    CompactHashCache,
+    /// Print the effective configuration and the layer each value came from
     PrintConfig,
+    /// Check the configuration for invalid globs, missing roots and other problems
+    ValidateConfig,
+    /// Replace the stored protected paths with the configured ones (scans only add to them)
+    ResetProtectedPaths,
     /// Truncate all database tables
     TruncateDb,
 }
@@ -1,36 +1,50 @@
 mod commands;
 mod logging;
 mod progress;
+mod signals;
 
 use std::io::{self, Write};
 use std::process;
+use std::time::Duration;
 
 use clap::{CommandFactory, Parser};
 use colored::*;
 use commands::{Cli, Commands};
 use dotenv::dotenv;
 use progress::CliReporter;
+use signals::PauseSignals;
+use super_duper_core::config::{ConfigOverrides, LoadedConfig, Severity};
+use super_duper_core::hasher::EvictionPolicy;
 use super_duper_core::ScanEngine;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv().ok();
 
     let _guard = logging::init_logger();
 
-    let config = match super_duper_core::config::load_configuration() {
-        Ok(config) => config,
+    let args = Cli::parse();
+
+    let overrides = ConfigOverrides {
+        config_file: args.config.clone(),
+        root_paths: args.roots.clone(),
+        ignore_patterns: args.ignores.clone(),
+    };
+    let loaded = match super_duper_core::config::load_layered_configuration(&overrides) {
+        Ok(loaded) => loaded,
         Err(err) => {
             error!("Error loading configuration: {}", err);
             process::exit(1);
         }
     };
-
-    let args = Cli::parse();
+    let config = loaded.config.clone();
 
     match args.command {
-        Some(Commands::Process) => {
-            if let Err(err) = run_process(&config) {
+        Some(Commands::Process { verify, full, resume }) => {
+            let mut config = config.clone();
+            config.verify_duplicates |= verify;
+            config.full_rescan |= full;
+            if let Err(err) = run_process(&config, resume) {
                 error!("Error: {}", err);
             }
         }
@@ -41,10 +55,50 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
         Some(Commands::CountHashCache) => {
             info!("Counting content cache hash...");
-            super_duper_core::hasher::cache::print_count();
+            match ScanEngine::new(config.clone()).hash_cache() {
+                Ok(cache) => super_duper_core::hasher::cache::print_count(cache.as_ref()),
+                Err(e) => error!("Error opening hash cache: {}", e),
+            }
         }
-        Some(Commands::PrintConfig) => {
-            println!("Configuration: {:?}", config);
+        Some(Commands::HashCacheStats) => {
+            if let Err(err) = run_hash_cache_stats(&config) {
+                error!("Error: {}", err);
+            }
+        }
+        Some(Commands::EvictHashCache {
+            max_age_days,
+            max_size_mb,
+            compact,
+        }) => {
+            let policy = EvictionPolicy {
+                max_age: max_age_days.map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+                max_size_bytes: max_size_mb.map(|mb| mb * 1024 * 1024),
+            };
+            if let Err(err) = run_evict_hash_cache(&config, &policy, compact) {
+                error!("Error: {}", err);
+            }
+        }
+        Some(Commands::CompactHashCache) => {
+            info!("Compacting hash cache...");
+            if let Err(err) = ScanEngine::new(config.clone()).compact_hash_cache() {
+                error!("Error: {}", err);
+            }
+        }
+        Some(Commands::PrintConfig) => print_config(&loaded),
+        Some(Commands::ValidateConfig) => {
+            if !print_config_issues(&config) {
+                process::exit(1);
+            }
+        }
+        Some(Commands::ResetProtectedPaths) => {
+            let protected = config.all_protected_paths();
+            match super_duper_core::storage::Database::open("super_duper.db") {
+                Ok(db) => match db.replace_protected_paths(&protected) {
+                    Ok(()) => println!("Protected paths: {}", protected.join(", ")),
+                    Err(e) => error!("Error storing protected paths: {}", e),
+                },
+                Err(e) => error!("Error opening database: {}", e),
+            }
         }
         Some(Commands::TruncateDb) => {
             match prompt_confirm(
@@ -78,10 +132,37 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 fn run_process(
     config: &super_duper_core::AppConfig,
+    resume: Option<i64>,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    if !print_config_issues(config) {
+        return Err("configuration is invalid; fix the errors above or run validate-config".into());
+    }
     let engine = ScanEngine::new(config.clone());
     let reporter = CliReporter::new();
-    let result = engine.scan(&reporter)?;
+    let _pause_signals = PauseSignals::watch(engine.pause_token())?;
+    if cfg!(unix) {
+        info!(
+            "Pause the scan with {} and resume it with {}",
+            format!("kill -USR1 {}", process::id()).cyan(),
+            format!("kill -USR2 {}", process::id()).cyan(),
+        );
+    }
+    let result = match resume {
+        Some(session_id) => engine.resume_scan(session_id, &reporter)?,
+        None => {
+            engine.abort_orphaned_sessions()?;
+            for session in engine.resumable_sessions()? {
+                info!(
+                    "Session {} ({}, started {}) can be resumed with {}",
+                    session.id,
+                    session.status.yellow(),
+                    session.started_at,
+                    format!("process --resume {}", session.id).cyan(),
+                );
+            }
+            engine.scan(&reporter)?
+        }
+    };
 
     println!();
     info!(
@@ -91,21 +172,194 @@ fn run_process(
         format!("{:.2}s", result.db_write_duration.as_secs_f64()).green(),
         format!("{:.2}s", result.dir_analysis_duration.as_secs_f64()).green(),
     );
+    if result.incremental {
+        info!(
+            "Incremental rescan: {} unchanged files kept, {} directory listings reused \
+             (use --full to rescan everything)",
+            format!("{}", result.unchanged_files).green(),
+            format!("{}", result.reused_listings).green(),
+        );
+    }
+    if result.resumed_files > 0 {
+        info!(
+            "Resumed: {} files taken from the checkpoint without rehashing",
+            format!("{}", result.resumed_files).green(),
+        );
+    }
+    let changes = &result.index_changes;
+    info!(
+        "Index: {} added, {} changed, {} removed",
+        format!("{}", changes.added).green(),
+        format!("{}", changes.changed).yellow(),
+        format!("{}", changes.removed).red(),
+    );
+    let hash_stats = &result.hash_stats;
+    for (tier, stats) in [
+        ("Partial", &hash_stats.partial),
+        ("Sample", &hash_stats.sample),
+        ("Full", &hash_stats.full),
+    ] {
+        info!(
+            "{} tier: {} files hashed, {} eliminated, {} bytes read",
+            tier,
+            stats.files_hashed,
+            stats.files_eliminated,
+            format!("{}", stats.bytes_read).cyan(),
+        );
+    }
+    info!(
+        "Hash cache: {} hits, {} misses, {} partial hashes reused ({} bytes read in total)",
+        format!("{}", hash_stats.cache.hits).green(),
+        format!("{}", hash_stats.cache.misses).yellow(),
+        hash_stats.partial_hashes_reused,
+        hash_stats.bytes_read(),
+    );
+    if result.verify_mismatches > 0 {
+        info!(
+            "{} files shared a hash but differed byte-for-byte and were not grouped",
+            format!("{}", result.verify_mismatches).yellow(),
+        );
+    }
     info!(
         "{} duplicate groups, {} files with duplicates, {} bytes wasted",
         format!("{}", result.duplicate_groups).red(),
         format!("{}", result.duplicate_files).red(),
         format!("{}", result.wasted_bytes).red(),
     );
+    if result.hard_links > 0 {
+        info!(
+            "{} extra hard-link names share data with scanned files ({} bytes already deduplicated)",
+            format!("{}", result.hard_links).green(),
+            format!("{}", result.hard_link_bytes).green(),
+        );
+    }
+    if result.symlinks > 0 {
+        info!("{} symlinks recorded in the file_link table", result.symlinks);
+    }
+    for mount in &result.skipped_mounts {
+        info!("Not scanned (other filesystem): {}", mount.display().to_string().yellow());
+    }
     info!(
         "{} directory fingerprints, {} similar directory pairs",
         format!("{}", result.dir_fingerprints).cyan(),
         format!("{}", result.dir_similarity_pairs).cyan(),
     );
+    if result.scan_errors > 0 {
+        print_scan_errors(&engine, result.session_id, result.scan_errors)?;
+    }
 
     Ok(())
 }
 
+/// List the configuration layers found, then each value set and the layer it came from.
+fn print_config(loaded: &LoadedConfig) {
+    println!("Layers, lowest precedence first:");
+    if loaded.layers.is_empty() {
+        println!("  (none; all defaults)");
+    }
+    for layer in &loaded.layers {
+        println!("  {}", layer);
+    }
+    println!();
+    let width = loaded.values.iter().map(|value| value.key.len()).max().unwrap_or(0);
+    for value in &loaded.values {
+        println!(
+            "{:<width$} = {}  {}",
+            value.key.cyan(),
+            value.value,
+            format!("# {}", value.layer).dimmed(),
+            width = width,
+        );
+    }
+    println!("Other settings have their defaults.");
+}
+
+/// Log each problem `validate` finds in the configuration. False if any is an error.
+fn print_config_issues(config: &super_duper_core::AppConfig) -> bool {
+    let issues = config.validate();
+    for issue in &issues {
+        match issue.severity {
+            Severity::Error => error!("{} {}", "error:".red(), issue),
+            Severity::Warning => warn!("{} {}", "warning:".yellow(), issue),
+        }
+    }
+    let errors = issues.iter().filter(|issue| issue.severity == Severity::Error).count();
+    if issues.is_empty() {
+        info!("Configuration is valid");
+    } else {
+        info!("{} errors, {} warnings", errors, issues.len() - errors);
+    }
+    errors == 0
+}
+
+/// Summarise the paths a scan skipped, by phase and error kind, with the first few listed.
+fn print_scan_errors(
+    engine: &ScanEngine,
+    session_id: i64,
+    total: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const LISTED: i64 = 10;
+
+    info!(
+        "{} paths could not be examined and are missing from the results:",
+        format!("{}", total).yellow(),
+    );
+    for (phase, kind, count) in engine.scan_error_summary(session_id)? {
+        info!("  {:<12} {:<20} {}", phase, kind, count);
+    }
+    let (errors, _) = engine.scan_errors(session_id, 0, LISTED)?;
+    for error in &errors {
+        info!("  {} ({}): {}", error.path.yellow(), error.phase, error.message);
+    }
+    if total as i64 > LISTED {
+        info!("  ... and {} more in the scan_error table", total as i64 - LISTED);
+    }
+    Ok(())
+}
+
+fn run_hash_cache_stats(
+    config: &super_duper_core::AppConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let stats = ScanEngine::new(config.clone()).hash_cache_stats()?;
+    info!(
+        "{} entries (estimated), {} bytes on disk",
+        format!("{}", stats.entries).cyan(),
+        format!("{}", stats.disk_bytes).cyan(),
+    );
+    match stats.last_scan {
+        Some(usage) => info!(
+            "Last scan: {} hits, {} misses ({})",
+            format!("{}", usage.hits).green(),
+            format!("{}", usage.misses).yellow(),
+            match usage.hit_ratio() {
+                Some(ratio) => format!("{:.1}% hit ratio", ratio * 100.0),
+                None => "no lookups".to_string(),
+            },
+        ),
+        None => info!("No completed scan recorded"),
+    }
+    Ok(())
+}
+
+fn run_evict_hash_cache(
+    config: &super_duper_core::AppConfig,
+    policy: &EvictionPolicy,
+    compact: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let summary = ScanEngine::new(config.clone()).evict_hash_cache(policy, compact)?;
+    info!(
+        "Evicted {} of {} entries ({} bytes): {} missing, {} superseded, {} expired, {} over size",
+        format!("{}", summary.evicted()).red(),
+        summary.entries_examined,
+        summary.bytes_freed,
+        summary.missing,
+        summary.superseded,
+        summary.expired,
+        summary.over_size,
+    );
+    Ok(())
+}
+
 fn run_analyze_directories() -> Result<(), Box<dyn std::error::Error>> {
     let db = super_duper_core::storage::Database::open("super_duper.db")?;
 
@@ -6,15 +6,19 @@ use super_duper_core::ProgressReporter;
 ///
 /// - Scan phase: spinner (unknown total files upfront)
 /// - Hash phase: progress bar (total files known from scan)
+/// - Verify phase (optional): spinner
 /// - DB write phase: spinner
 pub struct CliReporter {
     bar: Mutex<Option<ProgressBar>>,
+    /// Per-tier summary lines, printed under the hash completion line.
+    tier_lines: Mutex<Vec<String>>,
 }
 
 impl CliReporter {
     pub fn new() -> Self {
         Self {
             bar: Mutex::new(None),
+            tier_lines: Mutex::new(Vec::new()),
         }
     }
 
@@ -26,7 +30,15 @@ impl CliReporter {
         *guard = Some(pb);
     }
 
-    fn finish_bar(&self) {
+    /// Print a line above the current bar, if any.
+    fn println(&self, line: &str) {
+        match self.bar.lock().unwrap().as_ref() {
+            Some(pb) => pb.suspend(|| eprintln!("{}", line)),
+            None => eprintln!("{}", line),
+        }
+    }
+
+        fn finish_bar(&self) {
         let mut guard = self.bar.lock().unwrap();
         if let Some(pb) = guard.take() {
             pb.finish_and_clear();
@@ -93,6 +105,36 @@ impl ProgressReporter for CliReporter {
             "  \x1b[32m✓\x1b[0m Hash complete: {} duplicate groups in {:.2}s",
             total_dupes, duration_secs
         );
+        for line in self.tier_lines.lock().unwrap().drain(..) {
+            eprintln!("{}", line);
+        }
+    }
+
+    fn on_hash_tier_complete(&self, tier: &str, files_hashed: usize, files_eliminated: usize) {
+        self.tier_lines.lock().unwrap().push(format!(
+            "    {:<8} {} files hashed, {} eliminated",
+            tier, files_hashed, files_eliminated
+        ));
+    }
+
+    fn on_verify_start(&self) {
+        let pb = ProgressBar::new_spinner();
+        pb.set_style(
+            ProgressStyle::with_template("{spinner:.cyan} {msg}")
+                .unwrap()
+                .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏"),
+        );
+        pb.set_message("Verifying duplicates byte-for-byte...");
+        pb.enable_steady_tick(std::time::Duration::from_millis(80));
+        self.set_bar(pb);
+    }
+
+    fn on_verify_complete(&self, verified_groups: usize, mismatched_files: usize, duration_secs: f64) {
+        self.finish_bar();
+        eprintln!(
+            "  \x1b[32m✓\x1b[0m Verify complete: {} groups confirmed, {} mismatched files in {:.2}s",
+            verified_groups, mismatched_files, duration_secs
+        );
     }
 
     fn on_db_write_start(&self) {
@@ -134,4 +176,12 @@ impl ProgressReporter for CliReporter {
             fingerprints, similarity_pairs, duration_secs
         );
     }
+
+    fn on_scan_paused(&self) {
+        self.println("  \x1b[33m⏸\x1b[0m Paused; send SIGUSR2 or SIGCONT to resume");
+    }
+
+    fn on_scan_resumed(&self) {
+        self.println("  \x1b[32m▶\x1b[0m Resumed");
+    }
 }
@@ -0,0 +1,51 @@
+use std::sync::Arc;
+use super_duper_core::PauseToken;
+
+/// Pauses and resumes a running scan on signals: SIGUSR1 pauses it, SIGUSR2 or
+/// SIGCONT resumes it. Handlers are removed when this is dropped.
+pub struct PauseSignals {
+    #[cfg(unix)]
+    handle: signal_hook::iterator::Handle,
+    #[cfg(unix)]
+    listener: Option<std::thread::JoinHandle<()>>,
+}
+
+#[cfg(unix)]
+impl PauseSignals {
+    pub fn watch(pause: Arc<PauseToken>) -> std::io::Result<Self> {
+        use signal_hook::consts::{SIGCONT, SIGUSR1, SIGUSR2};
+        use signal_hook::iterator::Signals;
+
+        let mut signals = Signals::new([SIGUSR1, SIGUSR2, SIGCONT])?;
+        let handle = signals.handle();
+        let listener = std::thread::spawn(move || {
+            for signal in signals.forever() {
+                match signal {
+                    SIGUSR1 => pause.pause(),
+                    _ => pause.resume(),
+                };
+            }
+        });
+        Ok(Self {
+            handle,
+            listener: Some(listener),
+        })
+    }
+}
+
+#[cfg(not(unix))]
+impl PauseSignals {
+    pub fn watch(_pause: Arc<PauseToken>) -> std::io::Result<Self> {
+        Ok(Self {})
+    }
+}
+
+#[cfg(unix)]
+impl Drop for PauseSignals {
+    fn drop(&mut self) {
+        self.handle.close();
+        if let Some(listener) = self.listener.take() {
+            let _ = listener.join();
+        }
+    }
+}
@@ -1,16 +1,26 @@
+use crate::hasher::throttle::IoThrottle;
+use crate::hasher::{verify, IoLimits};
+use crate::platform;
 use crate::storage::models::ScannedFile;
 use crate::storage::Database;
 use rusqlite::params;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tracing::{debug, error, info, warn};
 
-/// Mark all files in a directory for deletion.
+/// Mark all files in a directory for deletion. Fails with `Error::Protected` when
+/// the directory is itself protected; protected files below it are left unmarked.
 pub fn mark_directory_for_deletion(
     db: &Database,
     directory_path: &str,
     strategy: Option<&str>,
 ) -> Result<usize, crate::Error> {
+    if let Some(protected_by) = db.protecting_path(directory_path)? {
+        return Err(crate::Error::Protected {
+            path: directory_path.to_string(),
+            protected_by,
+        });
+    }
     let mut stmt = db.connection().prepare(
         "SELECT id FROM scanned_file WHERE parent_dir = ?1 OR parent_dir LIKE ?2",
     )?;
@@ -19,26 +29,36 @@ pub fn mark_directory_for_deletion(
         .query_map(params![directory_path, like_pattern], |row| row.get(0))?
         .collect::<Result<Vec<_>, _>>()?;
 
+    let mut marked = 0;
     for file_id in &file_ids {
-        db.mark_file_for_deletion(*file_id, strategy)?;
+        match db.mark_file_for_deletion(*file_id, strategy) {
+            Ok(()) => marked += 1,
+            Err(crate::Error::Protected { path, protected_by }) => {
+                debug!("Not marking '{}': protected by '{}'", path, protected_by)
+            }
+            Err(e) => return Err(e),
+        }
     }
 
     info!(
         "Marked {} files in directory '{}' for deletion",
-        file_ids.len(),
-        directory_path
+        marked, directory_path
     );
-    Ok(file_ids.len())
+    Ok(marked)
 }
 
 /// Auto-mark duplicates for deletion using a strategy.
-/// For each duplicate group in the given session, keep one file (the first alphabetically)
-/// and mark the rest.
+/// For each duplicate group in the given session, keep one file and mark the rest.
+/// A protected file (under a protected path or root) is always kept, and is never
+/// marked. Otherwise the file kept is the one under the session root with the
+/// highest priority, the first alphabetically among equals.
 pub fn auto_mark_duplicates(
     db: &Database,
     session_id: i64,
     strategy: Option<&str>,
 ) -> Result<usize, crate::Error> {
+    let roots = session_root_rules(db, session_id)?;
+    let protected_paths = db.get_protected_paths()?;
     let groups = db.get_duplicate_groups(session_id, 0, i64::MAX)?;
     let mut marked_count = 0;
 
@@ -48,11 +68,29 @@ pub fn auto_mark_duplicates(
             continue;
         }
 
-        // Keep the first file (sorted by path), mark the rest
-        let mut sorted_files = files.clone();
-        sorted_files.sort_by(|a, b| a.canonical_path.cmp(&b.canonical_path));
+        // Keep the first file by protection, root priority then path, mark the rest
+        let mut ranked: Vec<_> = files
+            .iter()
+            .map(|file| {
+                let (priority, root_protected) = root_rule(&roots, &file.canonical_path);
+                let protected = root_protected
+                    || protected_paths
+                        .iter()
+                        .any(|protected| Path::new(&file.canonical_path).starts_with(protected));
+                ((priority, protected), file)
+            })
+            .collect();
+        ranked.sort_by(|((a_priority, a_protected), a), ((b_priority, b_protected), b)| {
+            b_protected
+                .cmp(a_protected)
+                .then_with(|| b_priority.cmp(a_priority))
+                .then_with(|| a.canonical_path.cmp(&b.canonical_path))
+        });
 
-        for file in sorted_files.iter().skip(1) {
+        for ((_, protected), file) in ranked.iter().skip(1) {
+            if *protected {
+                continue;
+            }
             db.mark_file_for_deletion(file.id, strategy)?;
             marked_count += 1;
         }
@@ -62,12 +100,55 @@ pub fn auto_mark_duplicates(
     Ok(marked_count)
 }
 
+/// A session root's canonical path with the priority and protection it was scanned with.
+#[derive(Debug)]
+struct RootRule {
+    path: PathBuf,
+    priority: i64,
+    protected: bool,
+}
+
+/// The session's roots, deepest first so the first prefix match is the closest root.
+fn session_root_rules(db: &Database, session_id: i64) -> Result<Vec<RootRule>, crate::Error> {
+    let mut rules: Vec<RootRule> = db
+        .get_session_roots(session_id)?
+        .into_iter()
+        .map(|root| RootRule {
+            // Files are stored under canonical paths; roots as configured
+            path: fs::canonicalize(&root.root_path).unwrap_or_else(|_| root.root_path.into()),
+            priority: root.priority,
+            protected: root.protected,
+        })
+        .collect();
+    rules.sort_by_key(|rule| std::cmp::Reverse(rule.path.components().count()));
+    Ok(rules)
+}
+
+/// Priority and protection of the root holding `canonical_path`; (0, false) outside every root.
+fn root_rule(rules: &[RootRule], canonical_path: &str) -> (i64, bool) {
+    rules
+        .iter()
+        .find(|rule| Path::new(canonical_path).starts_with(&rule.path))
+        .map_or((0, false), |rule| (rule.priority, rule.protected))
+}
+
 /// Execute the deletion plan. Returns (success_count, error_count).
 ///
 /// When `use_trash` is true, files are moved to the system Recycle Bin / Trash
 /// instead of being permanently deleted.
-pub fn execute_deletion_plan(db: &Database, use_trash: bool) -> Result<(usize, usize), crate::Error> {
+///
+/// A file that belongs to a duplicate group is only removed if at least one surviving
+/// member of that group still matches it byte-for-byte; otherwise the entry is recorded
+/// as `verification_failed` and counted as an error. The comparison's reads are held
+/// to `io_limits`.
+pub fn execute_deletion_plan(
+    db: &Database,
+    use_trash: bool,
+    io_limits: IoLimits,
+) -> Result<(usize, usize), crate::Error> {
     let plan = db.get_deletion_plan()?;
+    let throttle = IoThrottle::new(io_limits);
+    let device_of = |path: &Path| platform::device_id(path).unwrap_or(0);
     let mut success_count = 0;
     let mut error_count = 0;
 
@@ -78,7 +159,8 @@ pub fn execute_deletion_plan(db: &Database, use_trash: bool) -> Result<(usize, u
             .query_row(
                 "SELECT id, canonical_path, file_name, parent_dir, drive_letter, \
                  file_size, last_modified, partial_hash, content_hash, \
-                 last_seen_session_id, marked_deleted \
+                 last_seen_session_id, marked_deleted, content_digest, \
+                 last_modified_nanos, partial_hash_len \
                  FROM scanned_file WHERE id = ?1",
                 params![entry.file_id],
                 |row| {
@@ -90,8 +172,11 @@ pub fn execute_deletion_plan(db: &Database, use_trash: bool) -> Result<(usize, u
                         drive_letter: row.get(4)?,
                         file_size: row.get(5)?,
                         last_modified: row.get(6)?,
+                        last_modified_nanos: row.get(12)?,
                         partial_hash: row.get(7)?,
+                        partial_hash_len: row.get(13)?,
                         content_hash: row.get(8)?,
+                        content_digest: row.get(11)?,
                         last_seen_session_id: row.get(9)?,
                         marked_deleted: row.get(10)?,
                     })
@@ -110,6 +195,22 @@ pub fn execute_deletion_plan(db: &Database, use_trash: bool) -> Result<(usize, u
 
         let path = Path::new(&file.canonical_path);
 
+        // Protection may have been added since the file was marked
+        if let Some(protected_by) = db.protecting_path(&file.canonical_path)? {
+            warn!(
+                "'{}' is protected by '{}', refusing to delete",
+                file.canonical_path, protected_by
+            );
+            let now = chrono::Utc::now().to_rfc3339();
+            db.connection().execute(
+                "UPDATE deletion_plan SET executed_at = ?1, execution_result = 'protected' \
+                 WHERE id = ?2",
+                params![now, entry.id],
+            )?;
+            error_count += 1;
+            continue;
+        }
+
         // Verify file still exists
         if !path.exists() {
             warn!("File '{}' no longer exists, marking as executed", file.canonical_path);
@@ -122,6 +223,33 @@ pub fn execute_deletion_plan(db: &Database, use_trash: bool) -> Result<(usize, u
             continue;
         }
 
+        // Re-verify against a surviving copy right before removal
+        if let Some(survivors) = db.get_surviving_copies(file.id)? {
+            let matched = survivors.iter().find(|s| {
+                verify::files_identical(path, Path::new(s), &throttle, &device_of).unwrap_or_else(|e| {
+                    warn!("Error comparing '{}' with '{}': {}", file.canonical_path, s, e);
+                    false
+                })
+            });
+            match matched {
+                Some(survivor) => debug!("Verified '{}' against '{}'", file.canonical_path, survivor),
+                None => {
+                    warn!(
+                        "No surviving copy matches '{}' byte-for-byte, refusing to delete",
+                        file.canonical_path
+                    );
+                    let now = chrono::Utc::now().to_rfc3339();
+                    db.connection().execute(
+                        "UPDATE deletion_plan SET executed_at = ?1, \
+                         execution_result = 'verification_failed' WHERE id = ?2",
+                        params![now, entry.id],
+                    )?;
+                    error_count += 1;
+                    continue;
+                }
+            }
+        }
+
         // Delete or trash the file
         let delete_result: Result<(), String> = if use_trash {
             #[cfg(windows)]
@@ -1,21 +1,22 @@
+use crate::hasher::HashAlgorithm;
 use crate::storage::Database;
 use ahash::AHashMap;
 use rusqlite::params;
-use std::hash::Hasher as _;
+use std::path::Path;
 use tracing::info;
-use twox_hash::XxHash64;
 
 /// Build directory hierarchy from scanned files, compute fingerprints bottom-up.
 ///
 /// Algorithm:
 /// 1. Build directory_node tree from scanned_file.parent_dir
 /// 2. Process directories bottom-up by depth:
-///    - Collect content hashes of direct child files
-///    - Union with child directories' hash sets (already computed)
-///    - content_fingerprint = XxHash64 of sorted hash list
+///    - Collect full content digests of direct child files
+///    - Union with child directories' digest sets (already computed)
+///    - content_fingerprint = the latest session's hash algorithm over the sorted digests
 ///    - Store file_hash_set as JSON for Jaccard computation
 pub fn build_directory_fingerprints(db: &Database) -> Result<usize, crate::Error> {
     info!("Building directory hierarchy...");
+    let algorithm = fingerprint_algorithm(db)?;
 
     // Step 1: Collect all unique parent directories from scanned files
     let mut stmt = db.connection().prepare(
@@ -35,11 +36,18 @@ pub fn build_directory_fingerprints(db: &Database) -> Result<usize, crate::Error
 
     info!("Built {} directory nodes", dir_id_map.len());
 
-    // Step 3: Compute file counts and sizes for each directory
+    // Step 3: Compute file counts and sizes for each directory, in all and in
+    // duplicate groups
     db.connection().execute_batch(
         "UPDATE directory_node SET
             file_count = (SELECT COUNT(*) FROM scanned_file WHERE parent_dir = directory_node.path),
-            total_size = (SELECT COALESCE(SUM(file_size), 0) FROM scanned_file WHERE parent_dir = directory_node.path)"
+            total_size = (SELECT COALESCE(SUM(file_size), 0) FROM scanned_file WHERE parent_dir = directory_node.path),
+            duplicate_count = (SELECT COUNT(*) FROM scanned_file sf
+                WHERE sf.parent_dir = directory_node.path
+                AND EXISTS (SELECT 1 FROM duplicate_group_member m WHERE m.file_id = sf.id)),
+            duplicate_size = (SELECT COALESCE(SUM(sf.file_size), 0) FROM scanned_file sf
+                WHERE sf.parent_dir = directory_node.path
+                AND EXISTS (SELECT 1 FROM duplicate_group_member m WHERE m.file_id = sf.id))"
     )?;
 
     // Step 4: Propagate sizes up the tree (from deepest to shallowest)
@@ -57,6 +65,12 @@ pub fn build_directory_fingerprints(db: &Database) -> Result<usize, crate::Error
             ), file_count = file_count + COALESCE(
                 (SELECT SUM(dn2.file_count) FROM directory_node dn2 WHERE dn2.parent_id = directory_node.id),
                 0
+            ), duplicate_count = duplicate_count + COALESCE(
+                (SELECT SUM(dn2.duplicate_count) FROM directory_node dn2 WHERE dn2.parent_id = directory_node.id),
+                0
+            ), duplicate_size = duplicate_size + COALESCE(
+                (SELECT SUM(dn2.duplicate_size) FROM directory_node dn2 WHERE dn2.parent_id = directory_node.id),
+                0
             ) WHERE depth = ?1",
             params![depth],
         )?;
@@ -76,57 +90,155 @@ pub fn build_directory_fingerprints(db: &Database) -> Result<usize, crate::Error
             .collect::<Result<Vec<_>, _>>()?;
 
         for (dir_id, dir_path) in &dirs {
-            // Collect content hashes of direct child files
-            let mut hash_stmt = db.connection().prepare(
-                "SELECT content_hash FROM scanned_file WHERE parent_dir = ?1 AND content_hash IS NOT NULL",
-            )?;
-            let mut hashes: Vec<i64> = hash_stmt
-                .query_map(params![dir_path], |row| row.get(0))?
-                .collect::<Result<Vec<_>, _>>()?;
-
-            // Union with child directories' hash sets
-            let mut child_stmt = db.connection().prepare(
-                "SELECT df.file_hash_set FROM directory_fingerprint df \
-                 JOIN directory_node dn ON df.directory_id = dn.id \
-                 WHERE dn.parent_id = ?1",
-            )?;
-            let child_hash_sets: Vec<String> = child_stmt
-                .query_map(params![dir_id], |row| row.get(0))?
-                .collect::<Result<Vec<_>, _>>()?;
-
-            for hash_set_json in &child_hash_sets {
-                if let Ok(child_hashes) = serde_json::from_str::<Vec<i64>>(hash_set_json) {
-                    hashes.extend(child_hashes);
-                }
-            }
-
-            if hashes.is_empty() {
-                continue;
+            if fingerprint_directory(db, *dir_id, dir_path, algorithm)? {
+                fingerprint_count += 1;
             }
+        }
+    }
 
-            // Sort for deterministic fingerprint
-            hashes.sort();
-            hashes.dedup();
+    info!("Computed {} directory fingerprints", fingerprint_count);
+    Ok(fingerprint_count)
+}
 
-            // Compute content fingerprint = XxHash64 of sorted hash list
-            let mut hasher = XxHash64::with_seed(0);
-            for h in &hashes {
-                hasher.write_i64(*h);
-            }
-            let fingerprint = format!("{:016x}", hasher.finish());
+/// Recompute sizes, file and duplicate counts and fingerprints of `dirs` and every directory
+/// above them, e.g. those whose files an incremental scan rehashed or lost.
+/// Builds the whole hierarchy when none has been built yet.
+pub fn update_directory_fingerprints(db: &Database, dirs: &[String]) -> Result<usize, crate::Error> {
+    if !has_directory_tree(db)? {
+        return build_directory_fingerprints(db);
+    }
+    let algorithm = fingerprint_algorithm(db)?;
 
-            // Store hash set as JSON
-            let hash_set_json = serde_json::to_string(&hashes).unwrap_or_default();
+    // The map collects each directory's ancestors, which are affected too
+    let mut dir_id_map: AHashMap<String, i64> = AHashMap::new();
+    for dir_path in dirs {
+        insert_directory_hierarchy(db, dir_path, &mut dir_id_map)?;
+    }
+    let mut affected: Vec<(usize, String, i64)> = dir_id_map
+        .into_iter()
+        .map(|(path, id)| (Path::new(&path).components().count(), path, id))
+        .collect();
+    affected.sort_by_key(|(depth, _, _)| std::cmp::Reverse(*depth));
 
-            db.insert_directory_fingerprint(*dir_id, &fingerprint, &hash_set_json)?;
+    // Deepest first, so each directory sums children that are already up to date
+    let mut fingerprint_count = 0;
+    for (_, dir_path, dir_id) in &affected {
+        db.connection().execute(
+            "UPDATE directory_node SET
+                file_count = (SELECT COUNT(*) FROM scanned_file WHERE parent_dir = ?1)
+                    + COALESCE((SELECT SUM(dn2.file_count) FROM directory_node dn2 WHERE dn2.parent_id = ?2), 0),
+                total_size = (SELECT COALESCE(SUM(file_size), 0) FROM scanned_file WHERE parent_dir = ?1)
+                    + COALESCE((SELECT SUM(dn2.total_size) FROM directory_node dn2 WHERE dn2.parent_id = ?2), 0),
+                duplicate_count = (SELECT COUNT(*) FROM scanned_file sf WHERE sf.parent_dir = ?1
+                    AND EXISTS (SELECT 1 FROM duplicate_group_member m WHERE m.file_id = sf.id))
+                    + COALESCE((SELECT SUM(dn2.duplicate_count) FROM directory_node dn2 WHERE dn2.parent_id = ?2), 0),
+                duplicate_size = (SELECT COALESCE(SUM(sf.file_size), 0) FROM scanned_file sf WHERE sf.parent_dir = ?1
+                    AND EXISTS (SELECT 1 FROM duplicate_group_member m WHERE m.file_id = sf.id))
+                    + COALESCE((SELECT SUM(dn2.duplicate_size) FROM directory_node dn2 WHERE dn2.parent_id = ?2), 0)
+             WHERE id = ?2",
+            params![dir_path, dir_id],
+        )?;
+        // Pairs are recomputed from the new fingerprint; one that no longer
+        // clears the threshold mustn't linger
+        db.connection().execute(
+            "DELETE FROM directory_similarity WHERE dir_a_id = ?1 OR dir_b_id = ?1",
+            params![dir_id],
+        )?;
+        if fingerprint_directory(db, *dir_id, dir_path, algorithm)? {
             fingerprint_count += 1;
         }
     }
 
-    info!("Computed {} directory fingerprints", fingerprint_count);
+    info!("Updated {} directory fingerprints", fingerprint_count);
     Ok(fingerprint_count)
 }
 
+/// Whether a directory hierarchy has been built, so it can be updated in place.
+pub fn has_directory_tree(db: &Database) -> Result<bool, crate::Error> {
+    let built: i64 =
+        db.connection()
+            .query_row("SELECT COUNT(*) FROM directory_node", [], |row| row.get(0))?;
+    Ok(built > 0)
+}
+
+/// The hash algorithm of the latest completed session, which fingerprints are
+/// taken with. XxHash64 when there's none.
+fn fingerprint_algorithm(db: &Database) -> Result<HashAlgorithm, crate::Error> {
+    let algorithm: Option<String> = match db.connection().query_row(
+        "SELECT hash_algorithm FROM scan_session WHERE status = 'completed' ORDER BY id DESC LIMIT 1",
+        [],
+        |row| row.get(0),
+    ) {
+        Ok(algorithm) => algorithm,
+        Err(rusqlite::Error::QueryReturnedNoRows) => None,
+        Err(e) => return Err(e.into()),
+    };
+    Ok(algorithm
+        .and_then(|name| name.parse().ok())
+        .unwrap_or_default())
+}
+
+/// Recompute the fingerprint of one directory from its own files and its
+/// children's digest sets. A directory without hashed content loses any
+/// fingerprint it had. Returns whether it has one.
+fn fingerprint_directory(
+    db: &Database,
+    dir_id: i64,
+    dir_path: &str,
+    algorithm: HashAlgorithm,
+) -> Result<bool, crate::Error> {
+    // Collect the full digests of direct child files; the 64-bit content_hash
+    // alone would let colliding files count as the same content
+    let mut hash_stmt = db.connection().prepare(
+        "SELECT content_digest FROM scanned_file WHERE parent_dir = ?1 AND content_digest IS NOT NULL",
+    )?;
+    let mut hashes: Vec<String> = hash_stmt
+        .query_map(params![dir_path], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Union with child directories' hash sets
+    let mut child_stmt = db.connection().prepare(
+        "SELECT df.file_hash_set FROM directory_fingerprint df \
+         JOIN directory_node dn ON df.directory_id = dn.id \
+         WHERE dn.parent_id = ?1",
+    )?;
+    let child_hash_sets: Vec<String> = child_stmt
+        .query_map(params![dir_id], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for hash_set_json in &child_hash_sets {
+        if let Ok(child_hashes) = serde_json::from_str::<Vec<String>>(hash_set_json) {
+            hashes.extend(child_hashes);
+        }
+    }
+
+    if hashes.is_empty() {
+        db.connection().execute(
+            "DELETE FROM directory_fingerprint WHERE directory_id = ?1",
+            params![dir_id],
+        )?;
+        return Ok(false);
+    }
+
+    // Sort for deterministic fingerprint
+    hashes.sort();
+    hashes.dedup();
+
+    // Compute content fingerprint over the sorted digests, each newline-terminated
+    let mut hasher = algorithm.hasher();
+    for h in &hashes {
+        hasher.update(h.as_bytes());
+        hasher.update(b"\n");
+    }
+    let fingerprint = hasher.finish().to_hex();
+
+    // Store hash set as JSON
+    let hash_set_json = serde_json::to_string(&hashes).unwrap_or_default();
+
+    db.insert_directory_fingerprint(dir_id, &fingerprint, &hash_set_json)?;
+    Ok(true)
+}
+
 fn insert_directory_hierarchy(
     db: &Database,
     dir_path: &str,
@@ -136,7 +248,7 @@ fn insert_directory_hierarchy(
         return Ok(id);
     }
 
-    let path = std::path::Path::new(dir_path);
+    let path = Path::new(dir_path);
     let name = path
         .file_name()
         .map(|n| n.to_string_lossy().into_owned())
@@ -2,11 +2,12 @@ use crate::storage::Database;
 use ahash::{AHashMap, AHashSet};
 use rayon::prelude::*;
 use rusqlite::params;
+use std::path::Path;
 use tracing::info;
 
 /// Compute directory similarity using Jaccard index.
 ///
-/// 1. Build inverted index: content_hash → Vec<directory_id>
+/// 1. Build inverted index: content_digest → Vec<directory_id>
 /// 2. Identify candidate pairs (directories sharing at least one hash)
 /// 3. Skip hashes appearing in >50 directories (noise)
 /// 4. Compute Jaccard = |intersection| / |union| for each candidate pair
@@ -16,13 +17,73 @@ pub fn compute_directory_similarity(
     threshold: f64,
 ) -> Result<usize, crate::Error> {
     info!("Computing directory similarity (threshold={:.2})...", threshold);
+    store_similar_pairs(db, threshold, None)
+}
+
+/// `compute_directory_similarity` for only the pairs involving `dirs` or a
+/// directory above them, whose old pairs `update_directory_fingerprints` has
+/// dropped. Other pairs are left as they are; nothing is done when `dirs` is empty.
+pub fn update_directory_similarity(
+    db: &Database,
+    threshold: f64,
+    dirs: &[String],
+) -> Result<usize, crate::Error> {
+    let affected = directory_ids_with_ancestors(db, dirs)?;
+    if affected.is_empty() {
+        info!("No directory fingerprints changed, similarity left as is");
+        return Ok(0);
+    }
+    info!(
+        "Updating similarity of {} directories (threshold={:.2})...",
+        affected.len(),
+        threshold
+    );
+    store_similar_pairs(db, threshold, Some(&affected))
+}
+
+/// Ids of the nodes for `dirs` and every directory above them, as laid out by
+/// `dir_fingerprint::insert_directory_hierarchy`.
+fn directory_ids_with_ancestors(
+    db: &Database,
+    dirs: &[String],
+) -> Result<AHashSet<i64>, crate::Error> {
+    let mut stmt = db
+        .connection()
+        .prepare("SELECT id FROM directory_node WHERE path = ?1")?;
+    let mut visited: AHashSet<String> = AHashSet::new();
+    let mut ids = AHashSet::new();
+    for dir in dirs {
+        let mut path = Some(Path::new(dir));
+        while let Some(current) = path {
+            let current_str = current.to_string_lossy().into_owned();
+            if current_str.is_empty() || !visited.insert(current_str.clone()) {
+                break;
+            }
+            let id: Option<i64> = stmt
+                .query_map(params![current_str], |row| row.get(0))?
+                .next()
+                .transpose()?;
+            ids.extend(id);
+            path = current.parent();
+        }
+    }
+    Ok(ids)
+}
+
+/// Store the pairs clearing `threshold`, only those with a side in `only` when given.
+fn store_similar_pairs(
+    db: &Database,
+    threshold: f64,
+    only: Option<&AHashSet<i64>>,
+) -> Result<usize, crate::Error> {
+    let involved = |a: i64, b: i64| only.is_none_or(|ids| ids.contains(&a) || ids.contains(&b));
 
     // Load all directory fingerprints
     let mut stmt = db.connection().prepare(
         "SELECT directory_id, file_hash_set FROM directory_fingerprint",
     )?;
 
-    let fingerprints: Vec<(i64, Vec<i64>)> = stmt
+    let fingerprints: Vec<(i64, Vec<String>)> = stmt
         .query_map([], |row| {
             let dir_id: i64 = row.get(0)?;
             let hash_set_json: String = row.get(1)?;
@@ -30,7 +91,7 @@ pub fn compute_directory_similarity(
         })?
         .filter_map(|r| r.ok())
         .filter_map(|(dir_id, json)| {
-            serde_json::from_str::<Vec<i64>>(&json)
+            serde_json::from_str::<Vec<String>>(&json)
                 .ok()
                 .map(|hashes| (dir_id, hashes))
         })
@@ -41,27 +102,27 @@ pub fn compute_directory_similarity(
         return Ok(0);
     }
 
-    // Build content_hash → file_size map for accurate shared-bytes computation.
-    // Duplicate files share the same content_hash and file_size, so one row per hash suffices.
-    let hash_to_size: AHashMap<i64, i64> = {
+    // Build content_digest → file_size map for accurate shared-bytes computation.
+    // Duplicate files share the same digest and file_size, so one row per digest suffices.
+    let hash_to_size: AHashMap<String, i64> = {
         let mut stmt = db.connection().prepare(
-            "SELECT content_hash, file_size FROM scanned_file \
-             WHERE content_hash IS NOT NULL \
-             GROUP BY content_hash",
+            "SELECT content_digest, file_size FROM scanned_file \
+             WHERE content_digest IS NOT NULL \
+             GROUP BY content_digest",
         )?;
-        let map: AHashMap<i64, i64> = stmt
-            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?
+        let map: AHashMap<String, i64> = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?
             .filter_map(|r| r.ok())
             .collect();
         map
     };
 
     // Build inverted index: hash → Vec<dir_id>
-    let mut inverted_index: AHashMap<i64, Vec<i64>> = AHashMap::new();
-    let mut dir_hash_sets: AHashMap<i64, AHashSet<i64>> = AHashMap::new();
+    let mut inverted_index: AHashMap<&str, Vec<i64>> = AHashMap::new();
+    let mut dir_hash_sets: AHashMap<i64, AHashSet<&str>> = AHashMap::new();
 
     for (dir_id, hashes) in &fingerprints {
-        let hash_set: AHashSet<i64> = hashes.iter().copied().collect();
+        let hash_set: AHashSet<&str> = hashes.iter().map(String::as_str).collect();
         for &hash in &hash_set {
             inverted_index.entry(hash).or_default().push(*dir_id);
         }
@@ -84,7 +145,9 @@ pub fn compute_directory_similarity(
                 } else {
                     (dir_ids[j], dir_ids[i])
                 };
-                candidate_pairs.insert((a, b));
+                if involved(a, b) {
+                    candidate_pairs.insert((a, b));
+                }
             }
         }
     }
@@ -123,7 +186,7 @@ pub fn compute_directory_similarity(
             // Sum actual file sizes for shared hashes
             let shared_bytes: i64 = set_a
                 .intersection(set_b)
-                .map(|h| hash_to_size.get(h).copied().unwrap_or(0))
+                .map(|h| hash_to_size.get(*h).copied().unwrap_or(0))
                 .sum();
 
             Some((dir_a, dir_b, jaccard, shared_bytes, match_type))
@@ -138,7 +201,7 @@ pub fn compute_directory_similarity(
     }
 
     // Also find exact matches via content_fingerprint
-    let exact_count = find_exact_matches(db)?;
+    let exact_count = find_exact_matches(db, &involved)?;
 
     info!(
         "Computed {} similarity pairs ({} from Jaccard, {} exact fingerprint matches)",
@@ -151,7 +214,10 @@ pub fn compute_directory_similarity(
 }
 
 /// Find exact directory duplicates via matching content_fingerprint.
-fn find_exact_matches(db: &Database) -> Result<usize, crate::Error> {
+fn find_exact_matches(
+    db: &Database,
+    involved: &dyn Fn(i64, i64) -> bool,
+) -> Result<usize, crate::Error> {
     // Find fingerprints that appear more than once
     let mut stmt = db.connection().prepare(
         "SELECT df1.directory_id, df2.directory_id \
@@ -165,7 +231,7 @@ fn find_exact_matches(db: &Database) -> Result<usize, crate::Error> {
         .collect::<Result<Vec<_>, _>>()?;
 
     let mut count = 0;
-    for (dir_a, dir_b) in &pairs {
+    for (dir_a, dir_b) in pairs.iter().filter(|(a, b)| involved(*a, *b)) {
         // Only insert if not already present
         let existing: i64 = db.connection().query_row(
             "SELECT COUNT(*) FROM directory_similarity WHERE dir_a_id = ?1 AND dir_b_id = ?2",
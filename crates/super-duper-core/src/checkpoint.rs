@@ -0,0 +1,327 @@
+use crate::engine::{is_unchanged, WalkedFiles};
+use crate::error::Error;
+use crate::hasher::{CompletedBucket, ContentHash, PartialHash};
+use crate::scanner::DirListing;
+use crate::storage::Database;
+use dashmap::DashMap;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, TryLockError};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Checkpointed items are written once this many are waiting...
+const FLUSH_ITEMS: usize = 256;
+/// ...or once this long has passed since the last write.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// An exclusive lock on `<db>.scan-<session>.lock`, held for as long as a scan
+/// runs. The OS releases it when the process dies, so a 'running' session whose
+/// lock can be taken was left behind by a crash. The file is left in place:
+/// removing it once unlocked would let a second process lock a new file under
+/// the same name while a third still holds the old one.
+struct SessionLock {
+    _file: File,
+}
+
+impl SessionLock {
+    /// `None` if another scan holds the lock.
+    fn try_acquire(db_path: &str, session_id: i64) -> io::Result<Option<Self>> {
+        let path = PathBuf::from(format!("{}.scan-{}.lock", db_path, session_id));
+        let file = File::options().create(true).truncate(false).write(true).open(&path)?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(Self { _file: file })),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(e)) => Err(e),
+        }
+    }
+}
+
+/// Mark each session left 'running' by a scan that is no longer alive as
+/// 'aborted', so it can be resumed. Returns their ids.
+pub fn abort_orphaned_sessions(db: &Database, db_path: &str) -> Result<Vec<i64>, Error> {
+    let mut aborted = Vec::new();
+    for session_id in db.get_running_session_ids()? {
+        // Only if it's still running: it may have finished and let go of its
+        // lock since the ids were read
+        if SessionLock::try_acquire(db_path, session_id)?.is_some()
+            && db.abort_running_session(session_id)?
+        {
+            warn!(
+                "Session {} was interrupted and has been marked aborted; it can be resumed",
+                session_id
+            );
+            aborted.push(session_id);
+        }
+    }
+    Ok(aborted)
+}
+
+/// The 'running' session a scan checkpoints into, locked while the scan lasts.
+pub(crate) struct ScanRun {
+    pub session_id: i64,
+    /// Whether the session was started by an earlier scan that stopped short.
+    pub resumed: bool,
+    _lock: SessionLock,
+}
+
+impl ScanRun {
+    /// Create a session for a new scan of `root_paths`.
+    pub fn start(
+        db: &Database,
+        db_path: &str,
+        root_paths: &[String],
+        settings: &str,
+    ) -> Result<Self, Error> {
+        // The session is locked before it's committed, so no other connection
+        // sees it running with its lock free
+        let tx = db.connection().unchecked_transaction()?;
+        let session_id = db.create_scan_session(root_paths)?;
+        db.set_session_scan_settings(session_id, settings)?;
+        let lock = SessionLock::try_acquire(db_path, session_id)?
+            .ok_or_else(|| Error::Other(format!("Session {} is locked", session_id)))?;
+        tx.commit()?;
+        debug!("Started session {}", session_id);
+        Ok(Self {
+            session_id,
+            resumed: false,
+            _lock: lock,
+        })
+    }
+
+    /// Take over a cancelled or aborted session of the same roots, scanned with
+    /// the same settings.
+    pub fn resume(
+        db: &Database,
+        db_path: &str,
+        session_id: i64,
+        root_paths: &[String],
+        settings: &str,
+    ) -> Result<Self, Error> {
+        let session = db
+            .get_session(session_id)?
+            .ok_or_else(|| Error::Resume(format!("no scan session {}", session_id)))?;
+        let lock = SessionLock::try_acquire(db_path, session_id)?
+            .ok_or_else(|| Error::Resume(format!("session {} is still running", session_id)))?;
+        // Its lock is free, so a session still marked running was orphaned
+        if session.status == "running" {
+            db.set_session_status(session_id, "aborted")?;
+        } else if session.status != "cancelled" && session.status != "aborted" {
+            return Err(Error::Resume(format!(
+                "session {} is {}",
+                session_id, session.status
+            )));
+        }
+        if !db.get_resumable_sessions(root_paths)?.iter().any(|s| s.id == session_id) {
+            return Err(Error::Resume(format!(
+                "session {} scanned {}, not the configured roots",
+                session_id, session.root_paths
+            )));
+        }
+        if db.get_session_scan_settings(session_id)?.as_deref() != Some(settings) {
+            return Err(Error::Resume(format!(
+                "settings have changed since session {} started; start a new scan instead",
+                session_id
+            )));
+        }
+        db.set_session_status(session_id, "running")?;
+        info!("Resuming session {}", session_id);
+        Ok(Self {
+            session_id,
+            resumed: true,
+            _lock: lock,
+        })
+    }
+}
+
+/// Writes completed size buckets, or the walk's directory listings, to a
+/// session's checkpoint in batches, over a connection of its own so walker and
+/// hashing threads can hand them over.
+pub(crate) struct Checkpointer<T> {
+    db: Mutex<Database>,
+    session_id: i64,
+    pending: Mutex<(Vec<T>, Instant)>,
+    writer: fn(&Database, i64, &[T]) -> rusqlite::Result<usize>,
+    /// What a batch holds, for the log.
+    what: &'static str,
+}
+
+pub(crate) type BucketCheckpointer = Checkpointer<CompletedBucket>;
+pub(crate) type ListingCheckpointer = Checkpointer<DirListing>;
+
+impl BucketCheckpointer {
+    pub fn open(db_path: &str, session_id: i64) -> Result<Self, Error> {
+        Checkpointer::with_writer(
+            db_path,
+            session_id,
+            Database::insert_checkpoint_buckets,
+            "hashed size buckets",
+        )
+    }
+}
+
+impl ListingCheckpointer {
+    /// Checkpoints listings in place of any the session already has.
+    pub fn open(db_path: &str, session_id: i64) -> Result<Self, Error> {
+        let checkpointer = Checkpointer::with_writer(
+            db_path,
+            session_id,
+            Database::insert_dir_listings,
+            "directory listings",
+        )?;
+        checkpointer.db.lock().unwrap().delete_dir_listings(session_id)?;
+        Ok(checkpointer)
+    }
+}
+
+impl<T> Checkpointer<T> {
+    fn with_writer(
+        db_path: &str,
+        session_id: i64,
+        writer: fn(&Database, i64, &[T]) -> rusqlite::Result<usize>,
+        what: &'static str,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            db: Mutex::new(Database::open(db_path)?),
+            session_id,
+            pending: Mutex::new((Vec::new(), Instant::now())),
+            writer,
+            what,
+        })
+    }
+
+    /// Queue an item, writing the queue when it's full or due. A failed write
+    /// is logged; it only means more work on resume.
+    pub fn record(&self, item: T) {
+        let due = {
+            let mut pending = self.pending.lock().unwrap();
+            pending.0.push(item);
+            if pending.0.len() >= FLUSH_ITEMS || pending.1.elapsed() >= FLUSH_INTERVAL {
+                pending.1 = Instant::now();
+                std::mem::take(&mut pending.0)
+            } else {
+                Vec::new()
+            }
+        };
+        if !due.is_empty() {
+            if let Err(e) = self.write(&due) {
+                warn!("Could not checkpoint {} {}: {}", due.len(), self.what, e);
+            }
+        }
+    }
+
+    /// Write every queued item.
+    pub fn flush(&self) -> Result<(), Error> {
+        let due = std::mem::take(&mut self.pending.lock().unwrap().0);
+        self.write(&due)
+    }
+
+    fn write(&self, items: &[T]) -> Result<(), Error> {
+        if !items.is_empty() {
+            (self.writer)(&self.db.lock().unwrap(), self.session_id, items)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checkpointed buckets a resumed scan takes instead of hashing them again.
+#[derive(Default)]
+pub(crate) struct RestoredBuckets {
+    pub sizes: HashSet<u64>,
+    pub duplicates: Vec<(ContentHash, Vec<PathBuf>)>,
+    pub partial_hashes: Vec<(PathBuf, PartialHash)>,
+    pub files: usize,
+}
+
+/// The buckets checkpointed for `session_id` whose files are still exactly
+/// those in the walk's bucket of the same size, none modified since.
+pub(crate) fn restore_buckets(
+    db: &Database,
+    session_id: i64,
+    files: &DashMap<u64, Vec<PathBuf>>,
+    walked: WalkedFiles,
+) -> Result<RestoredBuckets, Error> {
+    let mut restored = RestoredBuckets::default();
+    for bucket in db.get_checkpoint_buckets(session_id)? {
+        let Some(paths) = files.get(&bucket.file_size) else {
+            continue;
+        };
+        let previous: HashMap<String, Duration> = bucket
+            .partial_hashes
+            .iter()
+            .map(|(path, partial)| (path.to_string_lossy().into_owned(), partial.modified))
+            .collect();
+        if !is_unchanged(&paths, &previous, walked) {
+            continue;
+        }
+        restored.sizes.insert(bucket.file_size);
+        restored.files += paths.len();
+        restored.duplicates.extend(bucket.duplicates);
+        restored.partial_hashes.extend(bucket.partial_hashes);
+    }
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_only_sessions_still_running_without_a_lock_are_aborted() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("sessions.db");
+        let db_path = db_path.to_str().unwrap();
+        let db = Database::open(db_path).unwrap();
+        let roots = ["/data".to_string()];
+
+        // A live scan holds its lock from before its session is visible
+        let run = ScanRun::start(&db, db_path, &roots, "settings").unwrap();
+        assert!(abort_orphaned_sessions(&db, db_path).unwrap().is_empty());
+        assert_eq!(db.get_session(run.session_id).unwrap().unwrap().status, "running");
+
+        // One that completed after its id was read as running is left alone
+        let session_id = run.session_id;
+        db.complete_scan_session(session_id, 0, 0).unwrap();
+        drop(run);
+        assert!(!db.abort_running_session(session_id).unwrap());
+        assert_eq!(db.get_session(session_id).unwrap().unwrap().status, "completed");
+        // The lock file stays, so every later locker shares its inode
+        assert!(Path::new(&format!("{}.scan-{}.lock", db_path, session_id)).exists());
+
+        // One left running without its lock was orphaned
+        let orphan = db.create_scan_session(&roots).unwrap();
+        assert_eq!(abort_orphaned_sessions(&db, db_path).unwrap(), vec![orphan]);
+        assert_eq!(db.get_session(orphan).unwrap().unwrap().status, "aborted");
+    }
+
+    #[test]
+    fn test_listings_written_in_batches_survive_without_a_flush() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("listings.db");
+        let db_path = db_path.to_str().unwrap();
+        let db = Database::open(db_path).unwrap();
+        let session_id = db.create_scan_session(&["/data".to_string()]).unwrap();
+        let listing = |i: usize| DirListing {
+            path: PathBuf::from(format!("/data/{}", i)),
+            modified: Duration::from_secs(i as u64),
+            entries: Vec::new(),
+        };
+
+        // A full batch is written as it fills; what's queued after is lost in a crash
+        let checkpointer = ListingCheckpointer::open(db_path, session_id).unwrap();
+        for i in 0..FLUSH_ITEMS + 1 {
+            checkpointer.record(listing(i));
+        }
+        drop(checkpointer);
+        assert_eq!(db.get_dir_listings(session_id).unwrap().len(), FLUSH_ITEMS);
+
+        // A new walk of the session checkpoints from scratch
+        let checkpointer = ListingCheckpointer::open(db_path, session_id).unwrap();
+        checkpointer.record(listing(0));
+        checkpointer.flush().unwrap();
+        assert_eq!(db.get_dir_listings(session_id).unwrap().len(), 1);
+    }
+}
@@ -1,18 +1,690 @@
-use config::{Config, ConfigError, File as ConfigFile};
+use crate::hasher::{CacheKeyScheme, HashAlgorithm, HashOptions, HashTiers, IoLimits, RootIoLimits};
+use crate::scanner::walk::{WalkOptions, DEFAULT_MAX_DEPTH};
+use crate::scanner::{
+    FilesystemFilter, RootFilesystemFilter, RootScanOptions, ScanFilter, SymlinkPolicy,
+};
+use config::{Config, ConfigError, Environment, File as ConfigFile, Source, Value, ValueKind};
+use glob::Pattern;
 use serde::Deserialize;
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct AppConfig {
+    /// Directories to scan, each a path or a table of per-root settings.
+    #[serde(default)]
+    pub root_paths: Vec<RootConfig>,
+    #[serde(default)]
+    pub ignore_patterns: Vec<String>,
+    /// How many directory levels below each root the scanner descends.
+    /// Defaults to 1024.
+    #[serde(default)]
+    pub max_scan_depth: Option<usize>,
+    /// What to do with symlinks and junctions (`skip`, `follow`, `record`).
+    #[serde(default)]
+    pub symlinks: SymlinkPolicy,
+    /// Don't descend into other filesystems mounted below a root.
+    #[serde(default)]
+    pub one_file_system: bool,
+    /// Filesystem types (`allow`/`deny`) roots and the mounts below them may be on.
+    #[serde(default)]
+    pub filesystem_types: FilesystemFilter,
+    /// Per-root replacements for `filesystem_types`.
+    #[serde(default)]
+    pub root_filesystem_types: Vec<RootFilesystemFilter>,
+    /// Size, modification time, extension, attribute and glob limits on the files scanned.
+    #[serde(default)]
+    pub filter: ScanFilter,
+    /// Files at or below these paths are never marked for deletion or deleted.
+    #[serde(default)]
+    pub protected_paths: Vec<String>,
+    /// Honour `.gitignore`, `.ignore` and `.superduperignore` files in scanned
+    /// directories, on top of `ignore_patterns`.
+    #[serde(default)]
+    pub ignore_files: bool,
+    /// Algorithm for the full-content hash tier (`xxh64`, `xxh3-128`, `blake3`, `sha256`).
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+    /// Sizes for the partial and head/middle/tail sampling tiers.
+    #[serde(default)]
+    pub hash_tiers: HashTiers,
+    /// Byte-compare every member of a hash group against its kept copy before the
+    /// group is recorded as duplicate.
+    #[serde(default)]
+    pub verify_duplicates: bool,
+    /// RocksDB hash cache location. Falls back to `HASH_CACHE_PATH`, then
+    /// `content_hash_cache.db`.
+    #[serde(default)]
+    pub hash_cache_path: Option<String>,
+    /// Key hash cache entries by path (`path`) or by file identity (`file-id`),
+    /// which keeps entries valid when files are renamed or moved.
+    #[serde(default)]
+    pub hash_cache_keys: CacheKeyScheme,
+    /// Per-device read concurrency and overall bandwidth caps for the hash phase,
+    /// for roots on network shares that a full-speed scan would saturate. A root
+    /// entry can add its own with `io_limits`.
+    #[serde(default)]
+    pub io_limits: IoLimits,
+    /// Index every walked file, not only those sharing a size with another file,
+    /// so directory sizes and duplicated shares cover the whole tree.
+    #[serde(default)]
+    pub index_all_files: bool,
+    /// Redo every scan from scratch instead of updating the previous scan of the
+    /// same roots.
+    #[serde(default)]
+    pub full_rescan: bool,
+}
+
+impl AppConfig {
+    /// The configured root paths, as written.
+    pub fn root_path_strings(&self) -> Vec<String> {
+        self.root_paths.iter().map(|root| root.path.clone()).collect()
+    }
+
+    /// Settings of the root configured as `path`.
+    pub fn root(&self, path: &str) -> Option<&RootConfig> {
+        self.root_paths.iter().find(|root| root.path == path)
+    }
+
+    /// `protected_paths` plus every root marked `protected`, canonicalized where
+    /// they exist so they compare with stored file paths.
+    pub fn all_protected_paths(&self) -> Vec<String> {
+        self.protected_paths
+            .iter()
+            .chain(self.root_paths.iter().filter(|root| root.protected).map(|root| &root.path))
+            .map(|path| match std::fs::canonicalize(path) {
+                Ok(canonical) => canonical.to_string_lossy().into_owned(),
+                Err(_) => path.clone(),
+            })
+            .collect()
+    }
+
+    /// Scanner settings taken from this configuration.
+    pub fn walk_options(&self) -> WalkOptions {
+        WalkOptions {
+            max_depth: self.max_scan_depth.unwrap_or(DEFAULT_MAX_DEPTH),
+            symlinks: self.symlinks,
+            one_file_system: self.one_file_system,
+            filesystem_types: self.filesystem_types.clone(),
+            root_filesystem_types: self.root_filesystem_types.clone(),
+            filter: self.filter.clone(),
+            root_options: self
+                .root_paths
+                .iter()
+                .filter(|root| !root.ignore_patterns.is_empty() || root.filter.is_some())
+                .map(|root| RootScanOptions {
+                    root: root.path.clone(),
+                    ignore_patterns: root.ignore_patterns.clone(),
+                    filter: root.filter.clone(),
+                })
+                .collect(),
+            ignore_files: self.ignore_files,
+        }
+    }
+
+    /// Hasher settings taken from this configuration.
+    pub fn hash_options(&self) -> HashOptions {
+        HashOptions {
+            algorithm: self.hash_algorithm,
+            tiers: self.hash_tiers,
+            cache_keys: self.hash_cache_keys,
+            io_limits: self.io_limits,
+            root_io_limits: self.root_io_limits(),
+        }
+    }
+
+    /// The `io_limits` of each root that sets its own.
+    pub fn root_io_limits(&self) -> Vec<RootIoLimits> {
+        self.root_paths
+            .iter()
+            .filter_map(|root| {
+                Some(RootIoLimits {
+                    root: PathBuf::from(&root.path),
+                    limits: root.io_limits?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// One entry of `root_paths`: either a plain path or a table such as
+/// `{ path = "D:/Backup", label = "backup", priority = -1 }`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(from = "RootEntry")]
+pub struct RootConfig {
+    pub path: String,
+    /// Globs ignored under this root, on top of `ignore_patterns`.
+    pub ignore_patterns: Vec<String>,
+    /// Replaces `[filter]` under this root.
+    pub filter: Option<ScanFilter>,
+    /// A name for the root, e.g. "primary" or "backup", stored with each session.
+    pub label: Option<String>,
+    /// Files under a protected root are never auto-marked for deletion.
+    pub protected: bool,
+    /// When auto-marking, copies under the root with the highest priority are
+    /// kept. Defaults to 0.
+    pub priority: i32,
+    /// Read limits for the files under this root, on top of `[io_limits]`.
+    pub io_limits: Option<IoLimits>,
+}
+
+impl From<String> for RootConfig {
+    fn from(path: String) -> Self {
+        Self {
+            path,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&str> for RootConfig {
+    fn from(path: &str) -> Self {
+        path.to_string().into()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RootEntry {
+    Path(String),
+    Table(Box<RootTable>),
+}
+
+#[derive(Deserialize)]
+struct RootTable {
+    path: String,
+    #[serde(default)]
+    ignore_patterns: Vec<String>,
+    #[serde(default)]
+    filter: Option<ScanFilter>,
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    protected: bool,
+    #[serde(default)]
+    priority: i32,
+    #[serde(default)]
+    io_limits: Option<IoLimits>,
+}
+
+impl From<RootEntry> for RootConfig {
+    fn from(entry: RootEntry) -> Self {
+        match entry {
+            RootEntry::Path(path) => path.into(),
+            RootEntry::Table(table) => {
+                let RootTable {
+                    path,
+                    ignore_patterns,
+                    filter,
+                    label,
+                    protected,
+                    priority,
+                    io_limits,
+                } = *table;
+                Self {
+                    path,
+                    ignore_patterns,
+                    filter,
+                    label,
+                    protected,
+                    priority,
+                    io_limits,
+                }
+            }
+        }
+    }
+}
+
+/// Prefix of environment variables that set configuration keys. `__` separates
+/// nested keys and `,` list items, e.g. `SUPER_DUPER_ROOT_PATHS=/data,/backup`
+/// or `SUPER_DUPER_FILTER__MIN_SIZE=4096`.
+pub const ENV_PREFIX: &str = "SUPER_DUPER";
+
+/// Directory holding the system and user configuration files.
+const CONFIG_DIR_NAME: &str = "super-duper";
+const CONFIG_FILE_NAME: &str = "config.toml";
+/// Keys read from the environment as comma-separated lists.
+const ENV_LIST_KEYS: [&str; 6] = [
+    "root_paths",
+    "ignore_patterns",
+    "protected_paths",
+    "filter.include_extensions",
+    "filter.exclude_extensions",
+    "filter.include_patterns",
+];
+
+/// A source of configuration values. Each layer overrides the ones before it,
+/// in the order listed here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigLayer {
+    /// `/etc/super-duper/config.toml`, or `%ProgramData%\super-duper\config.toml`.
+    System(PathBuf),
+    /// `$XDG_CONFIG_HOME/super-duper/config.toml` (`~/.config` by default), or
+    /// `%APPDATA%\super-duper\config.toml`.
+    User(PathBuf),
+    /// `Config.toml` (or `.json`, `.yaml`, ...) in the working directory.
+    Project(PathBuf),
+    /// A file named on the command line, read instead of the project file.
+    Explicit(PathBuf),
+    /// `SUPER_DUPER_*` variables.
+    Environment,
+    /// `--root` and `--ignore`.
+    CommandLine,
+}
+
+impl ConfigLayer {
+    fn path(&self) -> Option<&Path> {
+        match self {
+            ConfigLayer::System(path)
+            | ConfigLayer::User(path)
+            | ConfigLayer::Project(path)
+            | ConfigLayer::Explicit(path) => Some(path),
+            ConfigLayer::Environment | ConfigLayer::CommandLine => None,
+        }
+    }
+}
+
+impl fmt::Display for ConfigLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigLayer::System(path) => write!(f, "system file {}", path.display()),
+            ConfigLayer::User(path) => write!(f, "user file {}", path.display()),
+            ConfigLayer::Project(path) => write!(f, "project file {}", path.display()),
+            ConfigLayer::Explicit(path) => write!(f, "--config file {}", path.display()),
+            ConfigLayer::Environment => write!(f, "environment ({}_*)", ENV_PREFIX),
+            ConfigLayer::CommandLine => f.write_str("command line"),
+        }
+    }
+}
+
+/// Settings from the command line, which take precedence over every file and
+/// the environment.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    /// Read this file instead of the project file. It must exist.
+    pub config_file: Option<PathBuf>,
+    /// Replaces `root_paths` when non-empty.
     pub root_paths: Vec<String>,
+    /// Replaces `ignore_patterns` when non-empty.
     pub ignore_patterns: Vec<String>,
 }
 
+/// A configuration value and the layer that set it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigValue {
+    /// Dotted path, e.g. `filter.min_size`. Lists are reported whole.
+    pub key: String,
+    /// The value in TOML-like notation.
+    pub value: String,
+    pub layer: ConfigLayer,
+}
+
+/// The effective configuration, with where each of its values came from.
+#[derive(Debug, Clone)]
+pub struct LoadedConfig {
+    pub config: AppConfig,
+    /// The layers found, lowest precedence first.
+    pub layers: Vec<ConfigLayer>,
+    /// Every key some layer set, sorted by key. Keys not listed have their defaults.
+    pub values: Vec<ConfigValue>,
+}
+
+/// Load the configuration from every layer, without command-line overrides.
 pub fn load_configuration() -> Result<AppConfig, ConfigError> {
-    let builder = Config::builder()
-        .add_source(ConfigFile::with_name("Config").required(false))
+    load_layered_configuration(&ConfigOverrides::default()).map(|loaded| loaded.config)
+}
+
+/// Load the configuration from the system file, the user file, the project file
+/// (or `overrides.config_file`), `SUPER_DUPER_*` environment variables and the
+/// rest of `overrides`, each overriding the ones before it key by key. Missing
+/// files are skipped, except one named in `overrides`.
+pub fn load_layered_configuration(overrides: &ConfigOverrides) -> Result<LoadedConfig, ConfigError> {
+    let mut files: Vec<ConfigLayer> = [
+        system_config_path().map(ConfigLayer::System),
+        user_config_path().map(ConfigLayer::User),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    files.push(match &overrides.config_file {
+        Some(path) => ConfigLayer::Explicit(path.clone()),
+        None => ConfigLayer::Project(project_config_path()),
+    });
+    load_layers(files, environment_source(), overrides)
+}
+
+fn load_layers(
+    files: Vec<ConfigLayer>,
+    environment: Environment,
+    overrides: &ConfigOverrides,
+) -> Result<LoadedConfig, ConfigError> {
+    let mut layers: Vec<(ConfigLayer, Config)> = Vec::new();
+    for layer in files {
+        let Some(path) = layer.path().filter(|path| path.is_file()) else {
+            if let ConfigLayer::Explicit(path) = &layer {
+                return Err(ConfigError::Message(format!(
+                    "configuration file {} not found",
+                    path.display()
+                )));
+            }
+            continue;
+        };
+        let config = Config::builder().add_source(ConfigFile::from(path)).build()?;
+        layers.push((layer, config));
+    }
+    layers.push((
+        ConfigLayer::Environment,
+        Config::builder().add_source(environment).build()?,
+    ));
+    let mut command_line = Config::builder();
+    if !overrides.root_paths.is_empty() {
+        command_line = command_line.set_override("root_paths", overrides.root_paths.clone())?;
+    }
+    if !overrides.ignore_patterns.is_empty() {
+        command_line =
+            command_line.set_override("ignore_patterns", overrides.ignore_patterns.clone())?;
+    }
+    layers.push((ConfigLayer::CommandLine, command_line.build()?));
+
+    // The environment and command line count as layers only when they set something
+    let mut values = BTreeMap::new();
+    let mut found = Vec::new();
+    for (layer, config) in &layers {
+        let table = config.collect()?;
+        let is_file = layer.path().is_some();
+        if is_file || !table.is_empty() {
+            found.push(layer.clone());
+        }
+        collect_values(table, "", layer, &mut values);
+    }
+
+    let merged = layers
+        .into_iter()
+        .fold(Config::builder(), |builder, (_, config)| builder.add_source(config))
         .build()?;
-    builder.try_deserialize::<AppConfig>()
+    Ok(LoadedConfig {
+        config: merged.try_deserialize()?,
+        layers: found,
+        values: values.into_values().collect(),
+    })
+}
+
+/// Record each leaf of `table` under its dotted key, replacing what earlier layers set.
+fn collect_values(
+    table: config::Map<String, Value>,
+    prefix: &str,
+    layer: &ConfigLayer,
+    values: &mut BTreeMap<String, ConfigValue>,
+) {
+    for (name, value) in table {
+        let key = if prefix.is_empty() {
+            name
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        match value.kind {
+            ValueKind::Table(table) => collect_values(table, &key, layer, values),
+            _ => {
+                let value = format_value(&value);
+                let layer = layer.clone();
+                values.insert(key.clone(), ConfigValue { key, value, layer });
+            }
+        }
+    }
+}
+
+fn format_value(value: &Value) -> String {
+    match &value.kind {
+        ValueKind::String(text) => format!("{:?}", text),
+        ValueKind::Array(items) => {
+            let items: Vec<String> = items.iter().map(format_value).collect();
+            format!("[{}]", items.join(", "))
+        }
+        ValueKind::Table(table) => {
+            let mut entries: Vec<String> = table
+                .iter()
+                .map(|(name, value)| format!("{} = {}", name, format_value(value)))
+                .collect();
+            entries.sort();
+            format!("{{ {} }}", entries.join(", "))
+        }
+        kind => kind.to_string(),
+    }
+}
+
+fn environment_source() -> Environment {
+    ENV_LIST_KEYS.iter().fold(
+        Environment::with_prefix(ENV_PREFIX)
+            .prefix_separator("_")
+            .separator("__")
+            .try_parsing(true)
+            .list_separator(","),
+        |environment, key| environment.with_list_parse_key(key),
+    )
+}
+
+#[cfg(windows)]
+fn system_config_path() -> Option<PathBuf> {
+    env::var_os("ProgramData")
+        .map(|dir| PathBuf::from(dir).join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME))
+}
+
+#[cfg(not(windows))]
+fn system_config_path() -> Option<PathBuf> {
+    Some(Path::new("/etc").join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME))
+}
+
+#[cfg(windows)]
+fn user_config_path() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME))
+}
+
+#[cfg(not(windows))]
+fn user_config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME))
+}
+
+/// `Config` in the working directory with the first extension present, or
+/// `Config.toml` if there's none.
+fn project_config_path() -> PathBuf {
+    ["toml", "json", "yaml", "yml", "ini", "ron", "json5"]
+        .iter()
+        .map(|extension| PathBuf::from(format!("Config.{}", extension)))
+        .find(|path| path.is_file())
+        .unwrap_or_else(|| PathBuf::from("Config.toml"))
+}
+
+// ── Validation ─────────────────────────────────────────────────
+
+/// How much a configuration problem matters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// A scan wouldn't do what the setting asks.
+    Error,
+    /// A scan runs, but the setting is ignored or has a surprising effect.
+    Warning,
+}
+
+/// What a configuration problem is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigIssueKind {
+    NoRoots,
+    InvalidGlob,
+    MissingRoot,
+    RootNotADirectory,
+    UnreadableRoot,
+    /// A root inside another root, which is scanned as part of the outer one.
+    OverlappingRoot,
+    /// An ignore pattern matching a root, which patterns never exclude.
+    RootMatchesIgnorePattern,
+    /// A size or date range no file can fall in.
+    EmptyRange,
+}
+
+impl ConfigIssueKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NoRoots => "no_roots",
+            Self::InvalidGlob => "invalid_glob",
+            Self::MissingRoot => "missing_root",
+            Self::RootNotADirectory => "root_not_a_directory",
+            Self::UnreadableRoot => "unreadable_root",
+            Self::OverlappingRoot => "overlapping_root",
+            Self::RootMatchesIgnorePattern => "root_matches_ignore_pattern",
+            Self::EmptyRange => "empty_range",
+        }
+    }
+}
+
+/// A problem found by [`AppConfig::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    pub kind: ConfigIssueKind,
+    /// The setting at fault, e.g. `root_paths[1]` or `filter.include_patterns[0]`.
+    pub key: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+impl AppConfig {
+    /// Check the settings a scan would otherwise quietly work around: invalid
+    /// globs, which the scanner drops; missing or unreadable roots, which it
+    /// skips; roots inside other roots, which it merges; ignore patterns that
+    /// match a root; and size or date ranges that admit no file.
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+        if self.root_paths.is_empty() {
+            issues.push(ConfigIssue {
+                severity: Severity::Error,
+                kind: ConfigIssueKind::NoRoots,
+                key: "root_paths".to_string(),
+                message: "no root paths configured; set root_paths or pass --root".to_string(),
+            });
+        }
+        check_globs("ignore_patterns", &self.ignore_patterns, &mut issues);
+        check_filter("filter", &self.filter, &mut issues);
+
+        for (index, root) in self.root_paths.iter().enumerate() {
+            let key = format!("root_paths[{}]", index);
+            check_root_dir(&key, &root.path, &mut issues);
+            check_globs(&format!("{}.ignore_patterns", key), &root.ignore_patterns, &mut issues);
+            if let Some(filter) = &root.filter {
+                check_filter(&format!("{}.filter", key), filter, &mut issues);
+            }
+
+            let path = Path::new(&root.path);
+            for glob in self.ignore_patterns.iter().chain(&root.ignore_patterns) {
+                if Pattern::new(glob).is_ok_and(|pattern| pattern.matches_path(path)) {
+                    issues.push(ConfigIssue {
+                        severity: Severity::Error,
+                        kind: ConfigIssueKind::RootMatchesIgnorePattern,
+                        key: key.clone(),
+                        message: format!(
+                            "ignore pattern '{}' matches {} itself, so that root won't be scanned at all",
+                            glob, root.path
+                        ),
+                    });
+                }
+            }
+
+            // Mirrors non_overlapping_directories: an ancestor wins, and of two
+            // identical paths the first is kept
+            let outer = self.root_paths.iter().enumerate().find(|(other, outer)| {
+                *other != index
+                    && path.starts_with(&outer.path)
+                    && (path != Path::new(&outer.path) || *other < index)
+            });
+            if let Some((other, outer)) = outer {
+                issues.push(ConfigIssue {
+                    severity: Severity::Warning,
+                    kind: ConfigIssueKind::OverlappingRoot,
+                    key: key.clone(),
+                    message: format!(
+                        "{} is inside root_paths[{}] ({}) and is scanned as part of it, with that root's settings",
+                        root.path, other, outer.path
+                    ),
+                });
+            }
+        }
+        issues
+    }
+}
+
+fn check_globs(key: &str, globs: &[String], issues: &mut Vec<ConfigIssue>) {
+    for (index, glob) in globs.iter().enumerate() {
+        if let Err(e) = Pattern::new(glob) {
+            issues.push(ConfigIssue {
+                severity: Severity::Error,
+                kind: ConfigIssueKind::InvalidGlob,
+                key: format!("{}[{}]", key, index),
+                message: format!("'{}' is not a valid glob: {}", glob, e),
+            });
+        }
+    }
+}
+
+fn check_filter(key: &str, filter: &ScanFilter, issues: &mut Vec<ConfigIssue>) {
+    check_globs(&format!("{}.include_patterns", key), &filter.include_patterns, issues);
+    if let Some(max_size) = filter.max_size.filter(|max_size| *max_size < filter.min_size) {
+        issues.push(ConfigIssue {
+            severity: Severity::Error,
+            kind: ConfigIssueKind::EmptyRange,
+            key: format!("{}.max_size", key),
+            message: format!(
+                "max_size {} is below min_size {}, so no file is scanned",
+                max_size, filter.min_size
+            ),
+        });
+    }
+    if let (Some(after), Some(before)) = (filter.modified_after, filter.modified_before) {
+        if after >= before {
+            issues.push(ConfigIssue {
+                severity: Severity::Error,
+                kind: ConfigIssueKind::EmptyRange,
+                key: format!("{}.modified_before", key),
+                message: format!(
+                    "modified_before {} is not after modified_after {}, so no file is scanned",
+                    before.to_rfc3339(),
+                    after.to_rfc3339()
+                ),
+            });
+        }
+    }
+}
+
+fn check_root_dir(key: &str, path: &str, issues: &mut Vec<ConfigIssue>) {
+    let (kind, message) = match std::fs::metadata(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            (ConfigIssueKind::MissingRoot, format!("{} does not exist", path))
+        }
+        Err(e) => (ConfigIssueKind::UnreadableRoot, format!("{} can't be read: {}", path, e)),
+        Ok(metadata) if !metadata.is_dir() => (
+            ConfigIssueKind::RootNotADirectory,
+            format!("{} is not a directory", path),
+        ),
+        Ok(_) => match std::fs::read_dir(path) {
+            Ok(_) => return,
+            Err(e) => (ConfigIssueKind::UnreadableRoot, format!("{} can't be listed: {}", path, e)),
+        },
+    };
+    issues.push(ConfigIssue {
+        severity: Severity::Error,
+        kind,
+        key: key.to_string(),
+        message,
+    });
 }
 
 /// Remove directories that are subdirectories of other directories in the list.
@@ -50,6 +722,132 @@ pub fn non_overlapping_directories(dirs: Vec<String>) -> Vec<String> {
 mod tests {
     use super::*;
 
+    fn parse(toml: &str) -> AppConfig {
+        Config::builder()
+            .add_source(ConfigFile::from_str(toml, config::FileFormat::Toml))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let system = dir.path().join("system.toml");
+        let user = dir.path().join("user.toml");
+        let project = dir.path().join("Config.toml");
+        std::fs::write(
+            &system,
+            "root_paths = [\"/srv\"]\nverify_duplicates = true\n[filter]\nmin_size = 10\n",
+        )
+        .unwrap();
+        std::fs::write(&user, "hash_algorithm = \"blake3\"\n[filter]\nmax_size = 99\n").unwrap();
+        std::fs::write(&project, "root_paths = [\"/data\", \"/backup\"]\n").unwrap();
+        let files = vec![
+            ConfigLayer::System(system.clone()),
+            ConfigLayer::User(user.clone()),
+            ConfigLayer::Project(project.clone()),
+            ConfigLayer::User(dir.path().join("missing.toml")),
+        ];
+        let environment = environment_source().source(Some(
+            [
+                ("SUPER_DUPER_FILTER__MIN_SIZE", "4096"),
+                ("SUPER_DUPER_IGNORE_PATTERNS", "**/.git/**,**/target/**"),
+                ("OTHER_VARIABLE", "1"),
+            ]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+        ));
+        let overrides = ConfigOverrides {
+            root_paths: vec!["/mnt/photos".into()],
+            ..Default::default()
+        };
+
+        let loaded = load_layers(files, environment, &overrides).unwrap();
+        let config = &loaded.config;
+        assert_eq!(config.root_paths, [RootConfig::from("/mnt/photos")]);
+        assert_eq!(config.ignore_patterns, ["**/.git/**", "**/target/**"]);
+        assert_eq!(config.hash_algorithm, HashAlgorithm::Blake3);
+        assert!(config.verify_duplicates);
+        assert_eq!(config.filter.min_size, 4096);
+        assert_eq!(config.filter.max_size, Some(99));
+        assert_eq!(loaded.layers.len(), 5);
+
+        let layer_of = |key: &str| {
+            let value = loaded.values.iter().find(|value| value.key == key).unwrap();
+            (value.value.as_str(), value.layer.clone())
+        };
+        assert_eq!(layer_of("verify_duplicates"), ("true", ConfigLayer::System(system)));
+        assert_eq!(layer_of("filter.max_size"), ("99", ConfigLayer::User(user)));
+        assert_eq!(layer_of("filter.min_size"), ("4096", ConfigLayer::Environment));
+        assert_eq!(
+            layer_of("root_paths"),
+            ("[\"/mnt/photos\"]", ConfigLayer::CommandLine)
+        );
+    }
+
+    #[test]
+    fn test_missing_explicit_file_is_an_error() {
+        let missing = ConfigLayer::Explicit(PathBuf::from("/nonexistent/super-duper.toml"));
+        let environment = environment_source().source(Some(Default::default()));
+        assert!(load_layers(vec![missing], environment, &ConfigOverrides::default()).is_err());
+        // Without root_paths the configuration still loads
+        let loaded =
+            load_layers(vec![], environment_source().source(Some(Default::default())), &Default::default())
+                .unwrap();
+        assert!(loaded.config.root_paths.is_empty());
+        assert!(loaded.layers.is_empty());
+    }
+
+    #[test]
+    fn test_validate_reports_problems() {
+        let dir = tempfile::tempdir().unwrap();
+        let data = dir.path().join("data");
+        std::fs::create_dir_all(data.join("photos")).unwrap();
+        let data = data.to_string_lossy().into_owned();
+        let photos = format!("{}/photos", data);
+        let missing = dir.path().join("missing").to_string_lossy().into_owned();
+
+        let config = AppConfig {
+            root_paths: vec![data.clone().into(), photos.into(), missing.into()],
+            ignore_patterns: vec!["[".into(), "**/photos".into()],
+            filter: ScanFilter {
+                min_size: 4096,
+                max_size: Some(1024),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let issues: Vec<_> = config
+            .validate()
+            .into_iter()
+            .map(|issue| (issue.severity, issue.kind, issue.key))
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                (Severity::Error, ConfigIssueKind::InvalidGlob, "ignore_patterns[0]".to_string()),
+                (Severity::Error, ConfigIssueKind::EmptyRange, "filter.max_size".to_string()),
+                (
+                    Severity::Error,
+                    ConfigIssueKind::RootMatchesIgnorePattern,
+                    "root_paths[1]".to_string()
+                ),
+                (Severity::Warning, ConfigIssueKind::OverlappingRoot, "root_paths[1]".to_string()),
+                (Severity::Error, ConfigIssueKind::MissingRoot, "root_paths[2]".to_string()),
+            ]
+        );
+
+        let valid = AppConfig {
+            root_paths: vec![data.into()],
+            ..Default::default()
+        };
+        assert!(valid.validate().is_empty());
+        assert_eq!(AppConfig::default().validate()[0].kind, ConfigIssueKind::NoRoots);
+    }
+
     #[test]
     fn test_non_overlapping_no_overlap() {
         let dirs = vec![
@@ -78,4 +876,155 @@ mod tests {
         // /home/user/docs should be removed as it's under /home/user
         assert!(!result.contains(&"/home/user/docs".to_string()));
     }
+
+    #[test]
+    fn test_root_paths_accept_strings_and_tables() {
+        let toml = r#"
+            root_paths = [
+                "/home/user/docs",
+                { path = "/mnt/backup", label = "backup", priority = -1, protected = true, ignore_patterns = ["**/old/**"], filter = { min_size = 4096 } },
+            ]
+            ignore_patterns = ["**/.git/**"]
+        "#;
+        let config = parse(toml);
+        assert_eq!(config.root_paths[0], RootConfig::from("/home/user/docs"));
+        let backup = config.root("/mnt/backup").unwrap();
+        assert_eq!(backup.label.as_deref(), Some("backup"));
+        assert_eq!(backup.priority, -1);
+        assert!(backup.protected);
+
+        let options = config.walk_options();
+        assert_eq!(options.root_options.len(), 1);
+        let root_options = options.root_options(Path::new("/mnt/backup")).unwrap();
+        assert_eq!(root_options.ignore_patterns, ["**/old/**"]);
+        assert_eq!(root_options.filter.as_ref().unwrap().min_size, 4096);
+        assert!(options.root_options(Path::new("/home/user/docs")).is_none());
+    }
+
+    #[test]
+    fn test_hash_tiers_partial_override() {
+        let toml = r#"
+            root_paths = ["/data"]
+            ignore_patterns = []
+            [hash_tiers]
+            sample_bytes = 4096
+        "#;
+        let config = parse(toml);
+        assert_eq!(config.hash_tiers.sample_bytes, 4096);
+        assert_eq!(config.hash_tiers.partial_bytes, HashTiers::default().partial_bytes);
+        assert_eq!(config.hash_cache_keys, CacheKeyScheme::Path);
+    }
+
+    #[test]
+    fn test_hash_cache_keys_file_id() {
+        let toml = r#"
+            root_paths = ["/data"]
+            ignore_patterns = []
+            hash_cache_keys = "file-id"
+        "#;
+        let config = parse(toml);
+        assert_eq!(config.hash_options().cache_keys, CacheKeyScheme::FileId);
+    }
+
+    #[test]
+    fn test_filesystem_filters() {
+        let toml = r#"
+            root_paths = ["/", "/home"]
+            ignore_patterns = []
+            one_file_system = true
+            [filesystem_types]
+            deny = ["proc", "sysfs"]
+            [[root_filesystem_types]]
+            root = "/home"
+            deny = ["fuse"]
+        "#;
+        let config = parse(toml);
+        let options = config.walk_options();
+        assert!(options.one_file_system);
+        assert_eq!(options.filesystem_filter(Path::new("/")).deny, ["proc", "sysfs"]);
+        assert_eq!(options.filesystem_filter(Path::new("/home")).deny, ["fuse"]);
+        assert!(options.filesystem_filter(Path::new("/home")).allow.is_empty());
+    }
+
+    #[test]
+    fn test_scan_filter() {
+        let toml = r#"
+            root_paths = ["/photos"]
+            ignore_patterns = []
+            [filter]
+            min_size = 4096
+            modified_after = "2024-01-01"
+            modified_before = "2024-06-30T12:00:00+02:00"
+            include_extensions = ["jpg", "heic"]
+            include_hidden = false
+        "#;
+        let config = parse(toml);
+        let filter = config.walk_options().filter;
+        assert_eq!(filter.min_size, 4096);
+        assert_eq!(filter.max_size, None);
+        assert_eq!(
+            filter.modified_after.unwrap().to_rfc3339(),
+            "2024-01-01T00:00:00+00:00"
+        );
+        assert_eq!(
+            filter.modified_before.unwrap().to_rfc3339(),
+            "2024-06-30T10:00:00+00:00"
+        );
+        assert_eq!(filter.include_extensions, ["jpg", "heic"]);
+        assert!(!filter.include_hidden);
+        assert!(filter.include_system);
+    }
+
+    #[test]
+    fn test_symlink_policy() {
+        let toml = r#"
+            root_paths = ["/data"]
+            ignore_patterns = []
+            symlinks = "follow"
+        "#;
+        let config = parse(toml);
+        assert_eq!(config.walk_options().symlinks, SymlinkPolicy::Follow);
+        assert!(!config.walk_options().ignore_files);
+    }
+
+    #[test]
+    fn test_io_limits() {
+        let toml = r#"
+            root_paths = ["/mnt/share"]
+            ignore_patterns = []
+            [io_limits]
+            max_concurrent_reads_per_device = 2
+        "#;
+        let config = parse(toml);
+        let limits = config.hash_options().io_limits;
+        assert_eq!(limits.max_concurrent_reads_per_device, 2);
+        assert_eq!(limits.max_bytes_per_second, 0);
+        assert!(config.hash_options().root_io_limits.is_empty());
+    }
+
+    #[test]
+    fn test_root_io_limits() {
+        let toml = r#"
+            root_paths = [
+                "/data",
+                { path = "/mnt/share", io_limits = { max_concurrent_reads_per_device = 1, max_bytes_per_second = 1048576 } },
+            ]
+            ignore_patterns = []
+            [io_limits]
+            max_concurrent_reads_per_device = 4
+        "#;
+        let config = parse(toml);
+        let options = config.hash_options();
+        assert_eq!(options.io_limits.max_concurrent_reads_per_device, 4);
+        assert_eq!(
+            options.root_io_limits,
+            vec![RootIoLimits {
+                root: PathBuf::from("/mnt/share"),
+                limits: IoLimits {
+                    max_concurrent_reads_per_device: 1,
+                    max_bytes_per_second: 1048576,
+                },
+            }]
+        );
+    }
 }
@@ -1,24 +1,37 @@
 use crate::analysis::{dir_fingerprint, dir_similarity};
+use crate::checkpoint::{self, BucketCheckpointer, ListingCheckpointer, ScanRun};
 use crate::config::{self, AppConfig};
 use crate::error::Error;
-use crate::hasher;
+use crate::hasher::cache::{self, HashCache, RocksDbHashCache};
+use crate::hasher::maintenance::{self, CacheStats, EvictionPolicy, EvictionSummary};
+use crate::hasher::{self, verify, CacheUsage, ContentHash, HashControl, HashStats, PartialHash};
+use crate::pause::PauseToken;
 use crate::platform;
 use crate::progress::ProgressReporter;
-use crate::scanner;
-use crate::storage::models::ScannedFile;
+use crate::scan_error::{ScanError, ScanPhase};
+use crate::scanner::{
+    self, DirListing, DirListings, FileStat, LinkKind, RootDevice, WalkControl,
+};
+use crate::storage::models::{FileLinkRecord, ScanErrorRecord, ScanSession, ScannedFile, SessionRoot};
 use crate::storage::Database;
 use dashmap::DashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::hash::Hasher as _;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant, UNIX_EPOCH};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+use twox_hash::XxHash64;
 
 pub struct ScanEngine {
     config: AppConfig,
     db_path: String,
+    /// Opened on first use unless injected with `with_hash_cache`.
+    hash_cache: Mutex<Option<Arc<dyn HashCache>>>,
     cancel_token: Arc<AtomicBool>,
+    pause_token: Arc<PauseToken>,
 }
 
 #[derive(Debug)]
@@ -26,14 +39,57 @@ pub struct ScanResult {
     pub session_id: i64,
     pub scan_duration: Duration,
     pub hash_duration: Duration,
+    /// Zero when byte-for-byte verification is disabled.
+    pub verify_duration: Duration,
     pub db_write_duration: Duration,
     pub dir_analysis_duration: Duration,
     pub total_files_scanned: usize,
     pub duplicate_groups: usize,
     pub duplicate_files: usize,
     pub wasted_bytes: u64,
+    /// Files that shared a hash with a group but differed byte-for-byte.
+    pub verify_mismatches: usize,
     pub dir_fingerprints: usize,
     pub dir_similarity_pairs: usize,
+    /// Files and bytes each hashing tier processed, plus hash cache hits and misses.
+    pub hash_stats: HashStats,
+    /// Paths skipped because they couldn't be read; listed in `scan_error`.
+    pub scan_errors: usize,
+    /// Symlinks recorded under `symlinks = "record"`; listed in `file_link`.
+    pub symlinks: usize,
+    /// Extra names of hard-linked files. Each file is scanned under one name,
+    /// so these are never reported as duplicates; listed in `file_link`.
+    pub hard_links: usize,
+    /// Space the extra hard-link names would take as separate copies, already saved.
+    pub hard_link_bytes: u64,
+    /// Roots and mount points not entered because of `one_file_system` or the
+    /// filesystem type filters.
+    pub skipped_mounts: Vec<PathBuf>,
+    /// Whether the previous scan of the same roots was updated rather than redone.
+    pub incremental: bool,
+    /// Files whose size bucket was unchanged since the previous scan, so they
+    /// were neither rehashed nor regrouped.
+    pub unchanged_files: usize,
+    /// Directories whose previous listing was used instead of reading them.
+    pub reused_listings: usize,
+    /// How the file index under the roots changed.
+    pub index_changes: IndexChanges,
+    /// Files in size buckets a resumed scan took from its checkpoint instead of
+    /// hashing them again.
+    pub resumed_files: usize,
+}
+
+/// Files the scan added to, changed in or removed from the `scanned_file` index.
+/// Only indexed files count: without `index_all_files`, those sharing a size with
+/// another file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexChanges {
+    /// Indexed for the first time.
+    pub added: usize,
+    /// Indexed before with another size or mtime.
+    pub changed: usize,
+    /// Indexed under a root but no longer found there, and purged.
+    pub removed: usize,
 }
 
 #[derive(Debug)]
@@ -48,7 +104,9 @@ impl ScanEngine {
         Self {
             config,
             db_path: "super_duper.db".to_string(),
+            hash_cache: Mutex::new(None),
             cancel_token: Arc::new(AtomicBool::new(false)),
+            pause_token: Arc::new(PauseToken::new()),
         }
     }
 
@@ -57,6 +115,112 @@ impl ScanEngine {
         self
     }
 
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    /// Use a RocksDB hash cache at `path` instead of the configured one.
+    pub fn with_cache_path(mut self, path: &str) -> Self {
+        self.config.hash_cache_path = Some(path.to_string());
+        self.hash_cache = Mutex::new(None);
+        self
+    }
+
+    /// Use an already-open hash cache, e.g. `MemoryHashCache` in tests.
+    pub fn with_hash_cache(mut self, cache: Arc<dyn HashCache>) -> Self {
+        self.hash_cache = Mutex::new(Some(cache));
+        self
+    }
+
+    /// The engine's hash cache, opening the RocksDB cache on first use.
+    pub fn hash_cache(&self) -> Result<Arc<dyn HashCache>, Error> {
+        let mut guard = self
+            .hash_cache
+            .lock()
+            .map_err(|e| Error::Cache(format!("Failed to lock cache: {}", e)))?;
+        if let Some(cache) = guard.as_ref() {
+            return Ok(cache.clone());
+        }
+
+        let path = self
+            .config
+            .hash_cache_path
+            .clone()
+            .unwrap_or_else(cache::default_cache_path);
+        let cache: Arc<dyn HashCache> =
+            Arc::new(RocksDbHashCache::open(&path).map_err(|e| Error::Cache(e.to_string()))?);
+        *guard = Some(cache.clone());
+        Ok(cache)
+    }
+
+    /// Entry count and disk size of the hash cache, with the hit/miss counts of the
+    /// most recently completed scan.
+    pub fn hash_cache_stats(&self) -> Result<CacheStats, Error> {
+        let mut stats = maintenance::stats(self.hash_cache()?.as_ref())?;
+        let db = Database::open(&self.db_path)?;
+        stats.last_scan = db
+            .get_last_cache_usage()?
+            .map(|(hits, misses)| CacheUsage {
+                hits: hits as u64,
+                misses: misses as u64,
+            });
+        Ok(stats)
+    }
+
+    /// A page of the paths `session_id` skipped, with the total count.
+    pub fn scan_errors(
+        &self,
+        session_id: i64,
+        offset: i64,
+        limit: i64,
+    ) -> Result<(Vec<ScanErrorRecord>, i64), Error> {
+        let db = Database::open(&self.db_path)?;
+        Ok(db.get_scan_errors(session_id, offset, limit)?)
+    }
+
+    /// Counts of the paths `session_id` skipped, by (phase, error kind).
+    pub fn scan_error_summary(&self, session_id: i64) -> Result<Vec<(String, String, i64)>, Error> {
+        let db = Database::open(&self.db_path)?;
+        Ok(db.get_scan_error_summary(session_id)?)
+    }
+
+    /// The roots `session_id` scanned, with the device and filesystem of each.
+    pub fn session_roots(&self, session_id: i64) -> Result<Vec<SessionRoot>, Error> {
+        let db = Database::open(&self.db_path)?;
+        Ok(db.get_session_roots(session_id)?)
+    }
+
+    /// A page of the symlinks and hard links `session_id` recorded, with the total count.
+    pub fn file_links(
+        &self,
+        session_id: i64,
+        offset: i64,
+        limit: i64,
+    ) -> Result<(Vec<FileLinkRecord>, i64), Error> {
+        let db = Database::open(&self.db_path)?;
+        Ok(db.get_file_links(session_id, offset, limit)?)
+    }
+
+    /// Evict dead and out-of-policy entries from the hash cache, then compact it
+    /// if `compact` is set.
+    pub fn evict_hash_cache(
+        &self,
+        policy: &EvictionPolicy,
+        compact: bool,
+    ) -> Result<EvictionSummary, Error> {
+        let cache = self.hash_cache()?;
+        let summary = maintenance::evict(cache.as_ref(), policy)?;
+        if compact {
+            cache.compact()?;
+        }
+        Ok(summary)
+    }
+
+    /// Compact the hash cache, reclaiming space left by evicted entries.
+    pub fn compact_hash_cache(&self) -> Result<(), Error> {
+        Ok(self.hash_cache()?.compact()?)
+    }
+
     /// Request cancellation of the current scan.
     pub fn cancel(&self) {
         self.cancel_token.store(true, Ordering::Relaxed);
@@ -67,54 +231,281 @@ impl ScanEngine {
         self.cancel_token.clone()
     }
 
+    /// Pause the current scan: walker and hasher threads stop between files and
+    /// wait, keeping everything found so far, until `resume` or `cancel`.
+    /// Returns false if already paused.
+    pub fn pause(&self) -> bool {
+        self.pause_token.pause()
+    }
+
+    /// Resume a paused scan. Returns false if it wasn't paused.
+    pub fn resume(&self) -> bool {
+        self.pause_token.resume()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pause_token.is_paused()
+    }
+
+    /// Get a clone of the pause token (for signal handlers and the FFI layer).
+    pub fn pause_token(&self) -> Arc<PauseToken> {
+        self.pause_token.clone()
+    }
+
     /// Run the full duplicate detection pipeline:
     /// 1. Parallel directory scan (build file_size → paths map)
-    /// 2. Two-tier content hashing (partial 1KB, then full on matches)
-    /// 3. Write results to SQLite
+    /// 2. Tiered content hashing (partial, head/middle/tail samples, then full with the configured algorithm)
+    /// 3. Optional byte-for-byte verification of each group (`verify_duplicates`)
+    /// 4. Write results to SQLite
+    ///
+    /// Unless `full_rescan` is set, a previous scan of the same roots with the same
+    /// settings is updated in place: unchanged directories aren't re-read, and
+    /// only size buckets with new, changed or vanished files are rehashed and
+    /// regrouped.
+    ///
+    /// The scan checkpoints into a session of its own, marked 'cancelled' if it
+    /// is cancelled and 'aborted' if it fails, which `resume_scan` can pick up.
+    /// `pause` stops it in place for as long as needed without ending it.
     pub fn scan(&self, progress: &dyn ProgressReporter) -> Result<ScanResult, Error> {
-        // Reset cancel token for new scan
+        self.run_scan(None, progress)
+    }
+
+    /// Resume a cancelled or aborted scan of the same roots with the same
+    /// settings. Directories it listed and size buckets it hashed aren't read
+    /// again unless they've changed since.
+    pub fn resume_scan(
+        &self,
+        session_id: i64,
+        progress: &dyn ProgressReporter,
+    ) -> Result<ScanResult, Error> {
+        self.run_scan(Some(session_id), progress)
+    }
+
+    /// Cancelled or aborted scans of the configured roots, newest first.
+    pub fn resumable_sessions(&self) -> Result<Vec<ScanSession>, Error> {
+        let db = Database::open(&self.db_path)?;
+        let roots = config::non_overlapping_directories(self.config.root_path_strings());
+        Ok(db.get_resumable_sessions(&roots)?)
+    }
+
+    /// Mark sessions left 'running' by a scan that crashed or was killed as
+    /// 'aborted', so they can be resumed. Returns their ids.
+    pub fn abort_orphaned_sessions(&self) -> Result<Vec<i64>, Error> {
+        let db = Database::open(&self.db_path)?;
+        checkpoint::abort_orphaned_sessions(&db, &self.db_path)
+    }
+
+    fn run_scan(&self, resume: Option<i64>, progress: &dyn ProgressReporter) -> Result<ScanResult, Error> {
+        // Reset cancel and pause tokens for new scan
         self.cancel_token.store(false, Ordering::Relaxed);
+        self.pause_token.resume();
 
         let non_overlapping =
-            config::non_overlapping_directories(self.config.root_paths.clone());
+            config::non_overlapping_directories(self.config.root_path_strings());
         info!("Processing directories: {:?}", non_overlapping);
 
+        let db = Database::open(&self.db_path)?;
+        checkpoint::abort_orphaned_sessions(&db, &self.db_path)?;
+        let settings = scan_settings_digest(&self.config);
+        let run = match resume {
+            Some(session_id) => {
+                ScanRun::resume(&db, &self.db_path, session_id, &non_overlapping, &settings)?
+            }
+            None => ScanRun::start(&db, &self.db_path, &non_overlapping, &settings)?,
+        };
+
+        let result = self.scan_into(&db, &run, &non_overlapping, progress);
+        if let Err(e) = &result {
+            let status = match e {
+                Error::Cancelled => "cancelled",
+                _ => "aborted",
+            };
+            db.set_session_status(run.session_id, status)?;
+            info!("Session {} {}; it can be resumed", run.session_id, status);
+        }
+        result
+    }
+
+    /// The pipeline behind `scan` and `resume_scan`, checkpointing into `run`.
+    fn scan_into(
+        &self,
+        db: &Database,
+        run: &ScanRun,
+        non_overlapping: &[String],
+        progress: &dyn ProgressReporter,
+    ) -> Result<ScanResult, Error> {
         let root_path_slices: Vec<&str> = non_overlapping.iter().map(|s| s.as_str()).collect();
         let ignore_pattern_slices: Vec<&str> =
             self.config.ignore_patterns.iter().map(|s| s.as_str()).collect();
 
+        let reusable_session = db.find_session_for_paths(non_overlapping)?;
+        let previous_session = reusable_session.filter(|_| !self.config.full_rescan);
+        let mut previous_listings: DirListings = match previous_session {
+            Some(session_id) => db
+                .get_dir_listings(session_id)?
+                .into_iter()
+                .map(|listing| (listing.path.clone(), listing))
+                .collect(),
+            None => DirListings::new(),
+        };
+        // Listings the interrupted walk took are newer
+        if run.resumed {
+            previous_listings.extend(
+                db.get_dir_listings(run.session_id)?
+                    .into_iter()
+                    .map(|listing| (listing.path.clone(), listing)),
+            );
+        }
+
         // Phase 1: Scan
         info!("Scanning files...");
         progress.on_scan_start();
         let scan_start = Instant::now();
-        let size_to_files_map = scanner::build_size_to_files_map(
+        // Listings are checkpointed as they're taken, so a crash mid-walk keeps them
+        let listing_checkpointer = ListingCheckpointer::open(&self.db_path, run.session_id)?;
+        let on_listing = |listing: &DirListing| listing_checkpointer.record(listing.clone());
+        let mut walk = scanner::build_size_to_files_map_incremental(
             &root_path_slices,
             &ignore_pattern_slices,
-            &self.cancel_token,
+            &self.config.walk_options(),
+            &previous_listings,
+            WalkControl {
+                cancel_token: &self.cancel_token,
+                pause: Some(&self.pause_token),
+                on_listing: Some(&on_listing),
+            },
             progress,
-        )?;
+        );
+        drop(previous_listings);
         let scan_duration = scan_start.elapsed();
 
+        // Even a cancelled walk is checkpointed: each listing is complete
+        listing_checkpointer.flush()?;
         if self.cancel_token.load(Ordering::Relaxed) {
             return Err(Error::Cancelled);
         }
 
-        let stats = compute_scan_stats(&size_to_files_map);
+        let stats = compute_scan_stats(&walk.files);
         progress.on_scan_complete(stats.total_files, scan_duration.as_secs_f64());
         debug!(
-            "Scan completed in {:.2}s — {} distinct sizes, {} files, {} bytes total",
+            "Scan completed in {:.2}s — {} distinct sizes, {} files, {} bytes total on {} devices",
             scan_duration.as_secs_f64(),
             stats.distinct_sizes,
             stats.total_files,
             stats.total_size,
+            walk.devices.device_count(),
         );
+        let mut scan_errors = std::mem::take(&mut walk.errors);
+        let links = std::mem::take(&mut walk.links);
+
+        // Indexed files under the roots that aren't among these have gone, unless
+        // they're below a path the walk couldn't examine
+        let canonical_roots = canonical_roots(&walk.roots);
+        let walked = WalkedFiles {
+            roots: &canonical_roots,
+            stats: &walk.stats,
+        };
+        let seen: HashSet<PathBuf> = walk
+            .files
+            .iter()
+            .flat_map(|entry| {
+                let paths: Vec<PathBuf> = entry
+                    .value()
+                    .iter()
+                    .map(|path| canonical_under_root(&canonical_roots, path))
+                    .collect();
+                paths
+            })
+            .collect();
+        let unexamined: Vec<PathBuf> = scan_errors
+            .iter()
+            .map(|error| canonical_under_root(&canonical_roots, &error.path))
+            .collect();
+
+        // Size buckets the previous scan already grouped are left out of hashing
+        let settings = scan_settings_digest(&self.config);
+        let plan = match previous_session {
+            Some(session_id)
+                if db.get_session_scan_settings(session_id)?.as_deref() == Some(settings.as_str()) =>
+            {
+                Some(plan_rescan(db, session_id, &walk.files, walked)?)
+            }
+            Some(session_id) => {
+                info!("Settings changed since session {}, rescanning in full", session_id);
+                None
+            }
+            None => None,
+        };
+        if let Some(plan) = &plan {
+            info!(
+                "Updating session {}: {} unchanged files kept, {} sizes regrouped, {} files gone",
+                plan.session_id,
+                plan.unchanged_files,
+                plan.affected_sizes.len(),
+                plan.departed.len(),
+            );
+            walk.files.retain(|size, _| !plan.unchanged_sizes.contains(size));
+        }
+        // So are those the interrupted scan hashed
+        let restored = if run.resumed {
+            checkpoint::restore_buckets(db, run.session_id, &walk.files, walked)?
+        } else {
+            Default::default()
+        };
+        if !restored.sizes.is_empty() {
+            info!(
+                "Taking {} files in {} size buckets from session {}'s checkpoint",
+                restored.files,
+                restored.sizes.len(),
+                run.session_id,
+            );
+            walk.files.retain(|size, _| !restored.sizes.contains(size));
+        }
+        // The hasher never reads a file no other file shares a size with
+        let unhashed: Vec<PathBuf> = if self.config.index_all_files {
+            walk.files
+                .iter()
+                .filter(|entry| entry.value().len() == 1)
+                .map(|entry| entry.value()[0].clone())
+                .collect()
+        } else {
+            Vec::new()
+        };
 
         // Phase 2: Hash
         info!("Building content hash for possible dupes...");
         progress.on_hash_start();
         let hash_start = Instant::now();
-        let content_hash_map =
-            hasher::build_content_hash_map(size_to_files_map, &self.cancel_token, progress)?;
+        let known_partials =
+            load_partial_hashes(db, self.config.hash_tiers.partial_bytes, &walk.files)?;
+        let hash_cache = self.hash_cache()?;
+        let checkpointer = BucketCheckpointer::open(&self.db_path, run.session_id)?;
+        let on_bucket = |bucket| checkpointer.record(bucket);
+        let hash_outcome = hasher::build_content_hash_map(
+            walk.files,
+            self.config.hash_options(),
+            &known_partials,
+            hash_cache.as_ref(),
+            &walk.devices,
+            HashControl {
+                cancel_token: &self.cancel_token,
+                pause: Some(&self.pause_token),
+                on_bucket: Some(&on_bucket),
+            },
+            progress,
+        )?;
+        checkpointer.flush()?;
+        drop(known_partials);
+        let hash_stats = hash_outcome.stats;
+        scan_errors.extend(hash_outcome.errors);
+        let content_hash_map = hash_outcome.duplicates;
+        let partial_hashes = hash_outcome.partial_hashes;
+        for (hash, paths) in restored.duplicates {
+            content_hash_map.entry(hash).or_default().extend(paths);
+        }
+        for (path, partial) in restored.partial_hashes {
+            partial_hashes.insert(path, partial);
+        }
         let hash_duration = hash_start.elapsed();
 
         if self.cancel_token.load(Ordering::Relaxed) {
@@ -124,18 +515,107 @@ impl ScanEngine {
         let dupe_group_count = content_hash_map.len();
         progress.on_hash_complete(dupe_group_count, hash_duration.as_secs_f64());
         debug!(
-            "Hash completed in {:.2}s — {} duplicate groups",
+            "Hash completed in {:.2}s — {} duplicate groups, {} of {} partial hashes reused, \
+             {} cache hits, {} cache misses",
             hash_duration.as_secs_f64(),
             dupe_group_count,
+            hash_stats.partial_hashes_reused,
+            partial_hashes.len(),
+            hash_stats.cache.hits,
+            hash_stats.cache.misses,
         );
 
+        // Optional: byte-for-byte verification
+        let (groups, verify_mismatches, verify_duration) = if self.config.verify_duplicates {
+            info!("Verifying duplicate groups byte-for-byte...");
+            progress.on_verify_start();
+            let verify_start = Instant::now();
+            let outcome = verify::verify_duplicate_groups(
+                content_hash_map,
+                self.config.io_limits,
+                &self.config.root_io_limits(),
+                &walk.devices,
+                &self.cancel_token,
+                &self.pause_token,
+                progress,
+            );
+            if self.cancel_token.load(Ordering::Relaxed) {
+                return Err(Error::Cancelled);
+            }
+            let verify_duration = verify_start.elapsed();
+            progress.on_verify_complete(
+                outcome.groups.len(),
+                outcome.mismatched_files,
+                verify_duration.as_secs_f64(),
+            );
+            scan_errors.extend(outcome.errors);
+            (outcome.groups, outcome.mismatched_files, verify_duration)
+        } else {
+            (content_hash_map.into_iter().collect(), 0, Duration::ZERO)
+        };
+
         // Phase 3: Write to SQLite
         info!("Writing to database...");
         progress.on_db_write_start();
         let db_start = Instant::now();
-        let db = Database::open(&self.db_path)?;
-        let (groups_written, files_written, wasted_bytes, session_id) =
-            write_to_database(&db, &content_hash_map, &non_overlapping)?;
+        let DbWriteSummary {
+            groups_written,
+            duplicate_files,
+            files_written,
+            wasted_bytes,
+            session_id,
+            mut affected_dirs,
+            added_files,
+            changed_files,
+        } = write_to_database(
+            db,
+            &groups,
+            IndexedFiles {
+                partial_hashes: &partial_hashes,
+                unhashed: &unhashed,
+                walked,
+            },
+            SessionIds {
+                run: run.session_id,
+                reusable: reusable_session,
+            },
+            &self.config,
+            plan.as_ref(),
+            &mut scan_errors,
+        )?;
+        // Other interrupted scans of the roots are superseded
+        for session in db.get_resumable_sessions(non_overlapping)? {
+            db.delete_session(session.id)?;
+        }
+        let removed = purge_vanished_files(db, &canonical_roots, &seen, &unexamined)?;
+        drop(seen);
+        if !removed.is_empty() {
+            info!("{} indexed files have gone since the last scan", removed.len());
+        }
+        affected_dirs.extend(removed.iter().filter_map(|path| parent_dir(path)));
+        let index_changes = IndexChanges {
+            added: added_files,
+            changed: changed_files,
+            removed: removed.len(),
+        };
+        db.replace_dir_listings(session_id, &walk.listings)?;
+        db.set_session_cache_usage(session_id, hash_stats.cache.hits, hash_stats.cache.misses)?;
+        db.replace_scan_errors(session_id, &scan_errors)?;
+        db.replace_file_links(session_id, &links)?;
+        let session_roots: Vec<_> = walk
+            .roots
+            .iter()
+            .filter_map(|root| Some((root, self.config.root(root.path.to_str()?)?)))
+            .collect();
+        db.replace_session_roots(session_id, &session_roots)?;
+        let hard_links: Vec<_> = links
+            .iter()
+            .filter(|link| link.kind == LinkKind::HardLink)
+            .collect();
+        let hard_link_bytes = hard_links.iter().map(|link| link.file_size).sum();
+        if !scan_errors.is_empty() {
+            warn!("{} paths could not be scanned (session {})", scan_errors.len(), session_id);
+        }
         let db_duration = db_start.elapsed();
         progress.on_db_write_complete(files_written, db_duration.as_secs_f64());
         debug!(
@@ -150,9 +630,22 @@ impl ScanEngine {
         info!("Analyzing directory structure...");
         progress.on_dir_analysis_start();
         let dir_start = Instant::now();
-        let dir_fingerprints = dir_fingerprint::build_directory_fingerprints(&db)
+        // An incremental scan only revisits the directories it touched, and their pairs
+        let in_place = plan.is_some() && dir_fingerprint::has_directory_tree(db)?;
+        let (dir_fingerprints, dir_similarity_pairs) = if in_place {
+            (
+                dir_fingerprint::update_directory_fingerprints(db, &affected_dirs),
+                dir_similarity::update_directory_similarity(db, 0.5, &affected_dirs),
+            )
+        } else {
+            (
+                dir_fingerprint::build_directory_fingerprints(db),
+                dir_similarity::compute_directory_similarity(db, 0.5),
+            )
+        };
+        let dir_fingerprints = dir_fingerprints
             .unwrap_or_else(|e| { tracing::warn!("Directory fingerprint failed: {}", e); 0 });
-        let dir_similarity_pairs = dir_similarity::compute_directory_similarity(&db, 0.5)
+        let dir_similarity_pairs = dir_similarity_pairs
             .unwrap_or_else(|e| { tracing::warn!("Directory similarity failed: {}", e); 0 });
         let dir_duration = dir_start.elapsed();
         progress.on_dir_analysis_complete(dir_fingerprints, dir_similarity_pairs, dir_duration.as_secs_f64());
@@ -167,14 +660,27 @@ impl ScanEngine {
             session_id,
             scan_duration,
             hash_duration,
+            verify_duration,
             db_write_duration: db_duration,
             dir_analysis_duration: dir_duration,
             total_files_scanned: stats.total_files,
             duplicate_groups: groups_written,
-            duplicate_files: files_written,
+            duplicate_files,
             wasted_bytes,
+            verify_mismatches,
             dir_fingerprints,
             dir_similarity_pairs,
+            hash_stats,
+            scan_errors: scan_errors.len(),
+            symlinks: links.len() - hard_links.len(),
+            hard_links: hard_links.len(),
+            hard_link_bytes,
+            skipped_mounts: walk.skipped_mounts,
+            incremental: plan.is_some(),
+            unchanged_files: plan.as_ref().map_or(0, |plan| plan.unchanged_files),
+            reused_listings: walk.reused_listings,
+            index_changes,
+            resumed_files: restored.files,
         })
     }
 }
@@ -197,103 +703,475 @@ fn compute_scan_stats(map: &DashMap<u64, Vec<PathBuf>>) -> ScanStats {
     }
 }
 
+/// Stored partial hashes for the configured head size, keyed by canonical path.
+/// Only sizes the hasher will read, i.e. shared by more than one file, are loaded.
+fn load_partial_hashes(
+    db: &Database,
+    partial_bytes: usize,
+    files: &DashMap<u64, Vec<PathBuf>>,
+) -> Result<HashMap<PathBuf, PartialHash>, Error> {
+    let sizes: Vec<i64> = files
+        .iter()
+        .filter(|entry| entry.value().len() > 1)
+        .map(|entry| *entry.key() as i64)
+        .collect();
+    let rows = db.get_partial_hashes(partial_bytes as i64, &sizes)?;
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let record = PartialHash {
+                file_size: row.file_size as u64,
+                modified: Duration::new(row.last_modified as u64, row.last_modified_nanos as u32),
+                hash: row.partial_hash as u64,
+            };
+            (PathBuf::from(row.canonical_path), record)
+        })
+        .collect())
+}
+
+/// Digest of the settings that decide which files are scanned and how they're
+/// grouped. Groups are only kept across scans with the same digest.
+fn scan_settings_digest(config: &AppConfig) -> String {
+    let settings = format!(
+        "{:?} {:?} {} {:?} {} {}",
+        config.walk_options(),
+        config.ignore_patterns,
+        config.hash_algorithm.as_str(),
+        config.hash_tiers,
+        config.verify_duplicates,
+        config.index_all_files,
+    );
+    let mut hasher = XxHash64::with_seed(0);
+    hasher.write(settings.as_bytes());
+    format!("{:016x}", hasher.finish())
+}
+
+/// What an incremental scan keeps from the previous scan of the same roots.
+struct RescanPlan {
+    session_id: i64,
+    /// Sizes whose files are exactly those the session saw, unmodified, so their
+    /// groups still stand.
+    unchanged_sizes: HashSet<u64>,
+    /// Sizes whose groups are rebuilt from this scan's hashes.
+    affected_sizes: Vec<i64>,
+    /// Files the session saw that are no longer found at their old size.
+    departed: Vec<String>,
+    /// Directories of every file the session saw at an affected size, whose
+    /// duplicated shares may change.
+    regrouped_dirs: BTreeSet<String>,
+    unchanged_files: usize,
+    unchanged_bytes: u64,
+}
+
+/// Compare the walk's size buckets with the files `session_id` last saw.
+///
+/// Every file in a bucket of two or more was partially hashed and stored, so a
+/// bucket whose members and mtimes match the stored files of its size can't have
+/// gained, lost or changed a duplicate.
+fn plan_rescan(
+    db: &Database,
+    session_id: i64,
+    files: &DashMap<u64, Vec<PathBuf>>,
+    walked: WalkedFiles,
+) -> Result<RescanPlan, Error> {
+    let mut stored: HashMap<u64, HashMap<String, Duration>> = HashMap::new();
+    for state in db.get_session_file_states(session_id)? {
+        let modified = Duration::new(state.last_modified as u64, state.last_modified_nanos as u32);
+        stored
+            .entry(state.file_size as u64)
+            .or_default()
+            .insert(state.canonical_path, modified);
+    }
+
+    let mut plan = RescanPlan {
+        session_id,
+        unchanged_sizes: HashSet::new(),
+        affected_sizes: Vec::new(),
+        departed: Vec::new(),
+        regrouped_dirs: BTreeSet::new(),
+        unchanged_files: 0,
+        unchanged_bytes: 0,
+    };
+    for entry in files.iter() {
+        let (size, paths) = (*entry.key(), entry.value());
+        match stored.remove(&size) {
+            Some(previous) if is_unchanged(paths, &previous, walked) => {
+                plan.unchanged_sizes.insert(size);
+                plan.unchanged_files += paths.len();
+                plan.unchanged_bytes += size * paths.len() as u64;
+            }
+            Some(previous) => {
+                plan.regrouped_dirs.extend(previous.keys().filter_map(|path| parent_dir(path)));
+                let current: HashSet<String> = paths
+                    .iter()
+                    .map(|path| walked.canonical(path).to_string_lossy().into_owned())
+                    .collect();
+                plan.departed
+                    .extend(previous.into_keys().filter(|path| !current.contains(path)));
+                plan.affected_sizes.push(size as i64);
+            }
+            None if paths.len() > 1 => plan.affected_sizes.push(size as i64),
+            None => {}
+        }
+    }
+
+    // Sizes no file has any more
+    for (size, previous) in stored {
+        plan.regrouped_dirs.extend(previous.keys().filter_map(|path| parent_dir(path)));
+        plan.affected_sizes.push(size as i64);
+        plan.departed.extend(previous.into_keys());
+    }
+    Ok(plan)
+}
+
+fn parent_dir(path: &str) -> Option<String> {
+    Path::new(path).parent().map(|p| p.to_string_lossy().into_owned())
+}
+
+/// Whether `paths` are exactly the files in `previous`, none modified since the
+/// walk read them.
+pub(crate) fn is_unchanged(
+    paths: &[PathBuf],
+    previous: &HashMap<String, Duration>,
+    walked: WalkedFiles,
+) -> bool {
+    paths.len() == previous.len()
+        && paths.iter().all(|path| {
+            walked.stats.get(path).is_some_and(|stat| {
+                let canonical = walked.canonical(path);
+                previous.get(canonical.to_string_lossy().as_ref()) == Some(&stat.modified)
+            })
+        })
+}
+
+struct DbWriteSummary {
+    groups_written: usize,
+    duplicate_files: usize,
+    files_written: usize,
+    wasted_bytes: u64,
+    session_id: i64,
+    /// Directories whose files were written, regrouped or have gone, for
+    /// fingerprinting.
+    affected_dirs: Vec<String>,
+    /// Written files the index didn't hold, or held with another size or mtime.
+    added_files: usize,
+    changed_files: usize,
+}
+
+/// What the walk learnt about the files it found, so they aren't read again.
+#[derive(Clone, Copy)]
+pub(crate) struct WalkedFiles<'a> {
+    /// Each root with its canonical path, from `canonical_roots`.
+    roots: &'a [(PathBuf, PathBuf)],
+    stats: &'a DashMap<PathBuf, FileStat>,
+}
+
+impl WalkedFiles<'_> {
+    fn canonical(&self, path: &Path) -> PathBuf {
+        canonical_under_root(self.roots, path)
+    }
+}
+
+/// Each root the walk found, with its canonical path.
+fn canonical_roots(roots: &[RootDevice]) -> Vec<(PathBuf, PathBuf)> {
+    roots
+        .iter()
+        .filter_map(|root| Some((root.path.clone(), fs::canonicalize(&root.path).ok()?)))
+        .collect()
+}
+
+/// The canonical form of a walked path. Below a root, only the root itself may
+/// be non-canonical; followed symlinks are walked under their canonical paths.
+fn canonical_under_root(roots: &[(PathBuf, PathBuf)], path: &Path) -> PathBuf {
+    roots
+        .iter()
+        .find_map(|(root, canonical)| {
+            let relative = path.strip_prefix(root).ok()?;
+            Some(if relative.as_os_str().is_empty() {
+                canonical.clone()
+            } else {
+                canonical.join(relative)
+            })
+        })
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Purge indexed files below the roots that the walk didn't find and that aren't
+/// at or below a path it couldn't examine, e.g. an unreadable directory. Returns
+/// their paths.
+fn purge_vanished_files(
+    db: &Database,
+    roots: &[(PathBuf, PathBuf)],
+    seen: &HashSet<PathBuf>,
+    unexamined: &[PathBuf],
+) -> Result<Vec<String>, Error> {
+    let mut vanished: Vec<(i64, String)> = Vec::new();
+    for (_, canonical_root) in roots {
+        for (id, path) in db.get_indexed_files_under(&canonical_root.to_string_lossy())? {
+            let file = Path::new(&path);
+            if !seen.contains(file) && !unexamined.iter().any(|dir| file.starts_with(dir)) {
+                vanished.push((id, path));
+            }
+        }
+    }
+    let ids: Vec<i64> = vanished.iter().map(|(id, _)| *id).collect();
+    db.purge_files(&ids)?;
+    Ok(vanished.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Files indexed besides the members of duplicate groups.
+struct IndexedFiles<'a> {
+    /// Every candidate's partial hash, kept for the next scan to reuse.
+    partial_hashes: &'a DashMap<PathBuf, PartialHash>,
+    /// Files never read because no other file shares their size. Only collected
+    /// under `index_all_files`.
+    unhashed: &'a [PathBuf],
+    walked: WalkedFiles<'a>,
+}
+
+/// The canonical path, size and mtime of a file being indexed, as the walk read
+/// them. A file the walk didn't record, e.g. one taken from a checkpoint, is read
+/// now; one that can't be examined is recorded in `scan_errors` instead.
+fn stat_for_index(
+    path: &Path,
+    walked: WalkedFiles,
+    scan_errors: &mut Vec<ScanError>,
+) -> Option<(PathBuf, u64, Duration)> {
+    if let Some(stat) = walked.stats.get(path) {
+        return Some((walked.canonical(path), stat.size, stat.modified));
+    }
+    let metadata = match fs::metadata(path) {
+        Ok(m) => m,
+        Err(e) => {
+            tracing::error!("Error reading metadata for {}: {}", path.display(), e);
+            scan_errors.push(ScanError::new(path, ScanPhase::Metadata, &e));
+            return None;
+        }
+    };
+
+    let canonical_path = match fs::canonicalize(path) {
+        Ok(p) => p,
+        Err(e) => {
+            tracing::error!("Error canonicalizing {}: {}", path.display(), e);
+            scan_errors.push(ScanError::new(path, ScanPhase::Canonicalize, &e));
+            return None;
+        }
+    };
+
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    Some((canonical_path, metadata.len(), modified))
+}
+
+/// Build a `scanned_file` record for a canonical path.
+fn scanned_file_record(
+    canonical_path: &Path,
+    file_size: u64,
+    modified: Duration,
+    session_id: i64,
+) -> ScannedFile {
+    let drive_letter = match platform::get_drive_letter(canonical_path) {
+        Some(drive) => drive.to_string_lossy().into_owned(),
+        None => String::new(),
+    };
+
+    let parent_dir = canonical_path
+        .parent()
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let file_name = canonical_path
+        .file_name()
+        .map(|f| f.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    ScannedFile {
+        id: 0,
+        canonical_path: canonical_path.to_string_lossy().into_owned(),
+        file_name,
+        parent_dir,
+        drive_letter,
+        file_size: file_size as i64,
+        last_modified: modified.as_secs() as i64,
+        last_modified_nanos: modified.subsec_nanos() as i64,
+        partial_hash: None,
+        partial_hash_len: None,
+        content_hash: None,
+        content_digest: None,
+        last_seen_session_id: Some(session_id),
+        marked_deleted: false,
+    }
+}
+
+/// The sessions a scan's results may be written to.
+struct SessionIds {
+    /// The session the scan checkpointed into.
+    run: i64,
+    /// The last completed session of the same roots, if any.
+    reusable: Option<i64>,
+}
+
 fn write_to_database(
     db: &Database,
-    content_hash_map: &DashMap<u64, Vec<PathBuf>>,
-    root_paths: &[String],
-) -> Result<(usize, usize, u64, i64), Error> {
-    // Find or create session (idempotent: reuses existing session for same paths)
-    let session_id = db.find_or_create_session(root_paths)?;
+    groups: &[(ContentHash, Vec<PathBuf>)],
+    indexed: IndexedFiles,
+    sessions: SessionIds,
+    config: &AppConfig,
+    plan: Option<&RescanPlan>,
+    scan_errors: &mut Vec<ScanError>,
+) -> Result<DbWriteSummary, Error> {
+    // An incremental scan only drops the groups it rebuilds; a full one replaces
+    // all groups of the roots' last session. Either way that session is reused
+    // and the run's own is dropped with its checkpoint.
+    let session_id = match (plan, sessions.reusable) {
+        (Some(plan), _) => {
+            db.reset_scan_session(plan.session_id)?;
+            db.delete_duplicate_groups_of_sizes(plan.session_id, &plan.affected_sizes)?;
+            db.clear_last_seen(&plan.departed)?;
+            plan.session_id
+        }
+        (None, Some(session_id)) => {
+            db.delete_duplicate_groups_for_session(session_id)?;
+            db.reset_scan_session(session_id)?;
+            session_id
+        }
+        (None, None) => sessions.run,
+    };
+    if session_id == sessions.run {
+        db.delete_checkpoint_buckets(session_id)?;
+    } else {
+        db.delete_session(sessions.run)?;
+    }
+    db.set_session_scan_settings(session_id, &scan_settings_digest(config))?;
+    db.set_session_hash_algorithm(session_id, config.hash_algorithm.as_str())?;
+    db.set_session_scan_filter(session_id, &config.filter)?;
+    // Protection set by another handle or config must survive this scan
+    db.add_protected_paths(&config.all_protected_paths())?;
+    let partial_hashes = indexed.partial_hashes;
+    let partial_hash_len = config.hash_tiers.partial_bytes as i64;
 
     // Build file records and duplicate group info
     let mut all_files: Vec<ScannedFile> = Vec::new();
     let mut dupe_groups: Vec<(i64, i64, Vec<String>)> = Vec::new();
+    let mut grouped_paths: HashSet<PathBuf> = HashSet::new();
     let mut total_wasted: u64 = 0;
 
-    for entry in content_hash_map.iter() {
-        let content_hash = *entry.key();
-        let paths = entry.value();
+    for (hash, paths) in groups {
+        let content_hash = hash.to_i64();
+        let content_digest = hash.to_hex();
 
         let mut group_paths: Vec<String> = Vec::new();
         let mut file_size_for_group: i64 = 0;
 
         for path in paths.iter() {
-            let metadata = match fs::metadata(path) {
-                Ok(m) => m,
-                Err(e) => {
-                    tracing::error!("Error reading metadata for {}: {}", path.display(), e);
-                    continue;
-                }
-            };
-
-            let canonical_path = match fs::canonicalize(path) {
-                Ok(p) => p,
-                Err(e) => {
-                    tracing::error!("Error canonicalizing {}: {}", path.display(), e);
-                    continue;
-                }
+            let Some((canonical_path, file_size, modified)) = stat_for_index(path, indexed.walked, scan_errors)
+            else {
+                continue;
             };
 
-            let canonical_str = canonical_path.to_string_lossy().into_owned();
+            let mut file = scanned_file_record(&canonical_path, file_size, modified, session_id);
+            file.partial_hash = partial_hashes.get(&canonical_path).map(|p| p.hash as i64);
+            file.partial_hash_len = file.partial_hash.map(|_| partial_hash_len);
+            file.content_hash = Some(content_hash);
+            file.content_digest = Some(content_digest.clone());
+            file_size_for_group = file.file_size;
 
-            let drive_letter = match platform::get_drive_letter(&canonical_path) {
-                Some(drive) => drive.to_string_lossy().into_owned(),
-                None => String::new(),
-            };
-
-            let parent_dir = canonical_path
-                .parent()
-                .map(|p| p.to_string_lossy().into_owned())
-                .unwrap_or_default();
-
-            let file_name = canonical_path
-                .file_name()
-                .map(|f| f.to_string_lossy().into_owned())
-                .unwrap_or_default();
-
-            let last_modified = metadata
-                .modified()
-                .ok()
-                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
-                .map(|d| d.as_secs() as i64)
-                .unwrap_or(0);
-
-            let file_size = metadata.len() as i64;
-            file_size_for_group = file_size;
-
-            group_paths.push(canonical_str.clone());
-
-            all_files.push(ScannedFile {
-                id: 0,
-                canonical_path: canonical_str,
-                file_name,
-                parent_dir,
-                drive_letter,
-                file_size,
-                last_modified,
-                partial_hash: None,
-                content_hash: Some(content_hash as i64),
-                last_seen_session_id: Some(session_id),
-                marked_deleted: false,
-            });
+            group_paths.push(file.canonical_path.clone());
+            grouped_paths.insert(canonical_path);
+            all_files.push(file);
         }
 
         if group_paths.len() > 1 {
             let wasted = file_size_for_group as u64 * (group_paths.len() as u64 - 1);
             total_wasted += wasted;
-            dupe_groups.push((content_hash as i64, file_size_for_group, group_paths));
+            dupe_groups.push((content_hash, file_size_for_group, group_paths));
         }
     }
+    let duplicate_files = all_files.len();
 
-    // Upsert files into the global file index
+    // Every other partially hashed file is indexed too, so its partial hash can be
+    // reused next scan. A file no other file shares a size with is never read, so
+    // has no partial hash; it's only indexed, by size and mtime, with index_all_files
+    for entry in partial_hashes.iter() {
+        if grouped_paths.contains(entry.key()) {
+            continue;
+        }
+        let partial = entry.value();
+        let mut file = scanned_file_record(entry.key(), partial.file_size, partial.modified, session_id);
+        file.partial_hash = Some(partial.hash as i64);
+        file.partial_hash_len = Some(partial_hash_len);
+        all_files.push(file);
+    }
+
+    // So are files that were never read, when the whole inventory is wanted
+    for path in indexed.unhashed {
+        if let Some((canonical_path, file_size, modified)) = stat_for_index(path, indexed.walked, scan_errors) {
+            all_files.push(scanned_file_record(&canonical_path, file_size, modified, session_id));
+        }
+    }
+
+    // Upsert files into the global file index, noting which are new or changed
+    let paths: Vec<String> = all_files.iter().map(|f| f.canonical_path.clone()).collect();
+    let previous = db.get_file_states_by_path(&paths)?;
+    drop(paths);
+    let mut added_files = 0;
+    let mut changed_files = 0;
+    for file in &all_files {
+        match previous.get(&file.canonical_path) {
+            None => added_files += 1,
+            Some(state)
+                if (state.file_size, state.last_modified, state.last_modified_nanos)
+                    != (file.file_size, file.last_modified, file.last_modified_nanos) =>
+            {
+                changed_files += 1
+            }
+            Some(_) => {}
+        }
+    }
     let files_written = db.insert_scanned_files(&all_files)?;
 
-    // Insert duplicate groups for this session (old groups were pre-deleted by find_or_create_session)
+    // Insert duplicate groups for this session (old groups were deleted above)
     let groups_written = db.insert_duplicate_groups(session_id, &dupe_groups)?;
+    if config.verify_duplicates {
+        db.mark_session_groups_verified(session_id)?;
+    }
 
     // Complete session
     let total_bytes: i64 = all_files.iter().map(|f| f.file_size).sum();
-    db.complete_scan_session(session_id, files_written as i64, total_bytes)?;
+    let mut affected_dirs: BTreeSet<String> =
+        all_files.iter().map(|f| f.parent_dir.clone()).collect();
+    let (groups_written, duplicate_files, total_wasted) = match plan {
+        Some(plan) => {
+            db.complete_scan_session(
+                session_id,
+                (files_written + plan.unchanged_files) as i64,
+                total_bytes + plan.unchanged_bytes as i64,
+            )?;
+            affected_dirs.extend(plan.regrouped_dirs.iter().cloned());
+            // Kept groups count too
+            (
+                db.get_duplicate_group_count(session_id)? as usize,
+                db.get_duplicate_file_count(session_id)? as usize,
+                db.get_total_wasted_bytes(session_id)? as u64,
+            )
+        }
+        None => {
+            db.complete_scan_session(session_id, files_written as i64, total_bytes)?;
+            (groups_written, duplicate_files, total_wasted)
+        }
+    };
 
-    Ok((groups_written, files_written, total_wasted, session_id))
+    Ok(DbWriteSummary {
+        groups_written,
+        duplicate_files,
+        files_written,
+        wasted_bytes: total_wasted,
+        session_id,
+        affected_dirs: affected_dirs.into_iter().collect(),
+        added_files,
+        changed_files,
+    })
 }
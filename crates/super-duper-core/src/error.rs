@@ -17,6 +17,12 @@ pub enum Error {
     #[error("Operation cancelled")]
     Cancelled,
 
+    #[error("Cannot resume: {0}")]
+    Resume(String),
+
+    #[error("{path} is protected by {protected_by}")]
+    Protected { path: String, protected_by: String },
+
     #[error("{0}")]
     Other(String),
 }
@@ -0,0 +1,224 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::hash::Hasher as _;
+use std::str::FromStr;
+use twox_hash::XxHash64;
+
+/// Content hash algorithms available for the full-content tier.
+///
+/// The partial (1KB) tier always uses XxHash64 — it only eliminates non-matches,
+/// so the configured algorithm decides what counts as a confirmed duplicate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum HashAlgorithm {
+    #[default]
+    #[serde(rename = "xxh64", alias = "xxhash64")]
+    Xxh64,
+    #[serde(rename = "xxh3-128", alias = "xxh3")]
+    Xxh3_128,
+    #[serde(rename = "blake3")]
+    Blake3,
+    #[serde(rename = "sha256", alias = "sha-256")]
+    Sha256,
+}
+
+impl HashAlgorithm {
+    pub const ALL: [HashAlgorithm; 4] = [
+        HashAlgorithm::Xxh64,
+        HashAlgorithm::Xxh3_128,
+        HashAlgorithm::Blake3,
+        HashAlgorithm::Sha256,
+    ];
+
+    /// Stable identifier stored on `scan_session` and in cache keys.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Xxh64 => "xxh64",
+            HashAlgorithm::Xxh3_128 => "xxh3-128",
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Sha256 => "sha256",
+        }
+    }
+
+    /// Width of the digest in bytes.
+    pub fn digest_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Xxh64 => 8,
+            HashAlgorithm::Xxh3_128 => 16,
+            HashAlgorithm::Blake3 | HashAlgorithm::Sha256 => 32,
+        }
+    }
+
+    /// Create a fresh streaming hasher for this algorithm.
+    pub fn hasher(&self) -> Box<dyn ContentHasher> {
+        match self {
+            HashAlgorithm::Xxh64 => Box::new(Xxh64Hasher(XxHash64::with_seed(0))),
+            HashAlgorithm::Xxh3_128 => Box::new(Xxh3Hasher(xxhash_rust::xxh3::Xxh3::new())),
+            HashAlgorithm::Blake3 => Box::new(Blake3Hasher(blake3::Hasher::new())),
+            HashAlgorithm::Sha256 => Box::new(Sha256Hasher(sha2::Sha256::default())),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    /// Lenient parse: case-insensitive, ignores `-` and `_` (accepts "xxHash64", "SHA-256", ...).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized: String = s
+            .chars()
+            .filter(|c| *c != '-' && *c != '_')
+            .collect::<String>()
+            .to_ascii_lowercase();
+        match normalized.as_str() {
+            "xxh64" | "xxhash64" => Ok(HashAlgorithm::Xxh64),
+            "xxh3" | "xxh3128" | "xxhash3" => Ok(HashAlgorithm::Xxh3_128),
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            _ => Err(format!("Unknown hash algorithm '{}'", s)),
+        }
+    }
+}
+
+/// A full-content digest. Width depends on the algorithm (8, 16 or 32 bytes).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ContentHash(Vec<u8>);
+
+impl ContentHash {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        ContentHash(bytes)
+    }
+
+    /// Big-endian encoding, so `to_i64` round-trips the original XxHash64 value.
+    pub fn from_u64(value: u64) -> Self {
+        ContentHash(value.to_be_bytes().to_vec())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The leading 64 bits, used for the `content_hash` INTEGER columns.
+    /// For XxHash64 this is the full hash.
+    pub fn to_i64(&self) -> i64 {
+        let mut buf = [0u8; 8];
+        let n = self.0.len().min(8);
+        buf[..n].copy_from_slice(&self.0[..n]);
+        u64::from_be_bytes(buf) as i64
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Parse `to_hex` output; `None` if it isn't an even number of hex digits.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        if hex.len() % 2 != 0 || !hex.is_ascii() {
+            return None;
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect::<Option<Vec<u8>>>()
+            .map(ContentHash)
+    }
+}
+
+/// Streaming content hasher. Implementations are created via `HashAlgorithm::hasher`.
+pub trait ContentHasher: Send {
+    fn update(&mut self, data: &[u8]);
+    fn finish(self: Box<Self>) -> ContentHash;
+}
+
+struct Xxh64Hasher(XxHash64);
+
+impl ContentHasher for Xxh64Hasher {
+    fn update(&mut self, data: &[u8]) {
+        self.0.write(data);
+    }
+
+    fn finish(self: Box<Self>) -> ContentHash {
+        ContentHash::from_u64(self.0.finish())
+    }
+}
+
+struct Xxh3Hasher(xxhash_rust::xxh3::Xxh3);
+
+impl ContentHasher for Xxh3Hasher {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finish(self: Box<Self>) -> ContentHash {
+        ContentHash(self.0.digest128().to_be_bytes().to_vec())
+    }
+}
+
+struct Blake3Hasher(blake3::Hasher);
+
+impl ContentHasher for Blake3Hasher {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finish(self: Box<Self>) -> ContentHash {
+        ContentHash(self.0.finalize().as_bytes().to_vec())
+    }
+}
+
+struct Sha256Hasher(sha2::Sha256);
+
+impl ContentHasher for Sha256Hasher {
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(&mut self.0, data);
+    }
+
+    fn finish(self: Box<Self>) -> ContentHash {
+        ContentHash(sha2::Digest::finalize(self.0).to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(algorithm: HashAlgorithm, data: &[u8]) -> String {
+        let mut hasher = algorithm.hasher();
+        hasher.update(data);
+        hasher.finish().to_hex()
+    }
+
+    #[test]
+    fn test_known_digests() {
+        assert_eq!(
+            digest(HashAlgorithm::Sha256, b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            digest(HashAlgorithm::Blake3, b"abc"),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+    }
+
+    #[test]
+    fn test_xxh64_matches_legacy_hash() {
+        let data = b"shared content xyz";
+        let mut hasher = HashAlgorithm::Xxh64.hasher();
+        hasher.update(data);
+        let hash = hasher.finish();
+        assert_eq!(hash.to_i64(), super::super::xxhash::hash_data(data) as i64);
+    }
+
+    #[test]
+    fn test_parse_ui_names() {
+        assert_eq!("xxHash64".parse::<HashAlgorithm>().unwrap(), HashAlgorithm::Xxh64);
+        assert_eq!("SHA-256".parse::<HashAlgorithm>().unwrap(), HashAlgorithm::Sha256);
+        assert_eq!("xxh3-128".parse::<HashAlgorithm>().unwrap(), HashAlgorithm::Xxh3_128);
+        assert!("md5".parse::<HashAlgorithm>().is_err());
+    }
+}
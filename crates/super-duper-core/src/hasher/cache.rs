@@ -1,108 +1,493 @@
+use super::algorithm::{ContentHash, HashAlgorithm};
+use crate::platform::{self, FileId};
+use dashmap::DashMap;
 use rocksdb::{IteratorMode, Options, DB};
+use serde::Deserialize;
 use std::env;
 use std::fs;
-use std::io::{self, ErrorKind};
-use std::path::Path;
-use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tracing::{debug, error, info, trace};
 
 static DEFAULT_HASH_CACHE_PATH: &str = "content_hash_cache.db";
+const FILE_ID_MARKER: &str = "id|";
+const STORED_AT_LEN: usize = 8;
 
-lazy_static::lazy_static! {
-    pub static ref DB_INSTANCE: Arc<Mutex<DB>> = {
-        let db_path = env::var("HASH_CACHE_PATH")
-            .unwrap_or_else(|_| String::from(DEFAULT_HASH_CACHE_PATH));
-        debug!("Using '{}' for hash cache", db_path);
+/// Cache location used when none is configured: `HASH_CACHE_PATH`, else
+/// `content_hash_cache.db` in the working directory.
+pub fn default_cache_path() -> String {
+    env::var("HASH_CACHE_PATH").unwrap_or_else(|_| String::from(DEFAULT_HASH_CACHE_PATH))
+}
+
+/// Key/value store for full-content hashes.
+///
+/// Implementations must be safe to call from many rayon threads at once;
+/// `get_content_hash` does no locking of its own.
+pub trait HashCache: Send + Sync {
+    fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>>;
+    fn put(&self, key: &[u8], value: &[u8]) -> io::Result<()>;
+    fn delete_keys(&self, keys: &[Vec<u8>]) -> io::Result<()>;
+    /// Call `visit` with every key and value. `visit` must not write to the cache.
+    fn for_each_entry(&self, visit: &mut dyn FnMut(&[u8], &[u8])) -> io::Result<()>;
+    fn count_keys(&self) -> io::Result<usize>;
+    fn clear_all(&self) -> io::Result<()>;
+
+    /// Cheap entry count; may be approximate.
+    fn estimate_keys(&self) -> io::Result<u64> {
+        self.count_keys().map(|n| n as u64)
+    }
+
+    /// Bytes used on disk; 0 for caches that are not persisted.
+    fn disk_size(&self) -> io::Result<u64> {
+        Ok(0)
+    }
+
+    /// Reclaim space left behind by deleted entries.
+    fn compact(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Cache hits and misses counted during one hashing pass.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheUsage {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheUsage {
+    /// Fraction of lookups served from the cache, or `None` when nothing was looked up.
+    pub fn hit_ratio(&self) -> Option<f64> {
+        let total = self.hits + self.misses;
+        (total > 0).then(|| self.hits as f64 / total as f64)
+    }
+}
+
+/// Persistent cache backed by RocksDB.
+pub struct RocksDbHashCache {
+    db: DB,
+}
 
+impl RocksDbHashCache {
+    /// Open (or create) the cache at `path`. Fails instead of panicking when the
+    /// database is locked by another process or engine.
+    pub fn open(path: &str) -> io::Result<Self> {
+        debug!("Using '{}' for hash cache", path);
         let mut db_options = Options::default();
         db_options.create_if_missing(true);
-        let db_instance = DB::open(&db_options, db_path)
-            .expect("Failed to open RocksDB hash cache");
-        Arc::new(Mutex::new(db_instance))
-    };
+        let db = DB::open(&db_options, path).map_err(|e| {
+            io::Error::other(format!("Failed to open hash cache '{}': {}", path, e))
+        })?;
+        Ok(Self { db })
+    }
+}
+
+impl HashCache for RocksDbHashCache {
+    fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        self.db.get(key).map_err(io::Error::other)
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> io::Result<()> {
+        self.db.put(key, value).map_err(io::Error::other)
+    }
+
+    fn delete_keys(&self, keys: &[Vec<u8>]) -> io::Result<()> {
+        let mut batch = rocksdb::WriteBatch::default();
+        for key in keys {
+            batch.delete(key);
+        }
+        self.db.write(batch).map_err(io::Error::other)
+    }
+
+    fn for_each_entry(&self, visit: &mut dyn FnMut(&[u8], &[u8])) -> io::Result<()> {
+        for item in self.db.iterator(IteratorMode::Start) {
+            let (key, value) = item.map_err(io::Error::other)?;
+            visit(&key, &value);
+        }
+        Ok(())
+    }
+
+    fn count_keys(&self) -> io::Result<usize> {
+        let mut count = 0usize;
+        for item in self.db.iterator(IteratorMode::Start) {
+            item.map_err(io::Error::other)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn clear_all(&self) -> io::Result<()> {
+        let mut batch = rocksdb::WriteBatch::default();
+        for item in self.db.iterator(IteratorMode::Start) {
+            let (key, _) = item.map_err(io::Error::other)?;
+            batch.delete(&key);
+        }
+        self.db.write(batch).map_err(io::Error::other)?;
+        info!("Hash cache cleared");
+        Ok(())
+    }
+
+    fn estimate_keys(&self) -> io::Result<u64> {
+        let estimate = self
+            .db
+            .property_int_value("rocksdb.estimate-num-keys")
+            .map_err(io::Error::other)?;
+        Ok(estimate.unwrap_or_default())
+    }
+
+    fn disk_size(&self) -> io::Result<u64> {
+        let mut total = 0;
+        for entry in fs::read_dir(self.db.path())? {
+            let metadata = entry?.metadata()?;
+            if metadata.is_file() {
+                total += metadata.len();
+            }
+        }
+        Ok(total)
+    }
+
+    fn compact(&self) -> io::Result<()> {
+        self.db.compact_range::<&[u8], &[u8]>(None, None);
+        info!("Hash cache compacted");
+        Ok(())
+    }
+}
+
+/// Non-persistent cache, for tests and one-off scans.
+#[derive(Debug, Default)]
+pub struct MemoryHashCache {
+    entries: DashMap<Vec<u8>, Vec<u8>>,
+}
+
+impl MemoryHashCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl HashCache for MemoryHashCache {
+    fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.entries.get(key).map(|v| v.value().clone()))
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> io::Result<()> {
+        self.entries.insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn delete_keys(&self, keys: &[Vec<u8>]) -> io::Result<()> {
+        for key in keys {
+            self.entries.remove(key);
+        }
+        Ok(())
+    }
+
+    fn for_each_entry(&self, visit: &mut dyn FnMut(&[u8], &[u8])) -> io::Result<()> {
+        for entry in self.entries.iter() {
+            visit(entry.key(), entry.value());
+        }
+        Ok(())
+    }
+
+    fn count_keys(&self) -> io::Result<usize> {
+        Ok(self.entries.len())
+    }
+
+    fn clear_all(&self) -> io::Result<()> {
+        self.entries.clear();
+        Ok(())
+    }
+}
+
+/// How full-content cache entries are keyed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CacheKeyScheme {
+    /// Canonical path and mtime. Any rename or move is a cache miss.
+    #[default]
+    Path,
+    /// Device + inode (volume serial + file index on Windows), size and mtime, so
+    /// renamed and moved files still hit. Falls back to the path on filesystems
+    /// without stable file IDs.
+    FileId,
+}
+
+/// What identifies the file in a cache key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySubject {
+    Path(PathBuf),
+    FileId { id: FileId, size: u64 },
+}
+
+/// Cache key for a full-content hash: `path|secs.nanos` or
+/// `id|device:index|size|secs.nanos`, prefixed with the algorithm name for
+/// anything but XxHash64.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheKey {
+    pub algorithm: HashAlgorithm,
+    pub subject: KeySubject,
+    pub modified: Duration,
+}
+
+impl CacheKey {
+    pub fn encode(&self) -> Vec<u8> {
+        let subject = match &self.subject {
+            KeySubject::Path(path) => path.to_string_lossy().into_owned(),
+            KeySubject::FileId { id, size } => {
+                format!("{}{}:{}|{}", FILE_ID_MARKER, id.device, id.index, size)
+            }
+        };
+        // Include subsec_nanos for precision (fixes second-granularity cache key issue)
+        let key = format!(
+            "{}|{}.{}",
+            subject,
+            self.modified.as_secs(),
+            self.modified.subsec_nanos()
+        );
+        match self.algorithm {
+            HashAlgorithm::Xxh64 => key.into_bytes(),
+            other => format!("{}|{}", other.as_str(), key).into_bytes(),
+        }
+    }
+
+    /// Parse a key written by `encode`. Returns `None` for anything else.
+    pub fn parse(key: &[u8]) -> Option<Self> {
+        let key = std::str::from_utf8(key).ok()?;
+        let (rest, timestamp) = key.rsplit_once('|')?;
+        let (secs, nanos) = timestamp.split_once('.')?;
+        let modified = Duration::new(secs.parse().ok()?, nanos.parse().ok()?);
+
+        // Canonical paths never start with an algorithm name or the file ID marker
+        let (algorithm, rest) = HashAlgorithm::ALL
+            .iter()
+            .filter(|a| **a != HashAlgorithm::Xxh64)
+            .find_map(|a| {
+                rest.strip_prefix(a.as_str())
+                    .and_then(|p| p.strip_prefix('|'))
+                    .map(|p| (*a, p))
+            })
+            .unwrap_or((HashAlgorithm::Xxh64, rest));
+
+        let subject = match rest.strip_prefix(FILE_ID_MARKER) {
+            Some(id) => {
+                let (id, size) = id.split_once('|')?;
+                let (device, index) = id.split_once(':')?;
+                KeySubject::FileId {
+                    id: FileId {
+                        device: device.parse().ok()?,
+                        index: index.parse().ok()?,
+                    },
+                    size: size.parse().ok()?,
+                }
+            }
+            None => KeySubject::Path(PathBuf::from(rest)),
+        };
+
+        Some(Self {
+            algorithm,
+            subject,
+            modified,
+        })
+    }
 }
 
-/// Look up a file's content hash in the RocksDB cache.
+/// A content hash and whether it was served from the cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedHash {
+    pub hash: ContentHash,
+    pub hit: bool,
+}
+
+/// Look up a file's content hash in the cache.
 /// Cache key includes subsecond timestamp precision to avoid stale entries.
 /// On cache miss, reads the full file, hashes it, and stores the result.
-pub fn get_content_hash(file: &Path) -> io::Result<u64> {
-    let db = DB_INSTANCE
-        .lock()
-        .map_err(|e| io::Error::new(ErrorKind::Other, format!("Failed to lock cache: {}", e)))?;
+///
+/// XxHash64 entries keep the original `path|mtime` key and bincode `u64` digest so
+/// existing caches stay valid; other algorithms prefix the key with their name and
+/// store the raw digest bytes, so entries for different algorithms never mix. The
+/// digest is followed by the time the entry was stored (see `entry_stored_at`) and,
+/// for file ID keys, the file's canonical path (see `entry_path`).
+pub fn get_content_hash(
+    cache: &dyn HashCache,
+    file: &Path,
+    algorithm: HashAlgorithm,
+    scheme: CacheKeyScheme,
+) -> io::Result<CachedHash> {
+    get_content_hash_with(cache, file, algorithm, scheme, || {
+        super::xxhash::hash_file(file, algorithm)
+    })
+}
 
-    let canonical_path = fs::canonicalize(file)?.to_string_lossy().into_owned();
+/// `get_content_hash`, with `compute` hashing the file on a cache miss.
+pub fn get_content_hash_with<F>(
+    cache: &dyn HashCache,
+    file: &Path,
+    algorithm: HashAlgorithm,
+    scheme: CacheKeyScheme,
+    compute: F,
+) -> io::Result<CachedHash>
+where
+    F: FnOnce() -> io::Result<ContentHash>,
+{
     let metadata = fs::metadata(file)?;
     let modified: SystemTime = metadata.modified()?;
-    let modified_timestamp = modified
-        .duration_since(UNIX_EPOCH)
-        .map_err(|e| io::Error::new(ErrorKind::Other, e))?;
-
-    // Include subsec_nanos for precision (fixes second-granularity cache key issue)
-    let key = format!(
-        "{}|{}.{}",
-        canonical_path,
-        modified_timestamp.as_secs(),
-        modified_timestamp.subsec_nanos()
-    );
-    let db_key = key.into_bytes();
-
-    match db.get(&db_key) {
-        Ok(Some(value)) => {
-            let hash: u64 = bincode::deserialize(&value)
-                .map_err(|e| {
-                    io::Error::new(ErrorKind::Other, format!("Deserialize error: {}", e))
-                })?;
+    let file_id = match scheme {
+        CacheKeyScheme::FileId => platform::file_id(file)?,
+        CacheKeyScheme::Path => None,
+    };
+    // File ID entries also record where the file was last seen, so eviction can
+    // check it; path keys already carry the path.
+    let canonical = fs::canonicalize(file)?;
+    let (subject, recorded_path) = match file_id {
+        Some(id) => (
+            KeySubject::FileId {
+                id,
+                size: metadata.len(),
+            },
+            Some(canonical),
+        ),
+        None => (KeySubject::Path(canonical), None),
+    };
+    let key = CacheKey {
+        algorithm,
+        subject,
+        modified: modified.duration_since(UNIX_EPOCH).map_err(io::Error::other)?,
+    };
+    let db_key = key.encode();
+
+    match cache.get(&db_key)? {
+        Some(value) => {
+            let hash = decode_hash(algorithm, &value)?;
             trace!("Found hash for {} in cache", file.display());
-            Ok(hash)
+            // Moved since it was stored: follow it, keeping the store time. Entries
+            // from before store times were recorded are left for the age limit.
+            let moved = recorded_path
+                .filter(|path| entry_path(algorithm, &value).as_ref() != Some(path));
+            if let (Some(path), Some(_)) = (moved, entry_stored_at(algorithm, &value)) {
+                let mut updated = value[..algorithm.digest_len() + STORED_AT_LEN].to_vec();
+                updated.extend_from_slice(path.to_string_lossy().as_bytes());
+                let _ = cache.put(&db_key, &updated);
+            }
+            Ok(CachedHash { hash, hit: true })
         }
-        Ok(None) => {
-            let data = super::xxhash::read_full_file(file)?;
-            let hash = super::xxhash::hash_data(&data);
+        None => {
+            let hash = compute()?;
             trace!("No hash found for {} in cache, adding", file.display());
-            let serialized = bincode::serialize(&hash)
-                .map_err(|e| {
-                    io::Error::new(ErrorKind::Other, format!("Serialize error: {}", e))
-                })?;
-            let _ = db.put(&db_key, serialized);
-            Ok(hash)
+            let mut value = encode_hash(algorithm, &hash)?;
+            value.extend_from_slice(&unix_now().as_secs().to_le_bytes());
+            if let Some(path) = recorded_path {
+                value.extend_from_slice(path.to_string_lossy().as_bytes());
+            }
+            let _ = cache.put(&db_key, &value);
+            Ok(CachedHash { hash, hit: false })
         }
-        Err(e) => Err(io::Error::new(ErrorKind::Other, e)),
     }
 }
 
-pub fn count_keys() -> Result<usize, io::Error> {
-    let db = DB_INSTANCE
-        .lock()
-        .map_err(|e| io::Error::new(ErrorKind::Other, format!("Failed to lock cache: {}", e)))?;
+/// Unix time (seconds) an entry was stored, or `None` for entries written before
+/// the timestamp was recorded.
+pub fn entry_stored_at(algorithm: HashAlgorithm, value: &[u8]) -> Option<u64> {
+    let start = algorithm.digest_len();
+    let stamp = value.get(start..start + STORED_AT_LEN)?;
+    Some(u64::from_le_bytes(stamp.try_into().ok()?))
+}
 
-    let mut count = 0usize;
-    let iterator = DB::iterator(&db, IteratorMode::Start);
-    for _ in iterator {
-        count += 1;
+/// Canonical path a file ID entry's file was last hashed or looked up under, or
+/// `None` for path entries and file ID entries written before it was recorded.
+pub fn entry_path(algorithm: HashAlgorithm, value: &[u8]) -> Option<PathBuf> {
+    let path = value.get(algorithm.digest_len() + STORED_AT_LEN..)?;
+    if path.is_empty() {
+        return None;
     }
-    Ok(count)
+    Some(PathBuf::from(std::str::from_utf8(path).ok()?))
 }
 
-pub fn clear_all() -> io::Result<()> {
-    let db = DB_INSTANCE
-        .lock()
-        .map_err(|e| io::Error::new(ErrorKind::Other, format!("Failed to lock cache: {}", e)))?;
+pub(crate) fn unix_now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
 
-    let mut batch = rocksdb::WriteBatch::default();
-    for item in db.iterator(IteratorMode::Start) {
-        let (key, _) = item.map_err(|e| io::Error::new(ErrorKind::Other, e))?;
-        batch.delete(&key);
+fn encode_hash(algorithm: HashAlgorithm, hash: &ContentHash) -> io::Result<Vec<u8>> {
+    match algorithm {
+        HashAlgorithm::Xxh64 => bincode::serialize(&(hash.to_i64() as u64))
+            .map_err(|e| io::Error::other(format!("Serialize error: {}", e))),
+        _ => Ok(hash.as_bytes().to_vec()),
     }
-    db.write(batch)
-        .map_err(|e| io::Error::new(ErrorKind::Other, e))?;
-    info!("Hash cache cleared");
-    Ok(())
 }
 
-pub fn print_count() {
-    match count_keys() {
+fn decode_hash(algorithm: HashAlgorithm, value: &[u8]) -> io::Result<ContentHash> {
+    let value = value
+        .get(..algorithm.digest_len())
+        .ok_or_else(|| io::Error::other("Truncated hash cache entry"))?;
+    match algorithm {
+        HashAlgorithm::Xxh64 => {
+            let hash: u64 = bincode::deserialize(value)
+                .map_err(|e| io::Error::other(format!("Deserialize error: {}", e)))?;
+            Ok(ContentHash::from_u64(hash))
+        }
+        _ => Ok(ContentHash::from_bytes(value.to_vec())),
+    }
+}
+
+pub fn print_count(cache: &dyn HashCache) {
+    match cache.count_keys() {
         Ok(count) => info!("Total keys in hash cache: {}", count),
         Err(e) => error!("Error counting cache keys: {}", e),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key_round_trip() {
+        let keys = [
+            CacheKey {
+                algorithm: HashAlgorithm::Xxh64,
+                subject: KeySubject::Path(PathBuf::from("/data/a|b.txt")),
+                modified: Duration::new(1_700_000_000, 123),
+            },
+            CacheKey {
+                algorithm: HashAlgorithm::Blake3,
+                subject: KeySubject::FileId {
+                    id: FileId { device: 2049, index: 77 },
+                    size: 4096,
+                },
+                modified: Duration::new(1_700_000_000, 0),
+            },
+        ];
+        for key in keys {
+            assert_eq!(CacheKey::parse(&key.encode()), Some(key));
+        }
+        assert_eq!(CacheKey::parse(b"not a key"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_id_keys_survive_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = dir.path().join("photo.jpg");
+        let moved_dir = dir.path().join("archive");
+        fs::create_dir(&moved_dir).unwrap();
+        let moved = moved_dir.join("photo.jpg");
+        fs::write(&original, b"pixels").unwrap();
+
+        let by_path = MemoryHashCache::new();
+        let by_id = MemoryHashCache::new();
+        for (cache, scheme) in [(&by_path, CacheKeyScheme::Path), (&by_id, CacheKeyScheme::FileId)] {
+            assert!(!get_content_hash(cache, &original, HashAlgorithm::Xxh64, scheme).unwrap().hit);
+        }
+
+        fs::rename(&original, &moved).unwrap();
+        let path_lookup =
+            get_content_hash(&by_path, &moved, HashAlgorithm::Xxh64, CacheKeyScheme::Path).unwrap();
+        let id_lookup =
+            get_content_hash(&by_id, &moved, HashAlgorithm::Xxh64, CacheKeyScheme::FileId).unwrap();
+        assert!(!path_lookup.hit);
+        assert!(id_lookup.hit);
+        assert_eq!(path_lookup.hash, id_lookup.hash);
+    }
+}
@@ -0,0 +1,278 @@
+use super::cache::{self, CacheKey, CacheUsage, HashCache, KeySubject};
+use crate::platform::{self, FileId};
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+use tracing::{debug, info};
+
+/// Limits applied by `evict`. Entries whose file is gone or has changed since it
+/// was hashed are always removed; these bound what is left.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvictionPolicy {
+    /// Remove entries stored longer ago than this. Entries written before store
+    /// times were recorded count as expired.
+    pub max_age: Option<Duration>,
+    /// Remove the oldest entries until key and value bytes total at most this.
+    pub max_size_bytes: Option<u64>,
+}
+
+/// What `evict` removed, by reason.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvictionSummary {
+    pub entries_examined: u64,
+    /// The file no longer exists (for file ID entries: not where it was last seen).
+    pub missing: u64,
+    /// The file's mtime no longer matches the key, so the entry can never hit again.
+    pub superseded: u64,
+    pub expired: u64,
+    pub over_size: u64,
+    /// Key and value bytes removed.
+    pub bytes_freed: u64,
+}
+
+impl EvictionSummary {
+    pub fn evicted(&self) -> u64 {
+        self.missing + self.superseded + self.expired + self.over_size
+    }
+}
+
+/// Size of the cache and how well it served the last scan.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheStats {
+    /// Entry count; approximate for RocksDB.
+    pub entries: u64,
+    pub disk_bytes: u64,
+    /// Hits and misses of the most recently completed scan, if any.
+    pub last_scan: Option<CacheUsage>,
+}
+
+struct Survivor {
+    key: Vec<u8>,
+    stored_at: u64,
+    size: u64,
+}
+
+/// Remove dead entries and apply `policy`.
+///
+/// Keys that don't parse are left alone. A file that can't be stat'ed for any
+/// reason other than not existing (e.g. permissions) keeps its entry. File ID
+/// entries are checked at the path their file was last seen under; one whose file
+/// has since moved without being scanned counts as missing. File ID entries from
+/// before the path was recorded are only removed by the policy limits.
+pub fn evict(cache: &dyn HashCache, policy: &EvictionPolicy) -> io::Result<EvictionSummary> {
+    let now = cache::unix_now().as_secs();
+    let mut summary = EvictionSummary::default();
+    let mut doomed: Vec<Vec<u8>> = Vec::new();
+    let mut survivors: Vec<Survivor> = Vec::new();
+
+    cache.for_each_entry(&mut |key, value| {
+        summary.entries_examined += 1;
+        let size = (key.len() + value.len()) as u64;
+        let Some(parsed) = CacheKey::parse(key) else {
+            return;
+        };
+
+        let metadata = match &parsed.subject {
+            KeySubject::Path(path) => Some(fs::metadata(path)),
+            KeySubject::FileId { id, .. } => cache::entry_path(parsed.algorithm, value)
+                .map(|path| recorded_file_metadata(&path, id)),
+        };
+        let modified = match metadata {
+            None => None,
+            Some(Ok(metadata)) => metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok()),
+            Some(Err(e)) if e.kind() == io::ErrorKind::NotFound => {
+                summary.missing += 1;
+                summary.bytes_freed += size;
+                doomed.push(key.to_vec());
+                return;
+            }
+            Some(Err(_)) => None,
+        };
+        if modified.is_some_and(|m| m != parsed.modified) {
+            summary.superseded += 1;
+            summary.bytes_freed += size;
+            doomed.push(key.to_vec());
+            return;
+        }
+
+        let stored_at = cache::entry_stored_at(parsed.algorithm, value);
+        if let Some(max_age) = policy.max_age {
+            if stored_at.is_none_or(|t| now.saturating_sub(t) > max_age.as_secs()) {
+                summary.expired += 1;
+                summary.bytes_freed += size;
+                doomed.push(key.to_vec());
+                return;
+            }
+        }
+
+        survivors.push(Survivor {
+            key: key.to_vec(),
+            stored_at: stored_at.unwrap_or_default(),
+            size,
+        });
+    })?;
+
+    if let Some(max_size) = policy.max_size_bytes {
+        let mut total: u64 = survivors.iter().map(|s| s.size).sum();
+        survivors.sort_by_key(|s| s.stored_at);
+        for survivor in survivors {
+            if total <= max_size {
+                break;
+            }
+            total -= survivor.size;
+            summary.over_size += 1;
+            summary.bytes_freed += survivor.size;
+            doomed.push(survivor.key);
+        }
+    }
+
+    debug!("Evicting {} of {} cache entries", doomed.len(), summary.entries_examined);
+    cache.delete_keys(&doomed)?;
+    info!(
+        "Hash cache eviction: {} missing, {} superseded, {} expired, {} over size",
+        summary.missing, summary.superseded, summary.expired, summary.over_size
+    );
+    Ok(summary)
+}
+
+/// Metadata of the file with `id` at `path`, where a file ID entry last saw it.
+/// A different file at that path means the entry's file is gone from there.
+fn recorded_file_metadata(path: &Path, id: &FileId) -> io::Result<Metadata> {
+    if platform::file_id(path)?.as_ref() != Some(id) {
+        return Err(io::ErrorKind::NotFound.into());
+    }
+    fs::metadata(path)
+}
+
+/// Entry count and on-disk size of `cache`. `last_scan` is left for the caller,
+/// which knows where scan sessions are stored.
+pub fn stats(cache: &dyn HashCache) -> io::Result<CacheStats> {
+    Ok(CacheStats {
+        entries: cache.estimate_keys()?,
+        disk_bytes: cache.disk_size()?,
+        last_scan: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::cache::{get_content_hash, CacheKeyScheme, MemoryHashCache};
+    use crate::hasher::HashAlgorithm;
+
+    #[test]
+    fn test_evict_missing_and_superseded_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let kept = dir.path().join("kept.txt");
+        let removed = dir.path().join("removed.txt");
+        let edited = dir.path().join("edited.txt");
+        for path in [&kept, &removed, &edited] {
+            fs::write(path, "original").unwrap();
+        }
+
+        let cache = MemoryHashCache::new();
+        for path in [&kept, &removed, &edited] {
+            get_content_hash(&cache, path, HashAlgorithm::Xxh64, CacheKeyScheme::Path).unwrap();
+        }
+        let edited_key = CacheKey {
+            algorithm: HashAlgorithm::Xxh64,
+            subject: KeySubject::Path(fs::canonicalize(&edited).unwrap()),
+            modified: Duration::from_secs(1),
+        };
+        cache.put(&edited_key.encode(), &[0; 16]).unwrap();
+        fs::remove_file(&removed).unwrap();
+
+        let summary = evict(&cache, &EvictionPolicy::default()).unwrap();
+        assert_eq!(summary.entries_examined, 4);
+        assert_eq!(summary.missing, 1);
+        assert_eq!(summary.superseded, 1);
+        assert_eq!(cache.count_keys().unwrap(), 2);
+        assert!(get_content_hash(&cache, &kept, HashAlgorithm::Xxh64, CacheKeyScheme::Path)
+            .unwrap()
+            .hit);
+    }
+
+    #[test]
+    fn test_evict_file_id_entries_checked_where_last_seen() {
+        let dir = tempfile::tempdir().unwrap();
+        let kept = dir.path().join("kept.txt");
+        let removed = dir.path().join("removed.txt");
+        let edited = dir.path().join("edited.txt");
+        let moved = dir.path().join("moved.txt");
+        for path in [&kept, &removed, &edited, &moved] {
+            fs::write(path, "original").unwrap();
+        }
+        if platform::file_id(&kept).unwrap().is_none() {
+            return;
+        }
+
+        let cache = MemoryHashCache::new();
+        let hash = |path: &Path| {
+            get_content_hash(&cache, path, HashAlgorithm::Xxh64, CacheKeyScheme::FileId).unwrap()
+        };
+        for path in [&kept, &removed, &edited, &moved] {
+            assert!(!hash(path).hit);
+        }
+        fs::remove_file(&removed).unwrap();
+        fs::File::options()
+            .append(true)
+            .open(&edited)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(1_000_000))
+            .unwrap();
+        // A hit after the move records the new path, so the entry survives
+        let renamed = dir.path().join("renamed.txt");
+        fs::rename(&moved, &renamed).unwrap();
+        assert!(hash(&renamed).hit);
+
+        let summary = evict(&cache, &EvictionPolicy::default()).unwrap();
+        assert_eq!(summary.entries_examined, 4);
+        assert_eq!(summary.missing, 1);
+        assert_eq!(summary.superseded, 1);
+        assert!(hash(&kept).hit);
+        assert!(hash(&renamed).hit);
+    }
+
+    #[test]
+    fn test_evict_applies_age_and_size_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = MemoryHashCache::new();
+        let now = cache::unix_now().as_secs();
+        let mut sizes = Vec::new();
+        for (i, age_days) in [0u64, 2, 10, 30].iter().enumerate() {
+            let path = dir.path().join(format!("file_{}.txt", i));
+            fs::write(&path, format!("content {}", i)).unwrap();
+            let key = CacheKey {
+                algorithm: HashAlgorithm::Sha256,
+                subject: KeySubject::Path(fs::canonicalize(&path).unwrap()),
+                modified: fs::metadata(&path)
+                    .unwrap()
+                    .modified()
+                    .unwrap()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap(),
+            }
+            .encode();
+            let mut value = vec![0u8; 32];
+            value.extend_from_slice(&(now - age_days * 24 * 60 * 60).to_le_bytes());
+            cache.put(&key, &value).unwrap();
+            sizes.push((key.len() + value.len()) as u64);
+        }
+
+        // 30-day-old entry expires; of the rest, the 10-day-old one is dropped for size
+        let policy = EvictionPolicy {
+            max_age: Some(Duration::from_secs(20 * 24 * 60 * 60)),
+            max_size_bytes: Some(sizes[0] + sizes[1]),
+        };
+        let summary = evict(&cache, &policy).unwrap();
+        assert_eq!(summary.expired, 1);
+        assert_eq!(summary.over_size, 1);
+        assert_eq!(summary.evicted(), 2);
+        assert_eq!(summary.bytes_freed, sizes[2] + sizes[3]);
+        assert_eq!(cache.count_keys().unwrap(), 2);
+    }
+}
@@ -1,4 +1,15 @@
+pub mod algorithm;
 pub mod cache;
+pub mod maintenance;
+pub mod throttle;
+pub mod verify;
 pub mod xxhash;
 
-pub use xxhash::build_content_hash_map;
+pub use algorithm::{ContentHash, ContentHasher, HashAlgorithm};
+pub use cache::{CacheKeyScheme, CacheUsage, HashCache, MemoryHashCache, RocksDbHashCache};
+pub use maintenance::{CacheStats, EvictionPolicy, EvictionSummary};
+pub use throttle::{IoLimits, RootIoLimits};
+pub use xxhash::{
+    build_content_hash_map, CompletedBucket, HashControl, HashOptions, HashOutcome, HashStats,
+    HashTiers, PartialHash, TierStats,
+};
@@ -0,0 +1,398 @@
+use serde::Deserialize;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Read limits for the hash phase and the verification pass, configured under
+/// `[io_limits]` and, for the reads under one root, with an `io_limits` table on
+/// the root's entry (see `RootIoLimits`). The re-check before deletion is held to
+/// the global limits. 0 leaves a limit off; both are off by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct IoLimits {
+    /// Files read at once from any one device (disk, mount or network share).
+    pub max_concurrent_reads_per_device: usize,
+    /// Combined read rate across all devices.
+    pub max_bytes_per_second: u64,
+}
+
+/// A root's own `IoLimits`. They hold the reads of files under the root, per device
+/// and in total, on top of the global limits every read is held to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootIoLimits {
+    pub root: PathBuf,
+    pub limits: IoLimits,
+}
+
+/// How long a reader waiting for a slot sleeps when it has no other work to run.
+const WAIT_SLICE: Duration = Duration::from_millis(10);
+
+thread_local! {
+    /// Set while this thread runs other rayon work during a wait, so a wait inside
+    /// that work blocks instead of yielding again and growing the stack.
+    static YIELDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A device's read slots: across all roots (`None`) or under one limited root.
+type Slot = (Option<usize>, u64);
+
+/// Enforces `IoLimits` for the reads of one `build_content_hash_map` call,
+/// verification pass or deletion run.
+pub struct IoThrottle {
+    limits: IoLimits,
+    roots: Vec<RootThrottle>,
+    in_flight: Mutex<HashMap<Slot, usize>>,
+    released: Condvar,
+    window: Mutex<RateWindow>,
+}
+
+/// A limited root, matched under its configured and its canonical path.
+struct RootThrottle {
+    paths: Vec<PathBuf>,
+    limits: IoLimits,
+    window: Mutex<RateWindow>,
+}
+
+/// Bytes consumed since `start`; reset whenever reads fall behind the allowed rate.
+struct RateWindow {
+    start: Instant,
+    bytes: u64,
+}
+
+impl RateWindow {
+    fn new() -> Mutex<Self> {
+        Mutex::new(Self {
+            start: Instant::now(),
+            bytes: 0,
+        })
+    }
+
+    /// Account for `bytes` and return how long to wait to keep to `rate`.
+    fn consume(&mut self, bytes: u64, rate: u64) -> Duration {
+        if rate == 0 {
+            return Duration::ZERO;
+        }
+        let now = Instant::now();
+        // Time spent idle isn't banked as allowance for a later burst
+        if self.start + bytes_duration(self.bytes, rate) < now {
+            self.start = now;
+            self.bytes = 0;
+        }
+        self.bytes += bytes;
+        (self.start + bytes_duration(self.bytes, rate)).saturating_duration_since(now)
+    }
+}
+
+/// Read slots for one file, or for two read side by side, released on drop. What
+/// the file's reads consume is reported through it.
+pub struct ReadPermit<'a> {
+    throttle: &'a IoThrottle,
+    slots: Vec<Slot>,
+    /// The limited root the file is under, if any.
+    root: Option<usize>,
+}
+
+impl ReadPermit<'_> {
+    /// Account for `bytes` just read, sleeping long enough to keep the combined
+    /// rate, and that of the file's root, at or under their `max_bytes_per_second`.
+    pub fn consume(&self, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+        let throttle = self.throttle;
+        let mut delay = throttle
+            .window
+            .lock()
+            .unwrap()
+            .consume(bytes, throttle.limits.max_bytes_per_second);
+        if let Some(root) = self.root.map(|index| &throttle.roots[index]) {
+            let root_delay = root
+                .window
+                .lock()
+                .unwrap()
+                .consume(bytes, root.limits.max_bytes_per_second);
+            delay = delay.max(root_delay);
+        }
+        if !delay.is_zero() {
+            thread::sleep(delay);
+        }
+    }
+}
+
+impl Drop for ReadPermit<'_> {
+    fn drop(&mut self) {
+        if self.slots.is_empty() {
+            return;
+        }
+        let mut in_flight = self.throttle.in_flight.lock().unwrap();
+        for slot in &self.slots {
+            if let Some(count) = in_flight.get_mut(slot) {
+                *count -= 1;
+            }
+        }
+        self.throttle.released.notify_all();
+    }
+}
+
+impl IoThrottle {
+    pub fn new(limits: IoLimits) -> Self {
+        Self {
+            limits,
+            roots: Vec::new(),
+            in_flight: Mutex::new(HashMap::new()),
+            released: Condvar::new(),
+            window: RateWindow::new(),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(IoLimits::default())
+    }
+
+    /// Also hold the reads under each of `roots` to its own limits.
+    pub fn with_root_limits(mut self, roots: &[RootIoLimits]) -> Self {
+        self.roots = roots
+            .iter()
+            .map(|root| RootThrottle {
+                paths: std::iter::once(root.root.clone())
+                    .chain(fs::canonicalize(&root.root).ok())
+                    .collect(),
+                limits: root.limits,
+                window: RateWindow::new(),
+            })
+            .collect();
+        self
+    }
+
+    /// Wait for a read slot for `path` on `device`, and one under its root when
+    /// that has its own limit.
+    ///
+    /// Inside the rayon pool a waiting thread runs other queued work, typically
+    /// reads from other devices, so a slow mount with all its slots taken doesn't
+    /// park threads that local disks could use.
+    pub fn acquire(&self, path: &Path, device: u64) -> ReadPermit<'_> {
+        let root = self.root_of(path);
+        ReadPermit {
+            throttle: self,
+            slots: self.take_slots(self.slots_for(root, device)),
+            root,
+        }
+    }
+
+    /// Read slots for reading two files side by side, each given as its path and
+    /// device: a slot the two share is taken once. All are taken together, so
+    /// pairs can't deadlock. The first permit holds the slots.
+    pub fn acquire_pair(
+        &self,
+        a: (&Path, u64),
+        b: (&Path, u64),
+    ) -> (ReadPermit<'_>, ReadPermit<'_>) {
+        let (root_a, root_b) = (self.root_of(a.0), self.root_of(b.0));
+        let mut slots = self.slots_for(root_a, a.1);
+        for slot in self.slots_for(root_b, b.1) {
+            if !slots.contains(&slot) {
+                slots.push(slot);
+            }
+        }
+        let first = ReadPermit {
+            throttle: self,
+            slots: self.take_slots(slots),
+            root: root_a,
+        };
+        let second = ReadPermit {
+            throttle: self,
+            slots: Vec::new(),
+            root: root_b,
+        };
+        (first, second)
+    }
+
+    /// The limited root `path` is under; the deepest when roots nest.
+    fn root_of(&self, path: &Path) -> Option<usize> {
+        self.roots
+            .iter()
+            .enumerate()
+            .flat_map(|(index, root)| root.paths.iter().map(move |dir| (index, dir)))
+            .filter(|(_, dir)| path.starts_with(dir))
+            .max_by_key(|(_, dir)| dir.components().count())
+            .map(|(index, _)| index)
+    }
+
+    /// The limited slots a read on `device` under `root` needs, with their limits.
+    fn slots_for(&self, root: Option<usize>, device: u64) -> Vec<(Slot, usize)> {
+        let mut slots = vec![((None, device), self.limits.max_concurrent_reads_per_device)];
+        if let Some(index) = root {
+            let limit = self.roots[index].limits.max_concurrent_reads_per_device;
+            slots.push(((Some(index), device), limit));
+        }
+        slots.retain(|(_, limit)| *limit > 0);
+        slots
+    }
+
+    /// Wait until every one of `slots` is below its limit, then take them all.
+    fn take_slots(&self, slots: Vec<(Slot, usize)>) -> Vec<Slot> {
+        if slots.is_empty() {
+            return Vec::new();
+        }
+
+        loop {
+            {
+                let mut in_flight = self.in_flight.lock().unwrap();
+                let free = slots
+                    .iter()
+                    .all(|(slot, limit)| in_flight.get(slot).copied().unwrap_or_default() < *limit);
+                if free {
+                    for (slot, _) in &slots {
+                        *in_flight.entry(*slot).or_default() += 1;
+                    }
+                    return slots.into_iter().map(|(slot, _)| slot).collect();
+                }
+                if YIELDING.get() {
+                    let _ = self.released.wait_timeout(in_flight, WAIT_SLICE).unwrap();
+                    continue;
+                }
+            }
+
+            YIELDING.set(true);
+            let yielded = rayon::yield_now();
+            YIELDING.set(false);
+            if yielded != Some(rayon::Yield::Executed) {
+                let in_flight = self.in_flight.lock().unwrap();
+                let _ = self.released.wait_timeout(in_flight, WAIT_SLICE).unwrap();
+            }
+        }
+    }
+}
+
+fn bytes_duration(bytes: u64, rate: u64) -> Duration {
+    Duration::from_secs_f64(bytes as f64 / rate as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_concurrent_reads_limited_per_device() {
+        let throttle = IoThrottle::new(IoLimits {
+            max_concurrent_reads_per_device: 2,
+            max_bytes_per_second: 0,
+        });
+        let active = [AtomicUsize::new(0), AtomicUsize::new(0)];
+        let peak = [AtomicUsize::new(0), AtomicUsize::new(0)];
+
+        thread::scope(|s| {
+            for i in 0..8 {
+                let device = i % 2;
+                let (throttle, active, peak) = (&throttle, &active[device], &peak[device]);
+                s.spawn(move || {
+                    let _permit = throttle.acquire(Path::new("/data/file"), device as u64);
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(20));
+                    active.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        assert!(peak[0].load(Ordering::SeqCst) <= 2);
+        assert!(peak[1].load(Ordering::SeqCst) <= 2);
+    }
+
+    #[test]
+    fn test_pairs_share_slots_without_deadlock() {
+        let throttle = IoThrottle::new(IoLimits {
+            max_concurrent_reads_per_device: 1,
+            max_bytes_per_second: 0,
+        });
+        let active = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+
+        thread::scope(|s| {
+            for i in 0..8u64 {
+                // Crossed pairs, 0-1 and 1-0, plus pairs on a single device
+                let (a, b) = [(0, 1), (1, 0), (0, 0), (1, 1)][i as usize % 4];
+                let (throttle, active, peak) = (&throttle, &active, &peak);
+                s.spawn(move || {
+                    let _permits =
+                        throttle.acquire_pair((Path::new("/data/a"), a), (Path::new("/data/b"), b));
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(10));
+                    active.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        // One slot per device and two devices: at most two pairs at once
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+    }
+
+    #[test]
+    fn test_consume_paces_to_rate() {
+        let throttle = IoThrottle::new(IoLimits {
+            max_concurrent_reads_per_device: 0,
+            max_bytes_per_second: 1024 * 1024,
+        });
+        let start = Instant::now();
+        for _ in 0..4 {
+            throttle.acquire(Path::new("/data/file"), 0).consume(64 * 1024);
+        }
+        // 256KB at 1MB/s
+        assert!(start.elapsed() >= Duration::from_millis(240));
+
+        let unlimited = IoThrottle::unlimited();
+        let start = Instant::now();
+        unlimited.acquire(Path::new("/data/file"), 0).consume(u64::MAX);
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_root_limits_apply_under_the_root_only() {
+        let throttle = IoThrottle::new(IoLimits {
+            max_concurrent_reads_per_device: 4,
+            max_bytes_per_second: 0,
+        })
+        .with_root_limits(&[RootIoLimits {
+            root: PathBuf::from("/mnt/share"),
+            limits: IoLimits {
+                max_concurrent_reads_per_device: 1,
+                max_bytes_per_second: 1024 * 1024,
+            },
+        }]);
+        let active = [AtomicUsize::new(0), AtomicUsize::new(0)];
+        let peak = [AtomicUsize::new(0), AtomicUsize::new(0)];
+
+        // The share and a local root, both on device 0 as far as the throttle knows
+        thread::scope(|s| {
+            for i in 0..8 {
+                let (path, index) = [(Path::new("/mnt/share/a"), 0), (Path::new("/home/b"), 1)][i % 2];
+                let (throttle, active, peak) = (&throttle, &active[index], &peak[index]);
+                s.spawn(move || {
+                    let _permit = throttle.acquire(path, 0);
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(20));
+                    active.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        assert_eq!(peak[0].load(Ordering::SeqCst), 1);
+        assert!(peak[1].load(Ordering::SeqCst) <= 4);
+
+        // Only reads under the share are paced to its rate
+        let start = Instant::now();
+        throttle.acquire(Path::new("/home/b"), 0).consume(1024 * 1024);
+        assert!(start.elapsed() < Duration::from_millis(100));
+        let start = Instant::now();
+        for _ in 0..4 {
+            throttle.acquire(Path::new("/mnt/share/a"), 0).consume(64 * 1024);
+        }
+        assert!(start.elapsed() >= Duration::from_millis(240));
+    }
+}
@@ -0,0 +1,246 @@
+use super::algorithm::ContentHash;
+use super::throttle::{IoLimits, IoThrottle, RootIoLimits};
+use crate::pause::PauseToken;
+use crate::progress::ProgressReporter;
+use crate::scan_error::{ScanError, ScanPhase};
+use crate::scanner::DeviceMap;
+use dashmap::DashMap;
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use tracing::warn;
+
+const VERIFY_BUFFER_SIZE: usize = 64 * 1024; // 64KB
+
+/// Outcome of the byte-for-byte verification pass.
+#[derive(Debug, Default)]
+pub struct VerifyOutcome {
+    /// Groups whose members all matched their kept copy byte-for-byte.
+    pub groups: Vec<(ContentHash, Vec<PathBuf>)>,
+    /// Files that did not match the copy they were grouped with.
+    pub mismatched_files: usize,
+    /// Files dropped because they couldn't be read; one per file, so its
+    /// length is the count.
+    pub errors: Vec<ScanError>,
+}
+
+/// Which of the two compared files an error came from.
+enum Side {
+    First,
+    Second,
+}
+
+/// Compare two files byte-for-byte using fixed-size buffers. Holds a read slot
+/// for each file, on its device as given by `device_of`, and reports what it
+/// reads to `throttle`.
+pub fn files_identical(
+    a: &Path,
+    b: &Path,
+    throttle: &IoThrottle,
+    device_of: &dyn Fn(&Path) -> u64,
+) -> io::Result<bool> {
+    compare_files(a, b, throttle, device_of).map_err(|(_, e)| e)
+}
+
+/// `files_identical`, telling which file an error came from.
+fn compare_files(
+    a: &Path,
+    b: &Path,
+    throttle: &IoThrottle,
+    device_of: &dyn Fn(&Path) -> u64,
+) -> Result<bool, (Side, io::Error)> {
+    let first = |e| (Side::First, e);
+    let second = |e| (Side::Second, e);
+    let (permit_a, permit_b) = throttle.acquire_pair((a, device_of(a)), (b, device_of(b)));
+    let mut fa = File::open(a).map_err(first)?;
+    let mut fb = File::open(b).map_err(second)?;
+    if fa.metadata().map_err(first)?.len() != fb.metadata().map_err(second)?.len() {
+        return Ok(false);
+    }
+
+    let mut buf_a = vec![0; VERIFY_BUFFER_SIZE];
+    let mut buf_b = vec![0; VERIFY_BUFFER_SIZE];
+    loop {
+        let n = read_full(&mut fa, &mut buf_a).map_err(first)?;
+        let m = read_full(&mut fb, &mut buf_b).map_err(second)?;
+        permit_a.consume(n as u64);
+        permit_b.consume(m as u64);
+        if n != m || buf_a[..n] != buf_b[..m] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Fill `buf` as far as possible; returns fewer bytes only at end of file.
+fn read_full(f: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match f.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Verify every hash group by comparing each member against the kept copy (the first
+/// path, sorted). Members that differ are re-grouped among themselves, so a group can
+/// split into several; anything left without a partner is dropped. Unreadable files
+/// are dropped and returned in `errors`. Reads are held to `io_limits` and those
+/// under a root in `root_io_limits` to its own as well, with each file's device
+/// taken from `devices`. While `pause` is paused, threads stop between groups.
+pub fn verify_duplicate_groups(
+    content_hash_map: DashMap<ContentHash, Vec<PathBuf>>,
+    io_limits: IoLimits,
+    root_io_limits: &[RootIoLimits],
+    devices: &DeviceMap,
+    cancel_token: &AtomicBool,
+    pause: &PauseToken,
+    progress: &dyn ProgressReporter,
+) -> VerifyOutcome {
+    let mismatched = AtomicUsize::new(0);
+    let errors = Mutex::new(Vec::new());
+    let throttle = IoThrottle::new(io_limits).with_root_limits(root_io_limits);
+    let device_of = |path: &Path| devices.device_of(path);
+
+    let groups: Vec<(ContentHash, Vec<PathBuf>)> = content_hash_map.into_iter().collect();
+    let verified: Vec<(ContentHash, Vec<PathBuf>)> = groups
+        .into_par_iter()
+        .flat_map_iter(|(hash, mut paths)| {
+            pause.wait_while_paused(cancel_token, progress);
+            if cancel_token.load(Ordering::Relaxed) {
+                return Vec::new();
+            }
+            paths.sort();
+            let (subgroups, unreadable) = split_by_content(paths, &throttle, &device_of);
+            if !unreadable.is_empty() {
+                errors.lock().unwrap().extend(unreadable);
+            }
+            if subgroups.len() > 1 {
+                let dropped: usize = subgroups.iter().skip(1).map(|g| g.len()).sum();
+                warn!(
+                    "Hash {} matched {} files that are not byte-identical",
+                    hash.to_hex(),
+                    dropped
+                );
+                mismatched.fetch_add(dropped, Ordering::Relaxed);
+            }
+            subgroups
+                .into_iter()
+                .filter(|g| g.len() > 1)
+                .map(|g| (hash.clone(), g))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    VerifyOutcome {
+        groups: verified,
+        mismatched_files: mismatched.into_inner(),
+        errors: errors.into_inner().unwrap(),
+    }
+}
+
+/// Partition paths into sets of byte-identical files. Unreadable files are left
+/// out and returned as errors; if the keeper is the one that can't be read, the
+/// next member takes its place.
+fn split_by_content(
+    paths: Vec<PathBuf>,
+    throttle: &IoThrottle,
+    device_of: &dyn Fn(&Path) -> u64,
+) -> (Vec<Vec<PathBuf>>, Vec<ScanError>) {
+    let mut remaining = paths;
+    let mut subgroups = Vec::new();
+    let mut errors = Vec::new();
+
+    while !remaining.is_empty() {
+        let keeper = remaining.remove(0);
+        let mut same = vec![keeper.clone()];
+        let mut different = Vec::new();
+        let mut rest = remaining.into_iter();
+        let mut keeper_error = None;
+        for path in rest.by_ref() {
+            match compare_files(&keeper, &path, throttle, device_of) {
+                Ok(true) => same.push(path),
+                Ok(false) => different.push(path),
+                Err((Side::Second, e)) => {
+                    warn!("Error verifying '{}' against '{}': {}", path.display(), keeper.display(), e);
+                    errors.push(ScanError::new(path, ScanPhase::Full, &e));
+                }
+                Err((Side::First, e)) => {
+                    keeper_error = Some(e);
+                    different.push(path);
+                    break;
+                }
+            }
+        }
+
+        if let Some(e) = keeper_error {
+            warn!("Error verifying against '{}': {}", keeper.display(), e);
+            errors.push(ScanError::new(&keeper, ScanPhase::Full, &e));
+            // Start over with the next member as the keeper
+            remaining = same.into_iter().skip(1).chain(different).chain(rest).collect();
+            remaining.sort();
+            continue;
+        }
+        subgroups.push(same);
+        remaining = different;
+    }
+
+    (subgroups, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unreadable_keeper_is_replaced_by_next_member() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = ["a", "b", "c", "d"].iter().map(|name| dir.path().join(name)).collect();
+        for path in &paths[1..] {
+            std::fs::write(path, b"same content").unwrap();
+        }
+        std::fs::write(&paths[3], b"other bytes!").unwrap();
+
+        // "a" sorts first but was never written, so it can't be read
+        let (subgroups, errors) = split_by_content(paths.clone(), &IoThrottle::unlimited(), &|_| 0);
+        assert_eq!(subgroups, vec![vec![paths[1].clone(), paths[2].clone()], vec![paths[3].clone()]]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, paths[0]);
+        assert_eq!(errors[0].phase, ScanPhase::Full);
+        assert_eq!(errors[0].kind, io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_unreadable_member_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = ["a", "b", "c"].iter().map(|name| dir.path().join(name)).collect();
+        std::fs::write(&paths[0], b"same content").unwrap();
+        std::fs::write(&paths[2], b"same content").unwrap();
+
+        let content_hash_map = DashMap::new();
+        content_hash_map.insert(ContentHash::from_u64(7), paths.clone());
+        let outcome = verify_duplicate_groups(
+            content_hash_map,
+            IoLimits::default(),
+            &[],
+            &DeviceMap::default(),
+            &AtomicBool::new(false),
+            &PauseToken::new(),
+            &crate::SilentReporter,
+        );
+        assert_eq!(outcome.groups.len(), 1);
+        assert_eq!(outcome.groups[0].1, vec![paths[0].clone(), paths[2].clone()]);
+        assert_eq!(outcome.mismatched_files, 0);
+        assert_eq!(outcome.errors.len(), 1);
+        assert_eq!(outcome.errors[0].path, paths[1]);
+    }
+}
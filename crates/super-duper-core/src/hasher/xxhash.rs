@@ -1,28 +1,264 @@
-use super::cache;
+use super::algorithm::{ContentHash, HashAlgorithm};
+use super::cache::{self, CacheKeyScheme, CacheUsage, HashCache};
+use super::throttle::{IoLimits, IoThrottle, ReadPermit, RootIoLimits};
+use crate::pause::PauseToken;
 use crate::progress::ProgressReporter;
+use crate::scan_error::{ScanError, ScanPhase};
+use crate::scanner::DeviceMap;
 use dashmap::DashMap;
 use rayon::prelude::*;
-use std::fs::File;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::hash::Hash;
 use std::hash::Hasher as _;
-use std::io::{self, Read};
+use std::io::{self, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
 use twox_hash::XxHash64;
 
-const PARTIAL_HASH_LENGTH: usize = 1024; // 1KB
+const FULL_HASH_BUFFER_SIZE: usize = 64 * 1024; // 64KB
 
-/// Two-tier hashing strategy:
-/// 1. Partial hash (first 1KB via XxHash64) to quickly eliminate non-matches
-/// 2. Full content hash only on partial-hash collisions
+/// Sizes for the cheap tiers that run before full-content hashing.
+///
+/// Configured under `[hash_tiers]`; any omitted field keeps its default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct HashTiers {
+    /// Bytes read from the start of each file for the partial tier.
+    pub partial_bytes: usize,
+    /// Size of each head, middle and tail block in the sampling tier. 0 disables the tier.
+    pub sample_bytes: usize,
+    /// Files smaller than this skip the sampling tier and go straight to full hashing.
+    pub sample_min_file_size: u64,
+}
+
+impl Default for HashTiers {
+    fn default() -> Self {
+        Self {
+            partial_bytes: 1024,              // 1KB
+            sample_bytes: 16 * 1024,          // 16KB
+            sample_min_file_size: 1024 * 1024, // 1MB
+        }
+    }
+}
+
+/// Tier names passed to `ProgressReporter::on_hash_tier_complete`.
+pub const TIER_PARTIAL: &str = "partial";
+pub const TIER_SAMPLE: &str = "sample";
+pub const TIER_FULL: &str = "full";
+
+/// Work done by one hashing tier.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TierStats {
+    pub files_hashed: usize,
+    /// Files left without a match after this tier.
+    pub files_eliminated: usize,
+    /// Bytes read from disk; reused partial hashes and cache hits read nothing.
+    pub bytes_read: u64,
+}
+
+/// I/O accounting for one `build_content_hash_map` call.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HashStats {
+    pub partial: TierStats,
+    pub sample: TierStats,
+    pub full: TierStats,
+    /// Partial hashes reused from `known_partials` without reading the file.
+    pub partial_hashes_reused: usize,
+    /// Full-hash lookups served from (or missed in) the hash cache.
+    pub cache: CacheUsage,
+}
+
+impl HashStats {
+    pub fn bytes_read(&self) -> u64 {
+        self.partial.bytes_read + self.sample.bytes_read + self.full.bytes_read
+    }
+}
+
+/// Running totals behind a `TierStats`.
+#[derive(Default)]
+struct TierCount {
+    hashed: AtomicUsize,
+    eliminated: AtomicUsize,
+    bytes_read: AtomicU64,
+}
+
+impl TierCount {
+    fn record<K: Eq + Hash>(&self, groups: &DashMap<K, Vec<PathBuf>>) {
+        let hashed: usize = groups.iter().map(|g| g.value().len()).sum();
+        let eliminated = groups.iter().filter(|g| g.value().len() == 1).count();
+        self.hashed.fetch_add(hashed, Ordering::Relaxed);
+        self.eliminated.fetch_add(eliminated, Ordering::Relaxed);
+    }
+
+    fn add_bytes(&self, bytes: u64) {
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn stats(&self) -> TierStats {
+        TierStats {
+            files_hashed: self.hashed.load(Ordering::Relaxed),
+            files_eliminated: self.eliminated.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Default)]
+struct TierCounters {
+    partial: TierCount,
+    sample: TierCount,
+    full: TierCount,
+}
+
+impl TierCounters {
+    fn report(&self, progress: &dyn ProgressReporter) {
+        for (name, count) in [
+            (TIER_PARTIAL, &self.partial),
+            (TIER_SAMPLE, &self.sample),
+            (TIER_FULL, &self.full),
+        ] {
+            let stats = count.stats();
+            progress.on_hash_tier_complete(name, stats.files_hashed, stats.files_eliminated);
+        }
+    }
+}
+
+/// Settings for one `build_content_hash_map` call, usually from `AppConfig::hash_options`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HashOptions {
+    /// Algorithm for the full-content tier.
+    pub algorithm: HashAlgorithm,
+    pub tiers: HashTiers,
+    /// How full-content hashes are keyed in the cache.
+    pub cache_keys: CacheKeyScheme,
+    /// Per-device concurrency and overall bandwidth limits for every tier's reads.
+    pub io_limits: IoLimits,
+    /// Limits of the roots that have their own, on top of `io_limits`.
+    pub root_io_limits: Vec<RootIoLimits>,
+}
+
+/// A file's partial hash together with the size and mtime it was computed at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialHash {
+    pub file_size: u64,
+    /// Modification time since the Unix epoch, with sub-second precision.
+    pub modified: Duration,
+    pub hash: u64,
+}
+
+/// Result of `build_content_hash_map`.
+#[derive(Debug, Default)]
+pub struct HashOutcome {
+    /// content_hash → paths, for confirmed duplicates only.
+    pub duplicates: DashMap<ContentHash, Vec<PathBuf>>,
+    /// Partial hash of every file that reached the partial tier, keyed by canonical path.
+    pub partial_hashes: DashMap<PathBuf, PartialHash>,
+    pub stats: HashStats,
+    /// Files dropped because a tier couldn't read them.
+    pub errors: Vec<ScanError>,
+}
+
+/// The results of one size bucket that every tier ran over without an error.
+#[derive(Debug)]
+pub struct CompletedBucket {
+    pub file_size: u64,
+    /// Partial hash of each file, keyed by canonical path.
+    pub partial_hashes: Vec<(PathBuf, PartialHash)>,
+    /// content_hash → paths, for the bucket's confirmed duplicates.
+    pub duplicates: Vec<(ContentHash, Vec<PathBuf>)>,
+}
+
+/// Cancellation, pausing and per-bucket reporting for one `build_content_hash_map` call.
+#[derive(Clone, Copy)]
+pub struct HashControl<'a> {
+    pub cancel_token: &'a AtomicBool,
+    /// While paused, hashing threads stop between files.
+    pub pause: Option<&'a PauseToken>,
+    /// Called as each size bucket completes, e.g. to checkpoint it. Buckets cut
+    /// short by cancellation or a read error aren't reported.
+    pub on_bucket: Option<&'a (dyn Fn(CompletedBucket) + Sync)>,
+}
+
+impl<'a> HashControl<'a> {
+    pub fn new(cancel_token: &'a AtomicBool) -> Self {
+        Self {
+            cancel_token,
+            pause: None,
+            on_bucket: None,
+        }
+    }
+}
+
+/// State shared by every size bucket during one `build_content_hash_map` call.
+struct BucketContext<'a> {
+    options: HashOptions,
+    known_partials: &'a HashMap<PathBuf, PartialHash>,
+    cache: &'a dyn HashCache,
+    devices: &'a DeviceMap,
+    throttle: IoThrottle,
+    outcome: &'a HashOutcome,
+    reused: AtomicUsize,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    counters: TierCounters,
+    errors: Mutex<Vec<ScanError>>,
+    cancel_token: &'a AtomicBool,
+    pause: Option<&'a PauseToken>,
+    progress: &'a dyn ProgressReporter,
+}
+
+impl BucketContext<'_> {
+    fn wait_while_paused(&self) {
+        if let Some(pause) = self.pause {
+            pause.wait_while_paused(self.cancel_token, self.progress);
+        }
+    }
+}
+
+/// Three-tier hashing strategy:
+/// 1. Partial hash (first `partial_bytes` via XxHash64) to quickly eliminate non-matches
+/// 2. Sample hash (head, middle and tail blocks via XxHash64) for large files that
+///    survive the partial tier, so files with identical headers don't all need a full read
+/// 3. Full content hash (using `algorithm`) only on files still colliding
 ///
 /// Takes a map keyed on file size (each value is a Vec of paths with that size)
-/// and returns a map of content_hash → Vec<PathBuf> for confirmed duplicates only.
+/// and returns the confirmed duplicates plus the partial hash of every candidate.
+/// Entries in `known_partials` (keyed by canonical path) are reused instead of
+/// re-reading the file head when size and mtime are unchanged. Full hashes are
+/// looked up in and added to `cache`. Reads are held to `options.io_limits`, with
+/// each file's device taken from `devices`.
 pub fn build_content_hash_map(
     size_to_file_map: DashMap<u64, Vec<PathBuf>>,
-    cancel_token: &AtomicBool,
+    options: HashOptions,
+    known_partials: &HashMap<PathBuf, PartialHash>,
+    cache: &dyn HashCache,
+    devices: &DeviceMap,
+    control: HashControl,
     progress: &dyn ProgressReporter,
-) -> io::Result<DashMap<u64, Vec<PathBuf>>> {
-    let confirmed_duplicates: DashMap<u64, Vec<PathBuf>> = DashMap::new();
+) -> io::Result<HashOutcome> {
+    let cancel_token = control.cancel_token;
+    let mut outcome = HashOutcome::default();
+    let throttle = IoThrottle::new(options.io_limits).with_root_limits(&options.root_io_limits);
+    let ctx = BucketContext {
+        options,
+        known_partials,
+        cache,
+        devices,
+        throttle,
+        outcome: &outcome,
+        reused: AtomicUsize::new(0),
+        cache_hits: AtomicU64::new(0),
+        cache_misses: AtomicU64::new(0),
+        counters: TierCounters::default(),
+        errors: Mutex::new(Vec::new()),
+        cancel_token,
+        pause: control.pause,
+        progress,
+    };
 
     // Count total files for progress reporting
     let total_files: usize = size_to_file_map.iter().map(|e| e.value().len()).sum();
@@ -30,47 +266,19 @@ pub fn build_content_hash_map(
 
     let size_to_file_vec: Vec<_> = size_to_file_map.iter().collect();
 
-    size_to_file_vec.par_iter().try_for_each(|files| {
+    size_to_file_vec.par_iter().for_each(|files| {
+        ctx.wait_while_paused();
         if cancel_token.load(Ordering::Relaxed) {
-            return Ok(());
+            return;
         }
 
-        let partial_hash_to_file_map: DashMap<u64, Vec<PathBuf>> = DashMap::new();
-        let full_hash_to_file_map: DashMap<u64, Vec<PathBuf>> = DashMap::new();
-
-        // First pass: partial hash to eliminate non-dupes quickly
-        files
-            .value()
-            .par_iter()
-            .try_for_each(|file| populate_partial_hash_map(file, &partial_hash_to_file_map))?;
-
-        // Second pass: full hash only on partial-hash collisions (>1 file)
-        let partial_hash_to_file_vec: Vec<_> = partial_hash_to_file_map.iter().collect();
-        partial_hash_to_file_vec.par_iter().try_for_each(|files| {
-            if cancel_token.load(Ordering::Relaxed) {
-                return Ok::<_, io::Error>(());
-            }
-            if files.value().len() > 1 {
-                files
-                    .value()
-                    .par_iter()
-                    .try_for_each(|file| {
-                        populate_full_hash_map(file, &full_hash_to_file_map)
-                    })?;
-            }
-            Ok::<_, io::Error>(())
-        })?;
-
-        // Collect confirmed duplicates (full hash groups with >1 file)
-        let full_hash_to_file_vec: Vec<_> = full_hash_to_file_map.iter().collect();
-        full_hash_to_file_vec.par_iter().for_each(|entry| {
-            if entry.value().len() > 1 {
-                confirmed_duplicates
-                    .entry(*entry.key())
-                    .or_default()
-                    .extend_from_slice(entry.value());
+        // A file with a unique size has no possible duplicate
+        if files.value().len() > 1 {
+            let bucket = hash_size_bucket(*files.key(), files.value(), &ctx);
+            if let (Some(on_bucket), Some(bucket)) = (control.on_bucket, bucket) {
+                on_bucket(bucket);
             }
-        });
+        }
 
         // Update progress
         let processed = files_processed.fetch_add(files.value().len(), Ordering::Relaxed)
@@ -78,65 +286,251 @@ pub fn build_content_hash_map(
         if processed % 500 < files.value().len() {
             progress.on_hash_progress(processed, total_files);
         }
+    });
 
-        Ok::<_, io::Error>(())
-    })?;
+    ctx.counters.report(progress);
+    let stats = HashStats {
+        partial: ctx.counters.partial.stats(),
+        sample: ctx.counters.sample.stats(),
+        full: ctx.counters.full.stats(),
+        partial_hashes_reused: ctx.reused.into_inner(),
+        cache: CacheUsage {
+            hits: ctx.cache_hits.into_inner(),
+            misses: ctx.cache_misses.into_inner(),
+        },
+    };
+    tracing::debug!("Reused {} stored partial hashes", stats.partial_hashes_reused);
+    let errors = ctx.errors.into_inner().unwrap();
+    outcome.stats = stats;
+    outcome.errors = errors;
+    Ok(outcome)
+}
 
-    Ok(confirmed_duplicates)
+/// Run the partial, sample and full tiers over one size bucket. Returns its
+/// results unless cancellation or a read error cut it short.
+fn hash_size_bucket(
+    file_size: u64,
+    files: &[PathBuf],
+    ctx: &BucketContext,
+) -> Option<CompletedBucket> {
+    let tiers = ctx.options.tiers;
+    let counters = &ctx.counters;
+    let errors = Mutex::new(Vec::new());
+
+    // First pass: partial hash to eliminate non-dupes quickly (reusing stored ones)
+    let partial_hashes: DashMap<PathBuf, PartialHash> = DashMap::new();
+    let partial_hash_to_file_map: DashMap<u64, Vec<PathBuf>> = DashMap::new();
+    files.par_iter().for_each(|file| {
+        ctx.wait_while_paused();
+        populate_hash_map(file, &partial_hash_to_file_map, &errors, |f| {
+            partial_hash(f, ctx, &partial_hashes)
+        })
+    });
+    counters.partial.record(&partial_hash_to_file_map);
+
+    // Second pass: head/middle/tail samples on partial-hash collisions of large files
+    let use_samples = tiers.sample_bytes > 0 && file_size >= tiers.sample_min_file_size;
+    let candidates: Vec<Vec<PathBuf>> = if use_samples {
+        // Keyed on (partial, sample) so distinct partial groups stay apart
+        let sample_hash_to_file_map: DashMap<(u64, u64), Vec<PathBuf>> = DashMap::new();
+        partial_hash_to_file_map
+            .iter()
+            .filter(|group| group.value().len() > 1)
+            .for_each(|group| {
+                let partial_hash = *group.key();
+                group.value().par_iter().for_each(|file| {
+                    ctx.wait_while_paused();
+                    populate_hash_map(file, &sample_hash_to_file_map, &errors, |f| {
+                        let permit = ctx.throttle.acquire(f, ctx.devices.device_of(f));
+                        let hash = hash_samples(f, file_size, tiers.sample_bytes, &permit)
+                            .map_err(ScanError::at(ScanPhase::Sample, f))?;
+                        counters
+                            .sample
+                            .add_bytes(3 * (tiers.sample_bytes as u64).min(file_size));
+                        Ok((partial_hash, hash))
+                    })
+                });
+            });
+        counters.sample.record(&sample_hash_to_file_map);
+        sample_hash_to_file_map.into_iter().map(|(_, v)| v).collect()
+    } else {
+        partial_hash_to_file_map.into_iter().map(|(_, v)| v).collect()
+    };
+
+    // Third pass: full hash only on remaining collisions (>1 file)
+    let full_hash_to_file_map: DashMap<ContentHash, Vec<PathBuf>> = DashMap::new();
+    candidates.par_iter().for_each(|group| {
+        if ctx.cancel_token.load(Ordering::Relaxed) || group.len() < 2 {
+            return;
+        }
+        group.par_iter().for_each(|file| {
+            ctx.wait_while_paused();
+            populate_hash_map(file, &full_hash_to_file_map, &errors, |f| {
+                let algorithm = ctx.options.algorithm;
+                let cached = cache::get_content_hash_with(
+                    ctx.cache,
+                    f,
+                    algorithm,
+                    ctx.options.cache_keys,
+                    || {
+                        let permit = ctx.throttle.acquire(f, ctx.devices.device_of(f));
+                        hash_file_with(f, algorithm, &permit)
+                    },
+                )
+                .map_err(ScanError::at(ScanPhase::Full, f))?;
+                if cached.hit {
+                    ctx.cache_hits.fetch_add(1, Ordering::Relaxed);
+                } else {
+                    ctx.cache_misses.fetch_add(1, Ordering::Relaxed);
+                    counters.full.add_bytes(file_size);
+                }
+                Ok(cached.hash)
+            })
+        });
+    });
+    counters.full.record(&full_hash_to_file_map);
+
+    // Collect confirmed duplicates (full hash groups with >1 file)
+    let duplicates: Vec<(ContentHash, Vec<PathBuf>)> = full_hash_to_file_map
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .collect();
+    for (hash, paths) in &duplicates {
+        ctx.outcome.duplicates.entry(hash.clone()).or_default().extend(paths.iter().cloned());
+    }
+    let partial_hashes: Vec<(PathBuf, PartialHash)> = partial_hashes.into_iter().collect();
+    for (path, partial) in &partial_hashes {
+        ctx.outcome.partial_hashes.insert(path.clone(), *partial);
+    }
+
+    let errors = errors.into_inner().unwrap();
+    let complete = errors.is_empty() && !ctx.cancel_token.load(Ordering::Relaxed);
+    ctx.errors.lock().unwrap().extend(errors);
+    complete.then_some(CompletedBucket {
+        file_size,
+        partial_hashes,
+        duplicates,
+    })
 }
 
-fn populate_partial_hash_map(
+/// Hash `file` with `hash_fn` and append it to the matching group. On error the
+/// file is left out and the error is logged and added to `errors`.
+fn populate_hash_map<K, F>(
     file: &Path,
-    partial_hash_to_file_map: &DashMap<u64, Vec<PathBuf>>,
-) -> io::Result<()> {
-    match read_portion(file) {
-        Ok(data) => {
-            let hash = hash_data(&data);
-            partial_hash_to_file_map
+    hash_to_file_map: &DashMap<K, Vec<PathBuf>>,
+    errors: &Mutex<Vec<ScanError>>,
+    hash_fn: F,
+) where
+    K: Eq + Hash,
+    F: Fn(&Path) -> Result<K, ScanError>,
+{
+    match hash_fn(file) {
+        Ok(hash) => {
+            hash_to_file_map
                 .entry(hash)
                 .or_default()
                 .push(file.to_path_buf());
-            Ok(())
         }
         Err(e) => {
-            tracing::error!("Error processing file '{}': {}", file.display(), e);
-            Ok(())
+            tracing::error!("Error processing file '{}': {}", file.display(), e.message);
+            errors.lock().unwrap().push(e);
         }
     }
 }
 
-fn populate_full_hash_map(
+/// Partial hash of `file`, taken from `known_partials` when size and mtime still match.
+/// The result is also recorded in `recorded` under the file's canonical path.
+fn partial_hash(
     file: &Path,
-    full_hash_to_file_map: &DashMap<u64, Vec<PathBuf>>,
-) -> io::Result<()> {
-    match cache::get_content_hash(file) {
-        Ok(hash) => {
-            full_hash_to_file_map
-                .entry(hash)
-                .or_default()
-                .push(file.to_path_buf());
-            Ok(())
+    ctx: &BucketContext,
+    recorded: &DashMap<PathBuf, PartialHash>,
+) -> Result<u64, ScanError> {
+    let canonical_path =
+        fs::canonicalize(file).map_err(ScanError::at(ScanPhase::Canonicalize, file))?;
+    let metadata = fs::metadata(&canonical_path).map_err(ScanError::at(ScanPhase::Metadata, file))?;
+    let modified = metadata
+        .modified()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).map_err(io::Error::other))
+        .map_err(ScanError::at(ScanPhase::Metadata, file))?;
+
+    let record = match ctx.known_partials.get(&canonical_path) {
+        Some(known) if known.file_size == metadata.len() && known.modified == modified => {
+            ctx.reused.fetch_add(1, Ordering::Relaxed);
+            *known
         }
-        Err(e) => {
-            tracing::error!("Error processing file '{}': {}", file.display(), e);
-            Ok(())
+        _ => {
+            let permit = ctx.throttle.acquire(file, ctx.devices.device_of(file));
+            let data = read_portion(file, ctx.options.tiers.partial_bytes)
+                .map_err(ScanError::at(ScanPhase::Partial, file))?;
+            permit.consume(data.len() as u64);
+            ctx.counters.partial.add_bytes(data.len() as u64);
+            PartialHash {
+                file_size: metadata.len(),
+                modified,
+                hash: hash_data(&data),
+            }
         }
-    }
+    };
+    recorded.insert(canonical_path, record);
+    Ok(record.hash)
 }
 
-fn read_portion(file: &Path) -> io::Result<Vec<u8>> {
-    let mut f = File::open(file)?;
-    let mut buffer = vec![0; PARTIAL_HASH_LENGTH];
-    let bytes_read = f.read(&mut buffer)?;
-    buffer.truncate(bytes_read);
+fn read_portion(file: &Path, length: usize) -> io::Result<Vec<u8>> {
+    let f = File::open(file)?;
+    let mut buffer = Vec::with_capacity(length);
+    f.take(length as u64).read_to_end(&mut buffer)?;
     Ok(buffer)
 }
 
-pub fn read_full_file(file: &Path) -> io::Result<Vec<u8>> {
+/// XxHash64 over head, middle and tail blocks of `block_size` bytes each.
+fn hash_samples(
+    file: &Path,
+    file_size: u64,
+    block_size: usize,
+    permit: &ReadPermit,
+) -> io::Result<u64> {
     let mut f = File::open(file)?;
-    let mut buffer = Vec::new();
-    f.read_to_end(&mut buffer)?;
-    Ok(buffer)
+    let block = (block_size as u64).min(file_size);
+    let offsets = [0, (file_size - block) / 2, file_size - block];
+
+    let mut hasher = XxHash64::with_seed(0);
+    let mut buffer = vec![0; block as usize];
+    for offset in offsets {
+        f.seek(SeekFrom::Start(offset))?;
+        f.read_exact(&mut buffer)?;
+        permit.consume(block);
+        hasher.write(&buffer);
+    }
+    Ok(hasher.finish())
+}
+
+/// Stream a file through the given algorithm using a fixed-size buffer.
+/// Produces the same digest as hashing the whole file at once, but memory use
+/// stays bounded regardless of file size.
+pub fn hash_file(file: &Path, algorithm: HashAlgorithm) -> io::Result<ContentHash> {
+    hash_file_with(file, algorithm, &IoThrottle::unlimited().acquire(file, 0))
+}
+
+/// `hash_file`, reporting each buffer read to `permit`.
+fn hash_file_with(
+    file: &Path,
+    algorithm: HashAlgorithm,
+    permit: &ReadPermit,
+) -> io::Result<ContentHash> {
+    let mut f = File::open(file)?;
+    let mut hasher = algorithm.hasher();
+    let mut buffer = vec![0; FULL_HASH_BUFFER_SIZE];
+    loop {
+        let bytes_read = match f.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        permit.consume(bytes_read as u64);
+        hasher.update(&buffer[..bytes_read]);
+    }
+    Ok(hasher.finish())
 }
 
 pub fn hash_data(data: &[u8]) -> u64 {
@@ -144,3 +538,188 @@ pub fn hash_data(data: &[u8]) -> u64 {
     hasher.write(data);
     hasher.finish()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::ProgressReporter;
+    use std::io::Write;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct TierRecorder(Mutex<Vec<(String, usize, usize)>>);
+
+    impl ProgressReporter for TierRecorder {
+        fn on_hash_tier_complete(&self, tier: &str, files_hashed: usize, files_eliminated: usize) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((tier.to_string(), files_hashed, files_eliminated));
+        }
+    }
+
+    #[test]
+    fn test_hash_file_matches_hash_data() {
+        // Larger than the buffer and not a multiple of it, to exercise the chunk boundary
+        let data: Vec<u8> = (0..FULL_HASH_BUFFER_SIZE * 3 + 123)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let mut tmp = tempfile::NamedTempFile::new().unwrap();
+        tmp.write_all(&data).unwrap();
+
+        let hash = hash_file(tmp.path(), HashAlgorithm::Xxh64).unwrap();
+        assert_eq!(hash.to_i64(), hash_data(&data) as i64);
+    }
+
+    #[test]
+    fn test_hash_file_empty() {
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        let hash = hash_file(tmp.path(), HashAlgorithm::Xxh64).unwrap();
+        assert_eq!(hash.to_i64(), hash_data(&[]) as i64);
+    }
+
+    #[test]
+    fn test_sample_tier_eliminates_same_header_files() {
+        let tiers = HashTiers {
+            partial_bytes: 1024,
+            sample_bytes: 4096,
+            sample_min_file_size: 64 * 1024,
+        };
+        let base = vec![0x11u8; 256 * 1024];
+        let mut different_middle = base.clone();
+        different_middle[128 * 1024] = 0x22;
+
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = [&base, &base, &different_middle]
+            .iter()
+            .enumerate()
+            .map(|(i, data)| {
+                let path = dir.path().join(format!("media_{}.bin", i));
+                std::fs::write(&path, data).unwrap();
+                path
+            })
+            .collect();
+
+        let size_map = DashMap::new();
+        size_map.insert(base.len() as u64, paths);
+        let recorder = TierRecorder::default();
+        let result = build_content_hash_map(
+            size_map,
+            HashOptions {
+                tiers,
+                ..Default::default()
+            },
+            &HashMap::new(),
+            &cache::MemoryHashCache::new(),
+            &DeviceMap::default(),
+            HashControl::new(&AtomicBool::new(false)),
+            &recorder,
+        )
+        .unwrap();
+
+        assert_eq!(result.duplicates.len(), 1);
+        assert_eq!(result.duplicates.iter().next().unwrap().value().len(), 2);
+        assert_eq!(result.partial_hashes.len(), 3);
+        let tiers = recorder.0.into_inner().unwrap();
+        assert_eq!(tiers[0], (TIER_PARTIAL.to_string(), 3, 0));
+        assert_eq!(tiers[1], (TIER_SAMPLE.to_string(), 3, 1));
+        assert_eq!(tiers[2], (TIER_FULL.to_string(), 2, 0));
+
+        // Only the two survivors of the sample tier were read in full
+        let stats = result.stats;
+        assert_eq!(stats.partial.bytes_read, 3 * 1024);
+        assert_eq!(stats.sample.bytes_read, 3 * 3 * 4096);
+        assert_eq!(stats.full.bytes_read, 2 * base.len() as u64);
+        assert_eq!(stats.sample.files_eliminated, 1);
+        assert_eq!((stats.cache.hits, stats.cache.misses), (0, 2));
+    }
+
+    #[test]
+    fn test_known_partial_hash_is_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.bin");
+        let b = dir.path().join("b.bin");
+        std::fs::write(&a, b"identical bytes").unwrap();
+        std::fs::write(&b, b"identical bytes").unwrap();
+
+        let size_map = || {
+            let map = DashMap::new();
+            map.insert(15u64, vec![a.clone(), b.clone()]);
+            map
+        };
+        let first = build_content_hash_map(
+            size_map(),
+            HashOptions::default(),
+            &HashMap::new(),
+            &cache::MemoryHashCache::new(),
+            &DeviceMap::default(),
+            HashControl::new(&AtomicBool::new(false)),
+            &crate::progress::SilentReporter,
+        )
+        .unwrap();
+        assert_eq!(first.duplicates.len(), 1);
+        assert_eq!(first.stats.partial_hashes_reused, 0);
+
+        // A stored (deliberately wrong) partial hash for `a` with a matching stamp is
+        // trusted as-is, which separates it from `b` without reading either head.
+        let mut known: HashMap<PathBuf, PartialHash> =
+            first.partial_hashes.into_iter().collect();
+        let canonical_a = std::fs::canonicalize(&a).unwrap();
+        known.get_mut(&canonical_a).unwrap().hash ^= 1;
+
+        let second = build_content_hash_map(
+            size_map(),
+            HashOptions::default(),
+            &known,
+            &cache::MemoryHashCache::new(),
+            &DeviceMap::default(),
+            HashControl::new(&AtomicBool::new(false)),
+            &crate::progress::SilentReporter,
+        )
+        .unwrap();
+        assert_eq!(second.stats.partial_hashes_reused, 2);
+        assert_eq!(second.stats.partial.bytes_read, 0);
+        assert!(second.duplicates.is_empty());
+    }
+
+    #[test]
+    fn test_completed_buckets_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, data: &[u8]| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, data).unwrap();
+            path
+        };
+        let size_map = DashMap::new();
+        size_map.insert(4u64, vec![write("a", b"same"), write("b", b"same")]);
+        size_map.insert(5u64, vec![write("c", b"one.."), write("d", b"two..")]);
+        size_map.insert(6u64, vec![write("e", b"single")]);
+
+        let completed = Mutex::new(Vec::new());
+        let on_bucket = |bucket: CompletedBucket| completed.lock().unwrap().push(bucket);
+        build_content_hash_map(
+            size_map,
+            HashOptions::default(),
+            &HashMap::new(),
+            &cache::MemoryHashCache::new(),
+            &DeviceMap::default(),
+            HashControl {
+                cancel_token: &AtomicBool::new(false),
+                pause: None,
+                on_bucket: Some(&on_bucket),
+            },
+            &crate::progress::SilentReporter,
+        )
+        .unwrap();
+
+        // Buckets of one file are never hashed, so never reported
+        let mut completed = completed.into_inner().unwrap();
+        completed.sort_by_key(|bucket| bucket.file_size);
+        assert_eq!(completed.len(), 2);
+        assert_eq!(completed[0].file_size, 4);
+        assert_eq!(completed[0].partial_hashes.len(), 2);
+        assert_eq!(completed[0].duplicates.len(), 1);
+        assert_eq!(completed[1].partial_hashes.len(), 2);
+        assert!(completed[1].duplicates.is_empty());
+    }
+}
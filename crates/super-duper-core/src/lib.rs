@@ -1,14 +1,19 @@
 pub mod analysis;
+pub mod checkpoint;
 pub mod config;
 pub mod engine;
 pub mod error;
 pub mod hasher;
+pub mod pause;
 pub mod platform;
 pub mod progress;
+pub mod scan_error;
 pub mod scanner;
 pub mod storage;
 
 pub use config::AppConfig;
-pub use engine::{ScanEngine, ScanResult, ScanStats};
+pub use engine::{IndexChanges, ScanEngine, ScanResult, ScanStats};
 pub use error::Error;
+pub use pause::PauseToken;
 pub use progress::{ProgressReporter, SilentReporter};
+pub use scan_error::{ScanError, ScanPhase};
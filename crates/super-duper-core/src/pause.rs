@@ -0,0 +1,135 @@
+use crate::progress::ProgressReporter;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// How often a paused thread looks at the cancel token.
+const CANCEL_POLL: Duration = Duration::from_millis(100);
+
+/// Pauses the walker and hasher threads of a running scan where they would
+/// otherwise check for cancellation: between directory entries and between
+/// files. Nothing is lost; the scan carries on from there once resumed.
+#[derive(Default)]
+pub struct PauseToken {
+    /// Read on every check, so a running scan never takes the lock.
+    paused: AtomicBool,
+    /// Whether `on_scan_paused` went out for the current pause.
+    reported: Mutex<bool>,
+    resumed: Condvar,
+}
+
+impl PauseToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the scan's threads to stop at their next check. Returns false if
+    /// already paused.
+    pub fn pause(&self) -> bool {
+        !self.paused.swap(true, Ordering::SeqCst)
+    }
+
+    /// Let paused threads carry on. Returns false if not paused.
+    pub fn resume(&self) -> bool {
+        let _reported = self.reported.lock().unwrap();
+        let was_paused = self.paused.swap(false, Ordering::SeqCst);
+        self.resumed.notify_all();
+        was_paused
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Block while paused, until resumed or `cancel_token` is set. The first
+    /// thread to stop reports `on_scan_paused` and the first to carry on
+    /// reports `on_scan_resumed`, so each pause is reported once.
+    pub fn wait_while_paused(&self, cancel_token: &AtomicBool, progress: &dyn ProgressReporter) {
+        if !self.is_paused() || cancel_token.load(Ordering::Relaxed) {
+            return;
+        }
+        // Reporters are called without the lock, so they may resume the scan
+        let first = {
+            let mut reported = self.reported.lock().unwrap();
+            !std::mem::replace(&mut *reported, true)
+        };
+        if first {
+            progress.on_scan_paused();
+        }
+        let mut reported = self.reported.lock().unwrap();
+        while self.is_paused() && !cancel_token.load(Ordering::Relaxed) {
+            reported = self.resumed.wait_timeout(reported, CANCEL_POLL).unwrap().0;
+        }
+        let last = std::mem::take(&mut *reported);
+        drop(reported);
+        if last {
+            progress.on_scan_resumed();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[derive(Default)]
+    struct PauseCounter {
+        paused: AtomicUsize,
+        resumed: AtomicUsize,
+    }
+
+    impl ProgressReporter for PauseCounter {
+        fn on_scan_paused(&self) {
+            self.paused.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn on_scan_resumed(&self) {
+            self.resumed.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_paused_threads_wait_for_resume_and_report_once() {
+        let token = Arc::new(PauseToken::new());
+        let counter = Arc::new(PauseCounter::default());
+        let cancel = Arc::new(AtomicBool::new(false));
+        assert!(token.pause());
+        assert!(!token.pause());
+
+        let waiters: Vec<_> = (0..4)
+            .map(|_| {
+                let (token, counter, cancel) = (token.clone(), counter.clone(), cancel.clone());
+                thread::spawn(move || token.wait_while_paused(&cancel, counter.as_ref()))
+            })
+            .collect();
+        while counter.paused.load(Ordering::SeqCst) == 0 {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(waiters.iter().all(|w| !w.is_finished()));
+
+        assert!(token.resume());
+        assert!(!token.resume());
+        for waiter in waiters {
+            waiter.join().unwrap();
+        }
+        assert_eq!(counter.paused.load(Ordering::SeqCst), 1);
+        assert_eq!(counter.resumed.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_cancel_releases_paused_threads() {
+        let token = PauseToken::new();
+        let cancel = AtomicBool::new(false);
+        token.pause();
+        thread::scope(|scope| {
+            let waiter = scope.spawn(|| token.wait_while_paused(&cancel, &crate::SilentReporter));
+            thread::sleep(Duration::from_millis(20));
+            cancel.store(true, Ordering::Relaxed);
+            waiter.join().unwrap();
+        });
+        assert!(token.is_paused());
+    }
+}
@@ -1,9 +1,131 @@
+#[cfg(unix)]
+pub mod unix;
 #[cfg(target_os = "windows")]
 pub mod windows;
 
 use std::ffi::OsString;
+use std::fs::Metadata;
+use std::io;
 use std::path::{Component, Path, PathBuf};
 
+/// Identity of a file that survives renames and moves within a volume:
+/// device + inode on Unix, volume serial + file index on Windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId {
+    pub device: u64,
+    pub index: u64,
+}
+
+/// The file's stable identity, or `None` when the filesystem doesn't provide one.
+#[cfg(unix)]
+pub fn file_id(path: &Path) -> io::Result<Option<FileId>> {
+    unix::file_id(path)
+}
+
+#[cfg(target_os = "windows")]
+pub fn file_id(path: &Path) -> io::Result<Option<FileId>> {
+    windows::file_id(path)
+}
+
+#[cfg(not(any(unix, target_os = "windows")))]
+pub fn file_id(_path: &Path) -> io::Result<Option<FileId>> {
+    Ok(None)
+}
+
+/// Identity shared by every name of a file with more than one hard link, or
+/// `None` for a file with a single name. `metadata` must be the file's own
+/// (not followed) metadata.
+#[cfg(unix)]
+pub fn hard_link_id(_path: &Path, metadata: &Metadata) -> io::Result<Option<FileId>> {
+    Ok(unix::hard_link_id(metadata))
+}
+
+#[cfg(target_os = "windows")]
+pub fn hard_link_id(path: &Path, _metadata: &Metadata) -> io::Result<Option<FileId>> {
+    windows::hard_link_id(path)
+}
+
+#[cfg(not(any(unix, target_os = "windows")))]
+pub fn hard_link_id(_path: &Path, _metadata: &Metadata) -> io::Result<Option<FileId>> {
+    Ok(None)
+}
+
+/// Key for the device (disk, mount or share) `path` lives on. Only compared for
+/// equality; the hasher uses it to apply per-device read limits.
+#[cfg(unix)]
+pub fn device_id(path: &Path) -> io::Result<u64> {
+    unix::device_id(path)
+}
+
+#[cfg(target_os = "windows")]
+pub fn device_id(path: &Path) -> io::Result<u64> {
+    windows::device_id(path)
+}
+
+#[cfg(not(any(unix, target_os = "windows")))]
+pub fn device_id(_path: &Path) -> io::Result<u64> {
+    Ok(0)
+}
+
+/// Filesystem type of the mount holding `path`, as the OS names it: `ext4`,
+/// `nfs4`, `fuse.rclone`, `NTFS` and so on. `None` where the platform doesn't say.
+#[cfg(target_os = "linux")]
+pub fn filesystem_type(path: &Path) -> io::Result<Option<String>> {
+    unix::filesystem_type(path)
+}
+
+#[cfg(target_os = "windows")]
+pub fn filesystem_type(path: &Path) -> io::Result<Option<String>> {
+    windows::filesystem_type(path)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub fn filesystem_type(_path: &Path) -> io::Result<Option<String>> {
+    Ok(None)
+}
+
+/// Device key of directory `path`, in the same terms as `device_id`, so the
+/// walker can spot mount points. Taken from `metadata` on Unix; Windows metadata
+/// doesn't carry the volume, so it's read through a handle there. `None` where
+/// it can't be had, leaving the directory on its parent's device.
+#[cfg(unix)]
+pub fn dir_device(_path: &Path, metadata: &Metadata) -> Option<u64> {
+    Some(unix::metadata_device(metadata))
+}
+
+#[cfg(target_os = "windows")]
+pub fn dir_device(path: &Path, _metadata: &Metadata) -> Option<u64> {
+    windows::device_id(path).ok()
+}
+
+#[cfg(not(any(unix, target_os = "windows")))]
+pub fn dir_device(_path: &Path, _metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+/// Whether the entry is hidden: a dot-name on Unix, the hidden attribute on Windows.
+#[cfg(target_os = "windows")]
+pub fn is_hidden(_path: &Path, metadata: &Metadata) -> bool {
+    windows::is_hidden(metadata)
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn is_hidden(path: &Path, _metadata: &Metadata) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.as_encoded_bytes().starts_with(b"."))
+}
+
+/// Whether the entry has the Windows system attribute. Always false elsewhere.
+#[cfg(target_os = "windows")]
+pub fn is_system(metadata: &Metadata) -> bool {
+    windows::is_system(metadata)
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn is_system(_metadata: &Metadata) -> bool {
+    false
+}
+
 #[cfg(target_os = "windows")]
 pub fn get_drive_letter(path: &Path) -> Option<OsString> {
     windows::get_drive_letter(path)
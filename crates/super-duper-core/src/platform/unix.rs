@@ -0,0 +1,121 @@
+use super::FileId;
+use std::fs::{self, Metadata};
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::{ffi::OsString, os::unix::ffi::OsStringExt, path::PathBuf};
+
+pub fn file_id(path: &Path) -> io::Result<Option<FileId>> {
+    let metadata = fs::metadata(path)?;
+    // Some FUSE and network filesystems report 0 instead of a real inode
+    if metadata.ino() == 0 {
+        return Ok(None);
+    }
+    Ok(Some(FileId {
+        device: metadata.dev(),
+        index: metadata.ino(),
+    }))
+}
+
+pub fn device_id(path: &Path) -> io::Result<u64> {
+    Ok(fs::metadata(path)?.dev())
+}
+
+pub fn metadata_device(metadata: &Metadata) -> u64 {
+    metadata.dev()
+}
+
+pub fn hard_link_id(metadata: &Metadata) -> Option<FileId> {
+    (metadata.nlink() > 1).then(|| FileId {
+        device: metadata.dev(),
+        index: metadata.ino(),
+    })
+}
+
+/// Type of the most recent mount over the deepest mount point containing `path`,
+/// from `/proc/self/mountinfo`.
+#[cfg(target_os = "linux")]
+pub fn filesystem_type(path: &Path) -> io::Result<Option<String>> {
+    let canonical = fs::canonicalize(path)?;
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo")?;
+    Ok(mount_type(&mountinfo, &canonical))
+}
+
+#[cfg(target_os = "linux")]
+fn mount_type(mountinfo: &str, path: &Path) -> Option<String> {
+    let mut best: Option<(usize, &str)> = None;
+    for line in mountinfo.lines() {
+        // id parent major:minor root mount-point options [optional...] - type source super-options
+        let mut fields = line.split(' ');
+        let Some(mount_point) = fields.nth(4) else {
+            continue;
+        };
+        let Some(fs_type) = fields.skip_while(|field| *field != "-").nth(1) else {
+            continue;
+        };
+        let mount_point = unescape_mount_point(mount_point);
+        if !path.starts_with(&mount_point) {
+            continue;
+        }
+        let depth = mount_point.components().count();
+        // Later lines are mounted over earlier ones at the same point
+        if best.is_none_or(|(best_depth, _)| depth >= best_depth) {
+            best = Some((depth, fs_type));
+        }
+    }
+    best.map(|(_, fs_type)| fs_type.to_string())
+}
+
+/// Undo mountinfo's octal escapes (`\040` for a space and so on).
+#[cfg(target_os = "linux")]
+fn unescape_mount_point(field: &str) -> PathBuf {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 4)
+            .filter(|_| bytes[i] == b'\\')
+            .filter(|digits| digits.iter().all(|d| (b'0'..=b'7').contains(d)))
+            .map(|digits| {
+                digits
+                    .iter()
+                    .fold(0u8, |byte, d| byte.wrapping_mul(8) + (d - b'0'))
+            });
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(OsString::from_vec(out))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mount_type_picks_deepest_latest_mount() {
+        let mountinfo = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+30 22 0:25 / /home rw,relatime shared:2 - btrfs /dev/sda2 rw
+41 30 0:40 / /home/me/cloud\\040drive rw,nosuid - fuse.rclone remote: rw
+42 30 0:41 / /home rw - tmpfs tmpfs rw";
+
+        let fs_type = |path: &str| mount_type(mountinfo, Path::new(path));
+        assert_eq!(fs_type("/etc/hosts").as_deref(), Some("ext4"));
+        assert_eq!(fs_type("/home/me/notes.txt").as_deref(), Some("tmpfs"));
+        assert_eq!(
+            fs_type("/home/me/cloud drive/a.txt").as_deref(),
+            Some("fuse.rclone")
+        );
+        assert_eq!(fs_type("/homework").as_deref(), Some("ext4"));
+    }
+}
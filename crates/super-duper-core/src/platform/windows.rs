@@ -1,5 +1,22 @@
+use super::FileId;
 use std::ffi::OsString;
+use std::fs::{File, Metadata, OpenOptions};
+use std::io;
+use std::mem;
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::os::windows::fs::{MetadataExt, OpenOptionsExt};
+use std::os::windows::io::AsRawHandle;
 use std::path::{Component, Path};
+use std::ptr;
+use winapi::shared::minwindef::{DWORD, MAX_PATH};
+use winapi::um::fileapi::{
+    GetFileInformationByHandle, GetVolumeInformationByHandleW, GetVolumeInformationW,
+    GetVolumePathNameW, BY_HANDLE_FILE_INFORMATION,
+};
+use winapi::um::winbase::FILE_FLAG_BACKUP_SEMANTICS;
+use winapi::um::winnt::{
+    FILE_ATTRIBUTE_HIDDEN, FILE_ATTRIBUTE_SYSTEM, FILE_SUPPORTS_OPEN_BY_FILE_ID, HANDLE,
+};
 
 pub fn get_drive_letter(path: &Path) -> Option<OsString> {
     for component in path.components() {
@@ -15,3 +32,113 @@ pub fn get_drive_letter(path: &Path) -> Option<OsString> {
     }
     None
 }
+
+/// Serial number of the volume holding `path`, file or directory. A volume
+/// mounted into a folder has its own, so the folder's differs from its parent's.
+pub fn device_id(path: &Path) -> io::Result<u64> {
+    Ok(file_information(path)?.dwVolumeSerialNumber as u64)
+}
+
+/// Handle information for `path`, which may be a directory. The handle asks for
+/// no data access, so files we may not read, or that another process holds open
+/// exclusively, still answer.
+fn file_information(path: &Path) -> io::Result<BY_HANDLE_FILE_INFORMATION> {
+    let file = OpenOptions::new()
+        .access_mode(0)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(path)?;
+    let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { mem::zeroed() };
+    if unsafe { GetFileInformationByHandle(file.as_raw_handle() as HANDLE, &mut info) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(info)
+}
+
+/// Volume serial number and file index. Volumes that can't open files by ID
+/// (FAT, exFAT, most network shares) don't keep the index stable, so they get `None`.
+pub fn file_id(path: &Path) -> io::Result<Option<FileId>> {
+    let file = File::open(path)?;
+    let handle = file.as_raw_handle() as HANDLE;
+
+    let mut flags: DWORD = 0;
+    let ok = unsafe {
+        GetVolumeInformationByHandleW(
+            handle,
+            ptr::null_mut(),
+            0,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            &mut flags,
+            ptr::null_mut(),
+            0,
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if flags & FILE_SUPPORTS_OPEN_BY_FILE_ID == 0 {
+        return Ok(None);
+    }
+
+    let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { mem::zeroed() };
+    if unsafe { GetFileInformationByHandle(handle, &mut info) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Some(FileId {
+        device: info.dwVolumeSerialNumber as u64,
+        index: ((info.nFileIndexHigh as u64) << 32) | info.nFileIndexLow as u64,
+    }))
+}
+
+/// Volume serial and file index of a file with more than one name. Needs a
+/// handle, since directory listings don't carry the link count.
+pub fn hard_link_id(path: &Path) -> io::Result<Option<FileId>> {
+    let info = file_information(path)?;
+    if info.nNumberOfLinks < 2 {
+        return Ok(None);
+    }
+    Ok(Some(FileId {
+        device: info.dwVolumeSerialNumber as u64,
+        index: ((info.nFileIndexHigh as u64) << 32) | info.nFileIndexLow as u64,
+    }))
+}
+
+/// Filesystem name of the volume holding `path`, e.g. `NTFS`, `ReFS`, `exFAT`.
+/// Follows volumes mounted into folders.
+pub fn filesystem_type(path: &Path) -> io::Result<Option<String>> {
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut volume = [0u16; MAX_PATH + 1];
+    let ok =
+        unsafe { GetVolumePathNameW(wide.as_ptr(), volume.as_mut_ptr(), volume.len() as DWORD) };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut name = [0u16; MAX_PATH + 1];
+    let ok = unsafe {
+        GetVolumeInformationW(
+            volume.as_ptr(),
+            ptr::null_mut(),
+            0,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            name.as_mut_ptr(),
+            name.len() as DWORD,
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+    let name = OsString::from_wide(&name[..len]);
+    Ok(Some(name.to_string_lossy().into_owned()))
+}
+
+pub fn is_hidden(metadata: &Metadata) -> bool {
+    metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+}
+
+pub fn is_system(metadata: &Metadata) -> bool {
+    metadata.file_attributes() & FILE_ATTRIBUTE_SYSTEM != 0
+}
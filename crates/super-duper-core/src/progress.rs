@@ -9,10 +9,18 @@ pub trait ProgressReporter: Send + Sync {
     fn on_hash_start(&self) {}
     fn on_hash_progress(&self, _files_hashed: usize, _total_files: usize) {}
     fn on_hash_complete(&self, _total_dupes: usize, _duration_secs: f64) {}
+    /// Per-tier totals ("partial", "sample", "full"), reported once hashing finishes.
+    fn on_hash_tier_complete(&self, _tier: &str, _files_hashed: usize, _files_eliminated: usize) {}
+    fn on_verify_start(&self) {}
+    fn on_verify_complete(&self, _verified_groups: usize, _mismatched_files: usize, _duration_secs: f64) {}
     fn on_db_write_start(&self) {}
     fn on_db_write_complete(&self, _rows: usize, _duration_secs: f64) {}
     fn on_dir_analysis_start(&self) {}
     fn on_dir_analysis_complete(&self, _fingerprints: usize, _similarity_pairs: usize, _duration_secs: f64) {}
+    /// The scan's threads have stopped for `ScanEngine::pause`.
+    fn on_scan_paused(&self) {}
+    /// A paused scan is running again.
+    fn on_scan_resumed(&self) {}
 }
 
 /// No-op progress reporter for silent operation.
@@ -0,0 +1,97 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Pipeline step at which a path was skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScanPhase {
+    /// Listing a directory, or a directory entry.
+    Walk,
+    /// Reading a file's size or modification time.
+    Metadata,
+    /// Resolving a file's canonical path.
+    Canonicalize,
+    /// Reading the head of a file for the partial hash.
+    Partial,
+    /// Reading head, middle and tail blocks for the sample hash.
+    Sample,
+    /// Reading a whole file for the content hash.
+    Full,
+}
+
+impl ScanPhase {
+    pub const ALL: [ScanPhase; 6] = [
+        ScanPhase::Walk,
+        ScanPhase::Metadata,
+        ScanPhase::Canonicalize,
+        ScanPhase::Partial,
+        ScanPhase::Sample,
+        ScanPhase::Full,
+    ];
+
+    /// Name stored in `scan_error.phase`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScanPhase::Walk => "walk",
+            ScanPhase::Metadata => "metadata",
+            ScanPhase::Canonicalize => "canonicalize",
+            ScanPhase::Partial => "partial",
+            ScanPhase::Sample => "sample",
+            ScanPhase::Full => "full",
+        }
+    }
+}
+
+impl fmt::Display for ScanPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ScanPhase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ScanPhase::ALL
+            .into_iter()
+            .find(|phase| phase.as_str() == s)
+            .ok_or_else(|| format!("unknown scan phase '{}'", s))
+    }
+}
+
+/// A path the scan couldn't examine. Anything under it is missing from the results.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanError {
+    pub path: PathBuf,
+    pub phase: ScanPhase,
+    pub kind: io::ErrorKind,
+    pub message: String,
+}
+
+impl ScanError {
+    pub fn new(path: impl Into<PathBuf>, phase: ScanPhase, error: &io::Error) -> Self {
+        Self {
+            path: path.into(),
+            phase,
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
+
+    /// For `map_err`: wraps an `io::Error` on `path` as a `ScanError` in `phase`.
+    pub fn at(phase: ScanPhase, path: &Path) -> impl FnOnce(io::Error) -> ScanError + '_ {
+        move |error| ScanError::new(path, phase, &error)
+    }
+
+    /// `io::ErrorKind` name stored in `scan_error.error_kind`, e.g. `PermissionDenied`.
+    pub fn kind_name(&self) -> String {
+        format!("{:?}", self.kind)
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): {}", self.path.display(), self.phase, self.message)
+    }
+}
@@ -0,0 +1,131 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Which device each part of a scanned tree lives on.
+///
+/// The walker records every root plus each directory that sits on a different
+/// device from its parent (a mount point), so a file's device is that of the
+/// deepest recorded directory containing it.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceMap {
+    mounts: Vec<(PathBuf, u64)>,
+}
+
+impl DeviceMap {
+    pub fn insert(&mut self, dir: PathBuf, device: u64) {
+        self.mounts.push((dir, device));
+    }
+
+    /// Device of `path`, or 0 when it's outside every recorded directory.
+    pub fn device_of(&self, path: &Path) -> u64 {
+        self.mounts
+            .iter()
+            .filter(|(dir, _)| path.starts_with(dir))
+            .max_by_key(|(dir, _)| dir.components().count())
+            .map_or(0, |(_, device)| *device)
+    }
+
+    /// Number of distinct devices seen.
+    pub fn device_count(&self) -> usize {
+        let mut devices: Vec<u64> = self.mounts.iter().map(|(_, d)| *d).collect();
+        devices.sort_unstable();
+        devices.dedup();
+        devices.len()
+    }
+}
+
+/// Filesystem types a scan may enter, configured under `[filesystem_types]` or
+/// per root under `[[root_filesystem_types]]`.
+///
+/// Names are compared case-insensitively, and a name also covers its subtypes:
+/// `fuse` matches `fuse.rclone` and `fuse.sshfs`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct FilesystemFilter {
+    /// When non-empty, only these types are scanned; mounts of unknown type are skipped.
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+impl FilesystemFilter {
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Whether a mount of type `fs_type` (`None` if unknown) may be scanned.
+    pub fn permits(&self, fs_type: Option<&str>) -> bool {
+        let Some(fs_type) = fs_type else {
+            return self.allow.is_empty();
+        };
+        let matches = |name: &String| type_matches(name, fs_type);
+        !self.deny.iter().any(matches) && (self.allow.is_empty() || self.allow.iter().any(matches))
+    }
+}
+
+fn type_matches(name: &str, fs_type: &str) -> bool {
+    match fs_type.get(..name.len()) {
+        Some(prefix) if prefix.eq_ignore_ascii_case(name) => {
+            matches!(fs_type.as_bytes().get(name.len()), None | Some(b'.'))
+        }
+        _ => false,
+    }
+}
+
+/// A `FilesystemFilter` for one root, overriding `filesystem_types`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct RootFilesystemFilter {
+    /// As written in `root_paths`.
+    pub root: String,
+    #[serde(flatten)]
+    pub filter: FilesystemFilter,
+}
+
+/// A scanned root and where it lives, recorded on the session in `session_root`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootDevice {
+    pub path: PathBuf,
+    pub device: u64,
+    pub filesystem_type: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_of_uses_deepest_mount() {
+        let mut devices = DeviceMap::default();
+        devices.insert(PathBuf::from("/data"), 1);
+        devices.insert(PathBuf::from("/data/nfs"), 2);
+        devices.insert(PathBuf::from("/data/nfs/local-cache"), 1);
+
+        assert_eq!(devices.device_of(Path::new("/data/a.txt")), 1);
+        assert_eq!(devices.device_of(Path::new("/data/nfs/b.txt")), 2);
+        assert_eq!(devices.device_of(Path::new("/data/nfs/local-cache/c.txt")), 1);
+        // Component-wise, so a sibling with a shared name prefix isn't matched
+        assert_eq!(devices.device_of(Path::new("/data/nfs2/d.txt")), 1);
+        assert_eq!(devices.device_of(Path::new("/other/e.txt")), 0);
+        assert_eq!(devices.device_count(), 2);
+    }
+
+    #[test]
+    fn test_filesystem_filter() {
+        let filter = FilesystemFilter {
+            allow: vec![],
+            deny: vec!["fuse".into(), "proc".into()],
+        };
+        assert!(filter.permits(Some("ext4")));
+        assert!(filter.permits(None));
+        assert!(!filter.permits(Some("fuse.rclone")));
+        assert!(!filter.permits(Some("FUSE")));
+        assert!(filter.permits(Some("fuseblk")));
+
+        let filter = FilesystemFilter {
+            allow: vec!["ntfs".into()],
+            deny: vec![],
+        };
+        assert!(filter.permits(Some("NTFS")));
+        assert!(!filter.permits(Some("exFAT")));
+        assert!(!filter.permits(None));
+    }
+}
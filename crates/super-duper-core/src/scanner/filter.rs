@@ -0,0 +1,194 @@
+use crate::platform;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::Metadata;
+use std::path::Path;
+
+/// Which files a scan considers, configured under `[filter]`. Applied during the
+/// walk, so excluded files are never read, and recorded on the session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanFilter {
+    /// Smallest file size scanned. The default of 1 skips empty files; 0 includes them.
+    pub min_size: u64,
+    pub max_size: Option<u64>,
+    /// Only files modified at or after this time. RFC 3339, or `YYYY-MM-DD` for
+    /// midnight UTC.
+    #[serde(with = "date")]
+    pub modified_after: Option<DateTime<Utc>>,
+    /// Only files modified before this time.
+    #[serde(with = "date")]
+    pub modified_before: Option<DateTime<Utc>>,
+    /// When non-empty, only files with one of these extensions. Case-insensitive,
+    /// with or without the leading dot.
+    pub include_extensions: Vec<String>,
+    pub exclude_extensions: Vec<String>,
+    /// Scan hidden files and directories: dot-names on Unix, the hidden attribute
+    /// on Windows.
+    pub include_hidden: bool,
+    /// Scan files and directories with the Windows system attribute.
+    pub include_system: bool,
+    /// When non-empty, only files whose path matches one of these globs.
+    pub include_patterns: Vec<String>,
+}
+
+impl Default for ScanFilter {
+    fn default() -> Self {
+        Self {
+            min_size: 1,
+            max_size: None,
+            modified_after: None,
+            modified_before: None,
+            include_extensions: Vec::new(),
+            exclude_extensions: Vec::new(),
+            include_hidden: true,
+            include_system: true,
+            include_patterns: Vec::new(),
+        }
+    }
+}
+
+impl ScanFilter {
+    /// Whether a file passes every setting but `include_patterns`, which the
+    /// walker matches itself.
+    pub fn admits_file(&self, path: &Path, metadata: &Metadata) -> bool {
+        let size = metadata.len();
+        if size < self.min_size || self.max_size.is_some_and(|max| size > max) {
+            return false;
+        }
+        if self.modified_after.is_some() || self.modified_before.is_some() {
+            let Ok(modified) = metadata.modified() else {
+                return false;
+            };
+            let modified = DateTime::<Utc>::from(modified);
+            if self.modified_after.is_some_and(|after| modified < after)
+                || self.modified_before.is_some_and(|before| modified >= before)
+            {
+                return false;
+            }
+        }
+        if !self.include_extensions.is_empty() || !self.exclude_extensions.is_empty() {
+            let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+            if !self.include_extensions.is_empty()
+                && !extension_listed(&self.include_extensions, extension)
+            {
+                return false;
+            }
+            if extension_listed(&self.exclude_extensions, extension) {
+                return false;
+            }
+        }
+        self.admits_attributes(path, metadata)
+    }
+
+    /// Whether a directory may be entered.
+    pub fn admits_dir(&self, path: &Path, metadata: &Metadata) -> bool {
+        self.admits_attributes(path, metadata)
+    }
+
+    fn admits_attributes(&self, path: &Path, metadata: &Metadata) -> bool {
+        (self.include_hidden || !platform::is_hidden(path, metadata))
+            && (self.include_system || !platform::is_system(metadata))
+    }
+}
+
+fn extension_listed(list: &[String], extension: &str) -> bool {
+    list.iter()
+        .any(|listed| listed.trim_start_matches('.').eq_ignore_ascii_case(extension))
+}
+
+/// `Option<DateTime<Utc>>` as RFC 3339 text, also accepting a plain `YYYY-MM-DD`.
+mod date {
+    use super::*;
+    use serde::de::Error;
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<DateTime<Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(time) => serializer.serialize_some(&time.to_rfc3339()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<Utc>>, D::Error> {
+        let Some(text) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+        if let Ok(time) = DateTime::parse_from_rfc3339(&text) {
+            return Ok(Some(time.with_timezone(&Utc)));
+        }
+        NaiveDate::parse_from_str(&text, "%Y-%m-%d")
+            .map(|date| Some(date.and_time(Default::default()).and_utc()))
+            .map_err(|_| D::Error::custom(format!("invalid date '{}'", text)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_admits_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let photo = dir.path().join("photo.JPG");
+        fs::write(&photo, "0123456789").unwrap();
+        let two_days_ago = SystemTime::now() - Duration::from_secs(2 * 86400);
+        File::options()
+            .write(true)
+            .open(&photo)
+            .unwrap()
+            .set_modified(two_days_ago)
+            .unwrap();
+        let metadata = fs::metadata(&photo).unwrap();
+        let admits = |filter: ScanFilter| filter.admits_file(&photo, &metadata);
+
+        assert!(admits(ScanFilter::default()));
+        assert!(!admits(ScanFilter {
+            min_size: 11,
+            ..Default::default()
+        }));
+        assert!(!admits(ScanFilter {
+            max_size: Some(9),
+            ..Default::default()
+        }));
+        assert!(admits(ScanFilter {
+            include_extensions: vec![".jpg".into(), "png".into()],
+            ..Default::default()
+        }));
+        assert!(!admits(ScanFilter {
+            exclude_extensions: vec!["jpg".into()],
+            ..Default::default()
+        }));
+        assert!(!admits(ScanFilter {
+            modified_after: Some(Utc::now() - chrono::Duration::days(1)),
+            ..Default::default()
+        }));
+        assert!(admits(ScanFilter {
+            modified_before: Some(Utc::now() - chrono::Duration::days(1)),
+            ..Default::default()
+        }));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hidden_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let hidden = dir.path().join(".cache");
+        fs::write(&hidden, "x").unwrap();
+        let metadata = fs::metadata(&hidden).unwrap();
+
+        assert!(ScanFilter::default().admits_file(&hidden, &metadata));
+        let filter = ScanFilter {
+            include_hidden: false,
+            ..Default::default()
+        };
+        assert!(!filter.admits_file(&hidden, &metadata));
+    }
+}
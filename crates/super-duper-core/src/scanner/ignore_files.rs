@@ -0,0 +1,98 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Ignore files read from each scanned directory, lowest precedence first.
+pub const IGNORE_FILE_NAMES: [&str; 3] = [".gitignore", ".ignore", ".superduperignore"];
+
+/// The rules of one directory's ignore files, linked to those of the
+/// directories above it.
+///
+/// Patterns follow gitignore: they're relative to the directory holding the
+/// file, a leading `/` anchors one to that directory, a trailing `/` matches
+/// only directories, and `!` re-includes a path an earlier rule excluded. Rules
+/// in deeper directories take precedence, and within a directory
+/// `.superduperignore` overrides `.ignore`, which overrides `.gitignore`.
+#[derive(Debug)]
+pub struct IgnoreRules {
+    matcher: Gitignore,
+    parent: Option<Arc<IgnoreRules>>,
+}
+
+impl IgnoreRules {
+    /// Add the rules in `dir`'s ignore files, named in `present`, below `parent`.
+    /// Returns `parent` when none of the files holds a rule.
+    pub fn load(
+        dir: &Path,
+        present: &[&str],
+        parent: Option<Arc<IgnoreRules>>,
+    ) -> Option<Arc<IgnoreRules>> {
+        let mut builder = GitignoreBuilder::new(dir);
+        for name in IGNORE_FILE_NAMES {
+            if present.contains(&name) {
+                if let Some(e) = builder.add(dir.join(name)) {
+                    warn!("Ignoring part of {}: {}", dir.join(name).display(), e);
+                }
+            }
+        }
+        match builder.build() {
+            Ok(matcher) if !matcher.is_empty() => Some(Arc::new(IgnoreRules { matcher, parent })),
+            Ok(_) => parent,
+            Err(e) => {
+                warn!("Ignore files in {} not used: {}", dir.display(), e);
+                parent
+            }
+        }
+    }
+
+    /// Whether `path`, an entry of the directory these rules were last loaded
+    /// for, is excluded.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut rules = Some(self);
+        while let Some(layer) = rules {
+            match layer.matcher.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => rules = layer.parent.as_deref(),
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_anchoring_negation_and_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let project = root.join("project");
+        fs::create_dir(&project).unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n/build/\n").unwrap();
+        fs::write(project.join(".gitignore"), "!keep.log\n").unwrap();
+        fs::write(project.join(".superduperignore"), "cache/\n*.tmp\n").unwrap();
+        fs::write(project.join(".ignore"), "!notes.tmp\n").unwrap();
+
+        let top = IgnoreRules::load(root, &[".gitignore"], None).unwrap();
+        assert!(top.is_ignored(&root.join("a.log"), false));
+        assert!(top.is_ignored(&root.join("build"), true));
+        assert!(!top.is_ignored(&root.join("a.txt"), false));
+
+        let rules = IgnoreRules::load(&project, &IGNORE_FILE_NAMES, Some(top)).unwrap();
+        // Unanchored parent rules still apply below; anchored ones don't
+        assert!(rules.is_ignored(&project.join("b.log"), false));
+        assert!(!rules.is_ignored(&project.join("build"), true));
+        // A deeper `!` re-includes
+        assert!(!rules.is_ignored(&project.join("keep.log"), false));
+        // Directory-only rule
+        assert!(rules.is_ignored(&project.join("cache"), true));
+        assert!(!rules.is_ignored(&project.join("cache"), false));
+        // .superduperignore beats .ignore in the same directory
+        assert!(rules.is_ignored(&project.join("notes.tmp"), false));
+    }
+}
@@ -0,0 +1,52 @@
+use serde::Deserialize;
+use std::fmt;
+use std::path::PathBuf;
+
+/// What the scanner does with symbolic links (and junctions on Windows).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SymlinkPolicy {
+    /// Ignore links entirely.
+    #[default]
+    Skip,
+    /// Scan what links point to, unless the target is already inside a root or
+    /// a previously followed link, so loops and double counting can't happen.
+    Follow,
+    /// Don't scan targets; record each link and its target in `file_link`.
+    Record,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    Symlink,
+    /// Another name for a file already in the scan; its data is already shared.
+    HardLink,
+}
+
+impl LinkKind {
+    /// Name stored in `file_link.kind`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkKind::Symlink => "symlink",
+            LinkKind::HardLink => "hardlink",
+        }
+    }
+}
+
+impl fmt::Display for LinkKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A link found by the walker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileLink {
+    pub path: PathBuf,
+    /// Symlinks: the link's target as stored. Hard links: the name the file is
+    /// scanned under.
+    pub target: PathBuf,
+    pub kind: LinkKind,
+    /// Size of the linked file; 0 for symlinks.
+    pub file_size: u64,
+}
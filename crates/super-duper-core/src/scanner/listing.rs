@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::FileType;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How close to the start of a walk a directory's mtime may be before its
+/// listing isn't kept: an entry added in the same clock tick as the listing
+/// wouldn't move the mtime. Two seconds covers FAT's granularity.
+pub const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// What a directory entry is, as its directory's listing reported it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    Other,
+}
+
+impl From<FileType> for EntryKind {
+    fn from(file_type: FileType) -> Self {
+        if file_type.is_symlink() {
+            Self::Symlink
+        } else if file_type.is_dir() {
+            Self::Dir
+        } else if file_type.is_file() {
+            Self::File
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// Every entry of one directory, and the directory's mtime when it was listed.
+/// While the mtime is unchanged no entry has been added, removed or renamed, so
+/// the next walk can use the listing instead of reading the directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirListing {
+    /// As walked, not canonicalized.
+    pub path: PathBuf,
+    /// Since the Unix epoch.
+    pub modified: Duration,
+    /// Entry names and kinds, including those ignored or filtered out.
+    pub entries: Vec<(String, EntryKind)>,
+}
+
+/// Listings kept by an earlier walk, by directory path.
+pub type DirListings = HashMap<PathBuf, DirListing>;
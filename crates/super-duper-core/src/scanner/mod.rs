@@ -1,3 +1,16 @@
+pub mod devices;
+pub mod filter;
+pub mod ignore_files;
+pub mod links;
+pub mod listing;
 pub mod walk;
 
-pub use walk::build_size_to_files_map;
+pub use devices::{DeviceMap, FilesystemFilter, RootDevice, RootFilesystemFilter};
+pub use filter::ScanFilter;
+pub use ignore_files::IgnoreRules;
+pub use links::{FileLink, LinkKind, SymlinkPolicy};
+pub use listing::{DirListing, DirListings, EntryKind};
+pub use walk::{
+    build_size_to_files_map, build_size_to_files_map_incremental, FileStat, RootScanOptions,
+    WalkControl, WalkOptions, WalkOutcome,
+};
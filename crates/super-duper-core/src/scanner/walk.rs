@@ -1,25 +1,471 @@
+use super::devices::{FilesystemFilter, RootDevice, RootFilesystemFilter};
+use super::filter::ScanFilter;
+use super::ignore_files::{IgnoreRules, IGNORE_FILE_NAMES};
+use super::links::{FileLink, LinkKind, SymlinkPolicy};
+use super::listing::{DirListing, DirListings, EntryKind, RACY_WINDOW};
+use super::DeviceMap;
+use crate::pause::PauseToken;
+use crate::platform::{self, FileId};
 use crate::progress::ProgressReporter;
+use crate::scan_error::{ScanError, ScanPhase};
 use dashmap::DashMap;
 use glob::Pattern;
-use rayon::prelude::*;
-use std::fs;
+use std::fs::{self, DirEntry, Metadata};
 use std::io;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use tracing::error;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, error, info, warn};
+
+/// Depth limit used when `max_scan_depth` isn't configured. Deep enough for any
+/// real tree, shallow enough to stop a directory loop.
+pub const DEFAULT_MAX_DEPTH: usize = 1024;
+
+/// Settings for one `build_size_to_files_map` call, usually from `AppConfig::walk_options`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkOptions {
+    /// Directories this many levels below a root are listed but not descended into.
+    pub max_depth: usize,
+    pub symlinks: SymlinkPolicy,
+    /// Stay on each root's own device, skipping anything mounted below it.
+    pub one_file_system: bool,
+    /// Filesystem types roots and mounts below them may be on.
+    pub filesystem_types: FilesystemFilter,
+    /// Replacements for `filesystem_types` on particular roots.
+    pub root_filesystem_types: Vec<RootFilesystemFilter>,
+    pub filter: ScanFilter,
+    /// Extra ignore globs and replacement filters for particular roots.
+    pub root_options: Vec<RootScanOptions>,
+    /// Honour `.gitignore`, `.ignore` and `.superduperignore` files in scanned directories.
+    pub ignore_files: bool,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            symlinks: SymlinkPolicy::default(),
+            one_file_system: false,
+            filesystem_types: FilesystemFilter::default(),
+            root_filesystem_types: Vec::new(),
+            filter: ScanFilter::default(),
+            root_options: Vec::new(),
+            ignore_files: false,
+        }
+    }
+}
+
+impl WalkOptions {
+    /// The filesystem types allowed under `root`.
+    pub fn filesystem_filter(&self, root: &Path) -> &FilesystemFilter {
+        self.root_filesystem_types
+            .iter()
+            .find(|rule| Path::new(&rule.root) == root)
+            .map_or(&self.filesystem_types, |rule| &rule.filter)
+    }
+
+    /// Settings of `root_options` for `root`, if any.
+    pub fn root_options(&self, root: &Path) -> Option<&RootScanOptions> {
+        self.root_options
+            .iter()
+            .find(|options| Path::new(&options.root) == root)
+    }
+}
+
+/// Settings for one root that add to or replace those of the whole walk.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RootScanOptions {
+    /// As written in `root_paths`.
+    pub root: String,
+    /// Matched in addition to the walk's ignore globs.
+    pub ignore_patterns: Vec<String>,
+    /// Replaces `WalkOptions::filter`.
+    pub filter: Option<ScanFilter>,
+}
+
+/// Size and mtime of a walked file, as the walk read them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileStat {
+    pub size: u64,
+    /// Since the Unix epoch; zero when the platform doesn't report it.
+    pub modified: Duration,
+}
+
+/// Result of `build_size_to_files_map`.
+#[derive(Debug, Default)]
+pub struct WalkOutcome {
+    /// file_size → paths with that size.
+    pub files: DashMap<u64, Vec<PathBuf>>,
+    /// Stats of every path in `files`, so later phases needn't read them again.
+    pub stats: DashMap<PathBuf, FileStat>,
+    /// Device of each root and mount point, so the hasher can limit reads per device.
+    pub devices: DeviceMap,
+    /// Every directory or entry that couldn't be examined. Their contents are
+    /// missing from `files`.
+    pub errors: Vec<ScanError>,
+    /// Symlinks under `SymlinkPolicy::Record`, and every hard link beyond the one
+    /// name each multiply-linked file is scanned under.
+    pub links: Vec<FileLink>,
+    /// Every root that exists, with its device and filesystem type.
+    pub roots: Vec<RootDevice>,
+    /// Roots and mount points left out by `one_file_system` or the filesystem
+    /// type filters.
+    pub skipped_mounts: Vec<PathBuf>,
+    /// Listings of the directories visited, for the next walk to reuse. Those
+    /// modified within `RACY_WINDOW` of the walk starting, or not listed in
+    /// full, are left out.
+    pub listings: Vec<DirListing>,
+    /// Directories whose listing was taken from the previous walk's.
+    pub reused_listings: usize,
+}
+
+/// What applies under one root.
+struct RootContext<'a> {
+    device: u64,
+    filesystem_filter: &'a FilesystemFilter,
+    filter: &'a ScanFilter,
+    /// The walk's ignore globs followed by the root's own.
+    ignore_patterns: Vec<Pattern>,
+    /// `filter.include_patterns`, compiled.
+    include_patterns: Vec<Pattern>,
+}
+
+/// State shared by every directory visited during one walk.
+struct WalkContext<'a> {
+    options: &'a WalkOptions,
+    /// Settings of each root scanned, by index.
+    roots: Vec<RootContext<'a>>,
+    map: DashMap<u64, Vec<PathBuf>>,
+    stats: DashMap<PathBuf, FileStat>,
+    mounts: Mutex<Vec<(PathBuf, u64)>>,
+    errors: Mutex<Vec<ScanError>>,
+    links: Mutex<Vec<FileLink>>,
+    skipped_mounts: Mutex<Vec<PathBuf>>,
+    /// Names and stats of each multiply-linked file, resolved to one name after the walk.
+    hard_links: DashMap<FileId, (FileStat, Vec<PathBuf>)>,
+    /// Canonical roots plus every followed symlink target; a link into any of
+    /// these is already covered.
+    covered: Mutex<Vec<PathBuf>>,
+    cancel_token: &'a AtomicBool,
+    pause: Option<&'a PauseToken>,
+    on_listing: Option<&'a (dyn Fn(&DirListing) + Sync)>,
+    progress: &'a dyn ProgressReporter,
+    file_count: AtomicUsize,
+    /// Listings from the previous walk.
+    previous: &'a DirListings,
+    listings: Mutex<Vec<DirListing>>,
+    reused_listings: AtomicUsize,
+    /// When the walk started, since the Unix epoch.
+    started: Duration,
+}
+
+impl WalkContext<'_> {
+    /// Whether the walk is cancelled, once any pause is over.
+    fn is_cancelled(&self) -> bool {
+        if let Some(pause) = self.pause {
+            pause.wait_while_paused(self.cancel_token, self.progress);
+        }
+        self.cancel_token.load(Ordering::Relaxed)
+    }
+
+    /// Keep a listing for the next walk, reporting it first.
+    fn keep_listing(&self, listing: DirListing) {
+        if let Some(on_listing) = self.on_listing {
+            on_listing(&listing);
+        }
+        self.listings.lock().unwrap().push(listing);
+    }
+
+    fn is_ignored(&self, path: &Path, root: usize) -> bool {
+        self.roots[root]
+            .ignore_patterns
+            .iter()
+            .any(|pattern| pattern.matches_path(path))
+    }
+
+    fn is_included(&self, path: &Path, metadata: &Metadata, root: usize) -> bool {
+        let root = &self.roots[root];
+        root.filter.admits_file(path, metadata)
+            && (root.include_patterns.is_empty()
+                || root
+                    .include_patterns
+                    .iter()
+                    .any(|pattern| pattern.matches_path(path)))
+    }
+
+    fn admits_dir(&self, path: &Path, metadata: &Metadata, root: usize) -> bool {
+        self.roots[root].filter.admits_dir(path, metadata)
+    }
+
+    /// Claim a canonical symlink target for following. False when it's already
+    /// inside a root or a followed target.
+    fn claim_target(&self, target: &Path) -> bool {
+        let mut covered = self.covered.lock().unwrap();
+        if covered.iter().any(|dir| target.starts_with(dir)) {
+            return false;
+        }
+        covered.push(target.to_path_buf());
+        true
+    }
+
+    /// Whether to enter `dir`, a mount point below root number `root` on `device`.
+    /// Skipped mounts are recorded.
+    fn may_enter_mount(&self, root: usize, dir: &Path, device: u64) -> bool {
+        let RootContext {
+            device: root_device,
+            filesystem_filter: filter,
+            ..
+        } = self.roots[root];
+        let reason = if self.options.one_file_system && device != root_device {
+            Some("on another filesystem".to_string())
+        } else if filter.is_empty() {
+            None
+        } else {
+            let fs_type = platform::filesystem_type(dir).ok().flatten();
+            (!filter.permits(fs_type.as_deref()))
+                .then(|| format!("{} filesystem", fs_type.as_deref().unwrap_or("unknown")))
+        };
+        match reason {
+            Some(reason) => {
+                info!("Not entering {}: {}", dir.display(), reason);
+                self.skipped_mounts.lock().unwrap().push(dir.to_path_buf());
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// Record `path` in its size bucket.
+    fn add_to_map(&self, path: PathBuf, stat: FileStat) {
+        self.stats.insert(path.clone(), stat);
+        self.map.entry(stat.size).or_default().push(path);
+    }
+
+    /// Whether a directory last modified at `modified` has been left alone long
+    /// enough for its listing to be reused.
+    fn is_settled(&self, modified: Duration) -> bool {
+        modified + RACY_WINDOW < self.started
+    }
+
+    fn record_error(&self, path: PathBuf, phase: ScanPhase, error: io::Error) {
+        warn!("Skipping {}: {}", path.display(), error);
+        self.errors
+            .lock()
+            .unwrap()
+            .push(ScanError::new(path, phase, &error));
+    }
+}
 
 /// Parallel directory traversal. Builds a map of file_size → Vec<PathBuf>,
-/// filtering by glob ignore patterns. Skips symlinks and 0-byte files.
+/// filtering by glob ignore patterns, `options.filter` (or a root's own
+/// `root_options`) and, with
+/// `options.ignore_files`, the ignore files found along the way, and handles symlinks
+/// as `options.symlinks` says. A file with several hard links is listed under
+/// one name only; the others are returned in `links`.
+///
+/// Each directory is listed by its own rayon task, so the walk is spread over the
+/// pool by work stealing rather than recursing on the stack. Entry types come from
+/// the directory listing; only files (for their size) and directories (for their
+/// device) are stat'ed. Paths that can't be read are collected in the outcome's
+/// `errors` and the walk carries on. Directories on a different device from their
+/// parent are recorded in the outcome's `DeviceMap`; `options.one_file_system`
+/// and the filesystem type filters decide whether they're entered.
 pub fn build_size_to_files_map(
     root_paths: &[&str],
     ignore_globs: &[&str],
+    options: &WalkOptions,
     cancel_token: &AtomicBool,
     progress: &dyn ProgressReporter,
-) -> io::Result<DashMap<u64, Vec<PathBuf>>> {
-    let map: DashMap<u64, Vec<PathBuf>> = DashMap::new();
+) -> WalkOutcome {
+    build_size_to_files_map_incremental(
+        root_paths,
+        ignore_globs,
+        options,
+        &DirListings::new(),
+        WalkControl::new(cancel_token),
+        progress,
+    )
+}
+
+/// Cancellation, pausing and per-listing reporting for one walk.
+#[derive(Clone, Copy)]
+pub struct WalkControl<'a> {
+    pub cancel_token: &'a AtomicBool,
+    /// While paused, walker threads stop between directory entries.
+    pub pause: Option<&'a PauseToken>,
+    /// Called with each listing kept for the next walk as it's taken, e.g. to
+    /// checkpoint it.
+    pub on_listing: Option<&'a (dyn Fn(&DirListing) + Sync)>,
+}
+
+impl<'a> WalkControl<'a> {
+    pub fn new(cancel_token: &'a AtomicBool) -> Self {
+        Self {
+            cancel_token,
+            pause: None,
+            on_listing: None,
+        }
+    }
+}
+
+/// `build_size_to_files_map`, taking the entries of each directory whose mtime
+/// matches its listing in `previous` from that listing instead of reading the
+/// directory. The entries' metadata is still read, so changed files are found.
+pub fn build_size_to_files_map_incremental(
+    root_paths: &[&str],
+    ignore_globs: &[&str],
+    options: &WalkOptions,
+    previous: &DirListings,
+    control: WalkControl,
+    progress: &dyn ProgressReporter,
+) -> WalkOutcome {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let ignore_patterns = compile_globs(ignore_globs.iter().copied());
+
+    // Roots are located up front, so each mount below one can be compared with it
+    let mut roots = Vec::new();
+    let mut errors = Vec::new();
+    let mut skipped_mounts = Vec::new();
+    let mut root_states = Vec::new();
+    for root_dir in root_paths {
+        let root = PathBuf::from(root_dir);
+        let device = match platform::device_id(&root) {
+            Ok(device) => device,
+            Err(e) => {
+                warn!("Skipping {}: {}", root.display(), e);
+                errors.push(ScanError::new(root, ScanPhase::Walk, &e));
+                continue;
+            }
+        };
+        let filesystem_filter = options.filesystem_filter(&root);
+        let filesystem_type = platform::filesystem_type(&root).ok().flatten();
+        if filesystem_filter.permits(filesystem_type.as_deref()) {
+            let root_options = options.root_options(&root);
+            let filter = root_options
+                .and_then(|o| o.filter.as_ref())
+                .unwrap_or(&options.filter);
+            let mut root_ignores = ignore_patterns.clone();
+            if let Some(o) = root_options {
+                root_ignores.extend(compile_globs(o.ignore_patterns.iter().map(String::as_str)));
+            }
+            root_states.push((
+                root.clone(),
+                RootContext {
+                    device,
+                    filesystem_filter,
+                    filter,
+                    ignore_patterns: root_ignores,
+                    include_patterns: compile_globs(
+                        filter.include_patterns.iter().map(String::as_str),
+                    ),
+                },
+            ));
+        } else {
+            info!(
+                "Not scanning {}: {} filesystem",
+                root.display(),
+                filesystem_type.as_deref().unwrap_or("unknown")
+            );
+            skipped_mounts.push(root.clone());
+        }
+        roots.push(RootDevice {
+            path: root,
+            device,
+            filesystem_type,
+        });
+    }
+
+    let (root_dirs, root_contexts): (Vec<_>, Vec<_>) = root_states.into_iter().unzip();
+    let ctx = WalkContext {
+        options,
+        roots: root_contexts,
+        map: DashMap::new(),
+        stats: DashMap::new(),
+        mounts: Mutex::new(Vec::new()),
+        errors: Mutex::new(errors),
+        links: Mutex::new(Vec::new()),
+        skipped_mounts: Mutex::new(skipped_mounts),
+        hard_links: DashMap::new(),
+        covered: Mutex::new(
+            root_dirs
+                .iter()
+                .filter_map(|root| fs::canonicalize(root).ok())
+                .collect(),
+        ),
+        cancel_token: control.cancel_token,
+        pause: control.pause,
+        on_listing: control.on_listing,
+        progress,
+        file_count: AtomicUsize::new(0),
+        previous,
+        listings: Mutex::new(Vec::new()),
+        reused_listings: AtomicUsize::new(0),
+        started,
+    };
+
+    rayon::scope(|scope| {
+        for (index, root) in root_dirs.iter().enumerate() {
+            let (root, device) = (root.clone(), ctx.roots[index].device);
+            ctx.mounts.lock().unwrap().push((root.clone(), device));
+            let ctx = &ctx;
+            let frame = DirFrame {
+                device,
+                depth: 0,
+                root: index,
+                ignores: None,
+                modified: fs::metadata(&root).ok().as_ref().and_then(modified),
+            };
+            scope.spawn(move |scope| visit_dir(scope, root, frame, ctx));
+        }
+    });
+
+    // Scan each multiply-linked file under its first name in path order
+    let mut links = ctx.links.into_inner().unwrap();
+    for (_, (stat, mut paths)) in ctx.hard_links {
+        paths.sort();
+        let mut names = paths.into_iter();
+        let Some(scanned) = names.next() else {
+            continue;
+        };
+        links.extend(names.map(|path| FileLink {
+            path,
+            target: scanned.clone(),
+            kind: LinkKind::HardLink,
+            file_size: stat.size,
+        }));
+        ctx.stats.insert(scanned.clone(), stat);
+        ctx.map.entry(stat.size).or_default().push(scanned);
+    }
+    if options.symlinks == SymlinkPolicy::Follow {
+        // A link followed before one to its parent directory is listed twice
+        for mut paths in ctx.map.iter_mut() {
+            paths.sort();
+            paths.dedup();
+        }
+    }
+
+    let mut devices = DeviceMap::default();
+    for (dir, device) in ctx.mounts.into_inner().unwrap() {
+        devices.insert(dir, device);
+    }
+    WalkOutcome {
+        files: ctx.map,
+        stats: ctx.stats,
+        devices,
+        errors: ctx.errors.into_inner().unwrap(),
+        links,
+        roots,
+        skipped_mounts: ctx.skipped_mounts.into_inner().unwrap(),
+        listings: ctx.listings.into_inner().unwrap(),
+        reused_listings: ctx.reused_listings.into_inner(),
+    }
+}
 
-    let ignore_patterns: Vec<Pattern> = ignore_globs
-        .iter()
+fn compile_globs<'g>(globs: impl Iterator<Item = &'g str>) -> Vec<Pattern> {
+    globs
         .filter_map(|glob| match Pattern::new(glob) {
             Ok(p) => Some(p),
             Err(e) => {
@@ -27,119 +473,721 @@ pub fn build_size_to_files_map(
                 None
             }
         })
-        .collect();
-
-    let file_count = AtomicUsize::new(0);
-
-    root_paths
-        .par_iter()
-        .try_for_each(|root_dir| {
-            visit_dirs(
-                Path::new(root_dir),
-                &map,
-                &ignore_patterns,
-                cancel_token,
-                progress,
-                &file_count,
-            )
-        })?;
-
-    Ok(map)
-}
-
-fn visit_dirs(
-    dir: &Path,
-    map: &DashMap<u64, Vec<PathBuf>>,
-    ignore_patterns: &[Pattern],
-    cancel_token: &AtomicBool,
-    progress: &dyn ProgressReporter,
-    file_count: &AtomicUsize,
-) -> io::Result<()> {
-    if cancel_token.load(Ordering::Relaxed) {
-        return Ok(());
+        .collect()
+}
+
+/// A directory being listed: where it sits, and the ignore rules that apply in it.
+struct DirFrame {
+    device: u64,
+    depth: usize,
+    /// Index of the root the directory was reached from.
+    root: usize,
+    ignores: Option<Arc<IgnoreRules>>,
+    /// The directory's mtime, when known.
+    modified: Option<Duration>,
+}
+
+/// An entry of a directory being visited.
+struct Entry {
+    path: PathBuf,
+    kind: EntryKind,
+    /// `None` when the entry came from a stored listing.
+    dir_entry: Option<DirEntry>,
+}
+
+impl Entry {
+    fn metadata(&self) -> io::Result<Metadata> {
+        match &self.dir_entry {
+            Some(entry) => entry.metadata(),
+            None => fs::symlink_metadata(&self.path),
+        }
+    }
+}
+
+fn modified(metadata: &Metadata) -> Option<Duration> {
+    metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()
+}
+
+/// List `dir`, record its files and spawn a task for each subdirectory.
+fn visit_dir<'s>(
+    scope: &rayon::Scope<'s>,
+    dir: PathBuf,
+    mut frame: DirFrame,
+    ctx: &'s WalkContext<'s>,
+) {
+    if ctx.is_cancelled() || ctx.is_ignored(&dir, frame.root) {
+        return;
     }
+    let Some(listed) = list_dir(&dir, frame.modified, ctx) else {
+        return;
+    };
 
-    if !dir.is_dir() {
-        return Ok(());
+    // The listing shows which ignore files exist, so none are probed for
+    if ctx.options.ignore_files {
+        let present: Vec<&str> = IGNORE_FILE_NAMES
+            .into_iter()
+            .filter(|name| listed.iter().any(|entry| entry.path.file_name() == Some(name.as_ref())))
+            .collect();
+        if !present.is_empty() {
+            frame.ignores = IgnoreRules::load(&dir, &present, frame.ignores.take());
+        }
     }
 
-    if ignore_patterns
-        .iter()
-        .any(|pattern| pattern.matches_path(dir))
+    for entry in &listed {
+        if ctx.is_cancelled() {
+            return;
+        }
+        visit_entry(scope, entry, &frame, ctx);
+    }
+}
+
+/// The entries of `dir`: from the previous walk's listing while the directory's
+/// mtime, `modified`, matches it, otherwise read afresh. Complete listings of
+/// settled directories are kept for the next walk. `None` if the directory
+/// can't be read or the walk is cancelled.
+fn list_dir(dir: &Path, modified: Option<Duration>, ctx: &WalkContext) -> Option<Vec<Entry>> {
+    if let Some(listing) = ctx
+        .previous
+        .get(dir)
+        .filter(|listing| Some(listing.modified) == modified)
     {
-        return Ok(());
+        ctx.reused_listings.fetch_add(1, Ordering::Relaxed);
+        ctx.keep_listing(listing.clone());
+        let entries = listing
+            .entries
+            .iter()
+            .map(|(name, kind)| Entry {
+                path: dir.join(name),
+                kind: *kind,
+                dir_entry: None,
+            })
+            .collect();
+        return Some(entries);
     }
 
     let entries = match fs::read_dir(dir) {
         Ok(entries) => entries,
-        Err(err) => {
-            if err.kind() == io::ErrorKind::PermissionDenied {
-                error!(
-                    "Access denied reading directory {}: {}",
-                    dir.display(),
-                    err
-                );
-                return Ok(());
-            } else {
-                return Err(io::Error::new(
-                    err.kind(),
-                    format!("Error reading directory {}: {}", dir.display(), err),
-                ));
-            }
+        Err(e) => {
+            ctx.record_error(dir.to_path_buf(), ScanPhase::Walk, e);
+            return None;
         }
     };
 
-    entries.par_bridge().try_for_each(|entry_result| {
-        if cancel_token.load(Ordering::Relaxed) {
-            return Ok(());
+    let mut listed = Vec::new();
+    let mut complete = true;
+    for entry_result in entries {
+        if ctx.is_cancelled() {
+            return None;
         }
-
         let entry = match entry_result {
             Ok(entry) => entry,
-            Err(err) => {
-                return Err(io::Error::new(
-                    err.kind(),
-                    format!(
-                        "Error reading entry in directory {}: {}",
-                        dir.display(),
-                        err
-                    ),
-                ));
+            Err(e) => {
+                ctx.record_error(dir.to_path_buf(), ScanPhase::Walk, e);
+                complete = false;
+                continue;
             }
         };
+        match entry.file_type() {
+            Ok(file_type) => listed.push(Entry {
+                path: entry.path(),
+                kind: file_type.into(),
+                dir_entry: Some(entry),
+            }),
+            Err(e) => {
+                ctx.record_error(entry.path(), ScanPhase::Metadata, e);
+                complete = false;
+            }
+        }
+    }
 
-        let path = entry.path();
-        let metadata = match fs::metadata(&path) {
+    if let Some(modified) = modified.filter(|modified| complete && ctx.is_settled(*modified)) {
+        // Names that aren't valid Unicode can't be stored, so such a directory is always read
+        let entries: Option<Vec<_>> = listed
+            .iter()
+            .map(|entry| Some((entry.path.file_name()?.to_str()?.to_string(), entry.kind)))
+            .collect();
+        if let Some(entries) = entries {
+            ctx.keep_listing(DirListing {
+                path: dir.to_path_buf(),
+                modified,
+                entries,
+            });
+        }
+    }
+    Some(listed)
+}
+
+fn visit_entry<'s>(
+    scope: &rayon::Scope<'s>,
+    entry: &Entry,
+    frame: &DirFrame,
+    ctx: &'s WalkContext<'s>,
+) {
+    let path = entry.path.clone();
+    if frame
+        .ignores
+        .as_ref()
+        .is_some_and(|rules| rules.is_ignored(&path, entry.kind == EntryKind::Dir))
+    {
+        return;
+    }
+
+    if entry.kind == EntryKind::Symlink {
+        match ctx.options.symlinks {
+            SymlinkPolicy::Skip => {}
+            SymlinkPolicy::Follow => follow_symlink(scope, path, frame, ctx),
+            SymlinkPolicy::Record => match fs::read_link(&path) {
+                Ok(target) => ctx.links.lock().unwrap().push(FileLink {
+                    path,
+                    target,
+                    kind: LinkKind::Symlink,
+                    file_size: 0,
+                }),
+                Err(e) => ctx.record_error(path, ScanPhase::Metadata, e),
+            },
+        }
+    } else if entry.kind == EntryKind::Dir {
+        let metadata = match entry.metadata() {
             Ok(metadata) => metadata,
-            Err(err) => {
-                return Err(io::Error::new(
-                    err.kind(),
-                    format!(
-                        "Error getting metadata for {}: {}",
-                        path.display(),
-                        err
-                    ),
-                ));
+            Err(e) => {
+                ctx.record_error(path, ScanPhase::Metadata, e);
+                return;
             }
         };
+        if !ctx.admits_dir(&path, &metadata, frame.root) {
+            return;
+        }
+        let device = platform::dir_device(&path, &metadata).unwrap_or(frame.device);
+        let child = DirFrame {
+            device,
+            depth: frame.depth + 1,
+            root: frame.root,
+            ignores: frame.ignores.clone(),
+            modified: modified(&metadata),
+        };
+        enter_dir(scope, path, device != frame.device, child, ctx);
+    } else if entry.kind == EntryKind::File && !ctx.is_ignored(&path, frame.root) {
+        match entry.metadata() {
+            Ok(metadata) => add_file(path, &metadata, frame.root, ctx),
+            Err(e) => ctx.record_error(path, ScanPhase::Metadata, e),
+        }
+    }
+}
+
+/// Spawn a task for subdirectory `dir`, unless it's past the depth limit or a
+/// mount the options exclude. `is_mount` is set for directories on a different
+/// device from their parent, or reached through a symlink; these are recorded
+/// in the `DeviceMap`.
+fn enter_dir<'s>(
+    scope: &rayon::Scope<'s>,
+    dir: PathBuf,
+    is_mount: bool,
+    frame: DirFrame,
+    ctx: &'s WalkContext<'s>,
+) {
+    if frame.depth > ctx.options.max_depth {
+        let e = io::Error::other(format!(
+            "directory is more than {} levels below its root",
+            ctx.options.max_depth
+        ));
+        ctx.record_error(dir, ScanPhase::Walk, e);
+        return;
+    }
+    if is_mount {
+        if !ctx.may_enter_mount(frame.root, &dir, frame.device) {
+            return;
+        }
+        ctx.mounts.lock().unwrap().push((dir.clone(), frame.device));
+    }
+    scope.spawn(move |scope| visit_dir(scope, dir, frame, ctx));
+}
+
+/// Scan the target of symlink `link` under its canonical path, unless it's
+/// already covered by a root or another followed link. Ignore files above the
+/// target don't apply inside it.
+fn follow_symlink<'s>(
+    scope: &rayon::Scope<'s>,
+    link: PathBuf,
+    frame: &DirFrame,
+    ctx: &'s WalkContext<'s>,
+) {
+    let target = match fs::canonicalize(&link) {
+        Ok(target) => target,
+        Err(e) => {
+            // Typically a dangling link
+            ctx.record_error(link, ScanPhase::Canonicalize, e);
+            return;
+        }
+    };
+    let metadata = match fs::metadata(&target) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            ctx.record_error(link, ScanPhase::Metadata, e);
+            return;
+        }
+    };
+    if !ctx.claim_target(&target) {
+        return;
+    }
+
+    if metadata.is_dir() {
+        if !ctx.admits_dir(&target, &metadata, frame.root) {
+            return;
+        }
+        let device = platform::dir_device(&target, &metadata).unwrap_or_default();
+        // The target may be anywhere, so it's treated as a mount
+        let child = DirFrame {
+            device,
+            depth: frame.depth + 1,
+            root: frame.root,
+            ignores: None,
+            modified: modified(&metadata),
+        };
+        enter_dir(scope, target, true, child, ctx);
+    } else if metadata.is_file() && !ctx.is_ignored(&target, frame.root) {
+        add_file(target, &metadata, frame.root, ctx);
+    }
+}
 
-        if path.is_dir() {
-            visit_dirs(&path, map, ignore_patterns, cancel_token, progress, file_count)?;
-        } else if !metadata.file_type().is_symlink() && metadata.len() > 0 {
-            let file_size = metadata.len();
-            if !ignore_patterns
-                .iter()
-                .any(|pattern| pattern.matches_path(&path))
-            {
-                map.entry(file_size).or_default().push(path.to_path_buf());
-                let count = file_count.fetch_add(1, Ordering::Relaxed) + 1;
-                if count % 1000 == 0 {
-                    progress.on_scan_progress(count, &path.to_string_lossy());
-                }
-            }
+/// Add a file that passes the filter to the size map, or to the hard-link sets
+/// when it has more than one name. A file whose link count can't be read is
+/// treated as having one name; the hash tiers report it if it can't be read either.
+fn add_file(path: PathBuf, metadata: &Metadata, root: usize, ctx: &WalkContext) {
+    if !ctx.is_included(&path, metadata, root) {
+        return;
+    }
+    let stat = FileStat {
+        size: metadata.len(),
+        modified: modified(metadata).unwrap_or_default(),
+    };
+    let count = ctx.file_count.fetch_add(1, Ordering::Relaxed) + 1;
+    if count % 1000 == 0 {
+        ctx.progress.on_scan_progress(count, &path.to_string_lossy());
+    }
+
+    match platform::hard_link_id(&path, metadata) {
+        Ok(Some(id)) => ctx
+            .hard_links
+            .entry(id)
+            .or_insert_with(|| (stat, Vec::new()))
+            .1
+            .push(path),
+        Ok(None) => ctx.add_to_map(path, stat),
+        Err(e) => {
+            debug!("Couldn't read the link count of {}: {}", path.display(), e);
+            ctx.add_to_map(path, stat);
         }
-        Ok(())
-    })?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::SilentReporter;
 
-    Ok(())
+    fn walk(roots: &[&str], options: WalkOptions) -> WalkOutcome {
+        build_size_to_files_map(roots, &[], &options, &AtomicBool::new(false), &SilentReporter)
+    }
+
+    #[test]
+    fn test_missing_root_is_recorded_and_others_scanned() {
+        let dir = tempfile::tempdir().unwrap();
+        let present = dir.path().join("present");
+        fs::create_dir(&present).unwrap();
+        fs::write(present.join("a.txt"), "content").unwrap();
+        let missing = dir.path().join("missing");
+
+        let outcome = walk(
+            &[present.to_str().unwrap(), missing.to_str().unwrap()],
+            WalkOptions::default(),
+        );
+        assert_eq!(outcome.files.get(&7).unwrap().len(), 1);
+        assert_eq!(outcome.errors.len(), 1);
+        assert_eq!(outcome.errors[0].path, missing);
+        assert_eq!(outcome.errors[0].phase, ScanPhase::Walk);
+        assert_eq!(outcome.errors[0].kind, io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_max_depth_stops_descent() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut level = dir.path().to_path_buf();
+        for depth in 0..4 {
+            fs::write(level.join(format!("file_{}.txt", depth)), "x").unwrap();
+            level = level.join(format!("level_{}", depth + 1));
+            fs::create_dir(&level).unwrap();
+        }
+
+        let outcome = walk(
+            &[dir.path().to_str().unwrap()],
+            WalkOptions {
+                max_depth: 2,
+                ..Default::default()
+            },
+        );
+        // Files at depths 0, 1 and 2 are found; level_3 is reported, not entered
+        assert_eq!(outcome.files.get(&1).unwrap().len(), 3);
+        assert_eq!(outcome.errors.len(), 1);
+        assert!(outcome.errors[0].path.ends_with("level_3"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hard_links_scanned_under_one_name() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "shared").unwrap();
+        fs::hard_link(dir.path().join("a.txt"), dir.path().join("b.txt")).unwrap();
+        fs::write(dir.path().join("c.txt"), "shared").unwrap();
+
+        let outcome = walk(&[dir.path().to_str().unwrap()], WalkOptions::default());
+        let mut files = outcome.files.get(&6).unwrap().clone();
+        files.sort();
+        assert_eq!(files, vec![dir.path().join("a.txt"), dir.path().join("c.txt")]);
+        // Stats are kept for the name scanned only
+        assert_eq!(outcome.stats.len(), 2);
+        assert_eq!(outcome.stats.get(&dir.path().join("a.txt")).unwrap().size, 6);
+        assert_eq!(
+            outcome.links,
+            vec![FileLink {
+                path: dir.path().join("b.txt"),
+                target: dir.path().join("a.txt"),
+                kind: LinkKind::HardLink,
+                file_size: 6,
+            }]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks_without_loops_or_double_counting() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::create_dir(&outside).unwrap();
+        fs::write(root.join("sub/a.txt"), "aaa").unwrap();
+        fs::write(outside.join("b.txt"), "bbbb").unwrap();
+        // A loop back to the root, a second route into it, and one way out
+        std::os::unix::fs::symlink(&root, root.join("sub/loop")).unwrap();
+        std::os::unix::fs::symlink(root.join("sub"), root.join("sub_again")).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("out")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("gone"), root.join("dangling")).unwrap();
+
+        let roots = [root.to_str().unwrap()];
+        let skipped = walk(&roots, WalkOptions::default());
+        assert_eq!(skipped.files.len(), 1);
+        assert!(skipped.errors.is_empty());
+
+        let followed = walk(
+            &roots,
+            WalkOptions {
+                symlinks: SymlinkPolicy::Follow,
+                ..Default::default()
+            },
+        );
+        assert_eq!(followed.files.get(&3).unwrap().len(), 1);
+        assert_eq!(
+            *followed.files.get(&4).unwrap(),
+            vec![fs::canonicalize(&outside).unwrap().join("b.txt")]
+        );
+        assert_eq!(followed.errors.len(), 1);
+        assert_eq!(followed.errors[0].phase, ScanPhase::Canonicalize);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_record_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "aaa").unwrap();
+        std::os::unix::fs::symlink("a.txt", dir.path().join("link")).unwrap();
+
+        let outcome = walk(
+            &[dir.path().to_str().unwrap()],
+            WalkOptions {
+                symlinks: SymlinkPolicy::Record,
+                ..Default::default()
+            },
+        );
+        assert_eq!(outcome.files.get(&3).unwrap().len(), 1);
+        assert_eq!(outcome.links.len(), 1);
+        assert_eq!(outcome.links[0].path, dir.path().join("link"));
+        assert_eq!(outcome.links[0].target, PathBuf::from("a.txt"));
+        assert_eq!(outcome.links[0].kind, LinkKind::Symlink);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_root_on_denied_filesystem_is_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "aaa").unwrap();
+        let root = dir.path().to_str().unwrap();
+        let fs_type = platform::filesystem_type(dir.path()).unwrap().unwrap();
+
+        let outcome = walk(
+            &[root],
+            WalkOptions {
+                root_filesystem_types: vec![RootFilesystemFilter {
+                    root: root.to_string(),
+                    filter: FilesystemFilter {
+                        allow: vec![],
+                        deny: vec![fs_type.clone()],
+                    },
+                }],
+                ..Default::default()
+            },
+        );
+        assert!(outcome.files.is_empty());
+        assert_eq!(outcome.skipped_mounts, vec![dir.path().to_path_buf()]);
+        // Still recorded as a session root
+        assert_eq!(outcome.roots.len(), 1);
+        assert_eq!(outcome.roots[0].filesystem_type, Some(fs_type));
+    }
+
+    #[test]
+    fn test_directory_on_another_device_skipped_with_one_file_system() {
+        let dir = tempfile::tempdir().unwrap();
+        let mount = dir.path().join("mounted");
+        fs::create_dir(&mount).unwrap();
+        // Roots and the directories below them are keyed alike, so a mount shows up
+        let metadata = fs::metadata(&mount).unwrap();
+        if let Some(device) = platform::dir_device(&mount, &metadata) {
+            assert_eq!(device, platform::device_id(dir.path()).unwrap());
+        }
+
+        let (filter, filesystem_filter) = (ScanFilter::default(), FilesystemFilter::default());
+        let (previous, cancel) = (DirListings::new(), AtomicBool::new(false));
+        let context = |options| WalkContext {
+            options,
+            roots: vec![RootContext {
+                device: 1,
+                filesystem_filter: &filesystem_filter,
+                filter: &filter,
+                ignore_patterns: Vec::new(),
+                include_patterns: Vec::new(),
+            }],
+            map: DashMap::new(),
+            stats: DashMap::new(),
+            mounts: Mutex::new(Vec::new()),
+            errors: Mutex::new(Vec::new()),
+            links: Mutex::new(Vec::new()),
+            skipped_mounts: Mutex::new(Vec::new()),
+            hard_links: DashMap::new(),
+            covered: Mutex::new(Vec::new()),
+            cancel_token: &cancel,
+            pause: None,
+            on_listing: None,
+            progress: &SilentReporter,
+            file_count: AtomicUsize::new(0),
+            previous: &previous,
+            listings: Mutex::new(Vec::new()),
+            reused_listings: AtomicUsize::new(0),
+            started: Duration::ZERO,
+        };
+
+        let one_file_system = WalkOptions {
+            one_file_system: true,
+            ..Default::default()
+        };
+        let ctx = context(&one_file_system);
+        assert!(!ctx.may_enter_mount(0, &mount, 2));
+        assert!(ctx.may_enter_mount(0, &mount, 1));
+        assert_eq!(*ctx.skipped_mounts.lock().unwrap(), vec![mount.clone()]);
+
+        let default_options = WalkOptions::default();
+        assert!(context(&default_options).may_enter_mount(0, &mount, 2));
+    }
+
+    #[test]
+    fn test_filter_applied_during_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join("docs/a.txt"), "aaa").unwrap();
+        fs::write(dir.path().join("docs/b.log"), "bbb").unwrap();
+        fs::write(dir.path().join("docs/big.txt"), "x".repeat(100)).unwrap();
+        fs::write(dir.path().join("empty.txt"), "").unwrap();
+        fs::write(dir.path().join("c.txt"), "ccc").unwrap();
+        let root = dir.path().to_str().unwrap();
+
+        // Empty files are only scanned with min_size = 0
+        let outcome = walk(
+            &[root],
+            WalkOptions {
+                filter: ScanFilter {
+                    min_size: 0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        assert_eq!(outcome.files.get(&0).unwrap().len(), 1);
+
+        let outcome = walk(
+            &[root],
+            WalkOptions {
+                filter: ScanFilter {
+                    max_size: Some(10),
+                    include_extensions: vec!["txt".into()],
+                    include_patterns: vec!["**/docs/*".into()],
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        assert_eq!(outcome.files.len(), 1);
+        assert_eq!(
+            *outcome.files.get(&3).unwrap(),
+            vec![dir.path().join("docs/a.txt")]
+        );
+    }
+
+    #[test]
+    fn test_root_options_apply_to_their_root_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let (primary, backup) = (dir.path().join("primary"), dir.path().join("backup"));
+        for root in [&primary, &backup] {
+            fs::create_dir_all(root.join("cache")).unwrap();
+            fs::write(root.join("cache/a.bin"), "aaa").unwrap();
+            fs::write(root.join("b.txt"), "bb").unwrap();
+        }
+
+        let outcome = walk(
+            &[primary.to_str().unwrap(), backup.to_str().unwrap()],
+            WalkOptions {
+                root_options: vec![RootScanOptions {
+                    root: backup.to_str().unwrap().to_string(),
+                    ignore_patterns: vec!["**/cache".into()],
+                    filter: Some(ScanFilter {
+                        min_size: 3,
+                        ..Default::default()
+                    }),
+                }],
+                ..Default::default()
+            },
+        );
+        assert_eq!(*outcome.files.get(&3).unwrap(), vec![primary.join("cache/a.bin")]);
+        assert_eq!(*outcome.files.get(&2).unwrap(), vec![primary.join("b.txt")]);
+    }
+
+    #[test]
+    fn test_ignore_files_honoured_when_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::create_dir_all(root.join("src/target")).unwrap();
+        fs::write(root.join(".gitignore"), "/target/\n*.log\n").unwrap();
+        fs::write(root.join("src/.superduperignore"), "!keep.log\n").unwrap();
+        fs::write(root.join("target/debug/out.bin"), "aaaaa").unwrap();
+        fs::write(root.join("src/target/lib.rs"), "bbbbb").unwrap();
+        fs::write(root.join("src/run.log"), "ccccc").unwrap();
+        fs::write(root.join("src/keep.log"), "ddddd").unwrap();
+
+        let roots = [root.to_str().unwrap()];
+        let unfiltered = walk(&roots, WalkOptions::default());
+        assert_eq!(unfiltered.files.get(&5).unwrap().len(), 4);
+
+        let outcome = walk(
+            &roots,
+            WalkOptions {
+                ignore_files: true,
+                ..Default::default()
+            },
+        );
+        // The anchored rule misses src/target; the deeper `!` re-includes keep.log
+        let mut files = outcome.files.get(&5).unwrap().clone();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![root.join("src/keep.log"), root.join("src/target/lib.rs")]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unchanged_directory_listing_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("sub").join("b.txt"), "b").unwrap();
+        let past = SystemTime::now() - Duration::from_secs(60);
+        let settle = |path: &Path| fs::File::open(path).unwrap().set_modified(past).unwrap();
+        settle(&root);
+        settle(&root.join("sub"));
+        let roots = [root.to_str().unwrap()];
+
+        let first = walk(&roots, WalkOptions::default());
+        assert_eq!(first.listings.len(), 2);
+        assert_eq!(first.reused_listings, 0);
+        let previous: DirListings = first
+            .listings
+            .into_iter()
+            .map(|listing| (listing.path.clone(), listing))
+            .collect();
+
+        // A file added behind the mtime's back is missed while the listing is
+        // reused, but a changed file still is found from its own metadata
+        fs::write(root.join("hidden.txt"), "h").unwrap();
+        settle(&root);
+        fs::write(root.join("sub").join("b.txt"), "bigger").unwrap();
+        settle(&root.join("sub"));
+        let second = build_size_to_files_map_incremental(
+            &roots,
+            &[],
+            &WalkOptions::default(),
+            &previous,
+            WalkControl::new(&AtomicBool::new(false)),
+            &SilentReporter,
+        );
+        assert_eq!(second.reused_listings, 2);
+        assert_eq!(second.listings.len(), 2);
+        assert_eq!(second.files.get(&1).unwrap().len(), 1);
+        assert_eq!(second.files.get(&6).unwrap().len(), 1);
+
+        // The directory's mtime moves when an entry is added, so it's read again
+        fs::write(root.join("c.txt"), "c").unwrap();
+        let third = build_size_to_files_map_incremental(
+            &roots,
+            &[],
+            &WalkOptions::default(),
+            &previous,
+            WalkControl::new(&AtomicBool::new(false)),
+            &SilentReporter,
+        );
+        assert_eq!(third.reused_listings, 1);
+        assert_eq!(third.files.get(&1).unwrap().len(), 3);
+        // Just modified, so not kept for the next walk
+        assert_eq!(third.listings.len(), 1);
+    }
+
+    #[test]
+    fn test_listings_reported_as_taken() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("sub").join("a.txt"), "a").unwrap();
+        let past = SystemTime::now() - Duration::from_secs(60);
+        let settle = |path: &Path| fs::File::open(path).unwrap().set_modified(past).unwrap();
+        settle(&root.join("sub"));
+        settle(&root);
+
+        let reported = Mutex::new(Vec::new());
+        let on_listing = |listing: &DirListing| reported.lock().unwrap().push(listing.path.clone());
+        let outcome = build_size_to_files_map_incremental(
+            &[root.to_str().unwrap()],
+            &[],
+            &WalkOptions::default(),
+            &DirListings::new(),
+            WalkControl {
+                on_listing: Some(&on_listing),
+                ..WalkControl::new(&AtomicBool::new(false))
+            },
+            &SilentReporter,
+        );
+        let mut reported = reported.into_inner().unwrap();
+        reported.sort();
+        let mut kept: Vec<PathBuf> = outcome.listings.into_iter().map(|l| l.path).collect();
+        kept.sort();
+        assert_eq!(reported, vec![root.clone(), root.join("sub")]);
+        assert_eq!(reported, kept);
+    }
 }
@@ -6,8 +6,11 @@ pub struct ScanSession {
     pub completed_at: Option<String>,
     pub status: String,
     pub root_paths: String,
+    pub hash_algorithm: Option<String>,
     pub files_scanned: i64,
     pub total_bytes: i64,
+    /// JSON of the `ScanFilter` the session was scanned with.
+    pub scan_filter: Option<String>,
 }
 
 /// A file discovered during scanning.
@@ -20,12 +23,37 @@ pub struct ScannedFile {
     pub drive_letter: String,
     pub file_size: i64,
     pub last_modified: i64,
+    /// Sub-second part of `last_modified`.
+    pub last_modified_nanos: i64,
     pub partial_hash: Option<i64>,
+    /// Number of leading bytes `partial_hash` covers.
+    pub partial_hash_len: Option<i64>,
     pub content_hash: Option<i64>,
+    /// Full hex digest from the session's hash algorithm; `content_hash` is its leading 64 bits.
+    pub content_digest: Option<String>,
     pub last_seen_session_id: Option<i64>,
     pub marked_deleted: bool,
 }
 
+/// Stored partial hash of a file, with the size and mtime it was computed at.
+#[derive(Debug, Clone)]
+pub struct StoredPartialHash {
+    pub canonical_path: String,
+    pub file_size: i64,
+    pub last_modified: i64,
+    pub last_modified_nanos: i64,
+    pub partial_hash: i64,
+}
+
+/// Size and mtime of a file as a session last saw it.
+#[derive(Debug, Clone)]
+pub struct StoredFileState {
+    pub canonical_path: String,
+    pub file_size: i64,
+    pub last_modified: i64,
+    pub last_modified_nanos: i64,
+}
+
 /// A group of files sharing the same content hash and size, scoped to a session.
 #[derive(Debug, Clone)]
 pub struct DuplicateGroup {
@@ -35,6 +63,8 @@ pub struct DuplicateGroup {
     pub file_size: i64,
     pub file_count: i64,
     pub wasted_bytes: i64,
+    /// When the group was confirmed byte-for-byte; `None` if only hash-matched.
+    pub verified_at: Option<String>,
 }
 
 /// Links a scanned file to its duplicate group.
@@ -55,6 +85,21 @@ pub struct DirectoryNode {
     pub total_size: i64,
     pub file_count: i64,
     pub depth: i64,
+    /// Files at or below the directory that are in a duplicate group.
+    pub duplicate_count: i64,
+    /// Their total size.
+    pub duplicate_size: i64,
+}
+
+impl DirectoryNode {
+    /// Share of the directory's bytes in duplicated files, from 0 to 100.
+    /// Without `index_all_files`, unique files are missing from `total_size`.
+    pub fn duplicated_percent(&self) -> f64 {
+        if self.total_size == 0 {
+            return 0.0;
+        }
+        self.duplicate_size as f64 * 100.0 / self.total_size as f64
+    }
 }
 
 /// Directory fingerprint for exact and similarity matching.
@@ -79,6 +124,43 @@ pub struct DirectorySimilarity {
     pub match_type: String,
 }
 
+/// A path a scan session skipped, with the phase that failed.
+#[derive(Debug, Clone)]
+pub struct ScanErrorRecord {
+    pub id: i64,
+    pub session_id: i64,
+    pub path: String,
+    pub phase: String,
+    /// `io::ErrorKind` name, e.g. `PermissionDenied`.
+    pub error_kind: String,
+    pub message: String,
+}
+
+/// A root a session scanned, from `session_root`.
+#[derive(Debug, Clone)]
+pub struct SessionRoot {
+    pub id: i64,
+    pub session_id: i64,
+    pub root_path: String,
+    pub device_id: i64,
+    pub filesystem_type: Option<String>,
+    pub label: Option<String>,
+    pub priority: i64,
+    pub protected: bool,
+}
+
+/// A row of `file_link`.
+#[derive(Debug, Clone)]
+pub struct FileLinkRecord {
+    pub id: i64,
+    pub session_id: i64,
+    pub path: String,
+    pub target: String,
+    /// `symlink` or `hardlink`.
+    pub kind: String,
+    pub file_size: i64,
+}
+
 /// A file marked for deletion.
 #[derive(Debug, Clone)]
 pub struct DeletionPlanEntry {
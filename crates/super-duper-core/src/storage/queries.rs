@@ -1,8 +1,113 @@
 use super::models::*;
 use super::sqlite::Database;
+use crate::config::RootConfig;
+use crate::hasher::{CompletedBucket, ContentHash, PartialHash};
+use crate::scan_error::ScanError;
+use crate::scanner::{DirListing, EntryKind, FileLink, RootDevice, ScanFilter};
 use rusqlite::{params, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::time::Duration;
 use tracing::debug;
 
+/// `scanned_file` columns in the order `scanned_file_from_row` reads them.
+const SCANNED_FILE_COLUMNS: &str = "sf.id, sf.canonical_path, sf.file_name, sf.parent_dir, \
+     sf.drive_letter, sf.file_size, sf.last_modified, sf.partial_hash, sf.content_hash, \
+     sf.last_seen_session_id, sf.marked_deleted, sf.content_digest, sf.last_modified_nanos, \
+     sf.partial_hash_len";
+
+fn scanned_file_from_row(row: &rusqlite::Row) -> Result<ScannedFile> {
+    Ok(ScannedFile {
+        id: row.get(0)?,
+        canonical_path: row.get(1)?,
+        file_name: row.get(2)?,
+        parent_dir: row.get(3)?,
+        drive_letter: row.get(4)?,
+        file_size: row.get(5)?,
+        last_modified: row.get(6)?,
+        last_modified_nanos: row.get(12)?,
+        partial_hash: row.get(7)?,
+        partial_hash_len: row.get(13)?,
+        content_hash: row.get(8)?,
+        content_digest: row.get(11)?,
+        last_seen_session_id: row.get(9)?,
+        marked_deleted: row.get(10)?,
+    })
+}
+
+/// `directory_node` columns in the order `directory_node_from_row` reads them.
+const DIRECTORY_NODE_COLUMNS: &str =
+    "id, path, name, parent_id, total_size, file_count, depth, duplicate_count, duplicate_size";
+
+fn directory_node_from_row(row: &rusqlite::Row) -> Result<DirectoryNode> {
+    Ok(DirectoryNode {
+        id: row.get(0)?,
+        path: row.get(1)?,
+        name: row.get(2)?,
+        parent_id: row.get(3)?,
+        total_size: row.get(4)?,
+        file_count: row.get(5)?,
+        depth: row.get(6)?,
+        duplicate_count: row.get(7)?,
+        duplicate_size: row.get(8)?,
+    })
+}
+
+/// A `scan_session` row selected as id, started_at, completed_at, status,
+/// root_paths, hash_algorithm, files_scanned, total_bytes, scan_filter.
+fn scan_session_from_row(row: &rusqlite::Row) -> Result<ScanSession> {
+    Ok(ScanSession {
+        id: row.get(0)?,
+        started_at: row.get(1)?,
+        completed_at: row.get(2)?,
+        status: row.get(3)?,
+        root_paths: row.get(4)?,
+        hash_algorithm: row.get(5)?,
+        files_scanned: row.get(6)?,
+        total_bytes: row.get(7)?,
+        scan_filter: row.get(8)?,
+    })
+}
+
+fn json_text<T: serde::Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+fn json_column<T: serde::de::DeserializeOwned>(row: &rusqlite::Row, index: usize) -> Result<T> {
+    let json: String = row.get(index)?;
+    serde_json::from_str(&json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+fn insert_listings(
+    conn: &rusqlite::Connection,
+    session_id: i64,
+    listings: &[DirListing],
+) -> Result<()> {
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO dir_listing (session_id, path, last_modified, last_modified_nanos, entries) \
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    for listing in listings {
+        stmt.execute(params![
+            session_id,
+            listing.path.to_string_lossy(),
+            listing.modified.as_secs() as i64,
+            listing.modified.subsec_nanos() as i64,
+            json_text(&listing.entries)?,
+        ])?;
+    }
+    Ok(())
+}
+
+/// Sorted JSON of a session's root paths, which identifies its sessions.
+fn root_paths_key(root_paths: &[String]) -> String {
+    let mut sorted = root_paths.to_vec();
+    sorted.sort();
+    serde_json::to_string(&sorted).unwrap_or_default()
+}
+
 impl Database {
     // ── Scan Session ─────────────────────────────────────────────
 
@@ -34,13 +139,76 @@ impl Database {
         Ok(())
     }
 
+    /// Record which content hash algorithm produced a session's duplicate groups.
+    pub fn set_session_hash_algorithm(&self, session_id: i64, algorithm: &str) -> Result<()> {
+        self.connection().execute(
+            "UPDATE scan_session SET hash_algorithm = ?1 WHERE id = ?2",
+            params![algorithm, session_id],
+        )?;
+        Ok(())
+    }
+
+    /// Record the filter a session's files were selected with.
+    pub fn set_session_scan_filter(&self, session_id: i64, filter: &ScanFilter) -> Result<()> {
+        let json = serde_json::to_string(filter)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        self.connection().execute(
+            "UPDATE scan_session SET scan_filter = ?1 WHERE id = ?2",
+            params![json, session_id],
+        )?;
+        Ok(())
+    }
+
+    /// Record the digest of the settings that decided a session's groups.
+    pub fn set_session_scan_settings(&self, session_id: i64, digest: &str) -> Result<()> {
+        self.connection().execute(
+            "UPDATE scan_session SET scan_settings = ?1 WHERE id = ?2",
+            params![digest, session_id],
+        )?;
+        Ok(())
+    }
+
+    /// The settings digest a session was scanned with; `None` if never recorded.
+    pub fn get_session_scan_settings(&self, session_id: i64) -> Result<Option<String>> {
+        match self.connection().query_row(
+            "SELECT scan_settings FROM scan_session WHERE id = ?1",
+            params![session_id],
+            |row| row.get(0),
+        ) {
+            Ok(digest) => Ok(digest),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Record how the hash cache served a session's full-hash lookups.
+    pub fn set_session_cache_usage(&self, session_id: i64, hits: u64, misses: u64) -> Result<()> {
+        self.connection().execute(
+            "UPDATE scan_session SET cache_hits = ?1, cache_misses = ?2 WHERE id = ?3",
+            params![hits as i64, misses as i64, session_id],
+        )?;
+        Ok(())
+    }
+
+    /// Cache hits and misses of the most recently completed session, as (hits, misses).
+    pub fn get_last_cache_usage(&self) -> Result<Option<(i64, i64)>> {
+        match self.connection().query_row(
+            "SELECT cache_hits, cache_misses FROM scan_session \
+             WHERE status = 'completed' ORDER BY completed_at DESC LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ) {
+            Ok(usage) => Ok(Some(usage)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Find an existing completed session with the same sorted root paths, or create a new one.
     /// If found, deletes its old duplicate groups (they'll be rebuilt by the current scan)
     /// and resets its status to 'running'.
     pub fn find_or_create_session(&self, root_paths: &[String]) -> Result<i64> {
-        let mut sorted = root_paths.to_vec();
-        sorted.sort();
-        let paths_json = serde_json::to_string(&sorted).unwrap_or_default();
+        let paths_json = root_paths_key(root_paths);
 
         match self.find_session_by_paths_hash(&paths_json)? {
             Some(session_id) => {
@@ -63,6 +231,11 @@ impl Database {
         }
     }
 
+    /// The most recent completed session of exactly these root paths, in any order.
+    pub fn find_session_for_paths(&self, root_paths: &[String]) -> Result<Option<i64>> {
+        self.find_session_by_paths_hash(&root_paths_key(root_paths))
+    }
+
     /// Find the id of the most recent completed session with the given paths hash.
     pub fn find_session_by_paths_hash(&self, hash: &str) -> Result<Option<i64>> {
         match self.connection().query_row(
@@ -92,12 +265,73 @@ impl Database {
         let now = chrono::Utc::now().to_rfc3339();
         self.connection().execute(
             "UPDATE scan_session SET started_at = ?1, completed_at = NULL, \
-             status = 'running', files_scanned = 0, total_bytes = 0 WHERE id = ?2",
+             status = 'running', files_scanned = 0, total_bytes = 0, \
+             cache_hits = 0, cache_misses = 0 WHERE id = ?2",
             params![now, session_id],
         )?;
         Ok(())
     }
 
+    /// Set a session's status, e.g. to 'cancelled' or 'aborted' when a scan stops
+    /// short.
+    pub fn set_session_status(&self, session_id: i64, status: &str) -> Result<()> {
+        self.connection().execute(
+            "UPDATE scan_session SET status = ?1 WHERE id = ?2",
+            params![status, session_id],
+        )?;
+        Ok(())
+    }
+
+    /// Mark a session 'aborted' if it's still 'running'. False if it has since
+    /// finished one way or another.
+    pub fn abort_running_session(&self, session_id: i64) -> Result<bool> {
+        let changed = self.connection().execute(
+            "UPDATE scan_session SET status = 'aborted' WHERE id = ?1 AND status = 'running'",
+            params![session_id],
+        )?;
+        Ok(changed > 0)
+    }
+
+    pub fn get_session(&self, session_id: i64) -> Result<Option<ScanSession>> {
+        match self.connection().query_row(
+            "SELECT id, started_at, completed_at, status, root_paths, hash_algorithm, \
+                    files_scanned, total_bytes, scan_filter \
+             FROM scan_session WHERE id = ?1",
+            params![session_id],
+            scan_session_from_row,
+        ) {
+            Ok(session) => Ok(Some(session)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Ids of every session still marked 'running'.
+    pub fn get_running_session_ids(&self) -> Result<Vec<i64>> {
+        let mut stmt = self
+            .connection()
+            .prepare("SELECT id FROM scan_session WHERE status = 'running' ORDER BY id")?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ids)
+    }
+
+    /// Cancelled or aborted sessions of exactly these root paths, newest first.
+    pub fn get_resumable_sessions(&self, root_paths: &[String]) -> Result<Vec<ScanSession>> {
+        let mut stmt = self.connection().prepare(
+            "SELECT id, started_at, completed_at, status, root_paths, hash_algorithm, \
+                    files_scanned, total_bytes, scan_filter \
+             FROM scan_session \
+             WHERE root_paths_hash = ?1 AND status IN ('cancelled', 'aborted') \
+             ORDER BY id DESC",
+        )?;
+        let sessions = stmt
+            .query_map(params![root_paths_key(root_paths)], scan_session_from_row)?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(sessions)
+    }
+
     /// Get the id of the most recent completed session, if any.
     /// List scan sessions ordered newest-first, with per-session duplicate group counts.
     /// Returns (sessions_with_group_count, total_session_count).
@@ -108,7 +342,8 @@ impl Database {
 
         let mut stmt = self.connection().prepare(
             "SELECT ss.id, ss.started_at, ss.completed_at, ss.status, ss.root_paths, \
-                    ss.files_scanned, ss.total_bytes, COUNT(dg.id) as group_count \
+                    ss.files_scanned, ss.total_bytes, COUNT(dg.id) as group_count, \
+                    ss.hash_algorithm, ss.scan_filter \
              FROM scan_session ss \
              LEFT JOIN duplicate_group dg ON dg.session_id = ss.id \
              GROUP BY ss.id \
@@ -125,8 +360,10 @@ impl Database {
                         completed_at: row.get(2)?,
                         status: row.get(3)?,
                         root_paths: row.get(4)?,
+                        hash_algorithm: row.get(8)?,
                         files_scanned: row.get(5)?,
                         total_bytes: row.get(6)?,
+                        scan_filter: row.get(9)?,
                     },
                     row.get::<_, i64>(7)?,
                 ))
@@ -136,7 +373,7 @@ impl Database {
         Ok((sessions, total))
     }
 
-    /// Delete a session and its duplicate groups (members cascade automatically).
+    /// Delete a session and its duplicate groups (members and scan errors cascade automatically).
     /// scanned_file rows are NOT deleted — they remain in the global file index.
     pub fn delete_session(&self, session_id: i64) -> Result<()> {
         self.connection().execute(
@@ -171,16 +408,20 @@ impl Database {
             let mut stmt = tx.prepare_cached(
                 "INSERT INTO scanned_file \
                  (canonical_path, file_name, parent_dir, drive_letter, file_size, \
-                  last_modified, partial_hash, content_hash, last_seen_session_id) \
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9) \
+                  last_modified, partial_hash, content_hash, last_seen_session_id, \
+                  content_digest, last_modified_nanos, partial_hash_len) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12) \
                  ON CONFLICT(canonical_path) DO UPDATE SET \
                      file_name = excluded.file_name, \
                      parent_dir = excluded.parent_dir, \
                      drive_letter = excluded.drive_letter, \
                      file_size = excluded.file_size, \
                      last_modified = excluded.last_modified, \
+                     last_modified_nanos = excluded.last_modified_nanos, \
                      partial_hash = excluded.partial_hash, \
+                     partial_hash_len = excluded.partial_hash_len, \
                      content_hash = excluded.content_hash, \
+                     content_digest = excluded.content_digest, \
                      last_seen_session_id = excluded.last_seen_session_id",
             )?;
             for file in files {
@@ -194,6 +435,9 @@ impl Database {
                     file.partial_hash,
                     file.content_hash,
                     file.last_seen_session_id,
+                    file.content_digest,
+                    file.last_modified_nanos,
+                    file.partial_hash_len,
                 ])?;
             }
         }
@@ -202,6 +446,159 @@ impl Database {
         Ok(count)
     }
 
+    /// Path, size and mtime of every file a session last saw.
+    pub fn get_session_file_states(&self, session_id: i64) -> Result<Vec<StoredFileState>> {
+        let mut stmt = self.connection().prepare(
+            "SELECT canonical_path, file_size, last_modified, last_modified_nanos \
+             FROM scanned_file WHERE last_seen_session_id = ?1",
+        )?;
+        let rows = stmt
+            .query_map(params![session_id], |row| {
+                Ok(StoredFileState {
+                    canonical_path: row.get(0)?,
+                    file_size: row.get(1)?,
+                    last_modified: row.get(2)?,
+                    last_modified_nanos: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    /// Detach files from the session that last saw them, e.g. once they've gone.
+    pub fn clear_last_seen(&self, canonical_paths: &[String]) -> Result<usize> {
+        let tx = self.connection().unchecked_transaction()?;
+        let mut count = 0;
+        {
+            let mut stmt = tx.prepare_cached(
+                "UPDATE scanned_file SET last_seen_session_id = NULL WHERE canonical_path = ?1",
+            )?;
+            for path in canonical_paths {
+                count += stmt.execute(params![path])?;
+            }
+        }
+        tx.commit()?;
+        Ok(count)
+    }
+
+    /// Size and mtime of each of `canonical_paths` the index holds, by path.
+    pub fn get_file_states_by_path(
+        &self,
+        canonical_paths: &[String],
+    ) -> Result<HashMap<String, StoredFileState>> {
+        let mut stmt = self.connection().prepare_cached(
+            "SELECT canonical_path, file_size, last_modified, last_modified_nanos \
+             FROM scanned_file WHERE canonical_path = ?1",
+        )?;
+        let mut states = HashMap::new();
+        for path in canonical_paths {
+            let state = stmt.query_row(params![path], |row| {
+                Ok(StoredFileState {
+                    canonical_path: row.get(0)?,
+                    file_size: row.get(1)?,
+                    last_modified: row.get(2)?,
+                    last_modified_nanos: row.get(3)?,
+                })
+            });
+            match state {
+                Ok(state) => {
+                    states.insert(state.canonical_path.clone(), state);
+                }
+                Err(rusqlite::Error::QueryReturnedNoRows) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(states)
+    }
+
+    /// Id and path of every indexed file below the canonical directory `root`,
+    /// except those the tool deleted.
+    pub fn get_indexed_files_under(&self, root: &str) -> Result<Vec<(i64, String)>> {
+        // Paths below `root` sort between `root/` and `root0` ('0' follows '/'),
+        // or `root\` and `root]` on Windows
+        let root = root.trim_end_matches(MAIN_SEPARATOR);
+        let lower = format!("{}{}", root, MAIN_SEPARATOR);
+        let upper = format!("{}{}", root, (MAIN_SEPARATOR as u8 + 1) as char);
+        let mut stmt = self.connection().prepare(
+            "SELECT id, canonical_path FROM scanned_file \
+             WHERE canonical_path >= ?1 AND canonical_path < ?2 AND marked_deleted = 0",
+        )?;
+        let files = stmt
+            .query_map(params![lower, upper], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(files)
+    }
+
+    /// Remove files from the index, with their pending deletion-plan entries and
+    /// group memberships. Groups left with fewer than two members are deleted,
+    /// the rest recounted.
+    pub fn purge_files(&self, file_ids: &[i64]) -> Result<usize> {
+        let tx = self.connection().unchecked_transaction()?;
+        let mut count = 0;
+        {
+            let mut plan_stmt = tx.prepare_cached("DELETE FROM deletion_plan WHERE file_id = ?1")?;
+            let mut group_stmt = tx.prepare_cached(
+                "SELECT group_id FROM duplicate_group_member WHERE file_id = ?1",
+            )?;
+            let mut member_stmt =
+                tx.prepare_cached("DELETE FROM duplicate_group_member WHERE file_id = ?1")?;
+            let mut file_stmt = tx.prepare_cached("DELETE FROM scanned_file WHERE id = ?1")?;
+            let mut recount_stmt = tx.prepare_cached(
+                "UPDATE duplicate_group SET \
+                     file_count = (SELECT COUNT(*) FROM duplicate_group_member WHERE group_id = ?1), \
+                     wasted_bytes = file_size * ((SELECT COUNT(*) FROM duplicate_group_member \
+                                                  WHERE group_id = ?1) - 1) \
+                 WHERE id = ?1",
+            )?;
+
+            for file_id in file_ids {
+                let group_ids: Vec<i64> = group_stmt
+                    .query_map(params![file_id], |row| row.get(0))?
+                    .collect::<Result<Vec<_>>>()?;
+                plan_stmt.execute(params![file_id])?;
+                member_stmt.execute(params![file_id])?;
+                count += file_stmt.execute(params![file_id])?;
+                for group_id in group_ids {
+                    recount_stmt.execute(params![group_id])?;
+                }
+            }
+            tx.execute("DELETE FROM duplicate_group WHERE file_count < 2", [])?;
+        }
+        tx.commit()?;
+        debug!("Purged {} files from the index", count);
+        Ok(count)
+    }
+
+    /// All stored partial hashes computed over `partial_hash_len` leading bytes.
+    pub fn get_partial_hashes(
+        &self,
+        partial_hash_len: i64,
+        file_sizes: &[i64],
+    ) -> Result<Vec<StoredPartialHash>> {
+        let mut stmt = self.connection().prepare_cached(
+            "SELECT canonical_path, file_size, last_modified, last_modified_nanos, partial_hash \
+             FROM scanned_file \
+             WHERE file_size = ?1 AND partial_hash IS NOT NULL AND partial_hash_len = ?2 \
+                   AND marked_deleted = 0",
+        )?;
+        let mut rows = Vec::new();
+        for size in file_sizes {
+            let stored = stmt.query_map(params![size, partial_hash_len], |row| {
+                Ok(StoredPartialHash {
+                    canonical_path: row.get(0)?,
+                    file_size: row.get(1)?,
+                    last_modified: row.get(2)?,
+                    last_modified_nanos: row.get(3)?,
+                    partial_hash: row.get(4)?,
+                })
+            })?;
+            for row in stored {
+                rows.push(row?);
+            }
+        }
+        Ok(rows)
+    }
+
     // ── Duplicate Groups ─────────────────────────────────────────
 
     /// Insert duplicate groups for a session. Each entry is (content_hash, file_size, Vec<canonical_path>).
@@ -245,6 +642,397 @@ impl Database {
         Ok(group_count)
     }
 
+    /// Delete a session's groups of the given file sizes, so they can be rebuilt.
+    pub fn delete_duplicate_groups_of_sizes(
+        &self,
+        session_id: i64,
+        sizes: &[i64],
+    ) -> Result<usize> {
+        let tx = self.connection().unchecked_transaction()?;
+        let mut count = 0;
+        {
+            let mut stmt = tx.prepare_cached(
+                "DELETE FROM duplicate_group WHERE session_id = ?1 AND file_size = ?2",
+            )?;
+            for size in sizes {
+                count += stmt.execute(params![session_id, size])?;
+            }
+        }
+        tx.commit()?;
+        Ok(count)
+    }
+
+    /// Stamp `verified_at` on every group of a session after a byte-for-byte pass.
+    pub fn mark_session_groups_verified(&self, session_id: i64) -> Result<usize> {
+        let now = chrono::Utc::now().to_rfc3339();
+        self.connection().execute(
+            "UPDATE duplicate_group SET verified_at = ?1 WHERE session_id = ?2",
+            params![now, session_id],
+        )
+    }
+
+    /// Paths of the other members of any group containing `file_id` that are still
+    /// meant to survive: not deleted and not pending in the deletion plan.
+    /// Returns `None` when the file belongs to no duplicate group.
+    pub fn get_surviving_copies(&self, file_id: i64) -> Result<Option<Vec<String>>> {
+        let grouped: bool = self.connection().query_row(
+            "SELECT EXISTS(SELECT 1 FROM duplicate_group_member WHERE file_id = ?1)",
+            params![file_id],
+            |row| row.get(0),
+        )?;
+        if !grouped {
+            return Ok(None);
+        }
+
+        let mut stmt = self.connection().prepare(
+            "SELECT DISTINCT sf.canonical_path \
+             FROM duplicate_group_member self_m \
+             JOIN duplicate_group_member other_m \
+               ON other_m.group_id = self_m.group_id AND other_m.file_id != self_m.file_id \
+             JOIN scanned_file sf ON sf.id = other_m.file_id \
+             WHERE self_m.file_id = ?1 AND sf.marked_deleted = 0 \
+               AND NOT EXISTS (SELECT 1 FROM deletion_plan dp \
+                               WHERE dp.file_id = sf.id AND dp.executed_at IS NULL)",
+        )?;
+        let paths = stmt
+            .query_map(params![file_id], |row| row.get(0))?
+            .collect::<Result<Vec<String>>>()?;
+        Ok(Some(paths))
+    }
+
+    /// Replace the roots recorded for a session, each with the config entry it was scanned under.
+    pub fn replace_session_roots(
+        &self,
+        session_id: i64,
+        roots: &[(&RootDevice, &RootConfig)],
+    ) -> Result<()> {
+        let tx = self.connection().unchecked_transaction()?;
+        tx.execute("DELETE FROM session_root WHERE session_id = ?1", params![session_id])?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO session_root \
+                 (session_id, root_path, device_id, filesystem_type, label, priority, protected) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for (root, config) in roots {
+                stmt.execute(params![
+                    session_id,
+                    root.path.to_string_lossy(),
+                    root.device as i64,
+                    root.filesystem_type,
+                    config.label,
+                    config.priority,
+                    config.protected,
+                ])?;
+            }
+        }
+        tx.commit()
+    }
+
+    pub fn get_session_roots(&self, session_id: i64) -> Result<Vec<SessionRoot>> {
+        let mut stmt = self.connection().prepare(
+            "SELECT id, session_id, root_path, device_id, filesystem_type, label, priority, protected \
+             FROM session_root WHERE session_id = ?1 ORDER BY id",
+        )?;
+        let roots = stmt
+            .query_map(params![session_id], |row| {
+                Ok(SessionRoot {
+                    id: row.get(0)?,
+                    session_id: row.get(1)?,
+                    root_path: row.get(2)?,
+                    device_id: row.get(3)?,
+                    filesystem_type: row.get(4)?,
+                    label: row.get(5)?,
+                    priority: row.get(6)?,
+                    protected: row.get(7)?,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(roots)
+    }
+
+    // ── Scan Errors ──────────────────────────────────────────────
+
+    /// Replace a session's recorded scan errors with `errors`.
+    pub fn replace_scan_errors(&self, session_id: i64, errors: &[ScanError]) -> Result<usize> {
+        let tx = self.connection().unchecked_transaction()?;
+        tx.execute("DELETE FROM scan_error WHERE session_id = ?1", params![session_id])?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO scan_error (session_id, path, phase, error_kind, message) \
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for error in errors {
+                stmt.execute(params![
+                    session_id,
+                    error.path.to_string_lossy(),
+                    error.phase.as_str(),
+                    error.kind_name(),
+                    error.message,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        debug!("Recorded {} scan errors for session {}", errors.len(), session_id);
+        Ok(errors.len())
+    }
+
+    /// A page of a session's scan errors in the order they were recorded, plus the total.
+    pub fn get_scan_errors(
+        &self,
+        session_id: i64,
+        offset: i64,
+        limit: i64,
+    ) -> Result<(Vec<ScanErrorRecord>, i64)> {
+        let total: i64 = self.connection().query_row(
+            "SELECT COUNT(*) FROM scan_error WHERE session_id = ?1",
+            params![session_id],
+            |row| row.get(0),
+        )?;
+
+        let mut stmt = self.connection().prepare(
+            "SELECT id, session_id, path, phase, error_kind, message FROM scan_error \
+             WHERE session_id = ?1 ORDER BY id LIMIT ?2 OFFSET ?3",
+        )?;
+        let errors = stmt
+            .query_map(params![session_id, limit, offset], |row| {
+                Ok(ScanErrorRecord {
+                    id: row.get(0)?,
+                    session_id: row.get(1)?,
+                    path: row.get(2)?,
+                    phase: row.get(3)?,
+                    error_kind: row.get(4)?,
+                    message: row.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((errors, total))
+    }
+
+    /// Scan error counts for a session by (phase, error_kind), largest first.
+    pub fn get_scan_error_summary(&self, session_id: i64) -> Result<Vec<(String, String, i64)>> {
+        let mut stmt = self.connection().prepare(
+            "SELECT phase, error_kind, COUNT(*) AS n FROM scan_error WHERE session_id = ?1 \
+             GROUP BY phase, error_kind ORDER BY n DESC, phase, error_kind",
+        )?;
+        let rows = stmt
+            .query_map(params![session_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(rows)
+    }
+
+    // ── File Links ───────────────────────────────────────────────
+
+    /// Replace a session's recorded symlinks and hard links with `links`.
+    pub fn replace_file_links(&self, session_id: i64, links: &[FileLink]) -> Result<usize> {
+        let tx = self.connection().unchecked_transaction()?;
+        tx.execute("DELETE FROM file_link WHERE session_id = ?1", params![session_id])?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO file_link (session_id, path, target, kind, file_size) \
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for link in links {
+                stmt.execute(params![
+                    session_id,
+                    link.path.to_string_lossy(),
+                    link.target.to_string_lossy(),
+                    link.kind.as_str(),
+                    link.file_size as i64,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        debug!("Recorded {} file links for session {}", links.len(), session_id);
+        Ok(links.len())
+    }
+
+    /// A page of a session's file links ordered by path, plus the total.
+    pub fn get_file_links(
+        &self,
+        session_id: i64,
+        offset: i64,
+        limit: i64,
+    ) -> Result<(Vec<FileLinkRecord>, i64)> {
+        let total: i64 = self.connection().query_row(
+            "SELECT COUNT(*) FROM file_link WHERE session_id = ?1",
+            params![session_id],
+            |row| row.get(0),
+        )?;
+
+        let mut stmt = self.connection().prepare(
+            "SELECT id, session_id, path, target, kind, file_size FROM file_link \
+             WHERE session_id = ?1 ORDER BY path LIMIT ?2 OFFSET ?3",
+        )?;
+        let links = stmt
+            .query_map(params![session_id, limit, offset], |row| {
+                Ok(FileLinkRecord {
+                    id: row.get(0)?,
+                    session_id: row.get(1)?,
+                    path: row.get(2)?,
+                    target: row.get(3)?,
+                    kind: row.get(4)?,
+                    file_size: row.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((links, total))
+    }
+
+    // ── Directory Listings ───────────────────────────────────────
+
+    /// Replace the directory listings kept from a session's walk.
+    pub fn replace_dir_listings(&self, session_id: i64, listings: &[DirListing]) -> Result<usize> {
+        let tx = self.connection().unchecked_transaction()?;
+        tx.execute("DELETE FROM dir_listing WHERE session_id = ?1", params![session_id])?;
+        insert_listings(&tx, session_id, listings)?;
+        tx.commit()?;
+        debug!("Kept {} directory listings for session {}", listings.len(), session_id);
+        Ok(listings.len())
+    }
+
+    /// Add to a session's listings, e.g. a batch checkpointed while the walk runs.
+    pub fn insert_dir_listings(&self, session_id: i64, listings: &[DirListing]) -> Result<usize> {
+        let tx = self.connection().unchecked_transaction()?;
+        insert_listings(&tx, session_id, listings)?;
+        tx.commit()?;
+        Ok(listings.len())
+    }
+
+    pub fn delete_dir_listings(&self, session_id: i64) -> Result<()> {
+        self.connection().execute(
+            "DELETE FROM dir_listing WHERE session_id = ?1",
+            params![session_id],
+        )?;
+        Ok(())
+    }
+
+    /// The directory listings kept from a session's walk.
+    pub fn get_dir_listings(&self, session_id: i64) -> Result<Vec<DirListing>> {
+        let mut stmt = self.connection().prepare(
+            "SELECT path, last_modified, last_modified_nanos, entries FROM dir_listing \
+             WHERE session_id = ?1",
+        )?;
+        let listings = stmt
+            .query_map(params![session_id], |row| {
+                let path: String = row.get(0)?;
+                let (secs, nanos): (i64, i64) = (row.get(1)?, row.get(2)?);
+                let entries: String = row.get(3)?;
+                let entries: Vec<(String, EntryKind)> =
+                    serde_json::from_str(&entries).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            3,
+                            rusqlite::types::Type::Text,
+                            Box::new(e),
+                        )
+                    })?;
+                Ok(DirListing {
+                    path: PathBuf::from(path),
+                    modified: Duration::new(secs as u64, nanos as u32),
+                    entries,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(listings)
+    }
+
+    // ── Checkpoints ──────────────────────────────────────────────
+
+    /// Keep size buckets an unfinished session hashed in full, replacing any
+    /// kept earlier for the same sizes.
+    pub fn insert_checkpoint_buckets(
+        &self,
+        session_id: i64,
+        buckets: &[CompletedBucket],
+    ) -> Result<usize> {
+        let tx = self.connection().unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT OR REPLACE INTO checkpoint_bucket \
+                 (session_id, file_size, partial_hashes, duplicates) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for bucket in buckets {
+                let partial_hashes: Vec<(String, u64, u32, u64)> = bucket
+                    .partial_hashes
+                    .iter()
+                    .map(|(path, partial)| {
+                        (
+                            path.to_string_lossy().into_owned(),
+                            partial.modified.as_secs(),
+                            partial.modified.subsec_nanos(),
+                            partial.hash,
+                        )
+                    })
+                    .collect();
+                let duplicates: Vec<(String, Vec<String>)> = bucket
+                    .duplicates
+                    .iter()
+                    .map(|(hash, paths)| {
+                        let paths = paths.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+                        (hash.to_hex(), paths)
+                    })
+                    .collect();
+                stmt.execute(params![
+                    session_id,
+                    bucket.file_size as i64,
+                    json_text(&partial_hashes)?,
+                    json_text(&duplicates)?,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(buckets.len())
+    }
+
+    /// The size buckets kept for a session by `insert_checkpoint_buckets`.
+    pub fn get_checkpoint_buckets(&self, session_id: i64) -> Result<Vec<CompletedBucket>> {
+        let mut stmt = self.connection().prepare(
+            "SELECT file_size, partial_hashes, duplicates FROM checkpoint_bucket \
+             WHERE session_id = ?1",
+        )?;
+        let buckets = stmt
+            .query_map(params![session_id], |row| {
+                let file_size = row.get::<_, i64>(0)? as u64;
+                let partial_hashes: Vec<(String, u64, u32, u64)> = json_column(row, 1)?;
+                let duplicates: Vec<(String, Vec<String>)> = json_column(row, 2)?;
+                Ok(CompletedBucket {
+                    file_size,
+                    partial_hashes: partial_hashes
+                        .into_iter()
+                        .map(|(path, secs, nanos, hash)| {
+                            let partial = PartialHash {
+                                file_size,
+                                modified: Duration::new(secs, nanos),
+                                hash,
+                            };
+                            (PathBuf::from(path), partial)
+                        })
+                        .collect(),
+                    duplicates: duplicates
+                        .into_iter()
+                        .filter_map(|(digest, paths)| {
+                            let paths = paths.into_iter().map(PathBuf::from).collect();
+                            Some((ContentHash::from_hex(&digest)?, paths))
+                        })
+                        .collect(),
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(buckets)
+    }
+
+    pub fn delete_checkpoint_buckets(&self, session_id: i64) -> Result<()> {
+        self.connection().execute(
+            "DELETE FROM checkpoint_bucket WHERE session_id = ?1",
+            params![session_id],
+        )?;
+        Ok(())
+    }
+
     // ── Paginated Queries ────────────────────────────────────────
 
     pub fn get_duplicate_groups(
@@ -254,7 +1042,8 @@ impl Database {
         limit: i64,
     ) -> Result<Vec<DuplicateGroup>> {
         let mut stmt = self.connection().prepare(
-            "SELECT id, session_id, content_hash, file_size, file_count, wasted_bytes \
+            "SELECT id, session_id, content_hash, file_size, file_count, wasted_bytes, \
+                    verified_at \
              FROM duplicate_group WHERE session_id = ?1 \
              ORDER BY wasted_bytes DESC LIMIT ?2 OFFSET ?3",
         )?;
@@ -267,6 +1056,7 @@ impl Database {
                     file_size: row.get(3)?,
                     file_count: row.get(4)?,
                     wasted_bytes: row.get(5)?,
+                    verified_at: row.get(6)?,
                 })
             })?
             .collect::<Result<Vec<_>>>()?;
@@ -274,30 +1064,14 @@ impl Database {
     }
 
     pub fn get_files_in_group(&self, group_id: i64) -> Result<Vec<ScannedFile>> {
-        let mut stmt = self.connection().prepare(
-            "SELECT sf.id, sf.canonical_path, sf.file_name, sf.parent_dir, sf.drive_letter, \
-                    sf.file_size, sf.last_modified, sf.partial_hash, sf.content_hash, \
-                    sf.last_seen_session_id, sf.marked_deleted \
-             FROM scanned_file sf \
+        let mut stmt = self.connection().prepare(&format!(
+            "SELECT {} FROM scanned_file sf \
              JOIN duplicate_group_member dgm ON sf.id = dgm.file_id \
              WHERE dgm.group_id = ?1",
-        )?;
+            SCANNED_FILE_COLUMNS,
+        ))?;
         let files = stmt
-            .query_map(params![group_id], |row| {
-                Ok(ScannedFile {
-                    id: row.get(0)?,
-                    canonical_path: row.get(1)?,
-                    file_name: row.get(2)?,
-                    parent_dir: row.get(3)?,
-                    drive_letter: row.get(4)?,
-                    file_size: row.get(5)?,
-                    last_modified: row.get(6)?,
-                    partial_hash: row.get(7)?,
-                    content_hash: row.get(8)?,
-                    last_seen_session_id: row.get(9)?,
-                    marked_deleted: row.get(10)?,
-                })
-            })?
+            .query_map(params![group_id], scanned_file_from_row)?
             .collect::<Result<Vec<_>>>()?;
         Ok(files)
     }
@@ -310,6 +1084,16 @@ impl Database {
         )
     }
 
+    /// Number of files in a session's duplicate groups.
+    pub fn get_duplicate_file_count(&self, session_id: i64) -> Result<i64> {
+        self.connection().query_row(
+            "SELECT COUNT(*) FROM duplicate_group_member m \
+             JOIN duplicate_group g ON m.group_id = g.id WHERE g.session_id = ?1",
+            params![session_id],
+            |row| row.get(0),
+        )
+    }
+
     pub fn get_total_wasted_bytes(&self, session_id: i64) -> Result<i64> {
         self.connection().query_row(
             "SELECT COALESCE(SUM(wasted_bytes), 0) FROM duplicate_group WHERE session_id = ?1",
@@ -320,6 +1104,7 @@ impl Database {
 
     // ── Directory Nodes ──────────────────────────────────────────
 
+    /// Insert a node unless `path` already has one. Returns the node's id either way.
     pub fn insert_directory_node(
         &self,
         path: &str,
@@ -334,7 +1119,11 @@ impl Database {
              VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
             params![path, name, parent_id, total_size, file_count, depth],
         )?;
-        Ok(self.connection().last_insert_rowid())
+        self.connection().query_row(
+            "SELECT id FROM directory_node WHERE path = ?1",
+            params![path],
+            |row| row.get(0),
+        )
     }
 
     pub fn get_directory_children(
@@ -344,35 +1133,66 @@ impl Database {
         limit: i64,
     ) -> Result<Vec<DirectoryNode>> {
         let mut stmt = if parent_id.is_some() {
-            self.connection().prepare(
-                "SELECT id, path, name, parent_id, total_size, file_count, depth \
-                 FROM directory_node WHERE parent_id = ?1 \
+            self.connection().prepare(&format!(
+                "SELECT {} FROM directory_node WHERE parent_id = ?1 \
                  ORDER BY total_size DESC LIMIT ?2 OFFSET ?3",
-            )?
+                DIRECTORY_NODE_COLUMNS,
+            ))?
         } else {
-            self.connection().prepare(
-                "SELECT id, path, name, parent_id, total_size, file_count, depth \
-                 FROM directory_node WHERE parent_id IS NULL \
+            self.connection().prepare(&format!(
+                "SELECT {} FROM directory_node WHERE parent_id IS NULL \
                  ORDER BY total_size DESC LIMIT ?2 OFFSET ?3",
-            )?
+                DIRECTORY_NODE_COLUMNS,
+            ))?
         };
 
         let nodes = stmt
-            .query_map(params![parent_id, limit, offset], |row| {
-                Ok(DirectoryNode {
-                    id: row.get(0)?,
-                    path: row.get(1)?,
-                    name: row.get(2)?,
-                    parent_id: row.get(3)?,
-                    total_size: row.get(4)?,
-                    file_count: row.get(5)?,
-                    depth: row.get(6)?,
-                })
-            })?
+            .query_map(params![parent_id, limit, offset], directory_node_from_row)?
             .collect::<Result<Vec<_>>>()?;
         Ok(nodes)
     }
 
+    /// The node of the directory at `path`; `None` if no indexed file is at or below it.
+    pub fn get_directory_node(&self, path: &str) -> Result<Option<DirectoryNode>> {
+        match self.connection().query_row(
+            &format!("SELECT {} FROM directory_node WHERE path = ?1", DIRECTORY_NODE_COLUMNS),
+            params![path],
+            directory_node_from_row,
+        ) {
+            Ok(node) => Ok(Some(node)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// A page of the files directly in `dir_path` that are in no duplicate group,
+    /// largest first, with the total count. Without `index_all_files` only files
+    /// that shared a size with another file are indexed.
+    pub fn get_unique_files_in_directory(
+        &self,
+        dir_path: &str,
+        offset: i64,
+        limit: i64,
+    ) -> Result<(Vec<ScannedFile>, i64)> {
+        const UNIQUE: &str = "sf.parent_dir = ?1 AND sf.marked_deleted = 0 AND NOT EXISTS \
+             (SELECT 1 FROM duplicate_group_member dgm WHERE dgm.file_id = sf.id)";
+        let total: i64 = self.connection().query_row(
+            &format!("SELECT COUNT(*) FROM scanned_file sf WHERE {}", UNIQUE),
+            params![dir_path],
+            |row| row.get(0),
+        )?;
+
+        let mut stmt = self.connection().prepare(&format!(
+            "SELECT {} FROM scanned_file sf WHERE {} \
+             ORDER BY sf.file_size DESC, sf.file_name LIMIT ?2 OFFSET ?3",
+            SCANNED_FILE_COLUMNS, UNIQUE,
+        ))?;
+        let files = stmt
+            .query_map(params![dir_path, limit, offset], scanned_file_from_row)?
+            .collect::<Result<Vec<_>>>()?;
+        Ok((files, total))
+    }
+
     // ── Directory Fingerprints & Similarity ──────────────────────
 
     pub fn insert_directory_fingerprint(
@@ -443,13 +1263,77 @@ impl Database {
         Ok(pairs)
     }
 
+    // ── Protected Paths ──────────────────────────────────────────
+
+    /// Add to the protected path list, keeping what's there.
+    pub fn add_protected_paths(&self, paths: &[String]) -> Result<()> {
+        let tx = self.connection().unchecked_transaction()?;
+        {
+            let mut stmt =
+                tx.prepare_cached("INSERT OR IGNORE INTO protected_path (path) VALUES (?1)")?;
+            for path in paths {
+                stmt.execute(params![path])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Replace the protected path list. Only for an explicit request to change
+    /// it; a scan only adds to it.
+    pub fn replace_protected_paths(&self, paths: &[String]) -> Result<()> {
+        let tx = self.connection().unchecked_transaction()?;
+        tx.execute("DELETE FROM protected_path", [])?;
+        {
+            let mut stmt =
+                tx.prepare_cached("INSERT OR IGNORE INTO protected_path (path) VALUES (?1)")?;
+            for path in paths {
+                stmt.execute(params![path])?;
+            }
+        }
+        tx.commit()
+    }
+
+    pub fn get_protected_paths(&self) -> Result<Vec<String>> {
+        let mut stmt = self
+            .connection()
+            .prepare("SELECT path FROM protected_path ORDER BY path")?;
+        let paths = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<String>>>()?;
+        Ok(paths)
+    }
+
+    /// The protected path `path` is at or below, if any.
+    pub fn protecting_path(&self, path: &str) -> Result<Option<String>> {
+        Ok(self
+            .get_protected_paths()?
+            .into_iter()
+            .find(|protected| Path::new(path).starts_with(protected)))
+    }
+
     // ── Deletion Planning ────────────────────────────────────────
 
+    /// Add a file to the deletion plan. Fails with `Error::Protected` when the
+    /// file is under a protected path.
     pub fn mark_file_for_deletion(
         &self,
         file_id: i64,
         strategy: Option<&str>,
-    ) -> Result<()> {
+    ) -> std::result::Result<(), crate::Error> {
+        let path = match self.connection().query_row(
+            "SELECT canonical_path FROM scanned_file WHERE id = ?1",
+            params![file_id],
+            |row| row.get::<_, String>(0),
+        ) {
+            Ok(path) => Some(path),
+            Err(rusqlite::Error::QueryReturnedNoRows) => None,
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(path) = path {
+            if let Some(protected_by) = self.protecting_path(&path)? {
+                return Err(crate::Error::Protected { path, protected_by });
+            }
+        }
         let now = chrono::Utc::now().to_rfc3339();
         self.connection().execute(
             "INSERT OR REPLACE INTO deletion_plan (file_id, marked_at, strategy) \
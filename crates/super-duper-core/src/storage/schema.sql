@@ -1,15 +1,21 @@
-PRAGMA user_version = 2;
+PRAGMA user_version = 15;
 
 -- Track scan runs
 CREATE TABLE IF NOT EXISTS scan_session (
     id INTEGER PRIMARY KEY AUTOINCREMENT,
     started_at TEXT NOT NULL,
     completed_at TEXT,
-    status TEXT NOT NULL DEFAULT 'running',
+    status TEXT NOT NULL DEFAULT 'running',  -- running, completed, cancelled or aborted
     root_paths TEXT NOT NULL,
     root_paths_hash TEXT,
+    hash_algorithm TEXT,
     files_scanned INTEGER DEFAULT 0,
-    total_bytes INTEGER DEFAULT 0
+    total_bytes INTEGER DEFAULT 0,
+    cache_hits INTEGER NOT NULL DEFAULT 0,  -- full-hash lookups served from the hash cache
+    cache_misses INTEGER NOT NULL DEFAULT 0,
+    scan_filter TEXT,                       -- ScanFilter the session was scanned with, as JSON
+    scan_settings TEXT                      -- digest of the settings that decide which files are
+                                            -- grouped; a rescan reuses groups only if it matches
 );
 
 -- Global file index: keyed by canonical_path, updated on each re-scan via upsert
@@ -21,13 +27,18 @@ CREATE TABLE IF NOT EXISTS scanned_file (
     drive_letter TEXT DEFAULT '',
     file_size INTEGER NOT NULL,
     last_modified INTEGER NOT NULL,
+    last_modified_nanos INTEGER NOT NULL DEFAULT 0,  -- sub-second part of last_modified
     partial_hash INTEGER,
+    partial_hash_len INTEGER,  -- bytes covered by partial_hash; reused only when it matches
     content_hash INTEGER,
+    content_digest TEXT,
     last_seen_session_id INTEGER REFERENCES scan_session(id),
     marked_deleted INTEGER NOT NULL DEFAULT 0
 );
 
--- Duplicate groups scoped to a session
+-- Duplicate groups scoped to a session.
+-- content_hash holds the leading 64 bits of the digest; groups are formed on the full
+-- digest, so two groups may share a content_hash when a wider algorithm is in use.
 CREATE TABLE IF NOT EXISTS duplicate_group (
     id INTEGER PRIMARY KEY AUTOINCREMENT,
     session_id INTEGER NOT NULL REFERENCES scan_session(id),
@@ -35,7 +46,7 @@ CREATE TABLE IF NOT EXISTS duplicate_group (
     file_size INTEGER NOT NULL,
     file_count INTEGER NOT NULL,
     wasted_bytes INTEGER NOT NULL,
-    UNIQUE(session_id, content_hash, file_size)
+    verified_at TEXT  -- set when members were byte-compared against the kept copy
 );
 
 CREATE TABLE IF NOT EXISTS duplicate_group_member (
@@ -53,7 +64,10 @@ CREATE TABLE IF NOT EXISTS directory_node (
     parent_id INTEGER REFERENCES directory_node(id),
     total_size INTEGER DEFAULT 0,
     file_count INTEGER DEFAULT 0,
-    depth INTEGER DEFAULT 0
+    depth INTEGER DEFAULT 0,
+    -- Files in a duplicate group, and their size
+    duplicate_count INTEGER DEFAULT 0,
+    duplicate_size INTEGER DEFAULT 0
 );
 
 -- Directory fingerprints for exact + similarity matching
@@ -86,6 +100,68 @@ CREATE TABLE IF NOT EXISTS deletion_plan (
     execution_result TEXT
 );
 
+-- Paths whose files may never be marked or deleted, canonical where they exist.
+-- Scans add the config's protected_paths and protected roots; only an explicit
+-- request replaces the list. Not session data.
+CREATE TABLE IF NOT EXISTS protected_path (
+    path TEXT PRIMARY KEY
+);
+
+-- Each root a session scanned and the device it was on
+CREATE TABLE IF NOT EXISTS session_root (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id INTEGER NOT NULL REFERENCES scan_session(id) ON DELETE CASCADE,
+    root_path TEXT NOT NULL,
+    device_id INTEGER NOT NULL,
+    filesystem_type TEXT,       -- as the OS names it, e.g. ext4 or NTFS; NULL if unknown
+    label TEXT,                 -- from the root's config entry, e.g. 'primary' or 'backup'
+    priority INTEGER NOT NULL DEFAULT 0,   -- auto-mark keeps copies under the highest
+    protected INTEGER NOT NULL DEFAULT 0
+);
+
+-- Paths a session couldn't examine, so unscanned parts of a tree are visible
+CREATE TABLE IF NOT EXISTS scan_error (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id INTEGER NOT NULL REFERENCES scan_session(id) ON DELETE CASCADE,
+    path TEXT NOT NULL,
+    phase TEXT NOT NULL,       -- walk, metadata, canonicalize, partial, sample or full
+    error_kind TEXT NOT NULL,  -- io::ErrorKind name, e.g. PermissionDenied
+    message TEXT NOT NULL
+);
+
+-- Symlinks recorded by the scanner, and hard-linked names beyond the one each file
+-- is scanned under; their data is already shared, so they're never duplicates
+CREATE TABLE IF NOT EXISTS file_link (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id INTEGER NOT NULL REFERENCES scan_session(id) ON DELETE CASCADE,
+    path TEXT NOT NULL,
+    target TEXT NOT NULL,      -- symlinks: target as stored; hard links: the scanned name
+    kind TEXT NOT NULL,        -- symlink or hardlink
+    file_size INTEGER NOT NULL
+);
+
+-- Directory listings from a session's walk, reused by the next one for each
+-- directory whose mtime hasn't changed
+CREATE TABLE IF NOT EXISTS dir_listing (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id INTEGER NOT NULL REFERENCES scan_session(id) ON DELETE CASCADE,
+    path TEXT NOT NULL,        -- as walked
+    last_modified INTEGER NOT NULL,
+    last_modified_nanos INTEGER NOT NULL DEFAULT 0,
+    entries TEXT NOT NULL      -- JSON [[name, kind], ...]; kind is file, dir, symlink or other
+);
+
+-- Size buckets an unfinished scan hashed in full, so resuming it can skip those
+-- whose files haven't changed
+CREATE TABLE IF NOT EXISTS checkpoint_bucket (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id INTEGER NOT NULL REFERENCES scan_session(id) ON DELETE CASCADE,
+    file_size INTEGER NOT NULL,
+    partial_hashes TEXT NOT NULL,  -- JSON [[canonical_path, mtime_secs, mtime_nanos, hash], ...]
+    duplicates TEXT NOT NULL,      -- JSON [[content_digest, [path, ...]], ...]
+    UNIQUE(session_id, file_size)
+);
+
 -- Indexes for common UI queries
 CREATE INDEX IF NOT EXISTS idx_file_size ON scanned_file(file_size);
 CREATE INDEX IF NOT EXISTS idx_file_content_hash ON scanned_file(content_hash) WHERE content_hash IS NOT NULL;
@@ -94,7 +170,12 @@ CREATE INDEX IF NOT EXISTS idx_file_canonical_path ON scanned_file(canonical_pat
 CREATE INDEX IF NOT EXISTS idx_group_session ON duplicate_group(session_id);
 CREATE INDEX IF NOT EXISTS idx_group_wasted ON duplicate_group(wasted_bytes DESC);
 CREATE INDEX IF NOT EXISTS idx_group_member_group ON duplicate_group_member(group_id);
+CREATE INDEX IF NOT EXISTS idx_group_member_file ON duplicate_group_member(file_id);
 CREATE INDEX IF NOT EXISTS idx_dir_parent ON directory_node(parent_id);
 CREATE INDEX IF NOT EXISTS idx_dir_fingerprint ON directory_fingerprint(content_fingerprint);
 CREATE INDEX IF NOT EXISTS idx_dir_similarity_score ON directory_similarity(similarity_score DESC);
+CREATE INDEX IF NOT EXISTS idx_scan_error_session ON scan_error(session_id);
+CREATE INDEX IF NOT EXISTS idx_session_root_session ON session_root(session_id);
+CREATE INDEX IF NOT EXISTS idx_file_link_session ON file_link(session_id);
+CREATE INDEX IF NOT EXISTS idx_dir_listing_session ON dir_listing(session_id);
 CREATE INDEX IF NOT EXISTS idx_session_paths_hash ON scan_session(root_paths_hash) WHERE root_paths_hash IS NOT NULL;
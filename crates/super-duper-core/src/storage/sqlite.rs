@@ -1,6 +1,9 @@
 use rusqlite::{Connection, Result};
 use tracing::debug;
 
+/// Must match the `PRAGMA user_version` set at the top of schema.sql.
+const SCHEMA_VERSION: i64 = 15;
+
 pub struct Database {
     conn: Connection,
 }
@@ -36,17 +39,26 @@ impl Database {
     }
 
     /// Check schema version and migrate if needed.
-    /// Version < 2: drop all tables and recreate (data is derived/recomputable).
+    /// Older versions: drop all tables and recreate (data is derived/recomputable),
+    /// except `protected_path`, which the user set and no scan can rebuild.
     fn migrate_schema(&self) -> Result<()> {
         let version: i64 = self
             .conn
             .query_row("PRAGMA user_version", [], |row| row.get(0))?;
 
-        if version < 2 {
-            debug!("Schema version {} < 2, dropping all tables and recreating", version);
+        if version < SCHEMA_VERSION {
+            debug!(
+                "Schema version {} < {}, dropping derived tables and recreating",
+                version, SCHEMA_VERSION
+            );
             // Disable FK enforcement for the drop batch so table order doesn't matter.
             self.conn.execute_batch(
                 "PRAGMA foreign_keys = OFF;
+                 DROP TABLE IF EXISTS checkpoint_bucket;
+                 DROP TABLE IF EXISTS dir_listing;
+                 DROP TABLE IF EXISTS session_root;
+                 DROP TABLE IF EXISTS file_link;
+                 DROP TABLE IF EXISTS scan_error;
                  DROP TABLE IF EXISTS deletion_plan;
                  DROP TABLE IF EXISTS directory_similarity;
                  DROP TABLE IF EXISTS directory_fingerprint;
@@ -60,7 +72,7 @@ impl Database {
         }
 
         self.conn.execute_batch(include_str!("schema.sql"))?;
-        debug!("SQLite schema initialized (version 2)");
+        debug!("SQLite schema initialized (version {})", SCHEMA_VERSION);
         Ok(())
     }
 
@@ -71,6 +83,11 @@ impl Database {
     pub fn truncate_all(&self) -> Result<()> {
         self.conn.execute_batch(
             "BEGIN;
+             DELETE FROM checkpoint_bucket;
+             DELETE FROM dir_listing;
+             DELETE FROM session_root;
+             DELETE FROM file_link;
+             DELETE FROM scan_error;
              DELETE FROM deletion_plan;
              DELETE FROM directory_similarity;
              DELETE FROM directory_fingerprint;
@@ -90,6 +107,11 @@ impl Database {
     pub fn delete_all_sessions(&self) -> Result<()> {
         self.conn.execute_batch(
             "BEGIN;
+             DELETE FROM checkpoint_bucket;
+             DELETE FROM dir_listing;
+             DELETE FROM session_root;
+             DELETE FROM file_link;
+             DELETE FROM scan_error;
              DELETE FROM deletion_plan;
              DELETE FROM directory_similarity;
              DELETE FROM directory_fingerprint;
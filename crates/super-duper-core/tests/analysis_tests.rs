@@ -1,4 +1,7 @@
 use super_duper_core::analysis::{deletion_plan, dir_fingerprint, dir_similarity};
+use super_duper_core::config::RootConfig;
+use super_duper_core::hasher::IoLimits;
+use super_duper_core::scanner::RootDevice;
 use super_duper_core::storage::models::ScannedFile;
 use super_duper_core::storage::Database;
 
@@ -14,8 +17,11 @@ fn make_test_scanned_file(path: &str, size: i64, hash: i64, session_id: i64) ->
         drive_letter: String::new(),
         file_size: size,
         last_modified: 1700000000,
+        last_modified_nanos: 0,
         partial_hash: None,
+        partial_hash_len: None,
         content_hash: Some(hash),
+        content_digest: Some(format!("{:016x}", hash as u64)),
         last_seen_session_id: Some(session_id),
         marked_deleted: false,
     }
@@ -106,7 +112,7 @@ fn test_build_directory_fingerprints_nested_dirs() {
         .ok();
 
     if let Some(hash_set_json) = root_fp {
-        let hashes: Vec<i64> = serde_json::from_str(&hash_set_json).unwrap();
+        let hashes: Vec<String> = serde_json::from_str(&hash_set_json).unwrap();
         // Should contain all 3 unique hashes (111, 222, 333)
         assert_eq!(hashes.len(), 3);
     }
@@ -201,6 +207,95 @@ fn test_compute_similarity_partial_overlap() {
     assert!(dir_pair.is_some(), "Expected partial overlap pair, got: {:?}", pairs);
 }
 
+#[test]
+fn test_similarity_keys_on_full_digest() {
+    // Same leading 64 bits, different sha256 digests: not the same content
+    let db = Database::open_in_memory().unwrap();
+    let session_id = db.create_scan_session(&["root".to_string()]).unwrap();
+    db.set_session_hash_algorithm(session_id, "sha256").unwrap();
+    db.complete_scan_session(session_id, 2, 200).unwrap();
+    let mut a = make_test_scanned_file("/dir_a/x.bin", 100, 111, session_id);
+    a.content_digest = Some(format!("{:016x}{}", 111, "a".repeat(48)));
+    let mut b = make_test_scanned_file("/dir_b/x.bin", 100, 111, session_id);
+    b.content_digest = Some(format!("{:016x}{}", 111, "b".repeat(48)));
+    db.insert_scanned_files(&[a, b]).unwrap();
+
+    dir_fingerprint::build_directory_fingerprints(&db).unwrap();
+    let fingerprints: Vec<String> = db
+        .connection()
+        .prepare(
+            "SELECT df.content_fingerprint FROM directory_fingerprint df \
+             JOIN directory_node dn ON df.directory_id = dn.id \
+             WHERE dn.path IN ('/dir_a', '/dir_b')",
+        )
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(fingerprints.len(), 2);
+    assert_ne!(fingerprints[0], fingerprints[1]);
+    // Taken with the session's algorithm
+    assert_eq!(fingerprints[0].len(), 64);
+
+    dir_similarity::compute_directory_similarity(&db, 0.1).unwrap();
+    let pairs: i64 = db
+        .connection()
+        .query_row(
+            "SELECT COUNT(*) FROM directory_similarity ds \
+             JOIN directory_node a ON ds.dir_a_id = a.id \
+             JOIN directory_node b ON ds.dir_b_id = b.id \
+             WHERE a.path IN ('/dir_a', '/dir_b') AND b.path IN ('/dir_a', '/dir_b')",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(pairs, 0);
+}
+
+#[test]
+fn test_update_similarity_only_revisits_affected_dirs() {
+    let (db, session_id) = setup_db_with_files(&[
+        ("/dir_a/x.txt", 100, 111),
+        ("/dir_a/y.txt", 200, 222),
+        ("/dir_b/x.txt", 100, 111),
+        ("/dir_b/y.txt", 200, 222),
+        ("/dir_c/z.txt", 300, 333),
+    ]);
+    dir_fingerprint::build_directory_fingerprints(&db).unwrap();
+    dir_similarity::compute_directory_similarity(&db, 0.5).unwrap();
+    let pair_of = |a: &str, b: &str| {
+        db.get_similar_directories(0.0, 0, 100)
+            .unwrap()
+            .into_iter()
+            .find(|p| p.dir_a_path == a && p.dir_b_path == b || p.dir_a_path == b && p.dir_b_path == a)
+    };
+    // Marked so a recomputed pair would show
+    db.connection()
+        .execute(
+            "UPDATE directory_similarity SET shared_bytes = -1 WHERE id = ?1",
+            [pair_of("/dir_a", "/dir_b").unwrap().id],
+        )
+        .unwrap();
+    assert!(pair_of("/dir_a", "/dir_c").is_none());
+
+    assert_eq!(dir_similarity::update_directory_similarity(&db, 0.5, &[]).unwrap(), 0);
+
+    db.insert_scanned_files(&[
+        make_test_scanned_file("/dir_c/x.txt", 100, 111, session_id),
+        make_test_scanned_file("/dir_c/y.txt", 200, 222, session_id),
+    ])
+    .unwrap();
+    let dirs = vec!["/dir_c".to_string()];
+    dir_fingerprint::update_directory_fingerprints(&db, &dirs).unwrap();
+    assert!(dir_similarity::update_directory_similarity(&db, 0.5, &dirs).unwrap() > 0);
+
+    assert_eq!(pair_of("/dir_a", "/dir_b").unwrap().shared_bytes, -1);
+    let subset = pair_of("/dir_a", "/dir_c").unwrap();
+    assert_eq!(subset.match_type, "subset");
+    assert_eq!(subset.shared_bytes, 300);
+}
+
 #[test]
 fn test_compute_similarity_below_threshold() {
     let (db, _) = setup_db_with_files(&[
@@ -290,6 +385,124 @@ fn test_auto_mark_duplicates() {
     assert_eq!(marked_path, "/z/beta.txt");
 }
 
+#[test]
+fn test_auto_mark_keeps_copy_under_highest_priority_root() {
+    let (db, session_id) = setup_db_with_files(&[
+        ("/archive/photo.jpg", 100, 111),
+        ("/backup/photo.jpg", 100, 111),
+        ("/primary/photo.jpg", 100, 111),
+        ("/backup/notes.txt", 50, 222),
+        ("/primary/notes.txt", 50, 222),
+    ]);
+    let groups = vec![
+        (
+            111_i64,
+            100_i64,
+            vec![
+                "/archive/photo.jpg".to_string(),
+                "/backup/photo.jpg".to_string(),
+                "/primary/photo.jpg".to_string(),
+            ],
+        ),
+        (
+            222_i64,
+            50_i64,
+            vec!["/backup/notes.txt".to_string(), "/primary/notes.txt".to_string()],
+        ),
+    ];
+    db.insert_duplicate_groups(session_id, &groups).unwrap();
+
+    let root = |path: &str| RootDevice {
+        path: path.into(),
+        device: 1,
+        filesystem_type: None,
+    };
+    let (archive, backup, primary) = (root("/archive"), root("/backup"), root("/primary"));
+    let archive_config = RootConfig {
+        protected: true,
+        ..RootConfig::from("/archive")
+    };
+    let backup_config = RootConfig {
+        priority: -1,
+        ..RootConfig::from("/backup")
+    };
+    let primary_config = RootConfig {
+        label: Some("primary".into()),
+        priority: 10,
+        ..RootConfig::from("/primary")
+    };
+    db.replace_session_roots(
+        session_id,
+        &[
+            (&archive, &archive_config),
+            (&backup, &backup_config),
+            (&primary, &primary_config),
+        ],
+    )
+    .unwrap();
+    let roots = db.get_session_roots(session_id).unwrap();
+    assert_eq!(roots[2].label.as_deref(), Some("primary"));
+    assert_eq!(roots[2].priority, 10);
+    assert!(roots[0].protected);
+
+    // The protected /archive copy is kept over everything; otherwise /primary
+    // outranks /backup
+    let marked = deletion_plan::auto_mark_duplicates(&db, session_id, None).unwrap();
+    assert_eq!(marked, 3);
+    let mut marked_paths: Vec<String> = db
+        .get_deletion_plan()
+        .unwrap()
+        .iter()
+        .map(|entry| {
+            db.connection()
+                .query_row(
+                    "SELECT canonical_path FROM scanned_file WHERE id = ?1",
+                    rusqlite::params![entry.file_id],
+                    |row| row.get(0),
+                )
+                .unwrap()
+        })
+        .collect();
+    marked_paths.sort();
+    assert_eq!(
+        marked_paths,
+        ["/backup/notes.txt", "/backup/photo.jpg", "/primary/photo.jpg"]
+    );
+}
+
+#[test]
+fn test_protected_paths_kept_and_rechecked_on_execute() {
+    let (db, session_id) = setup_db_with_files(&[
+        ("/a/copy.txt", 100, 111),
+        ("/masters/original.txt", 100, 111),
+    ]);
+    let groups = vec![(
+        111_i64,
+        100_i64,
+        vec!["/a/copy.txt".to_string(), "/masters/original.txt".to_string()],
+    )];
+    db.insert_duplicate_groups(session_id, &groups).unwrap();
+
+    // The protected copy is kept even though /a sorts first
+    db.replace_protected_paths(&["/masters".to_string()]).unwrap();
+    assert_eq!(deletion_plan::auto_mark_duplicates(&db, session_id, None).unwrap(), 1);
+    assert!(matches!(
+        deletion_plan::mark_directory_for_deletion(&db, "/masters", None),
+        Err(super_duper_core::Error::Protected { .. })
+    ));
+
+    // Protection added after marking is honoured when the plan runs
+    db.replace_protected_paths(&["/masters".to_string(), "/a".to_string()])
+        .unwrap();
+    let (success, errors) = deletion_plan::execute_deletion_plan(&db, false, IoLimits::default()).unwrap();
+    assert_eq!((success, errors), (0, 1));
+    let result: String = db
+        .connection()
+        .query_row("SELECT execution_result FROM deletion_plan", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(result, "protected");
+}
+
 #[test]
 fn test_execute_deletion_plan_real_files() {
     use std::io::Write;
@@ -337,7 +550,7 @@ fn test_execute_deletion_plan_real_files() {
     db.mark_file_for_deletion(id_a, None).unwrap();
     db.mark_file_for_deletion(id_b, None).unwrap();
 
-    let (success, errors) = deletion_plan::execute_deletion_plan(&db, false).unwrap();
+    let (success, errors) = deletion_plan::execute_deletion_plan(&db, false, IoLimits::default()).unwrap();
     assert_eq!(success, 2);
     assert_eq!(errors, 0);
 
@@ -1,13 +1,28 @@
 use std::fs;
 use std::io::Write;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
 use tempfile::tempdir;
 
 use super_duper_core::analysis::{
     deletion_plan, dir_fingerprint, dir_similarity,
 };
+use super_duper_core::hasher::{
+    CompletedBucket, ContentHash, EvictionPolicy, HashAlgorithm, HashCache, IoLimits,
+    MemoryHashCache, PartialHash,
+};
 use super_duper_core::storage::Database;
-use super_duper_core::{AppConfig, ScanEngine, SilentReporter};
+use super_duper_core::{
+    AppConfig, Error, IndexChanges, PauseToken, ProgressReporter, ScanEngine, SilentReporter,
+};
+
+/// Per-test hash cache next to the test database, so parallel tests never share
+/// (and lock) the default cache.
+fn cache_path(db_path: &Path) -> String {
+    db_path.with_extension("hashcache").to_string_lossy().into_owned()
+}
 
 fn count_files_recursive(dir: &Path) -> usize {
     let mut count = 0;
@@ -70,12 +85,14 @@ fn test_full_scan_pipeline() {
     let db_path = db_dir.path().join("test_e2e.db");
 
     let config = AppConfig {
-        root_paths: vec![root.to_string_lossy().into_owned()],
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
         ignore_patterns: vec![],
+        ..Default::default()
     };
 
     let engine = ScanEngine::new(config)
-        .with_db_path(db_path.to_str().unwrap());
+        .with_db_path(db_path.to_str().unwrap())
+        .with_cache_path(&cache_path(&db_path));
     let result = engine.scan(&SilentReporter).unwrap();
 
     // We expect at least 6 files scanned (2 unique + 2 shared + 2 large)
@@ -130,12 +147,14 @@ fn test_scan_with_ignore_patterns() {
 
     // Ignore folder_c entirely
     let config = AppConfig {
-        root_paths: vec![root.to_string_lossy().into_owned()],
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
         ignore_patterns: vec!["**/folder_c/**".to_string()],
+        ..Default::default()
     };
 
     let engine = ScanEngine::new(config)
-        .with_db_path(db_path.to_str().unwrap());
+        .with_db_path(db_path.to_str().unwrap())
+        .with_cache_path(&cache_path(&db_path));
     let result = engine.scan(&SilentReporter).unwrap();
 
     // With folder_c ignored, only 1 duplicate group (shared.txt)
@@ -157,12 +176,14 @@ fn test_scan_cancellation() {
     let db_path_str = db_path.to_str().unwrap().to_string();
 
     let config = AppConfig {
-        root_paths: vec![root.to_string_lossy().into_owned()],
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
         ignore_patterns: vec![],
+        ..Default::default()
     };
 
     let engine = ScanEngine::new(config)
-        .with_db_path(&db_path_str);
+        .with_db_path(&db_path_str)
+        .with_cache_path(&cache_path(&db_path));
 
     // Get the cancel token and cancel from another thread after a tiny delay.
     // scan() resets the token at start, so we must cancel after it begins.
@@ -186,6 +207,58 @@ fn test_scan_cancellation() {
     }
 }
 
+/// Pauses the scan as hashing starts and resumes it from another thread.
+struct PauseOnHash {
+    pause: Arc<PauseToken>,
+    events: Mutex<Vec<&'static str>>,
+}
+
+impl ProgressReporter for PauseOnHash {
+    fn on_hash_start(&self) {
+        assert!(self.pause.pause());
+    }
+
+    fn on_scan_paused(&self) {
+        self.events.lock().unwrap().push("paused");
+        let pause = self.pause.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            assert!(pause.resume());
+        });
+    }
+
+    fn on_scan_resumed(&self) {
+        self.events.lock().unwrap().push("resumed");
+    }
+}
+
+#[test]
+fn test_paused_scan_completes_once_resumed() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("scan_pause");
+    create_test_tree(&root);
+
+    let db_dir = tempdir().unwrap();
+    let db_path = db_dir.path().join("test_pause.db");
+    let engine = ScanEngine::new(AppConfig {
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
+        ..Default::default()
+    })
+    .with_db_path(db_path.to_str().unwrap())
+    .with_hash_cache(Arc::new(MemoryHashCache::new()));
+
+    let reporter = PauseOnHash {
+        pause: engine.pause_token(),
+        events: Mutex::new(Vec::new()),
+    };
+    let result = engine.scan(&reporter).unwrap();
+
+    // However many threads stopped, the pause is reported once each way
+    assert_eq!(*reporter.events.lock().unwrap(), ["paused", "resumed"]);
+    assert!(!engine.is_paused());
+    assert_eq!(result.duplicate_groups, 2);
+}
+
 #[test]
 fn test_full_pipeline_with_directory_analysis() {
     let tmp = tempdir().unwrap();
@@ -196,13 +269,15 @@ fn test_full_pipeline_with_directory_analysis() {
     let db_path = db_dir.path().join("test_diranalysis.db");
 
     let config = AppConfig {
-        root_paths: vec![root.to_string_lossy().into_owned()],
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
         ignore_patterns: vec![],
+        ..Default::default()
     };
 
     // Phase 1: Run scan
     let engine = ScanEngine::new(config)
-        .with_db_path(db_path.to_str().unwrap());
+        .with_db_path(db_path.to_str().unwrap())
+        .with_cache_path(&cache_path(&db_path));
     let scan_result = engine.scan(&SilentReporter).unwrap();
     assert_eq!(scan_result.duplicate_groups, 2);
 
@@ -238,6 +313,57 @@ fn test_full_pipeline_with_directory_analysis() {
     }
 }
 
+#[test]
+fn test_scan_without_protected_paths_keeps_earlier_protection() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("scan_protect");
+    create_test_tree(&root);
+    let masters = fs::canonicalize(root.join("folder_b")).unwrap();
+
+    let db_dir = tempdir().unwrap();
+    let db_path = db_dir.path().join("test_protect.db");
+    let scan = |protected_paths: Vec<String>| {
+        ScanEngine::new(AppConfig {
+            root_paths: vec![root.to_string_lossy().into_owned().into()],
+            protected_paths,
+            ..Default::default()
+        })
+        .with_db_path(db_path.to_str().unwrap())
+        .with_hash_cache(Arc::new(MemoryHashCache::new()))
+        .scan(&SilentReporter)
+        .unwrap()
+    };
+    scan(vec![masters.to_string_lossy().into_owned()]);
+    // Another handle or config with no protected paths scans the same database
+    scan(Vec::new());
+
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+    let master = masters.join("shared.txt");
+    let file_id: i64 = db
+        .connection()
+        .query_row(
+            "SELECT id FROM scanned_file WHERE canonical_path = ?1",
+            [master.to_string_lossy()],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert!(matches!(
+        db.mark_file_for_deletion(file_id, None),
+        Err(Error::Protected { .. })
+    ));
+
+    // Nor is it deleted if a plan entry slipped in some other way
+    db.connection()
+        .execute(
+            "INSERT INTO deletion_plan (file_id, marked_at) VALUES (?1, '2024-01-01T00:00:00Z')",
+            [file_id],
+        )
+        .unwrap();
+    let (deleted, _) = deletion_plan::execute_deletion_plan(&db, false, IoLimits::default()).unwrap();
+    assert_eq!(deleted, 0);
+    assert!(master.exists());
+}
+
 #[test]
 fn test_full_pipeline_with_deletion() {
     let tmp = tempdir().unwrap();
@@ -248,13 +374,15 @@ fn test_full_pipeline_with_deletion() {
     let db_path = db_dir.path().join("test_deletion.db");
 
     let config = AppConfig {
-        root_paths: vec![root.to_string_lossy().into_owned()],
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
         ignore_patterns: vec![],
+        ..Default::default()
     };
 
     // Scan
     let engine = ScanEngine::new(config)
-        .with_db_path(db_path.to_str().unwrap());
+        .with_db_path(db_path.to_str().unwrap())
+        .with_cache_path(&cache_path(&db_path));
     let scan_result = engine.scan(&SilentReporter).unwrap();
     assert_eq!(scan_result.duplicate_groups, 2);
 
@@ -272,7 +400,7 @@ fn test_full_pipeline_with_deletion() {
     assert!(bytes > 0, "Expected wasted bytes > 0");
 
     // Execute deletion
-    let (deleted, errors) = deletion_plan::execute_deletion_plan(&db, false).unwrap();
+    let (deleted, errors) = deletion_plan::execute_deletion_plan(&db, false, IoLimits::default()).unwrap();
     assert_eq!(deleted, 2);
     assert_eq!(errors, 0);
 
@@ -298,13 +426,15 @@ fn test_rescan_after_deletion() {
     let db_path = db_dir.path().join("test_rescan.db");
 
     let config = AppConfig {
-        root_paths: vec![root.to_string_lossy().into_owned()],
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
         ignore_patterns: vec![],
+        ..Default::default()
     };
 
     // First scan
     let engine = ScanEngine::new(config.clone())
-        .with_db_path(db_path.to_str().unwrap());
+        .with_db_path(db_path.to_str().unwrap())
+        .with_cache_path(&cache_path(&db_path));
     let result1 = engine.scan(&SilentReporter).unwrap();
     assert_eq!(result1.duplicate_groups, 2);
 
@@ -317,7 +447,8 @@ fn test_rescan_after_deletion() {
     db.truncate_all().unwrap();
 
     let engine2 = ScanEngine::new(config)
-        .with_db_path(db_path.to_str().unwrap());
+        .with_db_path(db_path.to_str().unwrap())
+        .with_cache_path(&cache_path(&db_path));
     let result2 = engine2.scan(&SilentReporter).unwrap();
 
     // Now only 1 duplicate group (the large files in folder_c)
@@ -338,19 +469,22 @@ fn test_idempotent_rescan() {
     let db_path = db_dir.path().join("test_idempotent.db");
 
     let config = AppConfig {
-        root_paths: vec![root.to_string_lossy().into_owned()],
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
         ignore_patterns: vec![],
+        ..Default::default()
     };
 
     // First scan
     let engine = ScanEngine::new(config.clone())
-        .with_db_path(db_path.to_str().unwrap());
+        .with_db_path(db_path.to_str().unwrap())
+        .with_cache_path(&cache_path(&db_path));
     let result1 = engine.scan(&SilentReporter).unwrap();
     assert_eq!(result1.duplicate_groups, 2);
 
     // Second scan — same paths, no truncate, must NOT crash and must produce same results
     let engine2 = ScanEngine::new(config)
-        .with_db_path(db_path.to_str().unwrap());
+        .with_db_path(db_path.to_str().unwrap())
+        .with_cache_path(&cache_path(&db_path));
     let result2 = engine2.scan(&SilentReporter).unwrap();
     assert_eq!(
         result2.duplicate_groups, 2,
@@ -365,3 +499,674 @@ fn test_idempotent_rescan() {
         .unwrap();
     assert_eq!(session_count, 1, "Idempotent rescan should reuse the same session");
 }
+
+#[test]
+fn test_incremental_rescan_regroups_changed_sizes() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("scan_incremental");
+    create_test_tree(&root);
+
+    let db_dir = tempdir().unwrap();
+    let db_path = db_dir.path().join("test_incremental.db");
+    let config = AppConfig {
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
+        ..Default::default()
+    };
+    let engine = ScanEngine::new(config.clone())
+        .with_db_path(db_path.to_str().unwrap())
+        .with_hash_cache(Arc::new(MemoryHashCache::new()));
+
+    let first = engine.scan(&SilentReporter).unwrap();
+    assert!(!first.incremental);
+    assert_eq!(first.duplicate_groups, 2);
+
+    // Nothing changed: every size bucket is kept and nothing is hashed
+    let second = engine.scan(&SilentReporter).unwrap();
+    assert!(second.incremental);
+    assert_eq!(second.session_id, first.session_id);
+    assert_eq!(second.unchanged_files, 6);
+    assert_eq!(second.hash_stats.partial.files_hashed, 0);
+    assert_eq!(second.duplicate_groups, 2);
+    assert_eq!(second.duplicate_files, 4);
+    assert_eq!(second.wasted_bytes, first.wasted_bytes);
+
+    // unique_b.txt becomes a copy of unique_a.txt, and one large copy goes
+    let unique_b = root.join("folder_b").join("unique_b.txt");
+    fs::write(&unique_b, "unique content a").unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&unique_b)
+        .unwrap()
+        .set_modified(UNIX_EPOCH + Duration::from_secs(1_000_000))
+        .unwrap();
+    fs::remove_file(root.join("folder_c").join("large_dup_2.bin")).unwrap();
+
+    let third = engine.scan(&SilentReporter).unwrap();
+    assert!(third.incremental);
+    assert_eq!(third.unchanged_files, 2, "only the shared.txt bucket is kept");
+    assert_eq!(third.duplicate_groups, 2);
+    assert_eq!(third.wasted_bytes, 18 + 16);
+
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+    let groups = db.get_duplicate_groups(third.session_id, 0, 10).unwrap();
+    let mut sizes: Vec<i64> = groups.iter().map(|g| g.file_size).collect();
+    sizes.sort();
+    assert_eq!(sizes, vec![16, 18]);
+
+    // A full rescan agrees
+    let full = ScanEngine::new(AppConfig {
+        full_rescan: true,
+        ..config
+    })
+    .with_db_path(db_path.to_str().unwrap())
+    .with_hash_cache(Arc::new(MemoryHashCache::new()))
+    .scan(&SilentReporter)
+    .unwrap();
+    assert!(!full.incremental);
+    assert_eq!(full.session_id, first.session_id);
+    assert_eq!(full.duplicate_groups, 2);
+    assert_eq!(full.wasted_bytes, 18 + 16);
+}
+
+#[test]
+fn test_incremental_rescan_redone_when_settings_change() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("scan_settings_changed");
+    create_test_tree(&root);
+
+    let db_dir = tempdir().unwrap();
+    let db_path = db_dir.path().join("test_settings_changed.db");
+    let config = AppConfig {
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
+        ..Default::default()
+    };
+    let first = ScanEngine::new(config.clone())
+        .with_db_path(db_path.to_str().unwrap())
+        .with_hash_cache(Arc::new(MemoryHashCache::new()))
+        .scan(&SilentReporter)
+        .unwrap();
+    assert_eq!(first.duplicate_groups, 2);
+
+    // Ignoring folder_c would leave its group standing if the buckets were kept
+    let second = ScanEngine::new(AppConfig {
+        ignore_patterns: vec!["*folder_c*".to_string()],
+        ..config
+    })
+    .with_db_path(db_path.to_str().unwrap())
+    .with_hash_cache(Arc::new(MemoryHashCache::new()))
+    .scan(&SilentReporter)
+    .unwrap();
+    assert!(!second.incremental);
+    assert_eq!(second.duplicate_groups, 1);
+}
+
+#[test]
+fn test_index_all_files_gives_directory_totals() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("scan_inventory");
+    create_test_tree(&root);
+    // No other file has this size, so it's never read
+    fs::write(root.join("folder_a").join("lonely.dat"), "lonely").unwrap();
+
+    let db_dir = tempdir().unwrap();
+    let db_path = db_dir.path().join("test_inventory.db");
+    let config = AppConfig {
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
+        ..Default::default()
+    };
+
+    // By default only files sharing a size with another are indexed
+    ScanEngine::new(config.clone())
+        .with_db_path(db_path.to_str().unwrap())
+        .with_hash_cache(Arc::new(MemoryHashCache::new()))
+        .scan(&SilentReporter)
+        .unwrap();
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+    let canonical_root = fs::canonicalize(&root).unwrap();
+    let folder_a = canonical_root.join("folder_a").to_string_lossy().into_owned();
+    assert_eq!(db.get_directory_node(&folder_a).unwrap().unwrap().total_size, 16 + 18);
+
+    let result = ScanEngine::new(AppConfig {
+        index_all_files: true,
+        ..config
+    })
+    .with_db_path(db_path.to_str().unwrap())
+    .with_hash_cache(Arc::new(MemoryHashCache::new()))
+    .scan(&SilentReporter)
+    .unwrap();
+    assert_eq!(result.duplicate_groups, 2);
+
+    let root_node = db
+        .get_directory_node(&canonical_root.to_string_lossy())
+        .unwrap()
+        .unwrap();
+    assert_eq!(root_node.file_count, 7);
+    assert_eq!(root_node.duplicate_count, 4);
+
+    let node_a = db.get_directory_node(&folder_a).unwrap().unwrap();
+    assert_eq!((node_a.file_count, node_a.total_size), (3, 16 + 18 + 6));
+    assert_eq!(node_a.duplicate_size, 18);
+    assert!((node_a.duplicated_percent() - 45.0).abs() < 1e-9);
+    let folder_c = canonical_root.join("folder_c").to_string_lossy().into_owned();
+    assert_eq!(db.get_directory_node(&folder_c).unwrap().unwrap().duplicated_percent(), 100.0);
+
+    let (unique, total) = db.get_unique_files_in_directory(&folder_a, 0, 10).unwrap();
+    assert_eq!(total, 2);
+    let names: Vec<&str> = unique.iter().map(|f| f.file_name.as_str()).collect();
+    assert_eq!(names, vec!["unique_a.txt", "lonely.dat"]);
+    assert!(unique[1].partial_hash.is_none());
+}
+
+#[test]
+fn test_scan_with_sha256_algorithm() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("scan_sha256");
+    create_test_tree(&root);
+
+    let db_dir = tempdir().unwrap();
+    let db_path = db_dir.path().join("test_sha256.db");
+
+    let config = AppConfig {
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
+        hash_algorithm: HashAlgorithm::Sha256,
+        ..Default::default()
+    };
+
+    let engine = ScanEngine::new(config)
+        .with_db_path(db_path.to_str().unwrap())
+        .with_cache_path(&cache_path(&db_path));
+    let result = engine.scan(&SilentReporter).unwrap();
+    assert_eq!(result.duplicate_groups, 2);
+
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+    let algorithm: String = db
+        .connection()
+        .query_row(
+            "SELECT hash_algorithm FROM scan_session WHERE id = ?1",
+            [result.session_id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(algorithm, "sha256");
+
+    // Full 256-bit digests are stored alongside the 64-bit content_hash
+    let groups = db.get_duplicate_groups(result.session_id, 0, 100).unwrap();
+    for group in &groups {
+        for file in db.get_files_in_group(group.id).unwrap() {
+            let digest = file.content_digest.expect("digest should be stored");
+            assert_eq!(digest.len(), 64);
+        }
+    }
+}
+
+#[test]
+fn test_scan_with_verification() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("scan_verify");
+    create_test_tree(&root);
+
+    let db_dir = tempdir().unwrap();
+    let db_path = db_dir.path().join("test_verify.db");
+
+    let config = AppConfig {
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
+        verify_duplicates: true,
+        ..Default::default()
+    };
+
+    let engine = ScanEngine::new(config)
+        .with_db_path(db_path.to_str().unwrap())
+        .with_cache_path(&cache_path(&db_path));
+    let result = engine.scan(&SilentReporter).unwrap();
+    assert_eq!(result.duplicate_groups, 2);
+    assert_eq!(result.verify_mismatches, 0);
+
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+    let groups = db.get_duplicate_groups(result.session_id, 0, 100).unwrap();
+    assert!(groups.iter().all(|g| g.verified_at.is_some()));
+}
+
+#[test]
+fn test_deletion_refused_when_surviving_copy_changed() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("scan_reverify");
+    create_test_tree(&root);
+
+    let db_dir = tempdir().unwrap();
+    let db_path = db_dir.path().join("test_reverify.db");
+
+    let config = AppConfig {
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
+        ..Default::default()
+    };
+
+    let engine = ScanEngine::new(config)
+        .with_db_path(db_path.to_str().unwrap())
+        .with_cache_path(&cache_path(&db_path));
+    let scan_result = engine.scan(&SilentReporter).unwrap();
+
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+    deletion_plan::auto_mark_duplicates(&db, scan_result.session_id, None).unwrap();
+
+    // The kept copy (first alphabetically) changes after the scan
+    fs::write(root.join("folder_a").join("shared.txt"), "shared content XYZ").unwrap();
+
+    let (deleted, errors) = deletion_plan::execute_deletion_plan(&db, false, IoLimits::default()).unwrap();
+    assert_eq!(deleted, 1, "Only the still-identical large_dup should be deleted");
+    assert_eq!(errors, 1);
+    assert!(root.join("folder_b").join("shared.txt").exists());
+
+    let result: String = db
+        .connection()
+        .query_row(
+            "SELECT dp.execution_result FROM deletion_plan dp \
+             JOIN scanned_file sf ON sf.id = dp.file_id \
+             WHERE sf.file_name = 'shared.txt'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(result, "verification_failed");
+}
+
+#[test]
+fn test_partial_hashes_persisted_for_all_candidates() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("scan_partials");
+    create_test_tree(&root);
+    // No other file has this size, so it's never read and, by default, not indexed
+    fs::write(root.join("folder_a").join("lonely.dat"), "lonely").unwrap();
+
+    let db_dir = tempdir().unwrap();
+    let db_path = db_dir.path().join("test_partials.db");
+
+    let config = AppConfig {
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
+        ..Default::default()
+    };
+
+    let engine = ScanEngine::new(config.clone())
+        .with_db_path(db_path.to_str().unwrap())
+        .with_cache_path(&cache_path(&db_path));
+    let result = engine.scan(&SilentReporter).unwrap();
+    assert_eq!(result.duplicate_groups, 2);
+    assert_eq!(result.duplicate_files, 4);
+
+    // unique_a.txt and unique_b.txt share a size, so both were partially hashed
+    // and are indexed even though neither is a duplicate
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+    let (partial, partial_len, content): (Option<i64>, Option<i64>, Option<i64>) = db
+        .connection()
+        .query_row(
+            "SELECT partial_hash, partial_hash_len, content_hash FROM scanned_file \
+             WHERE file_name = 'unique_a.txt'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    assert!(partial.is_some());
+    assert_eq!(partial_len, Some(1024));
+    assert!(content.is_none());
+
+    let lonely: i64 = db
+        .connection()
+        .query_row("SELECT COUNT(*) FROM scanned_file WHERE file_name = 'lonely.dat'", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(lonely, 0);
+
+    let stored = db.get_partial_hashes(1024, &[6, 16, 18, 4096]).unwrap();
+    assert_eq!(stored.len(), 6);
+    assert_eq!(db.get_partial_hashes(1024, &[16]).unwrap().len(), 2);
+    assert!(db.get_partial_hashes(4096, &[6, 16, 18, 4096]).unwrap().is_empty());
+
+    // A rescan with unchanged files finds the same groups from the stored hashes
+    let result2 = ScanEngine::new(config)
+        .with_db_path(db_path.to_str().unwrap())
+        .with_cache_path(&cache_path(&db_path))
+        .scan(&SilentReporter)
+        .unwrap();
+    assert_eq!(result2.duplicate_groups, 2);
+}
+
+#[test]
+fn test_engines_with_separate_hash_caches() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("scan_caches");
+    create_test_tree(&root);
+
+    let db_dir = tempdir().unwrap();
+    let config = AppConfig {
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
+        ..Default::default()
+    };
+
+    // Two engines in one process, each with its own cache, can scan side by side
+    let cache_a = Arc::new(MemoryHashCache::new());
+    let cache_b = Arc::new(MemoryHashCache::new());
+    let engine_a = ScanEngine::new(config.clone())
+        .with_db_path(db_dir.path().join("a.db").to_str().unwrap())
+        .with_hash_cache(cache_a.clone());
+    let engine_b = ScanEngine::new(config)
+        .with_db_path(db_dir.path().join("b.db").to_str().unwrap())
+        .with_hash_cache(cache_b.clone());
+
+    let result_a = engine_a.scan(&SilentReporter).unwrap();
+    let result_b = engine_b.scan(&SilentReporter).unwrap();
+    assert_eq!(result_a.duplicate_groups, 2);
+    assert_eq!(result_b.duplicate_groups, 2);
+
+    // Both caches were populated independently with the 4 fully hashed files
+    assert_eq!(cache_a.count_keys().unwrap(), 4);
+    assert_eq!(cache_b.count_keys().unwrap(), 4);
+
+    cache_a.clear_all().unwrap();
+    assert_eq!(cache_a.count_keys().unwrap(), 0);
+    assert_eq!(cache_b.count_keys().unwrap(), 4);
+}
+
+#[test]
+fn test_rescan_records_cache_hits() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("scan_cache_hits");
+    create_test_tree(&root);
+
+    let db_dir = tempdir().unwrap();
+    let db_path = db_dir.path().join("test_cache_hits.db");
+    // Full rescans, so the second scan goes through the hash cache
+    let config = AppConfig {
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
+        full_rescan: true,
+        ..Default::default()
+    };
+    let engine = ScanEngine::new(config)
+        .with_db_path(db_path.to_str().unwrap())
+        .with_hash_cache(Arc::new(MemoryHashCache::new()));
+
+    let first = engine.scan(&SilentReporter).unwrap();
+    let stats = engine.hash_cache_stats().unwrap();
+    assert_eq!(stats.entries, 4);
+    let usage = stats.last_scan.unwrap();
+    assert_eq!((usage.hits, usage.misses), (0, 4));
+    assert_eq!(first.hash_stats.cache, usage);
+    assert_eq!(first.hash_stats.partial.files_hashed, 6);
+    assert_eq!(first.hash_stats.full.files_hashed, 4);
+    // 2 x "shared content xyz" + 2 x 4 KB
+    assert_eq!(first.hash_stats.full.bytes_read, 2 * 18 + 2 * 4096);
+
+    // Nothing changed, so every full hash comes from the cache and every partial
+    // hash from the database: the rescan reads nothing
+    let second = engine.scan(&SilentReporter).unwrap();
+    assert_eq!(second.hash_stats.partial_hashes_reused, 6);
+    assert_eq!(second.hash_stats.bytes_read(), 0);
+    let usage = engine.hash_cache_stats().unwrap().last_scan.unwrap();
+    assert_eq!((usage.hits, usage.misses), (4, 0));
+    assert_eq!(usage.hit_ratio(), Some(1.0));
+
+    // Deleting a duplicate leaves its entry behind until eviction
+    fs::remove_file(root.join("folder_c").join("large_dup_2.bin")).unwrap();
+    let summary = engine
+        .evict_hash_cache(&EvictionPolicy::default(), true)
+        .unwrap();
+    assert_eq!(summary.missing, 1);
+    assert_eq!(summary.evicted(), 1);
+    assert_eq!(engine.hash_cache_stats().unwrap().entries, 3);
+}
+
+#[test]
+fn test_unreadable_root_recorded_as_scan_error() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("scan_errors");
+    create_test_tree(&root);
+    let missing = tmp.path().join("not_mounted");
+
+    let db_dir = tempdir().unwrap();
+    let db_path = db_dir.path().join("test_scan_errors.db");
+    let config = AppConfig {
+        root_paths: vec![
+            root.to_string_lossy().into_owned().into(),
+            missing.to_string_lossy().into_owned().into(),
+        ],
+        ..Default::default()
+    };
+    let engine = ScanEngine::new(config)
+        .with_db_path(db_path.to_str().unwrap())
+        .with_cache_path(&cache_path(&db_path));
+
+    // The missing root is reported; the readable one is still scanned
+    let result = engine.scan(&SilentReporter).unwrap();
+    assert_eq!(result.duplicate_groups, 2);
+    assert_eq!(result.scan_errors, 1);
+    // Only the root that exists is recorded on the session
+    let roots = engine.session_roots(result.session_id).unwrap();
+    assert_eq!(roots.len(), 1);
+    assert_eq!(roots[0].root_path, root.to_string_lossy());
+    let (errors, total) = engine.scan_errors(result.session_id, 0, 10).unwrap();
+    assert_eq!(total, 1);
+    assert_eq!(errors[0].path, missing.to_string_lossy());
+    assert_eq!(errors[0].phase, "walk");
+    assert_eq!(errors[0].error_kind, "NotFound");
+
+    // Once the root exists, the rescan clears the session's errors
+    fs::create_dir(&missing).unwrap();
+    let rescan = engine.scan(&SilentReporter).unwrap();
+    assert_eq!(rescan.session_id, result.session_id);
+    assert_eq!(rescan.scan_errors, 0);
+    assert!(engine.scan_error_summary(result.session_id).unwrap().is_empty());
+}
+
+#[test]
+fn test_hard_links_reported_as_already_deduplicated() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("hard_links");
+    fs::create_dir_all(&root).unwrap();
+    let content = "linked content".repeat(100);
+    fs::write(root.join("original.txt"), &content).unwrap();
+    fs::hard_link(root.join("original.txt"), root.join("link.txt")).unwrap();
+    fs::write(root.join("copy.txt"), &content).unwrap();
+
+    let db_dir = tempdir().unwrap();
+    let db_path = db_dir.path().join("test_hard_links.db");
+    let config = AppConfig {
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
+        ..Default::default()
+    };
+    let engine = ScanEngine::new(config)
+        .with_db_path(db_path.to_str().unwrap())
+        .with_cache_path(&cache_path(&db_path));
+
+    // Only the real copy counts as wasted; the second name shares its data
+    let result = engine.scan(&SilentReporter).unwrap();
+    assert_eq!(result.duplicate_groups, 1);
+    assert_eq!(result.wasted_bytes, content.len() as u64);
+    assert_eq!(result.hard_links, 1);
+    assert_eq!(result.hard_link_bytes, content.len() as u64);
+
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+    let groups = db.get_duplicate_groups(result.session_id, 0, 10).unwrap();
+    let members = db.get_files_in_group(groups[0].id).unwrap();
+    assert_eq!(members.len(), 2);
+
+    // The name not in the group is recorded as a link to the one that is
+    let (links, total) = engine.file_links(result.session_id, 0, 10).unwrap();
+    assert_eq!(total, 1);
+    assert_eq!(links[0].kind, "hardlink");
+    let names: Vec<&str> = members.iter().map(|m| m.file_name.as_str()).collect();
+    let link_name = Path::new(&links[0].path).file_name().unwrap().to_str().unwrap();
+    assert!(names.contains(&"copy.txt"));
+    assert!(["original.txt", "link.txt"].contains(&link_name));
+    assert!(!names.contains(&link_name));
+}
+
+#[test]
+fn test_rescan_purges_vanished_files() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("scan_vanished");
+    create_test_tree(&root);
+
+    let db_dir = tempdir().unwrap();
+    let db_path = db_dir.path().join("test_vanished.db");
+    let engine = ScanEngine::new(AppConfig {
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
+        ..Default::default()
+    })
+    .with_db_path(db_path.to_str().unwrap())
+    .with_hash_cache(Arc::new(MemoryHashCache::new()));
+
+    let first = engine.scan(&SilentReporter).unwrap();
+    assert_eq!(
+        first.index_changes,
+        IndexChanges { added: 6, changed: 0, removed: 0 }
+    );
+
+    // One large copy goes, a third shared copy arrives and another is touched
+    let gone = fs::canonicalize(root.join("folder_c").join("large_dup_2.bin")).unwrap();
+    fs::remove_file(&gone).unwrap();
+    fs::write(root.join("folder_c").join("shared.txt"), "shared content xyz").unwrap();
+    fs::File::options()
+        .write(true)
+        .open(root.join("folder_a").join("shared.txt"))
+        .unwrap()
+        .set_modified(UNIX_EPOCH + Duration::from_secs(1_000_000))
+        .unwrap();
+
+    let second = engine.scan(&SilentReporter).unwrap();
+    assert_eq!(
+        second.index_changes,
+        IndexChanges { added: 1, changed: 1, removed: 1 }
+    );
+    assert_eq!(second.duplicate_groups, 1, "the large pair is dissolved");
+    assert_eq!(second.wasted_bytes, 2 * 18);
+
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+    let gone = gone.to_string_lossy().into_owned();
+    assert!(db.get_file_states_by_path(&[gone]).unwrap().is_empty());
+    let groups = db.get_duplicate_groups(second.session_id, 0, 10).unwrap();
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].file_count, 3);
+
+    // Nothing changed since
+    let third = engine.scan(&SilentReporter).unwrap();
+    assert_eq!(third.index_changes, IndexChanges::default());
+}
+
+/// Cancels the scan as hashing starts, leaving the walk checkpointed.
+struct CancelOnHash(Arc<AtomicBool>);
+
+impl ProgressReporter for CancelOnHash {
+    fn on_hash_start(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn test_cancelled_scan_resumes_from_checkpoint() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("scan_resume");
+    create_test_tree(&root);
+    // Directories modified just before a walk aren't listed for reuse
+    let past = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    for dir in ["", "folder_a", "folder_b", "folder_c"] {
+        fs::File::open(root.join(dir)).unwrap().set_modified(past).unwrap();
+    }
+
+    let db_dir = tempdir().unwrap();
+    let db_path = db_dir.path().join("test_resume.db");
+    let engine = ScanEngine::new(AppConfig {
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
+        ..Default::default()
+    })
+    .with_db_path(db_path.to_str().unwrap())
+    .with_hash_cache(Arc::new(MemoryHashCache::new()));
+
+    let cancel = CancelOnHash(engine.cancel_token());
+    assert!(matches!(engine.scan(&cancel), Err(Error::Cancelled)));
+    let resumable = engine.resumable_sessions().unwrap();
+    assert_eq!(resumable.len(), 1);
+    assert_eq!(resumable[0].status, "cancelled");
+    let session_id = resumable[0].id;
+
+    // Checkpoint the large pair as hashed under a made-up content hash, so taking
+    // it from the checkpoint shows in the result
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+    assert!(!db.get_dir_listings(session_id).unwrap().is_empty());
+    let large: Vec<_> = ["large_dup_1.bin", "large_dup_2.bin"]
+        .iter()
+        .map(|name| root.join("folder_c").join(name))
+        .collect();
+    let partial_hashes = large
+        .iter()
+        .map(|path| {
+            let modified = fs::metadata(path)
+                .unwrap()
+                .modified()
+                .unwrap()
+                .duration_since(UNIX_EPOCH)
+                .unwrap();
+            let partial = PartialHash { file_size: 4096, modified, hash: 7 };
+            (fs::canonicalize(path).unwrap(), partial)
+        })
+        .collect();
+    db.insert_checkpoint_buckets(
+        session_id,
+        &[CompletedBucket {
+            file_size: 4096,
+            partial_hashes,
+            duplicates: vec![(ContentHash::from_u64(42), large)],
+        }],
+    )
+    .unwrap();
+
+    let resumed = engine.resume_scan(session_id, &SilentReporter).unwrap();
+    assert_eq!(resumed.session_id, session_id);
+    assert_eq!(resumed.resumed_files, 2);
+    assert_eq!(resumed.duplicate_groups, 2);
+    assert_eq!(resumed.reused_listings, 4);
+    let groups = db.get_duplicate_groups(session_id, 0, 10).unwrap();
+    assert!(groups.iter().any(|g| g.content_hash == 42 && g.file_size == 4096));
+    assert!(db.get_checkpoint_buckets(session_id).unwrap().is_empty());
+    assert_eq!(db.get_session(session_id).unwrap().unwrap().status, "completed");
+
+    // A completed session can't be resumed
+    assert!(matches!(
+        engine.resume_scan(session_id, &SilentReporter),
+        Err(Error::Resume(_))
+    ));
+}
+
+#[test]
+fn test_orphaned_running_session_is_aborted() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("scan_orphaned");
+    create_test_tree(&root);
+
+    let db_dir = tempdir().unwrap();
+    let db_path = db_dir.path().join("test_orphaned.db");
+    let engine = ScanEngine::new(AppConfig {
+        root_paths: vec![root.to_string_lossy().into_owned().into()],
+        ..Default::default()
+    })
+    .with_db_path(db_path.to_str().unwrap())
+    .with_hash_cache(Arc::new(MemoryHashCache::new()));
+
+    // A session left running by a scan that died without a trace
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+    let orphan = db
+        .create_scan_session(&[root.to_string_lossy().into_owned()])
+        .unwrap();
+
+    assert_eq!(engine.abort_orphaned_sessions().unwrap(), vec![orphan]);
+    assert_eq!(db.get_session(orphan).unwrap().unwrap().status, "aborted");
+    assert!(engine.abort_orphaned_sessions().unwrap().is_empty());
+
+    // Its settings weren't recorded, so it can't be resumed; a new scan supersedes it
+    assert!(matches!(
+        engine.resume_scan(orphan, &SilentReporter),
+        Err(Error::Resume(_))
+    ));
+    let result = engine.scan(&SilentReporter).unwrap();
+    assert_ne!(result.session_id, orphan);
+    assert!(db.get_session(orphan).unwrap().is_none());
+    assert!(engine.resumable_sessions().unwrap().is_empty());
+}
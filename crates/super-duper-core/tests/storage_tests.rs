@@ -1,4 +1,5 @@
 use super_duper_core::storage::models::*;
+use super_duper_core::scanner::ScanFilter;
 use super_duper_core::storage::Database;
 
 fn make_test_scanned_file(path: &str, size: i64, hash: i64, session_id: i64) -> ScannedFile {
@@ -13,8 +14,11 @@ fn make_test_scanned_file(path: &str, size: i64, hash: i64, session_id: i64) ->
         drive_letter: String::new(),
         file_size: size,
         last_modified: 1700000000,
+        last_modified_nanos: 0,
         partial_hash: None,
+        partial_hash_len: None,
         content_hash: Some(hash),
+        content_digest: None,
         last_seen_session_id: Some(session_id),
         marked_deleted: false,
     }
@@ -43,6 +47,23 @@ fn test_create_and_complete_scan_session() {
     assert_eq!(row.2, 100_000);
 }
 
+#[test]
+fn test_session_records_scan_filter() {
+    let db = Database::open_in_memory().unwrap();
+    let session_id = db.create_scan_session(&["path/a".to_string()]).unwrap();
+    let filter = ScanFilter {
+        min_size: 4096,
+        include_extensions: vec!["jpg".to_string()],
+        ..Default::default()
+    };
+    db.set_session_scan_filter(session_id, &filter).unwrap();
+
+    let (sessions, _) = db.list_sessions(0, 10).unwrap();
+    let recorded: ScanFilter =
+        serde_json::from_str(sessions[0].0.scan_filter.as_deref().unwrap()).unwrap();
+    assert_eq!(recorded, filter);
+}
+
 #[test]
 fn test_insert_and_query_scanned_files() {
     let db = Database::open_in_memory().unwrap();
@@ -157,6 +178,40 @@ fn test_mark_and_unmark_file_for_deletion() {
     assert_eq!(plan.len(), 0);
 }
 
+#[test]
+fn test_protected_file_cannot_be_marked() {
+    let db = Database::open_in_memory().unwrap();
+    let session_id = db.create_scan_session(&["root".to_string()]).unwrap();
+    let files = vec![
+        make_test_scanned_file("/masters/photo.jpg", 100, 1, session_id),
+        make_test_scanned_file("/masters-copy/photo.jpg", 100, 1, session_id),
+    ];
+    db.insert_scanned_files(&files).unwrap();
+    let id_of = |path: &str| -> i64 {
+        db.connection()
+            .query_row(
+                "SELECT id FROM scanned_file WHERE canonical_path = ?1",
+                [path],
+                |row| row.get(0),
+            )
+            .unwrap()
+    };
+
+    db.replace_protected_paths(&["/masters".to_string()]).unwrap();
+    assert_eq!(db.get_protected_paths().unwrap(), ["/masters"]);
+    match db.mark_file_for_deletion(id_of("/masters/photo.jpg"), None) {
+        Err(super_duper_core::Error::Protected { path, protected_by }) => {
+            assert_eq!(path, "/masters/photo.jpg");
+            assert_eq!(protected_by, "/masters");
+        }
+        other => panic!("expected Error::Protected, got {:?}", other),
+    }
+    // Matched by path component, not string prefix
+    db.mark_file_for_deletion(id_of("/masters-copy/photo.jpg"), None)
+        .unwrap();
+    assert_eq!(db.get_deletion_plan().unwrap().len(), 1);
+}
+
 #[test]
 fn test_get_deletion_plan_summary() {
     let db = Database::open_in_memory().unwrap();
@@ -216,6 +271,30 @@ fn test_truncate_all() {
     assert_eq!(session_count, 0);
 }
 
+#[test]
+fn test_schema_migration_keeps_protected_paths() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("old.db");
+    let db_path = db_path.to_str().unwrap();
+    {
+        let db = Database::open(db_path).unwrap();
+        let session_id = db.create_scan_session(&["root".to_string()]).unwrap();
+        db.insert_scanned_files(&[make_test_scanned_file("/t.txt", 100, 1, session_id)])
+            .unwrap();
+        db.add_protected_paths(&["/keep".to_string()]).unwrap();
+        // As if written by an older release
+        db.connection().execute_batch("PRAGMA user_version = 1;").unwrap();
+    }
+
+    let db = Database::open(db_path).unwrap();
+    let files: i64 = db
+        .connection()
+        .query_row("SELECT COUNT(*) FROM scanned_file", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(files, 0);
+    assert_eq!(db.get_protected_paths().unwrap(), vec!["/keep".to_string()]);
+}
+
 #[test]
 fn test_insert_directory_node_and_get_children() {
     let db = Database::open_in_memory().unwrap();
@@ -265,3 +344,66 @@ fn test_insert_and_query_directory_similarity() {
     let pairs = db.get_similar_directories(0.9, 0, 10).unwrap();
     assert_eq!(pairs.len(), 0);
 }
+
+#[test]
+fn test_replace_and_query_scan_errors() {
+    use std::io;
+    use super_duper_core::{ScanError, ScanPhase};
+
+    let db = Database::open_in_memory().unwrap();
+    let session_id = db.create_scan_session(&["root".to_string()]).unwrap();
+
+    let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+    let vanished = io::Error::from(io::ErrorKind::NotFound);
+    let errors = vec![
+        ScanError::new("/root/private", ScanPhase::Walk, &denied),
+        ScanError::new("/root/locked.bin", ScanPhase::Partial, &denied),
+        ScanError::new("/root/tmp.bin", ScanPhase::Full, &vanished),
+        ScanError::new("/root/other", ScanPhase::Walk, &denied),
+    ];
+    assert_eq!(db.replace_scan_errors(session_id, &errors).unwrap(), 4);
+
+    let (page, total) = db.get_scan_errors(session_id, 1, 2).unwrap();
+    assert_eq!(total, 4);
+    assert_eq!(page.len(), 2);
+    assert_eq!(page[0].path, "/root/locked.bin");
+    assert_eq!(page[0].phase, "partial");
+    assert_eq!(page[0].error_kind, "PermissionDenied");
+
+    let summary = db.get_scan_error_summary(session_id).unwrap();
+    assert_eq!(summary[0], ("walk".to_string(), "PermissionDenied".to_string(), 2));
+    assert_eq!(summary.len(), 3);
+
+    // A rescan of the same session replaces the previous errors
+    db.replace_scan_errors(session_id, &errors[..1]).unwrap();
+    assert_eq!(db.get_scan_errors(session_id, 0, 100).unwrap().1, 1);
+
+    db.delete_session(session_id).unwrap();
+    assert_eq!(db.get_scan_errors(session_id, 0, 100).unwrap().1, 0);
+}
+
+#[test]
+fn test_replace_and_get_dir_listings() {
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use super_duper_core::scanner::{DirListing, EntryKind};
+
+    let db = Database::open_in_memory().unwrap();
+    let session_id = db.create_scan_session(&["root".to_string()]).unwrap();
+
+    let listing = DirListing {
+        path: PathBuf::from("/root/photos"),
+        modified: Duration::new(1700000000, 123),
+        entries: vec![
+            ("a.jpg".to_string(), EntryKind::File),
+            ("raw".to_string(), EntryKind::Dir),
+            ("latest".to_string(), EntryKind::Symlink),
+        ],
+    };
+    assert_eq!(db.replace_dir_listings(session_id, std::slice::from_ref(&listing)).unwrap(), 1);
+    assert_eq!(db.get_dir_listings(session_id).unwrap(), vec![listing]);
+
+    // The next walk's listings replace these
+    db.replace_dir_listings(session_id, &[]).unwrap();
+    assert!(db.get_dir_listings(session_id).unwrap().is_empty());
+}
@@ -1,10 +1,19 @@
 use crate::callbacks::FfiProgressBridge;
-use crate::error::{map_core_error, set_last_error};
-use crate::handle::{allocate_handle, destroy_handle, with_handle, EngineState};
+use crate::error::{map_core_error, sd_free_string, set_last_error};
+use crate::handle::{
+    allocate_handle, destroy_handle, register_running_scan, unregister_running_scan, with_handle,
+    with_running_scan, EngineState, ScanControls,
+};
 use crate::types::*;
 use std::ffi::c_char;
 use std::sync::atomic::Ordering;
-use super_duper_core::{AppConfig, ScanEngine, SilentReporter};
+use std::time::Duration;
+use chrono::DateTime;
+use super_duper_core::config::Severity;
+use super_duper_core::hasher::{EvictionPolicy, HashAlgorithm, HashStats, IoLimits};
+use super_duper_core::checkpoint;
+use super_duper_core::scanner::ScanFilter;
+use super_duper_core::{AppConfig, IndexChanges, ProgressReporter, ScanEngine, SilentReporter};
 use super_duper_core::storage::Database;
 
 /// Create a new engine instance. Returns a handle (u64) or 0 on failure.
@@ -18,12 +27,7 @@ pub unsafe extern "C" fn sd_engine_create(db_path: *const c_char) -> u64 {
         None => "super_duper.db".to_string(),
     };
 
-    let config = AppConfig {
-        root_paths: Vec::new(),
-        ignore_patterns: Vec::new(),
-    };
-
-    let engine = ScanEngine::new(config).with_db_path(&db_path_str);
+    let engine = ScanEngine::new(AppConfig::default()).with_db_path(&db_path_str);
     let cancel_token = engine.cancel_token();
 
     let db = match Database::open(&db_path_str) {
@@ -34,6 +38,10 @@ pub unsafe extern "C" fn sd_engine_create(db_path: *const c_char) -> u64 {
         }
     };
 
+    // Sessions left running by a crash become resumable. Not being able to mark
+    // them doesn't stop the engine from working.
+    let _ = checkpoint::abort_orphaned_sessions(&db, &db_path_str);
+
     // Initialise active_session_id from the most recent completed session in the DB.
     let active_session_id = db.get_latest_session_id().unwrap_or(None);
 
@@ -43,10 +51,18 @@ pub unsafe extern "C" fn sd_engine_create(db_path: *const c_char) -> u64 {
         db_path: db_path_str,
         root_paths: Vec::new(),
         ignore_patterns: Vec::new(),
+        hash_algorithm: HashAlgorithm::default(),
+        hash_cache_path: None,
+        io_limits: IoLimits::default(),
+        scan_filter: ScanFilter::default(),
+        protected_paths: Vec::new(),
+        index_all_files: false,
         is_scanning: false,
         cancel_token,
         progress_bridge: None,
         active_session_id,
+        last_hash_stats: HashStats::default(),
+        last_index_changes: IndexChanges::default(),
     };
 
     allocate_handle(state)
@@ -92,12 +108,7 @@ pub unsafe extern "C" fn sd_engine_set_scan_paths(
 
     let result = with_handle(handle, |state| {
         state.root_paths = root_paths;
-        let config = AppConfig {
-            root_paths: state.root_paths.clone(),
-            ignore_patterns: state.ignore_patterns.clone(),
-        };
-        state.engine = ScanEngine::new(config).with_db_path(&state.db_path);
-        state.cancel_token = state.engine.cancel_token();
+        state.rebuild_engine();
         SdResultCode::Ok
     });
 
@@ -133,18 +144,342 @@ pub unsafe extern "C" fn sd_engine_set_ignore_patterns(
 
     let result = with_handle(handle, |state| {
         state.ignore_patterns = ignore_patterns;
-        let config = AppConfig {
-            root_paths: state.root_paths.clone(),
-            ignore_patterns: state.ignore_patterns.clone(),
+        state.rebuild_engine();
+        SdResultCode::Ok
+    });
+
+    result.unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Set the content hash algorithm used for full-file hashing.
+/// Accepts "xxh64", "xxh3-128", "blake3" or "sha256" (case-insensitive; "xxHash64" and
+/// "SHA-256" are also accepted).
+///
+/// # Safety
+/// `algorithm` must be a valid null-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn sd_engine_set_hash_algorithm(
+    handle: u64,
+    algorithm: *const c_char,
+) -> SdResultCode {
+    let name = match c_string_to_rust(algorithm) {
+        Some(s) => s,
+        None => {
+            set_last_error("algorithm is null".to_string());
+            return SdResultCode::InvalidArgument;
+        }
+    };
+    let hash_algorithm = match name.parse::<HashAlgorithm>() {
+        Ok(a) => a,
+        Err(e) => {
+            set_last_error(e);
+            return SdResultCode::InvalidArgument;
+        }
+    };
+
+    let result = with_handle(handle, |state| {
+        state.hash_algorithm = hash_algorithm;
+        state.rebuild_engine();
+        SdResultCode::Ok
+    });
+
+    result.unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Set the RocksDB hash cache location for this engine. Engines with different
+/// paths keep independent caches; the cache is opened on the next scan.
+///
+/// # Safety
+/// `path` must be a valid null-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn sd_engine_set_hash_cache_path(
+    handle: u64,
+    path: *const c_char,
+) -> SdResultCode {
+    let path = match c_string_to_rust(path) {
+        Some(s) => s,
+        None => {
+            set_last_error("path is null".to_string());
+            return SdResultCode::InvalidArgument;
+        }
+    };
+
+    let result = with_handle(handle, |state| {
+        if state.is_scanning {
+            return SdResultCode::ScanInProgress;
+        }
+        state.hash_cache_path = Some(path);
+        state.rebuild_engine();
+        SdResultCode::Ok
+    });
+
+    result.unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Limit hash-phase reads to `max_reads_per_device` files at once on each device
+/// and `max_bytes_per_second` overall. 0 leaves a limit off.
+#[no_mangle]
+pub extern "C" fn sd_engine_set_io_limits(
+    handle: u64,
+    max_reads_per_device: u32,
+    max_bytes_per_second: u64,
+) -> SdResultCode {
+    let result = with_handle(handle, |state| {
+        if state.is_scanning {
+            return SdResultCode::ScanInProgress;
+        }
+        state.io_limits = IoLimits {
+            max_concurrent_reads_per_device: max_reads_per_device as usize,
+            max_bytes_per_second,
+        };
+        state.rebuild_engine();
+        SdResultCode::Ok
+    });
+
+    result.unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Index every scanned file (`enabled` = 1), not only those sharing a size with
+/// another file, so directory sizes and duplicated shares cover the whole tree.
+#[no_mangle]
+pub extern "C" fn sd_engine_set_index_all_files(handle: u64, enabled: u8) -> SdResultCode {
+    let result = with_handle(handle, |state| {
+        if state.is_scanning {
+            return SdResultCode::ScanInProgress;
+        }
+        state.index_all_files = enabled != 0;
+        state.rebuild_engine();
+        SdResultCode::Ok
+    });
+
+    result.unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Set which files scans consider by size, modification time and attributes.
+/// The extension and include-pattern lists are kept.
+///
+/// # Safety
+/// `filter` must point to a valid `SdScanFilter`.
+#[no_mangle]
+pub unsafe extern "C" fn sd_engine_set_scan_filter(
+    handle: u64,
+    filter: *const SdScanFilter,
+) -> SdResultCode {
+    if filter.is_null() {
+        set_last_error("filter is null".to_string());
+        return SdResultCode::InvalidArgument;
+    }
+    let filter = &*filter;
+    let time = |secs: i64| (secs != 0).then(|| DateTime::from_timestamp(secs, 0)).flatten();
+
+    let result = with_handle(handle, |state| {
+        if state.is_scanning {
+            return SdResultCode::ScanInProgress;
+        }
+        state.scan_filter = ScanFilter {
+            min_size: filter.min_size,
+            max_size: (filter.max_size != 0).then_some(filter.max_size),
+            modified_after: time(filter.modified_after),
+            modified_before: time(filter.modified_before),
+            include_hidden: filter.include_hidden != 0,
+            include_system: filter.include_system != 0,
+            ..std::mem::take(&mut state.scan_filter)
+        };
+        state.rebuild_engine();
+        SdResultCode::Ok
+    });
+
+    result.unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Limit scans to files with one of `include` extensions (any, if `include_count`
+/// is 0) and none of `exclude`. Case-insensitive; a leading dot is optional.
+///
+/// # Safety
+/// `include` and `exclude` must be valid arrays of `include_count` and
+/// `exclude_count` null-terminated C strings.
+#[no_mangle]
+pub unsafe extern "C" fn sd_engine_set_filter_extensions(
+    handle: u64,
+    include: *const *const c_char,
+    include_count: u32,
+    exclude: *const *const c_char,
+    exclude_count: u32,
+) -> SdResultCode {
+    let Some(include) = c_string_array(include, include_count, "include") else {
+        return SdResultCode::InvalidArgument;
+    };
+    let Some(exclude) = c_string_array(exclude, exclude_count, "exclude") else {
+        return SdResultCode::InvalidArgument;
+    };
+
+    let result = with_handle(handle, |state| {
+        if state.is_scanning {
+            return SdResultCode::ScanInProgress;
+        }
+        state.scan_filter.include_extensions = include;
+        state.scan_filter.exclude_extensions = exclude;
+        state.rebuild_engine();
+        SdResultCode::Ok
+    });
+
+    result.unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Limit scans to files whose path matches one of `patterns` (any file, if
+/// `count` is 0).
+///
+/// # Safety
+/// `patterns` must be a valid array of `count` null-terminated C strings.
+#[no_mangle]
+pub unsafe extern "C" fn sd_engine_set_include_patterns(
+    handle: u64,
+    patterns: *const *const c_char,
+    count: u32,
+) -> SdResultCode {
+    let Some(patterns) = c_string_array(patterns, count, "patterns") else {
+        return SdResultCode::InvalidArgument;
+    };
+
+    let result = with_handle(handle, |state| {
+        if state.is_scanning {
+            return SdResultCode::ScanInProgress;
+        }
+        state.scan_filter.include_patterns = patterns;
+        state.rebuild_engine();
+        SdResultCode::Ok
+    });
+
+    result.unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Protect `paths`: files at or below them can't be marked for deletion, are
+/// kept by auto-mark and are skipped when the plan is executed. Replaces the
+/// previous list.
+///
+/// # Safety
+/// `paths` must be a valid array of `count` null-terminated C strings.
+#[no_mangle]
+pub unsafe extern "C" fn sd_engine_set_protected_paths(
+    handle: u64,
+    paths: *const *const c_char,
+    count: u32,
+) -> SdResultCode {
+    let Some(paths) = c_string_array(paths, count, "paths") else {
+        return SdResultCode::InvalidArgument;
+    };
+
+    let result = with_handle(handle, |state| {
+        if state.is_scanning {
+            return SdResultCode::ScanInProgress;
+        }
+        state.protected_paths = paths;
+        state.rebuild_engine();
+        let db = match &state.db {
+            Some(db) => db,
+            None => {
+                set_last_error("No database open".to_string());
+                return SdResultCode::DatabaseError;
+            }
+        };
+        match db.replace_protected_paths(&state.engine.config().all_protected_paths()) {
+            Ok(()) => SdResultCode::Ok,
+            Err(e) => {
+                set_last_error(format!("Failed to store protected paths: {}", e));
+                SdResultCode::DatabaseError
+            }
+        }
+    });
+
+    result.unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Check the engine's configuration for invalid globs, missing or unreadable
+/// roots, overlapping roots and other problems, before starting a scan.
+///
+/// # Safety
+/// `out_page` must be a valid pointer. The returned page must be freed with `sd_free_config_issue_page`.
+#[no_mangle]
+pub unsafe extern "C" fn sd_engine_validate_config(
+    handle: u64,
+    out_page: *mut SdConfigIssuePage,
+) -> SdResultCode {
+    if out_page.is_null() {
+        set_last_error("out_page is null".to_string());
+        return SdResultCode::InvalidArgument;
+    }
+
+    let result = with_handle(handle, |state| {
+        let issues = state.engine.config().validate();
+        let c_issues: Vec<SdConfigIssue> = issues
+            .iter()
+            .map(|issue| SdConfigIssue {
+                is_error: (issue.severity == Severity::Error) as u8,
+                kind: rust_string_to_c(issue.kind.as_str()),
+                key: rust_string_to_c(&issue.key),
+                message: rust_string_to_c(&issue.message),
+            })
+            .collect();
+        let count = c_issues.len() as u32;
+        let error_count = c_issues.iter().filter(|issue| issue.is_error != 0).count() as u32;
+        let issues = if c_issues.is_empty() {
+            std::ptr::null_mut()
+        } else {
+            Box::into_raw(c_issues.into_boxed_slice()) as *mut SdConfigIssue
+        };
+
+        *out_page = SdConfigIssuePage {
+            issues,
+            count,
+            error_count,
         };
-        state.engine = ScanEngine::new(config).with_db_path(&state.db_path);
-        state.cancel_token = state.engine.cancel_token();
         SdResultCode::Ok
     });
 
     result.unwrap_or(SdResultCode::InvalidHandle)
 }
 
+/// Free a config issue page allocated by `sd_engine_validate_config`.
+///
+/// # Safety
+/// `page` must have been returned by `sd_engine_validate_config`.
+#[no_mangle]
+pub unsafe extern "C" fn sd_free_config_issue_page(page: *mut SdConfigIssuePage) {
+    if page.is_null() {
+        return;
+    }
+    let page = &*page;
+    if !page.issues.is_null() && page.count > 0 {
+        let slice = std::slice::from_raw_parts_mut(page.issues, page.count as usize);
+        for issue in slice.iter() {
+            sd_free_string(issue.kind);
+            sd_free_string(issue.key);
+            sd_free_string(issue.message);
+        }
+        drop(Box::from_raw(slice as *mut [SdConfigIssue]));
+    }
+}
+
+/// Read `count` C strings from `array`, setting the last error and returning
+/// `None` if any is invalid. `array` may be null when `count` is 0.
+unsafe fn c_string_array(array: *const *const c_char, count: u32, name: &str) -> Option<Vec<String>> {
+    if array.is_null() && count > 0 {
+        set_last_error(format!("{} is null", name));
+        return None;
+    }
+    let mut strings = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        match c_string_to_rust(*array.add(i as usize)) {
+            Some(s) => strings.push(s),
+            None => {
+                set_last_error(format!("Invalid {} entry at index {}", name, i));
+                return None;
+            }
+        }
+    }
+    Some(strings)
+}
+
 /// Set a progress callback for scan operations.
 #[no_mangle]
 pub extern "C" fn sd_set_progress_callback(
@@ -173,6 +508,19 @@ pub extern "C" fn sd_clear_progress_callback(handle: u64) -> SdResultCode {
 /// Start a synchronous scan. Blocks until complete.
 #[no_mangle]
 pub extern "C" fn sd_scan_start(handle: u64) -> SdResultCode {
+    run_scan(handle, None)
+}
+
+/// Resume a cancelled or aborted scan session (blocking), skipping directories
+/// it listed and size buckets it hashed that haven't changed since. The engine's
+/// scan paths and settings must match the session's. Returns `InvalidArgument`
+/// if the session can't be resumed.
+#[no_mangle]
+pub extern "C" fn sd_scan_resume_session(handle: u64, session_id: i64) -> SdResultCode {
+    run_scan(handle, Some(session_id))
+}
+
+fn run_scan(handle: u64, resume: Option<i64>) -> SdResultCode {
     let result = with_handle(handle, |state| {
         if state.is_scanning {
             set_last_error("Scan already in progress".to_string());
@@ -180,16 +528,29 @@ pub extern "C" fn sd_scan_start(handle: u64) -> SdResultCode {
         }
 
         state.is_scanning = true;
-        let scan_result = if let Some(ref bridge) = state.progress_bridge {
-            state.engine.scan(bridge)
-        } else {
-            state.engine.scan(&SilentReporter)
+        register_running_scan(
+            handle,
+            ScanControls {
+                cancel_token: state.engine.cancel_token(),
+                pause_token: state.engine.pause_token(),
+            },
+        );
+        let reporter: &dyn ProgressReporter = match state.progress_bridge {
+            Some(ref bridge) => bridge,
+            None => &SilentReporter,
+        };
+        let scan_result = match resume {
+            Some(session_id) => state.engine.resume_scan(session_id, reporter),
+            None => state.engine.scan(reporter),
         };
+        unregister_running_scan(handle);
         state.is_scanning = false;
 
         match scan_result {
             Ok(result) => {
                 state.active_session_id = Some(result.session_id);
+                state.last_hash_stats = result.hash_stats;
+                state.last_index_changes = result.index_changes;
                 SdResultCode::Ok
             }
             Err(e) => map_core_error(e),
@@ -199,9 +560,70 @@ pub extern "C" fn sd_scan_start(handle: u64) -> SdResultCode {
     result.unwrap_or(SdResultCode::InvalidHandle)
 }
 
-/// Request cancellation of the current scan.
+/// Report files hashed, files eliminated and bytes read per tier, plus hash cache
+/// hits and misses, for the last successful scan on this handle. All zero until
+/// a scan completes.
+///
+/// # Safety
+/// `out_stats` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn sd_scan_hash_stats(handle: u64, out_stats: *mut SdHashStats) -> SdResultCode {
+    if out_stats.is_null() {
+        set_last_error("out_stats is null".to_string());
+        return SdResultCode::InvalidArgument;
+    }
+
+    let result = with_handle(handle, |state| {
+        let stats = &state.last_hash_stats;
+        *out_stats = SdHashStats {
+            partial: SdHashTierStats::from(&stats.partial),
+            sample: SdHashTierStats::from(&stats.sample),
+            full: SdHashTierStats::from(&stats.full),
+            partial_hashes_reused: stats.partial_hashes_reused as u64,
+            cache_hits: stats.cache.hits,
+            cache_misses: stats.cache.misses,
+        };
+        SdResultCode::Ok
+    });
+
+    result.unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Report files the last successful scan on this handle added to, changed in or
+/// removed from the file index. All zero until a scan completes.
+///
+/// # Safety
+/// `out_changes` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn sd_scan_index_changes(handle: u64, out_changes: *mut SdIndexChanges) -> SdResultCode {
+    if out_changes.is_null() {
+        set_last_error("out_changes is null".to_string());
+        return SdResultCode::InvalidArgument;
+    }
+
+    let result = with_handle(handle, |state| {
+        let changes = &state.last_index_changes;
+        *out_changes = SdIndexChanges {
+            added: changes.added as u64,
+            changed: changes.changed as u64,
+            removed: changes.removed as u64,
+        };
+        SdResultCode::Ok
+    });
+
+    result.unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Request cancellation of the current scan. Also ends a paused scan.
 #[no_mangle]
 pub extern "C" fn sd_scan_cancel(handle: u64) -> SdResultCode {
+    let cancelled = with_running_scan(handle, |scan| {
+        scan.cancel_token.store(true, Ordering::Relaxed);
+    });
+    if cancelled.is_some() {
+        return SdResultCode::Ok;
+    }
+
     let result = with_handle(handle, |state| {
         state.cancel_token.store(true, Ordering::Relaxed);
         SdResultCode::Ok
@@ -210,13 +632,41 @@ pub extern "C" fn sd_scan_cancel(handle: u64) -> SdResultCode {
     result.unwrap_or(SdResultCode::InvalidHandle)
 }
 
+/// Pause the current scan: its threads stop between files, keeping everything
+/// found so far, until `sd_scan_resume` or `sd_scan_cancel`. The progress
+/// callback reports phase 4 with current = 1 once they've stopped. Does nothing
+/// if no scan is running.
+#[no_mangle]
+pub extern "C" fn sd_scan_pause(handle: u64) -> SdResultCode {
+    if with_running_scan(handle, |scan| scan.pause_token.pause()).is_some() {
+        return SdResultCode::Ok;
+    }
+    with_handle(handle, |_| SdResultCode::Ok).unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Resume a paused scan. The progress callback reports phase 4 with
+/// current = 0 as it carries on. Does nothing if the scan isn't paused.
+#[no_mangle]
+pub extern "C" fn sd_scan_resume(handle: u64) -> SdResultCode {
+    if with_running_scan(handle, |scan| scan.pause_token.resume()).is_some() {
+        return SdResultCode::Ok;
+    }
+    with_handle(handle, |_| SdResultCode::Ok).unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Check if the current scan is paused.
+#[no_mangle]
+pub extern "C" fn sd_scan_is_paused(handle: u64) -> bool {
+    with_running_scan(handle, |scan| scan.pause_token.is_paused()).unwrap_or(false)
+}
+
 /// Check if a scan is currently running.
 #[no_mangle]
 pub extern "C" fn sd_scan_is_running(handle: u64) -> bool {
     with_handle(handle, |state| state.is_scanning).unwrap_or(false)
 }
 
-/// Mark a file for deletion.
+/// Mark a file for deletion. Returns `Protected` if it is under a protected path.
 #[no_mangle]
 pub extern "C" fn sd_mark_file_for_deletion(handle: u64, file_id: i64) -> SdResultCode {
     let result = with_handle(handle, |state| {
@@ -229,10 +679,7 @@ pub extern "C" fn sd_mark_file_for_deletion(handle: u64, file_id: i64) -> SdResu
         };
         match db.mark_file_for_deletion(file_id, None) {
             Ok(()) => SdResultCode::Ok,
-            Err(e) => {
-                set_last_error(format!("Failed to mark file: {}", e));
-                SdResultCode::DatabaseError
-            }
+            Err(e) => map_core_error(e),
         }
     });
 
@@ -301,7 +748,8 @@ pub unsafe extern "C" fn sd_deletion_plan_summary(
     result.unwrap_or(SdResultCode::InvalidHandle)
 }
 
-/// Mark all files in a directory for deletion.
+/// Mark all files in a directory for deletion, except protected ones. Returns
+/// `Protected` if the directory itself is protected.
 ///
 /// # Safety
 /// `directory_path` must be a valid null-terminated C string.
@@ -337,7 +785,8 @@ pub unsafe extern "C" fn sd_mark_directory_for_deletion(
     result.unwrap_or(SdResultCode::InvalidHandle)
 }
 
-/// Auto-mark duplicate files for deletion (keeps first alphabetically).
+/// Auto-mark duplicate files for deletion. Keeps a protected copy if there is one,
+/// otherwise the copy under the highest-priority root, then first alphabetically.
 #[no_mangle]
 pub extern "C" fn sd_auto_mark_for_deletion(handle: u64) -> SdResultCode {
     let result = with_handle(handle, |state| {
@@ -454,17 +903,118 @@ pub extern "C" fn sd_delete_all_sessions(handle: u64) -> SdResultCode {
     result.unwrap_or(SdResultCode::InvalidHandle)
 }
 
-/// Clear all entries from the RocksDB hash cache.
+/// Clear all entries from this engine's hash cache.
 /// Does not affect the SQLite database.
 #[no_mangle]
-pub extern "C" fn sd_clear_hash_cache() -> SdResultCode {
-    match super_duper_core::hasher::cache::clear_all() {
-        Ok(()) => SdResultCode::Ok,
-        Err(e) => {
-            set_last_error(e.to_string());
-            SdResultCode::InternalError
+pub extern "C" fn sd_clear_hash_cache(handle: u64) -> SdResultCode {
+    let result = with_handle(handle, |state| {
+        if state.is_scanning {
+            return SdResultCode::ScanInProgress;
+        }
+        let cleared = state
+            .engine
+            .hash_cache()
+            .and_then(|cache| cache.clear_all().map_err(super_duper_core::Error::from));
+        match cleared {
+            Ok(()) => SdResultCode::Ok,
+            Err(e) => {
+                set_last_error(e.to_string());
+                SdResultCode::InternalError
+            }
+        }
+    });
+    result.unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Report the hash cache's entry count, disk size and last-scan hit/miss counts.
+///
+/// # Safety
+/// `out_stats` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn sd_hash_cache_stats(
+    handle: u64,
+    out_stats: *mut SdHashCacheStats,
+) -> SdResultCode {
+    if out_stats.is_null() {
+        set_last_error("out_stats is null".to_string());
+        return SdResultCode::InvalidArgument;
+    }
+
+    let result = with_handle(handle, |state| match state.engine.hash_cache_stats() {
+        Ok(stats) => {
+            let last_scan = stats.last_scan.unwrap_or_default();
+            *out_stats = SdHashCacheStats {
+                entries: stats.entries,
+                disk_bytes: stats.disk_bytes,
+                last_scan_hits: last_scan.hits,
+                last_scan_misses: last_scan.misses,
+                has_last_scan: stats.last_scan.is_some() as u8,
+            };
+            SdResultCode::Ok
         }
+        Err(e) => map_core_error(e),
+    });
+    result.unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Evict hash cache entries for missing or changed files, then apply the limits.
+/// `max_age_secs` and `max_size_bytes` are ignored when 0. When `compact` is
+/// non-zero the cache is compacted afterwards.
+///
+/// # Safety
+/// `out_result` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn sd_evict_hash_cache(
+    handle: u64,
+    max_age_secs: u64,
+    max_size_bytes: u64,
+    compact: u8,
+    out_result: *mut SdCacheEvictionResult,
+) -> SdResultCode {
+    if out_result.is_null() {
+        set_last_error("out_result is null".to_string());
+        return SdResultCode::InvalidArgument;
     }
+
+    let policy = EvictionPolicy {
+        max_age: (max_age_secs > 0).then(|| Duration::from_secs(max_age_secs)),
+        max_size_bytes: (max_size_bytes > 0).then_some(max_size_bytes),
+    };
+    let result = with_handle(handle, |state| {
+        if state.is_scanning {
+            return SdResultCode::ScanInProgress;
+        }
+        match state.engine.evict_hash_cache(&policy, compact != 0) {
+            Ok(summary) => {
+                *out_result = SdCacheEvictionResult {
+                    entries_examined: summary.entries_examined,
+                    missing: summary.missing,
+                    superseded: summary.superseded,
+                    expired: summary.expired,
+                    over_size: summary.over_size,
+                    bytes_freed: summary.bytes_freed,
+                };
+                SdResultCode::Ok
+            }
+            Err(e) => map_core_error(e),
+        }
+    });
+    result.unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Compact this engine's hash cache to reclaim space left by deleted entries.
+#[no_mangle]
+pub extern "C" fn sd_compact_hash_cache(handle: u64) -> SdResultCode {
+    let result = with_handle(handle, |state| {
+        if state.is_scanning {
+            return SdResultCode::ScanInProgress;
+        }
+        match state.engine.compact_hash_cache() {
+            Ok(()) => SdResultCode::Ok,
+            Err(e) => map_core_error(e),
+        }
+    });
+    result.unwrap_or(SdResultCode::InvalidHandle)
 }
 
 /// Execute the deletion plan. Returns success/error counts via out parameters.
@@ -493,7 +1043,11 @@ pub unsafe extern "C" fn sd_deletion_execute(
                 return SdResultCode::DatabaseError;
             }
         };
-        match super_duper_core::analysis::deletion_plan::execute_deletion_plan(db, use_trash != 0) {
+        match super_duper_core::analysis::deletion_plan::execute_deletion_plan(
+            db,
+            use_trash != 0,
+            state.io_limits,
+        ) {
             Ok((success, errors)) => {
                 *out_result = SdDeletionResult {
                     success_count: success as u32,
@@ -62,4 +62,12 @@ impl ProgressReporter for FfiProgressBridge {
     fn on_dir_analysis_complete(&self, fingerprints: usize, similarity_pairs: usize, _duration_secs: f64) {
         self.fire(3, fingerprints as u64, similarity_pairs as u64, "dir_analysis_complete");
     }
+
+    fn on_scan_paused(&self) {
+        self.fire(4, 1, 0, "scan_paused");
+    }
+
+    fn on_scan_resumed(&self) {
+        self.fire(4, 0, 0, "scan_resumed");
+    }
 }
@@ -20,6 +20,8 @@ pub fn map_core_error(e: super_duper_core::Error) -> SdResultCode {
         super_duper_core::Error::Database(_) => SdResultCode::DatabaseError,
         super_duper_core::Error::Config(_) => SdResultCode::InvalidArgument,
         super_duper_core::Error::Cancelled => SdResultCode::Cancelled,
+        super_duper_core::Error::Protected { .. } => SdResultCode::Protected,
+        super_duper_core::Error::Resume(_) => SdResultCode::InvalidArgument,
         _ => SdResultCode::InternalError,
     }
 }
@@ -1,8 +1,11 @@
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use super_duper_core::ScanEngine;
+use super_duper_core::hasher::{HashAlgorithm, HashStats, IoLimits};
+use super_duper_core::IndexChanges;
+use super_duper_core::scanner::ScanFilter;
 use super_duper_core::storage::Database;
+use super_duper_core::{AppConfig, PauseToken, ScanEngine};
 
 static NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
 
@@ -12,6 +15,13 @@ pub struct EngineState {
     pub db_path: String,
     pub root_paths: Vec<String>,
     pub ignore_patterns: Vec<String>,
+    pub hash_algorithm: HashAlgorithm,
+    /// `None` uses `HASH_CACHE_PATH` or the default location.
+    pub hash_cache_path: Option<String>,
+    pub io_limits: IoLimits,
+    pub scan_filter: ScanFilter,
+    pub protected_paths: Vec<String>,
+    pub index_all_files: bool,
     pub is_scanning: bool,
     pub cancel_token: Arc<AtomicBool>,
     pub progress_bridge: Option<crate::callbacks::FfiProgressBridge>,
@@ -19,10 +29,43 @@ pub struct EngineState {
     /// Set to the most recent completed session on engine create,
     /// and updated after each successful scan.
     pub active_session_id: Option<i64>,
+    /// Hashing I/O of the last successful scan on this handle.
+    pub last_hash_stats: HashStats,
+    /// Index reconciliation of the last successful scan on this handle.
+    pub last_index_changes: IndexChanges,
+}
+
+impl EngineState {
+    /// Recreate the engine from the current settings (paths, patterns, algorithm,
+    /// cache, I/O limits, filter, protected paths, inventory).
+    pub fn rebuild_engine(&mut self) {
+        let config = AppConfig {
+            root_paths: self.root_paths.iter().map(|path| path.as_str().into()).collect(),
+            ignore_patterns: self.ignore_patterns.clone(),
+            hash_algorithm: self.hash_algorithm,
+            hash_cache_path: self.hash_cache_path.clone(),
+            io_limits: self.io_limits,
+            filter: self.scan_filter.clone(),
+            protected_paths: self.protected_paths.clone(),
+            index_all_files: self.index_all_files,
+            ..Default::default()
+        };
+        self.engine = ScanEngine::new(config).with_db_path(&self.db_path);
+        self.cancel_token = self.engine.cancel_token();
+    }
+}
+
+/// Cancel and pause tokens of a scan in progress.
+pub struct ScanControls {
+    pub cancel_token: Arc<AtomicBool>,
+    pub pause_token: Arc<PauseToken>,
 }
 
 lazy_static! {
     static ref HANDLES: Mutex<HashMap<u64, Box<EngineState>>> = Mutex::new(HashMap::new());
+    /// `with_handle` holds `HANDLES` for as long as a scan runs, so the scan's
+    /// controls are kept here for other threads to reach meanwhile.
+    static ref RUNNING_SCANS: Mutex<HashMap<u64, ScanControls>> = Mutex::new(HashMap::new());
 }
 
 pub fn allocate_handle(state: EngineState) -> u64 {
@@ -44,3 +87,20 @@ pub fn destroy_handle(handle: u64) -> bool {
     let mut handles = HANDLES.lock().unwrap();
     handles.remove(&handle).is_some()
 }
+
+pub fn register_running_scan(handle: u64, controls: ScanControls) {
+    RUNNING_SCANS.lock().unwrap().insert(handle, controls);
+}
+
+pub fn unregister_running_scan(handle: u64) {
+    RUNNING_SCANS.lock().unwrap().remove(&handle);
+}
+
+/// Run `f` on the controls of the scan running on `handle`, if there is one.
+/// Never waits for the scan.
+pub fn with_running_scan<F, R>(handle: u64, f: F) -> Option<R>
+where
+    F: FnOnce(&ScanControls) -> R,
+{
+    RUNNING_SCANS.lock().unwrap().get(&handle).map(f)
+}
@@ -1,6 +1,7 @@
 use crate::error::set_last_error;
 use crate::handle::with_handle;
 use crate::types::*;
+use std::ffi::c_char;
 
 /// Query duplicate groups with pagination.
 ///
@@ -144,6 +145,7 @@ pub unsafe extern "C" fn sd_query_files_in_group(
                 *out_page = SdFileRecordPage {
                     files: ptr,
                     count,
+                    total_available: count,
                 };
 
                 SdResultCode::Ok
@@ -158,10 +160,83 @@ pub unsafe extern "C" fn sd_query_files_in_group(
     result.unwrap_or(SdResultCode::InvalidHandle)
 }
 
-/// Free a file record page allocated by `sd_query_files_in_group`.
+/// Query a page of the files directly in `dir_path` that are in no duplicate group,
+/// largest first.
 ///
 /// # Safety
-/// `page` must have been returned by `sd_query_files_in_group`.
+/// `dir_path` must be a valid null-terminated C string and `out_page` a valid pointer.
+/// The returned page must be freed with `sd_free_file_record_page`.
+#[no_mangle]
+pub unsafe extern "C" fn sd_query_unique_files(
+    handle: u64,
+    dir_path: *const c_char,
+    offset: i64,
+    limit: i64,
+    out_page: *mut SdFileRecordPage,
+) -> SdResultCode {
+    if out_page.is_null() {
+        set_last_error("out_page is null".to_string());
+        return SdResultCode::InvalidArgument;
+    }
+    let dir_path = match c_string_to_rust(dir_path) {
+        Some(s) => s,
+        None => {
+            set_last_error("dir_path is null".to_string());
+            return SdResultCode::InvalidArgument;
+        }
+    };
+
+    let result = with_handle(handle, |state| {
+        let db = match &state.db {
+            Some(db) => db,
+            None => {
+                set_last_error("No database open".to_string());
+                return SdResultCode::DatabaseError;
+            }
+        };
+
+        match db.get_unique_files_in_directory(&dir_path, offset, limit) {
+            Ok((files, total)) => {
+                let count = files.len() as u32;
+                let c_files: Vec<SdFileRecord> = files
+                    .iter()
+                    .map(|f| SdFileRecord {
+                        id: f.id,
+                        canonical_path: rust_string_to_c(&f.canonical_path),
+                        file_name: rust_string_to_c(&f.file_name),
+                        parent_dir: rust_string_to_c(&f.parent_dir),
+                        file_size: f.file_size,
+                        content_hash: f.content_hash.unwrap_or(0),
+                        is_marked_for_deletion: 0,
+                    })
+                    .collect();
+
+                let boxed = c_files.into_boxed_slice();
+                let ptr = Box::into_raw(boxed) as *mut SdFileRecord;
+
+                *out_page = SdFileRecordPage {
+                    files: ptr,
+                    count,
+                    total_available: total as u32,
+                };
+
+                SdResultCode::Ok
+            }
+            Err(e) => {
+                set_last_error(format!("Query error: {}", e));
+                SdResultCode::DatabaseError
+            }
+        }
+    });
+
+    result.unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Free a file record page allocated by `sd_query_files_in_group` or
+/// `sd_query_unique_files`.
+///
+/// # Safety
+/// `page` must have been returned by `sd_query_files_in_group` or `sd_query_unique_files`.
 #[no_mangle]
 pub unsafe extern "C" fn sd_free_file_record_page(page: *mut SdFileRecordPage) {
     if page.is_null() {
@@ -220,6 +295,8 @@ pub unsafe extern "C" fn sd_query_directory_children(
                         total_size: n.total_size,
                         file_count: n.file_count,
                         depth: n.depth,
+                        duplicate_count: n.duplicate_count,
+                        duplicate_size: n.duplicate_size,
                     })
                     .collect();
 
@@ -442,5 +519,100 @@ pub unsafe extern "C" fn sd_free_session_page(page: *mut SdSessionPage) {
     }
 }
 
+/// Query the paths the active session couldn't examine, with pagination.
+///
+/// # Safety
+/// `out_page` must be a valid pointer. The returned page must be freed with `sd_free_scan_error_page`.
+#[no_mangle]
+pub unsafe extern "C" fn sd_query_scan_errors(
+    handle: u64,
+    offset: i64,
+    limit: i64,
+    out_page: *mut SdScanErrorPage,
+) -> SdResultCode {
+    if out_page.is_null() {
+        set_last_error("out_page is null".to_string());
+        return SdResultCode::InvalidArgument;
+    }
+
+    let result = with_handle(handle, |state| {
+        let session_id = match state.active_session_id {
+            Some(id) => id,
+            None => {
+                *out_page = SdScanErrorPage {
+                    errors: std::ptr::null_mut(),
+                    count: 0,
+                    total_available: 0,
+                };
+                return SdResultCode::Ok;
+            }
+        };
+
+        let db = match &state.db {
+            Some(db) => db,
+            None => {
+                set_last_error("No database open".to_string());
+                return SdResultCode::DatabaseError;
+            }
+        };
+
+        match db.get_scan_errors(session_id, offset, limit) {
+            Ok((errors, total)) => {
+                let count = errors.len() as u32;
+
+                let c_errors: Vec<SdScanError> = errors
+                    .iter()
+                    .map(|e| SdScanError {
+                        id: e.id,
+                        path: rust_string_to_c(&e.path),
+                        phase: rust_string_to_c(&e.phase),
+                        error_kind: rust_string_to_c(&e.error_kind),
+                        message: rust_string_to_c(&e.message),
+                    })
+                    .collect();
+
+                let boxed = c_errors.into_boxed_slice();
+                let ptr = Box::into_raw(boxed) as *mut SdScanError;
+
+                *out_page = SdScanErrorPage {
+                    errors: ptr,
+                    count,
+                    total_available: total as u32,
+                };
+
+                SdResultCode::Ok
+            }
+            Err(e) => {
+                set_last_error(format!("Query error: {}", e));
+                SdResultCode::DatabaseError
+            }
+        }
+    });
+
+    result.unwrap_or(SdResultCode::InvalidHandle)
+}
+
+/// Free a scan error page allocated by `sd_query_scan_errors`.
+///
+/// # Safety
+/// `page` must have been returned by `sd_query_scan_errors`.
+#[no_mangle]
+pub unsafe extern "C" fn sd_free_scan_error_page(page: *mut SdScanErrorPage) {
+    if page.is_null() {
+        return;
+    }
+    let page = &*page;
+    if !page.errors.is_null() && page.count > 0 {
+        let slice = std::slice::from_raw_parts_mut(page.errors, page.count as usize);
+        for error in slice.iter() {
+            sd_free_string(error.path);
+            sd_free_string(error.phase);
+            sd_free_string(error.error_kind);
+            sd_free_string(error.message);
+        }
+        drop(Box::from_raw(slice as *mut [SdScanError]));
+    }
+}
+
 // Re-export sd_free_string so it's accessible from this module
 use crate::error::sd_free_string;
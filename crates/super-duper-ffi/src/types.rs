@@ -1,5 +1,6 @@
 use std::ffi::{c_char, CStr, CString};
 use std::ptr;
+use super_duper_core::hasher::TierStats;
 
 /// Result codes returned by all FFI functions.
 #[repr(C)]
@@ -13,6 +14,8 @@ pub enum SdResultCode {
     ScanInProgress = 5,
     ScanNotRunning = 6,
     Cancelled = 7,
+    /// The file or directory is under a protected path.
+    Protected = 8,
     InternalError = 99,
 }
 
@@ -39,6 +42,7 @@ pub struct SdDuplicateGroup {
 pub struct SdFileRecordPage {
     pub files: *mut SdFileRecord,
     pub count: u32,
+    pub total_available: u32,
 }
 
 /// A single file record.
@@ -70,6 +74,9 @@ pub struct SdDirectoryNode {
     pub total_size: i64,
     pub file_count: i64,
     pub depth: i64,
+    /// Files at or below the directory in a duplicate group, and their size.
+    pub duplicate_count: i64,
+    pub duplicate_size: i64,
 }
 
 /// A page of directory similarity pairs.
@@ -114,6 +121,41 @@ pub struct SdSessionInfo {
     pub is_active: u8,
 }
 
+/// A page of scan errors.
+#[repr(C)]
+pub struct SdScanErrorPage {
+    pub errors: *mut SdScanError,
+    pub count: u32,
+    pub total_available: u32,
+}
+
+/// A path a scan couldn't examine.
+#[repr(C)]
+pub struct SdScanError {
+    pub id: i64,
+    pub path: *mut c_char,
+    pub phase: *mut c_char,      // walk, metadata, canonicalize, partial, sample or full
+    pub error_kind: *mut c_char, // e.g. "PermissionDenied"
+    pub message: *mut c_char,
+}
+
+/// Problems found in an engine's configuration.
+#[repr(C)]
+pub struct SdConfigIssuePage {
+    pub issues: *mut SdConfigIssue,
+    pub count: u32,
+    pub error_count: u32,
+}
+
+/// A configuration problem.
+#[repr(C)]
+pub struct SdConfigIssue {
+    pub is_error: u8,      // 0 for a warning
+    pub kind: *mut c_char, // e.g. "missing_root", "invalid_glob"
+    pub key: *mut c_char,  // e.g. "root_paths[1]"
+    pub message: *mut c_char,
+}
+
 /// Deletion execution result.
 #[repr(C)]
 pub struct SdDeletionResult {
@@ -121,9 +163,78 @@ pub struct SdDeletionResult {
     pub error_count: u32,
 }
 
+/// Work done by one hashing tier.
+#[repr(C)]
+pub struct SdHashTierStats {
+    pub files_hashed: u64,
+    pub files_eliminated: u64,
+    pub bytes_read: u64,
+}
+
+impl From<&TierStats> for SdHashTierStats {
+    fn from(stats: &TierStats) -> Self {
+        Self {
+            files_hashed: stats.files_hashed as u64,
+            files_eliminated: stats.files_eliminated as u64,
+            bytes_read: stats.bytes_read,
+        }
+    }
+}
+
+/// Hashing I/O of a scan, per tier.
+#[repr(C)]
+pub struct SdHashStats {
+    pub partial: SdHashTierStats,
+    pub sample: SdHashTierStats,
+    pub full: SdHashTierStats,
+    pub partial_hashes_reused: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+}
+
+/// Files a scan added to, changed in or removed from the file index.
+#[repr(C)]
+pub struct SdIndexChanges {
+    pub added: u64,
+    pub changed: u64,
+    pub removed: u64,
+}
+
+/// Scalar settings of a scan filter. Extension and glob lists are set separately.
+#[repr(C)]
+pub struct SdScanFilter {
+    pub min_size: u64,        // 0 includes empty files
+    pub max_size: u64,        // 0 for no limit
+    pub modified_after: i64,  // Unix seconds; 0 for no limit
+    pub modified_before: i64, // Unix seconds; 0 for no limit
+    pub include_hidden: u8,
+    pub include_system: u8,
+}
+
+/// Hash cache size and how it served the last completed scan.
+#[repr(C)]
+pub struct SdHashCacheStats {
+    pub entries: u64, // approximate
+    pub disk_bytes: u64,
+    pub last_scan_hits: u64,
+    pub last_scan_misses: u64,
+    pub has_last_scan: u8, // 0 if no scan has completed
+}
+
+/// Hash cache eviction result, by reason.
+#[repr(C)]
+pub struct SdCacheEvictionResult {
+    pub entries_examined: u64,
+    pub missing: u64,
+    pub superseded: u64,
+    pub expired: u64,
+    pub over_size: u64,
+    pub bytes_freed: u64,
+}
+
 /// Progress callback signature.
 pub type SdProgressCallback = extern "C" fn(
-    phase: u32,           // 0=scan, 1=hash, 2=db_write
+    phase: u32,           // 0=scan, 1=hash, 2=db_write, 3=dir_analysis, 4=paused (current = 1) or resumed
     current: u64,
     total: u64,
     message: *const c_char,
@@ -20,6 +20,10 @@ typedef enum SdResultCode {
     ScanInProgress = 5,
     ScanNotRunning = 6,
     Cancelled = 7,
+    /**
+     * The file or directory is under a protected path.
+     */
+    Protected = 8,
     InternalError = 99,
 } SdResultCode;
 
@@ -31,6 +35,49 @@ typedef struct SdDeletionResult {
     uint32_t error_count;
 } SdDeletionResult;
 
+/**
+ * Scalar settings of a scan filter. Extension and glob lists are set separately.
+ */
+typedef struct SdScanFilter {
+    uint64_t min_size;
+    uint64_t max_size;
+    int64_t modified_after;
+    int64_t modified_before;
+    uint8_t include_hidden;
+    uint8_t include_system;
+} SdScanFilter;
+
+/**
+ * A configuration problem.
+ */
+typedef struct SdConfigIssue {
+    uint8_t is_error;
+    char *kind;
+    char *key;
+    char *message;
+} SdConfigIssue;
+
+/**
+ * Problems found in an engine's configuration.
+ */
+typedef struct SdConfigIssuePage {
+    struct SdConfigIssue *issues;
+    uint32_t count;
+    uint32_t error_count;
+} SdConfigIssuePage;
+
+/**
+ * Hash cache eviction result, by reason.
+ */
+typedef struct SdCacheEvictionResult {
+    uint64_t entries_examined;
+    uint64_t missing;
+    uint64_t superseded;
+    uint64_t expired;
+    uint64_t over_size;
+    uint64_t bytes_freed;
+} SdCacheEvictionResult;
+
 /**
  * A single directory node.
  */
@@ -42,6 +89,11 @@ typedef struct SdDirectoryNode {
     int64_t total_size;
     int64_t file_count;
     int64_t depth;
+    /**
+     * Files at or below the directory in a duplicate group, and their size.
+     */
+    int64_t duplicate_count;
+    int64_t duplicate_size;
 } SdDirectoryNode;
 
 /**
@@ -113,8 +165,29 @@ typedef struct SdFileRecord {
 typedef struct SdFileRecordPage {
     struct SdFileRecord *files;
     uint32_t count;
+    uint32_t total_available;
 } SdFileRecordPage;
 
+/**
+ * A path a scan couldn't examine.
+ */
+typedef struct SdScanError {
+    int64_t id;
+    char *path;
+    char *phase;
+    char *error_kind;
+    char *message;
+} SdScanError;
+
+/**
+ * A page of scan errors.
+ */
+typedef struct SdScanErrorPage {
+    struct SdScanError *errors;
+    uint32_t count;
+    uint32_t total_available;
+} SdScanErrorPage;
+
 /**
  * A single scan session record.
  */
@@ -139,6 +212,47 @@ typedef struct SdSessionPage {
     uint32_t total_available;
 } SdSessionPage;
 
+/**
+ * Hash cache size and how it served the last completed scan.
+ */
+typedef struct SdHashCacheStats {
+    uint64_t entries;
+    uint64_t disk_bytes;
+    uint64_t last_scan_hits;
+    uint64_t last_scan_misses;
+    uint8_t has_last_scan;
+} SdHashCacheStats;
+
+/**
+ * Work done by one hashing tier.
+ */
+typedef struct SdHashTierStats {
+    uint64_t files_hashed;
+    uint64_t files_eliminated;
+    uint64_t bytes_read;
+} SdHashTierStats;
+
+/**
+ * Hashing I/O of a scan, per tier.
+ */
+typedef struct SdHashStats {
+    struct SdHashTierStats partial;
+    struct SdHashTierStats sample;
+    struct SdHashTierStats full;
+    uint64_t partial_hashes_reused;
+    uint64_t cache_hits;
+    uint64_t cache_misses;
+} SdHashStats;
+
+/**
+ * Files a scan added to, changed in or removed from the file index.
+ */
+typedef struct SdIndexChanges {
+    uint64_t added;
+    uint64_t changed;
+    uint64_t removed;
+} SdIndexChanges;
+
 /**
  * Progress callback signature.
  */
@@ -152,21 +266,27 @@ extern "C" {
 #endif // __cplusplus
 
 /**
- * Auto-mark duplicate files for deletion (keeps first alphabetically).
+ * Auto-mark duplicate files for deletion. Keeps a protected copy if there is one,
+ * otherwise the copy under the highest-priority root, then first alphabetically.
  */
 enum SdResultCode sd_auto_mark_for_deletion(uint64_t handle);
 
 /**
- * Clear all entries from the RocksDB hash cache.
+ * Clear all entries from this engine's hash cache.
  * Does not affect the SQLite database.
  */
-enum SdResultCode sd_clear_hash_cache(void);
+enum SdResultCode sd_clear_hash_cache(uint64_t handle);
 
 /**
  * Clear the progress callback.
  */
 enum SdResultCode sd_clear_progress_callback(uint64_t handle);
 
+/**
+ * Compact this engine's hash cache to reclaim space left by deleted entries.
+ */
+enum SdResultCode sd_compact_hash_cache(uint64_t handle);
+
 /**
  * Delete all session history and derived analysis results.
  * The scanned_file global index and hash cache are preserved.
@@ -216,6 +336,39 @@ uint64_t sd_engine_create(const char *db_path);
  */
 enum SdResultCode sd_engine_destroy(uint64_t handle);
 
+/**
+ * Limit scans to files with one of `include` extensions (any, if `include_count`
+ * is 0) and none of `exclude`. Case-insensitive; a leading dot is optional.
+ *
+ * # Safety
+ * `include` and `exclude` must be valid arrays of `include_count` and
+ * `exclude_count` null-terminated C strings.
+ */
+enum SdResultCode sd_engine_set_filter_extensions(uint64_t handle,
+                                                  const char *const *include,
+                                                  uint32_t include_count,
+                                                  const char *const *exclude,
+                                                  uint32_t exclude_count);
+
+/**
+ * Set the content hash algorithm used for full-file hashing.
+ * Accepts "xxh64", "xxh3-128", "blake3" or "sha256" (case-insensitive; "xxHash64" and
+ * "SHA-256" are also accepted).
+ *
+ * # Safety
+ * `algorithm` must be a valid null-terminated C string.
+ */
+enum SdResultCode sd_engine_set_hash_algorithm(uint64_t handle, const char *algorithm);
+
+/**
+ * Set the RocksDB hash cache location for this engine. Engines with different
+ * paths keep independent caches; the cache is opened on the next scan.
+ *
+ * # Safety
+ * `path` must be a valid null-terminated C string.
+ */
+enum SdResultCode sd_engine_set_hash_cache_path(uint64_t handle, const char *path);
+
 /**
  * Set ignore patterns for file scanning.
  *
@@ -226,6 +379,52 @@ enum SdResultCode sd_engine_set_ignore_patterns(uint64_t handle,
                                                 const char *const *patterns,
                                                 uint32_t count);
 
+/**
+ * Limit scans to files whose path matches one of `patterns` (any file, if
+ * `count` is 0).
+ *
+ * # Safety
+ * `patterns` must be a valid array of `count` null-terminated C strings.
+ */
+enum SdResultCode sd_engine_set_include_patterns(uint64_t handle,
+                                                 const char *const *patterns,
+                                                 uint32_t count);
+
+/**
+ * Index every scanned file (`enabled` = 1), not only those sharing a size with
+ * another file, so directory sizes and duplicated shares cover the whole tree.
+ */
+enum SdResultCode sd_engine_set_index_all_files(uint64_t handle, uint8_t enabled);
+
+/**
+ * Limit hash-phase reads to `max_reads_per_device` files at once on each device
+ * and `max_bytes_per_second` overall. 0 leaves a limit off.
+ */
+enum SdResultCode sd_engine_set_io_limits(uint64_t handle,
+                                          uint32_t max_reads_per_device,
+                                          uint64_t max_bytes_per_second);
+
+/**
+ * Protect `paths`: files at or below them can't be marked for deletion, are
+ * kept by auto-mark and are skipped when the plan is executed. Replaces the
+ * previous list.
+ *
+ * # Safety
+ * `paths` must be a valid array of `count` null-terminated C strings.
+ */
+enum SdResultCode sd_engine_set_protected_paths(uint64_t handle,
+                                                const char *const *paths,
+                                                uint32_t count);
+
+/**
+ * Set which files scans consider by size, modification time and attributes.
+ * The extension and include-pattern lists are kept.
+ *
+ * # Safety
+ * `filter` must point to a valid `SdScanFilter`.
+ */
+enum SdResultCode sd_engine_set_scan_filter(uint64_t handle, const struct SdScanFilter *filter);
+
 /**
  * Set the scan paths for an engine instance.
  *
@@ -236,6 +435,38 @@ enum SdResultCode sd_engine_set_scan_paths(uint64_t handle,
                                            const char *const *paths,
                                            uint32_t count);
 
+/**
+ * Check the engine's configuration for invalid globs, missing or unreadable
+ * roots, overlapping roots and other problems, before starting a scan.
+ *
+ * # Safety
+ * `out_page` must be a valid pointer. The returned page must be freed with `sd_free_config_issue_page`.
+ */
+enum SdResultCode sd_engine_validate_config(uint64_t handle,
+                                            struct SdConfigIssuePage *out_page);
+
+/**
+ * Evict hash cache entries for missing or changed files, then apply the limits.
+ * `max_age_secs` and `max_size_bytes` are ignored when 0. When `compact` is
+ * non-zero the cache is compacted afterwards.
+ *
+ * # Safety
+ * `out_result` must be a valid pointer.
+ */
+enum SdResultCode sd_evict_hash_cache(uint64_t handle,
+                                      uint64_t max_age_secs,
+                                      uint64_t max_size_bytes,
+                                      uint8_t compact,
+                                      struct SdCacheEvictionResult *out_result);
+
+/**
+ * Free a config issue page allocated by `sd_engine_validate_config`.
+ *
+ * # Safety
+ * `page` must have been returned by `sd_engine_validate_config`.
+ */
+void sd_free_config_issue_page(struct SdConfigIssuePage *page);
+
 /**
  * Free a directory node page allocated by `sd_query_directory_children`.
  *
@@ -261,13 +492,22 @@ void sd_free_directory_similarity_page(struct SdDirectorySimilarityPage *page);
 void sd_free_duplicate_group_page(struct SdDuplicateGroupPage *page);
 
 /**
- * Free a file record page allocated by `sd_query_files_in_group`.
+ * Free a file record page allocated by `sd_query_files_in_group` or
+ * `sd_query_unique_files`.
  *
  * # Safety
- * `page` must have been returned by `sd_query_files_in_group`.
+ * `page` must have been returned by `sd_query_files_in_group` or `sd_query_unique_files`.
  */
 void sd_free_file_record_page(struct SdFileRecordPage *page);
 
+/**
+ * Free a scan error page allocated by `sd_query_scan_errors`.
+ *
+ * # Safety
+ * `page` must have been returned by `sd_query_scan_errors`.
+ */
+void sd_free_scan_error_page(struct SdScanErrorPage *page);
+
 /**
  * Free a session page allocated by `sd_list_sessions`.
  *
@@ -284,6 +524,14 @@ void sd_free_session_page(struct SdSessionPage *page);
  */
 void sd_free_string(char *ptr);
 
+/**
+ * Report the hash cache's entry count, disk size and last-scan hit/miss counts.
+ *
+ * # Safety
+ * `out_stats` must be a valid pointer.
+ */
+enum SdResultCode sd_hash_cache_stats(uint64_t handle, struct SdHashCacheStats *out_stats);
+
 /**
  * Get the last error message. Returns a C string that must be freed with `sd_free_string`.
  *
@@ -305,7 +553,8 @@ enum SdResultCode sd_list_sessions(uint64_t handle,
                                    struct SdSessionPage *out_page);
 
 /**
- * Mark all files in a directory for deletion.
+ * Mark all files in a directory for deletion, except protected ones. Returns
+ * `Protected` if the directory itself is protected.
  *
  * # Safety
  * `directory_path` must be a valid null-terminated C string.
@@ -313,7 +562,7 @@ enum SdResultCode sd_list_sessions(uint64_t handle,
 enum SdResultCode sd_mark_directory_for_deletion(uint64_t handle, const char *directory_path);
 
 /**
- * Mark a file for deletion.
+ * Mark a file for deletion. Returns `Protected` if it is under a protected path.
  */
 enum SdResultCode sd_mark_file_for_deletion(uint64_t handle, int64_t file_id);
 
@@ -350,6 +599,17 @@ enum SdResultCode sd_query_files_in_group(uint64_t handle,
                                           int64_t group_id,
                                           struct SdFileRecordPage *out_page);
 
+/**
+ * Query the paths the active session couldn't examine, with pagination.
+ *
+ * # Safety
+ * `out_page` must be a valid pointer. The returned page must be freed with `sd_free_scan_error_page`.
+ */
+enum SdResultCode sd_query_scan_errors(uint64_t handle,
+                                       int64_t offset,
+                                       int64_t limit,
+                                       struct SdScanErrorPage *out_page);
+
 /**
  * Query similar directory pairs above a minimum score.
  *
@@ -363,15 +623,75 @@ enum SdResultCode sd_query_similar_directories(uint64_t handle,
                                                struct SdDirectorySimilarityPage *out_page);
 
 /**
- * Request cancellation of the current scan.
+ * Query a page of the files directly in `dir_path` that are in no duplicate group,
+ * largest first.
+ *
+ * # Safety
+ * `dir_path` must be a valid null-terminated C string and `out_page` a valid pointer.
+ * The returned page must be freed with `sd_free_file_record_page`.
+ */
+enum SdResultCode sd_query_unique_files(uint64_t handle,
+                                        const char *dir_path,
+                                        int64_t offset,
+                                        int64_t limit,
+                                        struct SdFileRecordPage *out_page);
+
+/**
+ * Request cancellation of the current scan. Also ends a paused scan.
  */
 enum SdResultCode sd_scan_cancel(uint64_t handle);
 
+/**
+ * Report files hashed, files eliminated and bytes read per tier, plus hash cache
+ * hits and misses, for the last successful scan on this handle. All zero until
+ * a scan completes.
+ *
+ * # Safety
+ * `out_stats` must be a valid pointer.
+ */
+enum SdResultCode sd_scan_hash_stats(uint64_t handle, struct SdHashStats *out_stats);
+
+/**
+ * Report files the last successful scan on this handle added to, changed in or
+ * removed from the file index. All zero until a scan completes.
+ *
+ * # Safety
+ * `out_changes` must be a valid pointer.
+ */
+enum SdResultCode sd_scan_index_changes(uint64_t handle, struct SdIndexChanges *out_changes);
+
+/**
+ * Check if the current scan is paused.
+ */
+bool sd_scan_is_paused(uint64_t handle);
+
 /**
  * Check if a scan is currently running.
  */
 bool sd_scan_is_running(uint64_t handle);
 
+/**
+ * Pause the current scan: its threads stop between files, keeping everything
+ * found so far, until `sd_scan_resume` or `sd_scan_cancel`. The progress
+ * callback reports phase 4 with current = 1 once they've stopped. Does nothing
+ * if no scan is running.
+ */
+enum SdResultCode sd_scan_pause(uint64_t handle);
+
+/**
+ * Resume a paused scan. The progress callback reports phase 4 with
+ * current = 0 as it carries on. Does nothing if the scan isn't paused.
+ */
+enum SdResultCode sd_scan_resume(uint64_t handle);
+
+/**
+ * Resume a cancelled or aborted scan session (blocking), skipping directories
+ * it listed and size buckets it hashed that haven't changed since. The engine's
+ * scan paths and settings must match the session's. Returns `InvalidArgument`
+ * if the session can't be resumed.
+ */
+enum SdResultCode sd_scan_resume_session(uint64_t handle, int64_t session_id);
+
 /**
  * Start a synchronous scan. Blocks until complete.
  */
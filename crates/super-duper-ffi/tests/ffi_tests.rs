@@ -1,9 +1,10 @@
 use std::ffi::{c_char, CStr, CString};
 use std::fs;
 use std::ptr;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use tempfile::tempdir;
 
+use super_duper_core::storage::Database;
 use super_duper_ffi::actions::*;
 use super_duper_ffi::error::*;
 use super_duper_ffi::queries::*;
@@ -17,7 +18,11 @@ fn c_str(s: &str) -> CString {
 
 fn create_engine(db_path: &str) -> u64 {
     let path = c_str(db_path);
-    unsafe { sd_engine_create(path.as_ptr()) }
+    let handle = unsafe { sd_engine_create(path.as_ptr()) };
+    // Keep each test's hash cache beside its database instead of the shared default
+    let cache_path = c_str(&format!("{}.hashcache", db_path));
+    unsafe { sd_engine_set_hash_cache_path(handle, cache_path.as_ptr()) };
+    handle
 }
 
 /// Create a temp directory tree with known duplicates for scanning.
@@ -146,6 +151,287 @@ fn test_set_ignore_patterns_null_with_nonzero_count() {
     sd_engine_destroy(handle);
 }
 
+// ── Validate config ──────────────────────────────────────────────────────────
+
+#[test]
+fn test_validate_config_reports_issues() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let handle = create_engine(db_path.to_str().unwrap());
+
+    let scan_dir = dir.path().join("data");
+    fs::create_dir(&scan_dir).unwrap();
+    let scan_path = c_str(scan_dir.to_str().unwrap());
+    let missing_path = c_str(dir.path().join("missing").to_str().unwrap());
+    let paths = [scan_path.as_ptr(), missing_path.as_ptr()];
+    unsafe { sd_engine_set_scan_paths(handle, paths.as_ptr(), 2) };
+    let bad_glob = c_str("[");
+    let patterns = [bad_glob.as_ptr()];
+    unsafe { sd_engine_set_ignore_patterns(handle, patterns.as_ptr(), 1) };
+
+    let mut page = SdConfigIssuePage {
+        issues: ptr::null_mut(),
+        count: 0,
+        error_count: 0,
+    };
+    let result = unsafe { sd_engine_validate_config(handle, &mut page) };
+    assert_eq!(result, SdResultCode::Ok);
+    assert_eq!(page.count, 2);
+    assert_eq!(page.error_count, 2);
+    let issues = unsafe { std::slice::from_raw_parts(page.issues, page.count as usize) };
+    let kinds: Vec<String> = issues
+        .iter()
+        .map(|issue| unsafe { CStr::from_ptr(issue.kind) }.to_str().unwrap().to_string())
+        .collect();
+    assert_eq!(kinds, ["invalid_glob", "missing_root"]);
+    let key = unsafe { CStr::from_ptr(issues[1].key) }.to_str().unwrap();
+    assert_eq!(key, "root_paths[1]");
+    unsafe { sd_free_config_issue_page(&mut page) };
+
+    // A valid configuration returns an empty page
+    unsafe { sd_engine_set_scan_paths(handle, paths.as_ptr(), 1) };
+    unsafe { sd_engine_set_ignore_patterns(handle, ptr::null(), 0) };
+    let result = unsafe { sd_engine_validate_config(handle, &mut page) };
+    assert_eq!(result, SdResultCode::Ok);
+    assert_eq!(page.count, 0);
+    assert!(page.issues.is_null());
+
+    let result = unsafe { sd_engine_validate_config(handle, ptr::null_mut()) };
+    assert_eq!(result, SdResultCode::InvalidArgument);
+
+    sd_engine_destroy(handle);
+}
+
+// ── Set hash algorithm ───────────────────────────────────────────────────────
+
+#[test]
+fn test_set_hash_algorithm() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let handle = create_engine(db_path.to_str().unwrap());
+
+    let algo = c_str("SHA-256");
+    let result = unsafe { sd_engine_set_hash_algorithm(handle, algo.as_ptr()) };
+    assert_eq!(result, SdResultCode::Ok);
+
+    let bad = c_str("md5");
+    let result = unsafe { sd_engine_set_hash_algorithm(handle, bad.as_ptr()) };
+    assert_eq!(result, SdResultCode::InvalidArgument);
+
+    sd_engine_destroy(handle);
+}
+
+// ── Hash cache ───────────────────────────────────────────────────────────────
+
+#[test]
+fn test_set_hash_cache_path() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let handle = create_engine(db_path.to_str().unwrap());
+
+    let cache_path = c_str(dir.path().join("other_cache").to_str().unwrap());
+    let result = unsafe { sd_engine_set_hash_cache_path(handle, cache_path.as_ptr()) };
+    assert_eq!(result, SdResultCode::Ok);
+
+    let result = unsafe { sd_engine_set_hash_cache_path(handle, ptr::null()) };
+    assert_eq!(result, SdResultCode::InvalidArgument);
+
+    let result = unsafe { sd_engine_set_hash_cache_path(999999, cache_path.as_ptr()) };
+    assert_eq!(result, SdResultCode::InvalidHandle);
+
+    sd_engine_destroy(handle);
+}
+
+#[test]
+fn test_set_io_limits_scan_still_finds_duplicates() {
+    let dir = tempdir().unwrap();
+    let scan_dir = dir.path().join("data");
+    let db_path = dir.path().join("test.db");
+    create_test_tree(&scan_dir);
+
+    let handle = create_engine(db_path.to_str().unwrap());
+    assert_eq!(sd_engine_set_io_limits(handle, 1, 1024 * 1024), SdResultCode::Ok);
+    assert_eq!(sd_engine_set_io_limits(999999, 1, 0), SdResultCode::InvalidHandle);
+
+    let scan_path_str = c_str(scan_dir.to_str().unwrap());
+    let paths = [scan_path_str.as_ptr()];
+    unsafe { sd_engine_set_scan_paths(handle, paths.as_ptr(), 1) };
+    assert_eq!(sd_scan_start(handle), SdResultCode::Ok);
+
+    let mut page = SdDuplicateGroupPage {
+        groups: ptr::null_mut(),
+        count: 0,
+        total_available: 0,
+    };
+    let result = unsafe { sd_query_duplicate_groups(handle, 0, 100, &mut page) };
+    assert_eq!(result, SdResultCode::Ok);
+    assert_eq!(page.total_available, 2);
+    unsafe { sd_free_duplicate_group_page(&mut page) };
+
+    sd_engine_destroy(handle);
+}
+
+#[test]
+fn test_scan_filter_limits_scanned_files() {
+    let dir = tempdir().unwrap();
+    let scan_dir = dir.path().join("data");
+    let db_path = dir.path().join("test.db");
+    create_test_tree(&scan_dir);
+
+    let handle = create_engine(db_path.to_str().unwrap());
+    let scan_path_str = c_str(scan_dir.to_str().unwrap());
+    let paths = [scan_path_str.as_ptr()];
+    unsafe { sd_engine_set_scan_paths(handle, paths.as_ptr(), 1) };
+    assert_eq!(
+        unsafe { sd_engine_set_scan_filter(handle, ptr::null()) },
+        SdResultCode::InvalidArgument
+    );
+
+    // Only large_dup.bin is big enough
+    let filter = SdScanFilter {
+        min_size: 1000,
+        max_size: 0,
+        modified_after: 0,
+        modified_before: 0,
+        include_hidden: 1,
+        include_system: 1,
+    };
+    assert_eq!(unsafe { sd_engine_set_scan_filter(handle, &filter) }, SdResultCode::Ok);
+    assert_eq!(sd_scan_start(handle), SdResultCode::Ok);
+    let mut page = SdDuplicateGroupPage {
+        groups: ptr::null_mut(),
+        count: 0,
+        total_available: 0,
+    };
+    unsafe { sd_query_duplicate_groups(handle, 0, 100, &mut page) };
+    assert_eq!(page.total_available, 1);
+    unsafe { sd_free_duplicate_group_page(&mut page) };
+
+    // The size filter is kept; excluding .bin leaves nothing
+    let bin = c_str("bin");
+    let exclude = [bin.as_ptr()];
+    let result = unsafe {
+        sd_engine_set_filter_extensions(handle, ptr::null(), 0, exclude.as_ptr(), 1)
+    };
+    assert_eq!(result, SdResultCode::Ok);
+    assert_eq!(sd_scan_start(handle), SdResultCode::Ok);
+    unsafe { sd_query_duplicate_groups(handle, 0, 100, &mut page) };
+    assert_eq!(page.total_available, 0);
+    unsafe { sd_free_duplicate_group_page(&mut page) };
+
+    sd_engine_destroy(handle);
+}
+
+#[test]
+fn test_query_scan_errors() {
+    let dir = tempdir().unwrap();
+    let scan_dir = dir.path().join("data");
+    let missing = dir.path().join("missing");
+    let db_path = dir.path().join("test.db");
+    create_test_tree(&scan_dir);
+
+    let handle = create_engine(db_path.to_str().unwrap());
+    let scan_path_str = c_str(scan_dir.to_str().unwrap());
+    let missing_str = c_str(missing.to_str().unwrap());
+    let paths = [scan_path_str.as_ptr(), missing_str.as_ptr()];
+    unsafe { sd_engine_set_scan_paths(handle, paths.as_ptr(), 2) };
+    assert_eq!(sd_scan_start(handle), SdResultCode::Ok);
+
+    let mut page = SdScanErrorPage {
+        errors: ptr::null_mut(),
+        count: 0,
+        total_available: 0,
+    };
+    let result = unsafe { sd_query_scan_errors(handle, 0, 100, &mut page) };
+    assert_eq!(result, SdResultCode::Ok);
+    assert_eq!(page.count, 1);
+    assert_eq!(page.total_available, 1);
+    let error = unsafe { &*page.errors };
+    let read = |p: *mut c_char| unsafe { CStr::from_ptr(p) }.to_str().unwrap().to_string();
+    assert_eq!(read(error.path), missing.to_str().unwrap());
+    assert_eq!(read(error.phase), "walk");
+    assert_eq!(read(error.error_kind), "NotFound");
+    unsafe { sd_free_scan_error_page(&mut page) };
+
+    let result = unsafe { sd_query_scan_errors(handle, 0, 100, ptr::null_mut()) };
+    assert_eq!(result, SdResultCode::InvalidArgument);
+    let result = unsafe { sd_query_scan_errors(999999, 0, 100, &mut page) };
+    assert_eq!(result, SdResultCode::InvalidHandle);
+
+    sd_engine_destroy(handle);
+}
+
+#[test]
+fn test_clear_hash_cache() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let handle = create_engine(db_path.to_str().unwrap());
+
+    assert_eq!(sd_clear_hash_cache(handle), SdResultCode::Ok);
+    assert_eq!(sd_clear_hash_cache(999999), SdResultCode::InvalidHandle);
+
+    sd_engine_destroy(handle);
+}
+
+#[test]
+fn test_hash_cache_stats_and_eviction() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let handle = create_engine(db_path.to_str().unwrap());
+
+    let mut stats = SdHashCacheStats {
+        entries: 0,
+        disk_bytes: 0,
+        last_scan_hits: 0,
+        last_scan_misses: 0,
+        has_last_scan: 1,
+    };
+    let result = unsafe { sd_hash_cache_stats(handle, &mut stats) };
+    assert_eq!(result, SdResultCode::Ok);
+    assert_eq!(stats.has_last_scan, 0);
+
+    let mut eviction = SdCacheEvictionResult {
+        entries_examined: 0,
+        missing: 0,
+        superseded: 0,
+        expired: 0,
+        over_size: 0,
+        bytes_freed: 0,
+    };
+    let result = unsafe { sd_evict_hash_cache(handle, 3600, 0, 1, &mut eviction) };
+    assert_eq!(result, SdResultCode::Ok);
+    assert_eq!(eviction.missing + eviction.superseded + eviction.expired, 0);
+
+    assert_eq!(sd_compact_hash_cache(handle), SdResultCode::Ok);
+
+    sd_engine_destroy(handle);
+}
+
+#[test]
+fn test_hash_cache_maintenance_null_and_invalid_handle() {
+    let dir = tempdir().unwrap();
+    let db_path = dir.path().join("test.db");
+    let handle = create_engine(db_path.to_str().unwrap());
+
+    let result = unsafe { sd_hash_cache_stats(handle, ptr::null_mut()) };
+    assert_eq!(result, SdResultCode::InvalidArgument);
+    let result = unsafe { sd_evict_hash_cache(handle, 0, 0, 0, ptr::null_mut()) };
+    assert_eq!(result, SdResultCode::InvalidArgument);
+
+    let mut stats = SdHashCacheStats {
+        entries: 0,
+        disk_bytes: 0,
+        last_scan_hits: 0,
+        last_scan_misses: 0,
+        has_last_scan: 1,
+    };
+    let result = unsafe { sd_hash_cache_stats(999999, &mut stats) };
+    assert_eq!(result, SdResultCode::InvalidHandle);
+    assert_eq!(sd_compact_hash_cache(999999), SdResultCode::InvalidHandle);
+
+    sd_engine_destroy(handle);
+}
+
 // ── Scan operations ──────────────────────────────────────────────────────────
 
 #[test]
@@ -182,6 +468,7 @@ fn test_scan_and_query_duplicates() {
     let mut file_page = SdFileRecordPage {
         files: ptr::null_mut(),
         count: 0,
+        total_available: 0,
     };
     let result = unsafe { sd_query_files_in_group(handle, first_group_id, &mut file_page) };
     assert_eq!(result, SdResultCode::Ok);
@@ -205,6 +492,119 @@ fn test_scan_and_query_duplicates() {
     sd_engine_destroy(handle);
 }
 
+#[test]
+fn test_scan_hash_stats() {
+    let dir = tempdir().unwrap();
+    let scan_dir = dir.path().join("data");
+    let db_path = dir.path().join("test.db");
+    create_test_tree(&scan_dir);
+
+    let handle = create_engine(db_path.to_str().unwrap());
+    let empty_tier = || SdHashTierStats {
+        files_hashed: 0,
+        files_eliminated: 0,
+        bytes_read: 0,
+    };
+    let mut stats = SdHashStats {
+        partial: empty_tier(),
+        sample: empty_tier(),
+        full: empty_tier(),
+        partial_hashes_reused: 0,
+        cache_hits: 0,
+        cache_misses: 0,
+    };
+
+    // Zero before any scan
+    let result = unsafe { sd_scan_hash_stats(handle, &mut stats) };
+    assert_eq!(result, SdResultCode::Ok);
+    assert_eq!(stats.partial.files_hashed, 0);
+
+    let scan_path_str = c_str(scan_dir.to_str().unwrap());
+    let paths = [scan_path_str.as_ptr()];
+    unsafe { sd_engine_set_scan_paths(handle, paths.as_ptr(), 1) };
+    assert_eq!(sd_scan_start(handle), SdResultCode::Ok);
+
+    let result = unsafe { sd_scan_hash_stats(handle, &mut stats) };
+    assert_eq!(result, SdResultCode::Ok);
+    assert!(stats.partial.files_hashed > 0);
+    assert!(stats.partial.bytes_read > 0);
+    assert!(stats.full.files_hashed >= 2);
+    assert_eq!(stats.cache_hits + stats.cache_misses, stats.full.files_hashed);
+
+    let result = unsafe { sd_scan_hash_stats(handle, ptr::null_mut()) };
+    assert_eq!(result, SdResultCode::InvalidArgument);
+    let result = unsafe { sd_scan_hash_stats(999999, &mut stats) };
+    assert_eq!(result, SdResultCode::InvalidHandle);
+
+    sd_engine_destroy(handle);
+}
+
+#[test]
+fn test_resume_session() {
+    let dir = tempdir().unwrap();
+    let scan_dir = dir.path().join("data");
+    let db_path = dir.path().join("test.db");
+    create_test_tree(&scan_dir);
+
+    // A session a crashed scan left running is marked aborted on create
+    let orphan = {
+        let db = Database::open(db_path.to_str().unwrap()).unwrap();
+        db.create_scan_session(&[scan_dir.to_string_lossy().into_owned()])
+            .unwrap()
+    };
+    let handle = create_engine(db_path.to_str().unwrap());
+    let db = Database::open(db_path.to_str().unwrap()).unwrap();
+    assert_eq!(db.get_session(orphan).unwrap().unwrap().status, "aborted");
+
+    let scan_path_str = c_str(scan_dir.to_str().unwrap());
+    let paths = [scan_path_str.as_ptr()];
+    unsafe { sd_engine_set_scan_paths(handle, paths.as_ptr(), 1) };
+
+    // It never recorded its settings, so it can't be resumed; nor can a missing one
+    assert_eq!(sd_scan_resume_session(handle, orphan), SdResultCode::InvalidArgument);
+    assert_eq!(sd_scan_resume_session(handle, 999), SdResultCode::InvalidArgument);
+    assert_eq!(sd_scan_resume_session(999999, orphan), SdResultCode::InvalidHandle);
+
+    sd_engine_destroy(handle);
+}
+
+#[test]
+fn test_scan_index_changes() {
+    let dir = tempdir().unwrap();
+    let scan_dir = dir.path().join("data");
+    let db_path = dir.path().join("test.db");
+    create_test_tree(&scan_dir);
+
+    let handle = create_engine(db_path.to_str().unwrap());
+    let mut changes = SdIndexChanges {
+        added: 0,
+        changed: 0,
+        removed: 0,
+    };
+
+    let scan_path_str = c_str(scan_dir.to_str().unwrap());
+    let paths = [scan_path_str.as_ptr()];
+    unsafe { sd_engine_set_scan_paths(handle, paths.as_ptr(), 1) };
+    assert_eq!(sd_scan_start(handle), SdResultCode::Ok);
+    let result = unsafe { sd_scan_index_changes(handle, &mut changes) };
+    assert_eq!(result, SdResultCode::Ok);
+    assert_eq!((changes.added, changes.changed, changes.removed), (6, 0, 0));
+
+    // A vanished file is purged on the next scan
+    fs::remove_file(scan_dir.join("folder_b").join("large_dup.bin")).unwrap();
+    assert_eq!(sd_scan_start(handle), SdResultCode::Ok);
+    let result = unsafe { sd_scan_index_changes(handle, &mut changes) };
+    assert_eq!(result, SdResultCode::Ok);
+    assert_eq!((changes.added, changes.changed, changes.removed), (0, 0, 1));
+
+    let result = unsafe { sd_scan_index_changes(handle, ptr::null_mut()) };
+    assert_eq!(result, SdResultCode::InvalidArgument);
+    let result = unsafe { sd_scan_index_changes(999999, &mut changes) };
+    assert_eq!(result, SdResultCode::InvalidHandle);
+
+    sd_engine_destroy(handle);
+}
+
 #[test]
 fn test_query_empty_database() {
     let dir = tempdir().unwrap();
@@ -282,6 +682,72 @@ fn test_progress_callback_fires() {
     sd_engine_destroy(handle);
 }
 
+static PAUSE_TEST_HANDLE: AtomicU64 = AtomicU64::new(0);
+static PAUSE_EVENTS: AtomicU32 = AtomicU32::new(0);
+static RESUME_EVENTS: AtomicU32 = AtomicU32::new(0);
+
+/// Pauses the scan as hashing starts and resumes it from another thread.
+extern "C" fn pause_on_hash_callback(phase: u32, current: u64, _total: u64, message: *const c_char) {
+    let handle = PAUSE_TEST_HANDLE.load(Ordering::SeqCst);
+    let message = unsafe { CStr::from_ptr(message) }.to_str().unwrap();
+    match (phase, current) {
+        (1, _) if message == "hash_start" => assert_eq!(sd_scan_pause(handle), SdResultCode::Ok),
+        (4, 1) => {
+            PAUSE_EVENTS.fetch_add(1, Ordering::SeqCst);
+            assert!(sd_scan_is_paused(handle));
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                assert_eq!(sd_scan_resume(handle), SdResultCode::Ok);
+            });
+        }
+        (4, 0) => {
+            RESUME_EVENTS.fetch_add(1, Ordering::SeqCst);
+        }
+        _ => {}
+    }
+}
+
+#[test]
+fn test_pause_and_resume_running_scan() {
+    let dir = tempdir().unwrap();
+    let scan_dir = dir.path().join("data");
+    let db_path = dir.path().join("test.db");
+    create_test_tree(&scan_dir);
+
+    let handle = create_engine(db_path.to_str().unwrap());
+    // Nothing to pause before a scan starts
+    assert_eq!(sd_scan_pause(handle), SdResultCode::Ok);
+    assert!(!sd_scan_is_paused(handle));
+
+    PAUSE_TEST_HANDLE.store(handle, Ordering::SeqCst);
+    sd_set_progress_callback(handle, pause_on_hash_callback);
+    let scan_path_str = c_str(scan_dir.to_str().unwrap());
+    let paths = [scan_path_str.as_ptr()];
+    unsafe { sd_engine_set_scan_paths(handle, paths.as_ptr(), 1) };
+    assert_eq!(sd_scan_start(handle), SdResultCode::Ok);
+
+    assert_eq!(PAUSE_EVENTS.load(Ordering::SeqCst), 1);
+    assert_eq!(RESUME_EVENTS.load(Ordering::SeqCst), 1);
+    assert!(!sd_scan_is_paused(handle));
+    let mut page = SdDuplicateGroupPage {
+        groups: ptr::null_mut(),
+        count: 0,
+        total_available: 0,
+    };
+    unsafe { sd_query_duplicate_groups(handle, 0, 100, &mut page) };
+    assert_eq!(page.total_available, 2);
+    unsafe { sd_free_duplicate_group_page(&mut page) };
+
+    sd_engine_destroy(handle);
+}
+
+#[test]
+fn test_scan_pause_invalid_handle() {
+    assert_eq!(sd_scan_pause(999999), SdResultCode::InvalidHandle);
+    assert_eq!(sd_scan_resume(999999), SdResultCode::InvalidHandle);
+    assert!(!sd_scan_is_paused(999999));
+}
+
 // ── Deletion operations ──────────────────────────────────────────────────────
 
 #[test]
@@ -312,6 +778,7 @@ fn test_mark_unmark_deletion() {
     let mut file_page = SdFileRecordPage {
         files: ptr::null_mut(),
         count: 0,
+        total_available: 0,
     };
     unsafe { sd_query_files_in_group(handle, group_id, &mut file_page) };
     assert!(file_page.count >= 2);
@@ -330,6 +797,7 @@ fn test_mark_unmark_deletion() {
     let mut file_page2 = SdFileRecordPage {
         files: ptr::null_mut(),
         count: 0,
+        total_available: 0,
     };
     unsafe { sd_query_files_in_group(handle, group_id, &mut file_page2) };
     let marked_file = (0..file_page2.count as usize)
@@ -346,6 +814,7 @@ fn test_mark_unmark_deletion() {
     let mut file_page3 = SdFileRecordPage {
         files: ptr::null_mut(),
         count: 0,
+        total_available: 0,
     };
     unsafe { sd_query_files_in_group(handle, group_id, &mut file_page3) };
     let unmarked_file = (0..file_page3.count as usize)
@@ -397,6 +866,66 @@ fn test_deletion_plan_summary() {
     sd_engine_destroy(handle);
 }
 
+#[test]
+fn test_protected_paths_kept_and_never_marked() {
+    let dir = tempdir().unwrap();
+    let scan_dir = dir.path().join("data");
+    let db_path = dir.path().join("test.db");
+    create_test_tree(&scan_dir);
+
+    let handle = create_engine(db_path.to_str().unwrap());
+    let scan_path_str = c_str(scan_dir.to_str().unwrap());
+    let paths = [scan_path_str.as_ptr()];
+    unsafe { sd_engine_set_scan_paths(handle, paths.as_ptr(), 1) };
+    sd_scan_start(handle);
+
+    let protected_dir = fs::canonicalize(scan_dir.join("folder_b")).unwrap();
+    let protected_str = c_str(protected_dir.to_str().unwrap());
+    let protected = [protected_str.as_ptr()];
+    let result = unsafe { sd_engine_set_protected_paths(handle, protected.as_ptr(), 1) };
+    assert_eq!(result, SdResultCode::Ok);
+
+    // The folder_b copy of each group is kept, though folder_a sorts first
+    assert_eq!(sd_auto_mark_for_deletion(handle), SdResultCode::Ok);
+    let (mut count, mut bytes) = (0i64, 0i64);
+    unsafe { sd_deletion_plan_summary(handle, &mut count, &mut bytes) };
+    assert_eq!(count, 2);
+    assert_eq!(bytes, 18 + 4096);
+
+    let mut page = SdDuplicateGroupPage {
+        groups: ptr::null_mut(),
+        count: 0,
+        total_available: 0,
+    };
+    unsafe { sd_query_duplicate_groups(handle, 0, 100, &mut page) };
+    let mut file_page = SdFileRecordPage {
+        files: ptr::null_mut(),
+        count: 0,
+        total_available: 0,
+    };
+    unsafe { sd_query_files_in_group(handle, (*page.groups).id, &mut file_page) };
+    let protected_file = (0..file_page.count as usize)
+        .map(|i| unsafe { &*file_page.files.add(i) })
+        .find(|file| {
+            let path = unsafe { CStr::from_ptr(file.canonical_path) }.to_str().unwrap();
+            path.starts_with(protected_dir.to_str().unwrap())
+        })
+        .unwrap();
+    assert_eq!(protected_file.is_marked_for_deletion, 0);
+    assert_eq!(
+        sd_mark_file_for_deletion(handle, protected_file.id),
+        SdResultCode::Protected
+    );
+    let result = unsafe { sd_mark_directory_for_deletion(handle, protected_str.as_ptr()) };
+    assert_eq!(result, SdResultCode::Protected);
+
+    unsafe {
+        sd_free_file_record_page(&mut file_page);
+        sd_free_duplicate_group_page(&mut page);
+    }
+    sd_engine_destroy(handle);
+}
+
 #[test]
 fn test_deletion_plan_summary_null_pointers() {
     let dir = tempdir().unwrap();
@@ -618,6 +1147,43 @@ fn test_query_similar_directories_empty() {
     sd_engine_destroy(handle);
 }
 
+#[test]
+fn test_index_all_files_lists_unique_files() {
+    let dir = tempdir().unwrap();
+    let scan_dir = dir.path().join("data");
+    let db_path = dir.path().join("test.db");
+    create_test_tree(&scan_dir);
+    // No other file has this size, so it's never read
+    fs::write(scan_dir.join("folder_a").join("lonely.dat"), "lonely").unwrap();
+
+    let handle = create_engine(db_path.to_str().unwrap());
+    let scan_path_str = c_str(scan_dir.to_str().unwrap());
+    let paths = [scan_path_str.as_ptr()];
+    unsafe { sd_engine_set_scan_paths(handle, paths.as_ptr(), 1) };
+    assert_eq!(sd_engine_set_index_all_files(handle, 1), SdResultCode::Ok);
+    assert_eq!(sd_scan_start(handle), SdResultCode::Ok);
+
+    let folder_a = fs::canonicalize(scan_dir.join("folder_a")).unwrap();
+    let folder_a = c_str(folder_a.to_str().unwrap());
+    let mut page = SdFileRecordPage {
+        files: ptr::null_mut(),
+        count: 0,
+        total_available: 0,
+    };
+    let result = unsafe { sd_query_unique_files(handle, folder_a.as_ptr(), 0, 1, &mut page) };
+    assert_eq!(result, SdResultCode::Ok);
+    assert_eq!(page.count, 1);
+    assert_eq!(page.total_available, 2, "unique_a.txt and lonely.dat");
+    let largest = unsafe { CStr::from_ptr((*page.files).file_name) };
+    assert_eq!(largest.to_str().unwrap(), "unique_a.txt");
+    unsafe { sd_free_file_record_page(&mut page) };
+
+    let result = unsafe { sd_query_unique_files(handle, ptr::null(), 0, 10, &mut page) };
+    assert_eq!(result, SdResultCode::InvalidArgument);
+
+    sd_engine_destroy(handle);
+}
+
 // ── Multiple handles ─────────────────────────────────────────────────────────
 
 #[test]
@@ -78,6 +78,136 @@ public sealed class EngineWrapper : IDisposable
         finally { FreeUtf8StringArray(handles); }
     }
 
+    public void SetHashAlgorithm(string algorithm)
+    {
+        ThrowIfDisposed();
+        var result = sd_engine_set_hash_algorithm(_handle, algorithm);
+        ThrowOnError(result, "SetHashAlgorithm");
+    }
+
+    public void SetHashCachePath(string path)
+    {
+        ThrowIfDisposed();
+        var result = sd_engine_set_hash_cache_path(_handle, path);
+        ThrowOnError(result, "SetHashCachePath");
+    }
+
+    /// <summary>Caps hash-phase reads; 0 leaves a limit off.</summary>
+    public void SetIoLimits(uint maxReadsPerDevice, ulong maxBytesPerSecond)
+    {
+        ThrowIfDisposed();
+        var result = sd_engine_set_io_limits(_handle, maxReadsPerDevice, maxBytesPerSecond);
+        ThrowOnError(result, "SetIoLimits");
+    }
+
+    /// <summary>
+    /// Indexes every scanned file, not only those sharing a size with another file, so
+    /// directory sizes and duplicated shares cover the whole tree.
+    /// </summary>
+    public void SetIndexAllFiles(bool enabled)
+    {
+        ThrowIfDisposed();
+        var result = sd_engine_set_index_all_files(_handle, (byte)(enabled ? 1 : 0));
+        ThrowOnError(result, "SetIndexAllFiles");
+    }
+
+    /// <summary>
+    /// Limits scans by size, modification time and attributes. <paramref name="maxSize"/> of 0
+    /// and null times leave those limits off.
+    /// </summary>
+    public void SetScanFilter(
+        ulong minSize = 1,
+        ulong maxSize = 0,
+        DateTimeOffset? modifiedAfter = null,
+        DateTimeOffset? modifiedBefore = null,
+        bool includeHidden = true,
+        bool includeSystem = true)
+    {
+        ThrowIfDisposed();
+        var filter = new SdScanFilter
+        {
+            MinSize = minSize,
+            MaxSize = maxSize,
+            ModifiedAfter = modifiedAfter?.ToUnixTimeSeconds() ?? 0,
+            ModifiedBefore = modifiedBefore?.ToUnixTimeSeconds() ?? 0,
+            IncludeHidden = (byte)(includeHidden ? 1 : 0),
+            IncludeSystem = (byte)(includeSystem ? 1 : 0),
+        };
+        var result = sd_engine_set_scan_filter(_handle, in filter);
+        ThrowOnError(result, "SetScanFilter");
+    }
+
+    public void SetFilterExtensions(string[] include, string[] exclude)
+    {
+        ThrowIfDisposed();
+        var (includePtrs, includeHandles) = MarshalUtf8StringArray(include);
+        var (excludePtrs, excludeHandles) = MarshalUtf8StringArray(exclude);
+        try
+        {
+            var result = sd_engine_set_filter_extensions(
+                _handle, includePtrs, (uint)includePtrs.Length, excludePtrs, (uint)excludePtrs.Length);
+            ThrowOnError(result, "SetFilterExtensions");
+        }
+        finally
+        {
+            FreeUtf8StringArray(includeHandles);
+            FreeUtf8StringArray(excludeHandles);
+        }
+    }
+
+    public void SetIncludePatterns(string[] patterns)
+    {
+        ThrowIfDisposed();
+        var (ptrs, handles) = MarshalUtf8StringArray(patterns);
+        try
+        {
+            var result = sd_engine_set_include_patterns(_handle, ptrs, (uint)ptrs.Length);
+            ThrowOnError(result, "SetIncludePatterns");
+        }
+        finally { FreeUtf8StringArray(handles); }
+    }
+
+    public void SetProtectedPaths(string[] paths)
+    {
+        ThrowIfDisposed();
+        var (ptrs, handles) = MarshalUtf8StringArray(paths);
+        try
+        {
+            var result = sd_engine_set_protected_paths(_handle, ptrs, (uint)ptrs.Length);
+            ThrowOnError(result, "SetProtectedPaths");
+        }
+        finally { FreeUtf8StringArray(handles); }
+    }
+
+    public List<ConfigIssueInfo> ValidateConfig()
+    {
+        ThrowIfDisposed();
+        var result = sd_engine_validate_config(_handle, out var page);
+        ThrowOnError(result, "ValidateConfig");
+
+        var issues = new List<ConfigIssueInfo>((int)page.Count);
+        try
+        {
+            for (int i = 0; i < page.Count; i++)
+            {
+                var ptr = page.Issues + i * Marshal.SizeOf<SdConfigIssue>();
+                var native = Marshal.PtrToStructure<SdConfigIssue>(ptr);
+                issues.Add(new ConfigIssueInfo
+                {
+                    IsError = native.IsError != 0,
+                    Kind = Marshal.PtrToStringUTF8(native.Kind) ?? "",
+                    Key = Marshal.PtrToStringUTF8(native.Key) ?? "",
+                    Message = Marshal.PtrToStringUTF8(native.Message) ?? "",
+                });
+            }
+        }
+        finally
+        {
+            sd_free_config_issue_page(ref page);
+        }
+
+        return issues;
+    }
 
     public void StartScan()
     {
@@ -86,6 +216,14 @@ public sealed class EngineWrapper : IDisposable
         ThrowOnError(result, "StartScan");
     }
 
+    /// <summary>Resume a cancelled or aborted session, reusing the work it checkpointed.</summary>
+    public void ResumeScanSession(long sessionId)
+    {
+        ThrowIfDisposed();
+        var result = sd_scan_resume_session(_handle, sessionId);
+        ThrowOnError(result, "ResumeScanSession");
+    }
+
     public void CancelScan()
     {
         ThrowIfDisposed();
@@ -93,6 +231,39 @@ public sealed class EngineWrapper : IDisposable
         ThrowOnError(result, "CancelScan");
     }
 
+    /// <summary>Stops the running scan's threads in place until <see cref="ResumeScan"/>.</summary>
+    public void PauseScan()
+    {
+        ThrowIfDisposed();
+        var result = sd_scan_pause(_handle);
+        ThrowOnError(result, "PauseScan");
+    }
+
+    public void ResumeScan()
+    {
+        ThrowIfDisposed();
+        var result = sd_scan_resume(_handle);
+        ThrowOnError(result, "ResumeScan");
+    }
+
+    /// <summary>Per-tier hashing I/O of the last successful scan; all zero before one completes.</summary>
+    public SdHashStats GetLastScanHashStats()
+    {
+        ThrowIfDisposed();
+        var result = sd_scan_hash_stats(_handle, out var stats);
+        ThrowOnError(result, "GetLastScanHashStats");
+        return stats;
+    }
+
+    /// <summary>Files the last successful scan added to, changed in or removed from the index.</summary>
+    public SdIndexChanges GetLastScanIndexChanges()
+    {
+        ThrowIfDisposed();
+        var result = sd_scan_index_changes(_handle, out var changes);
+        ThrowOnError(result, "GetLastScanIndexChanges");
+        return changes;
+    }
+
     private SdProgressCallback? _progressCallbackRef;
 
     public void SetProgressCallback(SdProgressCallback callback)
@@ -120,6 +291,15 @@ public sealed class EngineWrapper : IDisposable
         }
     }
 
+    public bool IsScanPaused
+    {
+        get
+        {
+            ThrowIfDisposed();
+            return sd_scan_is_paused(_handle);
+        }
+    }
+
     public (List<DuplicateGroupInfo> Groups, int TotalAvailable) QueryDuplicateGroups(
         long offset = 0, long limit = 100)
     {
@@ -187,6 +367,43 @@ public sealed class EngineWrapper : IDisposable
         return files;
     }
 
+    /// <summary>
+    /// Files directly in <paramref name="dirPath"/> that are in no duplicate group, largest first.
+    /// </summary>
+    public (List<FileInfo> Files, int TotalAvailable) QueryUniqueFiles(
+        string dirPath, long offset = 0, long limit = 100)
+    {
+        ThrowIfDisposed();
+
+        var result = sd_query_unique_files(_handle, dirPath, offset, limit, out var page);
+        ThrowOnError(result, "QueryUniqueFiles");
+
+        var files = new List<FileInfo>((int)page.Count);
+        try
+        {
+            for (int i = 0; i < page.Count; i++)
+            {
+                var ptr = page.Files + i * Marshal.SizeOf<SdFileRecord>();
+                var native = Marshal.PtrToStructure<SdFileRecord>(ptr);
+                files.Add(new FileInfo
+                {
+                    Id = native.Id,
+                    CanonicalPath = Marshal.PtrToStringUTF8(native.CanonicalPath) ?? "",
+                    FileName = Marshal.PtrToStringUTF8(native.FileName) ?? "",
+                    ParentDir = Marshal.PtrToStringUTF8(native.ParentDir) ?? "",
+                    FileSize = native.FileSize,
+                    ContentHash = native.ContentHash,
+                });
+            }
+        }
+        finally
+        {
+            sd_free_file_record_page(ref page);
+        }
+
+        return (files, (int)page.TotalAvailable);
+    }
+
     public void MarkForDeletion(long fileId)
     {
         ThrowIfDisposed();
@@ -224,6 +441,8 @@ public sealed class EngineWrapper : IDisposable
                     TotalSize = native.TotalSize,
                     FileCount = native.FileCount,
                     Depth = native.Depth,
+                    DuplicateCount = native.DuplicateCount,
+                    DuplicateSize = native.DuplicateSize,
                 });
             }
         }
@@ -329,6 +548,37 @@ public sealed class EngineWrapper : IDisposable
         return (sessions, (int)page.TotalAvailable);
     }
 
+    public (List<ScanErrorInfo> Errors, int TotalAvailable) QueryScanErrors(long offset = 0, long limit = 100)
+    {
+        ThrowIfDisposed();
+        var result = sd_query_scan_errors(_handle, offset, limit, out var page);
+        ThrowOnError(result, "QueryScanErrors");
+
+        var errors = new List<ScanErrorInfo>((int)page.Count);
+        try
+        {
+            for (int i = 0; i < page.Count; i++)
+            {
+                var ptr = page.Errors + i * Marshal.SizeOf<SdScanError>();
+                var native = Marshal.PtrToStructure<SdScanError>(ptr);
+                errors.Add(new ScanErrorInfo
+                {
+                    Id = native.Id,
+                    Path = Marshal.PtrToStringUTF8(native.Path) ?? "",
+                    Phase = Marshal.PtrToStringUTF8(native.Phase) ?? "",
+                    ErrorKind = Marshal.PtrToStringUTF8(native.ErrorKind) ?? "",
+                    Message = Marshal.PtrToStringUTF8(native.Message) ?? "",
+                });
+            }
+        }
+        finally
+        {
+            sd_free_scan_error_page(ref page);
+        }
+
+        return (errors, (int)page.TotalAvailable);
+    }
+
     public void SetActiveSession(long sessionId)
     {
         ThrowIfDisposed();
@@ -359,10 +609,39 @@ public sealed class EngineWrapper : IDisposable
 
     public void ClearHashCache()
     {
-        var result = sd_clear_hash_cache();
+        ThrowIfDisposed();
+        var result = sd_clear_hash_cache(_handle);
         ThrowOnError(result, "ClearHashCache");
     }
 
+    public (ulong Entries, ulong DiskBytes, ulong? LastScanHits, ulong? LastScanMisses) GetHashCacheStats()
+    {
+        ThrowIfDisposed();
+        var result = sd_hash_cache_stats(_handle, out var stats);
+        ThrowOnError(result, "GetHashCacheStats");
+        return stats.HasLastScan != 0
+            ? (stats.Entries, stats.DiskBytes, stats.LastScanHits, stats.LastScanMisses)
+            : (stats.Entries, stats.DiskBytes, null, null);
+    }
+
+    public SdCacheEvictionResult EvictHashCache(
+        TimeSpan? maxAge = null, ulong maxSizeBytes = 0, bool compact = false)
+    {
+        ThrowIfDisposed();
+        var maxAgeSecs = maxAge.HasValue ? (ulong)maxAge.Value.TotalSeconds : 0;
+        var result = sd_evict_hash_cache(
+            _handle, maxAgeSecs, maxSizeBytes, compact ? (byte)1 : (byte)0, out var eviction);
+        ThrowOnError(result, "EvictHashCache");
+        return eviction;
+    }
+
+    public void CompactHashCache()
+    {
+        ThrowIfDisposed();
+        var result = sd_compact_hash_cache(_handle);
+        ThrowOnError(result, "CompactHashCache");
+    }
+
     public (long FileCount, long TotalBytes) GetDeletionPlanSummary()
     {
         ThrowIfDisposed();
@@ -440,6 +719,9 @@ public class DirectoryNodeInfo
     public long TotalSize { get; set; }
     public long FileCount { get; set; }
     public long Depth { get; set; }
+    public long DuplicateCount { get; set; }
+    public long DuplicateSize { get; set; }
+    public double DuplicatedPercent => TotalSize == 0 ? 0 : DuplicateSize * 100.0 / TotalSize;
 }
 
 public class SessionInfo
@@ -455,6 +737,23 @@ public class SessionInfo
     public bool IsActive { get; set; }
 }
 
+public class ScanErrorInfo
+{
+    public long Id { get; set; }
+    public string Path { get; set; } = "";
+    public string Phase { get; set; } = "";
+    public string ErrorKind { get; set; } = "";
+    public string Message { get; set; } = "";
+}
+
+public class ConfigIssueInfo
+{
+    public bool IsError { get; set; }
+    public string Kind { get; set; } = "";
+    public string Key { get; set; } = "";
+    public string Message { get; set; } = "";
+}
+
 public class DirectorySimilarityInfo
 {
     public long Id { get; set; }
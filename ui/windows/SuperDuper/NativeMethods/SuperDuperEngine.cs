@@ -23,6 +23,7 @@ public static partial class SuperDuperEngine
         ScanInProgress = 5,
         ScanNotRunning = 6,
         Cancelled = 7,
+        Protected = 8,
         InternalError = 99,
     }
 
@@ -63,6 +64,7 @@ public static partial class SuperDuperEngine
     {
         public IntPtr Files;
         public uint Count;
+        public uint TotalAvailable;
     }
 
     [StructLayout(LayoutKind.Sequential)]
@@ -75,6 +77,8 @@ public static partial class SuperDuperEngine
         public long TotalSize;
         public long FileCount;
         public long Depth;
+        public long DuplicateCount;
+        public long DuplicateSize;
     }
 
     [StructLayout(LayoutKind.Sequential)]
@@ -111,6 +115,65 @@ public static partial class SuperDuperEngine
         public uint ErrorCount;
     }
 
+    [StructLayout(LayoutKind.Sequential)]
+    public struct SdHashTierStats
+    {
+        public ulong FilesHashed;
+        public ulong FilesEliminated;
+        public ulong BytesRead;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public struct SdHashStats
+    {
+        public SdHashTierStats Partial;
+        public SdHashTierStats Sample;
+        public SdHashTierStats Full;
+        public ulong PartialHashesReused;
+        public ulong CacheHits;
+        public ulong CacheMisses;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public struct SdIndexChanges
+    {
+        public ulong Added;
+        public ulong Changed;
+        public ulong Removed;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public struct SdScanFilter
+    {
+        public ulong MinSize;          // 0 includes empty files
+        public ulong MaxSize;          // 0 for no limit
+        public long ModifiedAfter;     // Unix seconds; 0 for no limit
+        public long ModifiedBefore;    // Unix seconds; 0 for no limit
+        public byte IncludeHidden;
+        public byte IncludeSystem;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public struct SdHashCacheStats
+    {
+        public ulong Entries;        // approximate
+        public ulong DiskBytes;
+        public ulong LastScanHits;
+        public ulong LastScanMisses;
+        public byte HasLastScan;     // 0 if no scan has completed
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public struct SdCacheEvictionResult
+    {
+        public ulong EntriesExamined;
+        public ulong Missing;
+        public ulong Superseded;
+        public ulong Expired;
+        public ulong OverSize;
+        public ulong BytesFreed;
+    }
+
     [StructLayout(LayoutKind.Sequential)]
     public struct SdSessionInfo
     {
@@ -133,11 +196,46 @@ public static partial class SuperDuperEngine
         public uint TotalAvailable;
     }
 
+    [StructLayout(LayoutKind.Sequential)]
+    public struct SdScanError
+    {
+        public long Id;
+        public IntPtr Path;
+        public IntPtr Phase;       // walk, metadata, canonicalize, partial, sample or full
+        public IntPtr ErrorKind;   // e.g. "PermissionDenied"
+        public IntPtr Message;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public struct SdScanErrorPage
+    {
+        public IntPtr Errors;
+        public uint Count;
+        public uint TotalAvailable;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public struct SdConfigIssue
+    {
+        public byte IsError;       // 0 for a warning
+        public IntPtr Kind;        // e.g. "missing_root", "invalid_glob"
+        public IntPtr Key;         // e.g. "root_paths[1]"
+        public IntPtr Message;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    public struct SdConfigIssuePage
+    {
+        public IntPtr Issues;
+        public uint Count;
+        public uint ErrorCount;
+    }
+
     // ── Callbacks ────────────────────────────────────────────────
 
     [UnmanagedFunctionPointer(CallingConvention.Cdecl)]
     public delegate void SdProgressCallback(
-        uint phase,        // 0=scan, 1=hash, 2=db_write
+        uint phase,        // 0=scan, 1=hash, 2=db_write, 3=dir_analysis, 4=paused (current = 1) or resumed
         ulong current,
         ulong total,
         IntPtr message     // const char*
@@ -164,11 +262,65 @@ public static partial class SuperDuperEngine
         IntPtr[] patterns,
         uint count);
 
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_engine_set_hash_algorithm(
+        ulong handle,
+        [MarshalAs(UnmanagedType.LPUTF8Str)] string algorithm);
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_engine_set_hash_cache_path(
+        ulong handle,
+        [MarshalAs(UnmanagedType.LPUTF8Str)] string path);
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_engine_set_io_limits(
+        ulong handle,
+        uint maxReadsPerDevice,
+        ulong maxBytesPerSecond);
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_engine_set_index_all_files(ulong handle, byte enabled);
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_engine_set_scan_filter(
+        ulong handle,
+        in SdScanFilter filter);
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_engine_set_filter_extensions(
+        ulong handle,
+        IntPtr[] include,
+        uint includeCount,
+        IntPtr[] exclude,
+        uint excludeCount);
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_engine_set_include_patterns(
+        ulong handle,
+        IntPtr[] patterns,
+        uint count);
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_engine_set_protected_paths(
+        ulong handle,
+        IntPtr[] paths,
+        uint count);
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_engine_validate_config(
+        ulong handle, out SdConfigIssuePage page);
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern void sd_free_config_issue_page(ref SdConfigIssuePage page);
+
     // ── Scan Operations ──────────────────────────────────────────
 
     [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
     public static extern SdResultCode sd_scan_start(ulong handle);
 
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_scan_resume_session(ulong handle, long sessionId);
+
     [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
     [return: MarshalAs(UnmanagedType.U1)]
     public static extern bool sd_scan_is_running(ulong handle);
@@ -178,6 +330,24 @@ public static partial class SuperDuperEngine
     [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
     public static extern SdResultCode sd_scan_cancel(ulong handle);
 
+    // ── Scan Pause ──────────────────────────────────────────────
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_scan_pause(ulong handle);
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_scan_resume(ulong handle);
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    [return: MarshalAs(UnmanagedType.U1)]
+    public static extern bool sd_scan_is_paused(ulong handle);
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_scan_hash_stats(ulong handle, out SdHashStats stats);
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_scan_index_changes(ulong handle, out SdIndexChanges changes);
+
     // ── Progress Callback ──────────────────────────────────────
 
     [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
@@ -212,6 +382,14 @@ public static partial class SuperDuperEngine
         long limit,
         out SdDirectoryNodePage page);
 
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_query_unique_files(
+        ulong handle,
+        [MarshalAs(UnmanagedType.LPUTF8Str)] string dirPath,
+        long offset,
+        long limit,
+        out SdFileRecordPage page);
+
     [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
     public static extern SdResultCode sd_query_similar_directories(
         ulong handle,
@@ -285,6 +463,13 @@ public static partial class SuperDuperEngine
     [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
     public static extern void sd_free_session_page(ref SdSessionPage page);
 
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_query_scan_errors(
+        ulong handle, long offset, long limit, out SdScanErrorPage page);
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern void sd_free_scan_error_page(ref SdScanErrorPage page);
+
     // ── Database Management ──────────────────────────────────────
 
     [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
@@ -294,7 +479,18 @@ public static partial class SuperDuperEngine
     public static extern SdResultCode sd_delete_all_sessions(ulong handle);
 
     [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
-    public static extern SdResultCode sd_clear_hash_cache();
+    public static extern SdResultCode sd_clear_hash_cache(ulong handle);
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_hash_cache_stats(ulong handle, out SdHashCacheStats stats);
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_evict_hash_cache(
+        ulong handle, ulong maxAgeSecs, ulong maxSizeBytes, byte compact,
+        out SdCacheEvictionResult result);
+
+    [DllImport(DllName, CallingConvention = CallingConvention.Cdecl)]
+    public static extern SdResultCode sd_compact_hash_cache(ulong handle);
 
     // ── Helpers ──────────────────────────────────────────────────
 
@@ -93,6 +93,9 @@ public partial class ScanService : ObservableObject
     [ObservableProperty]
     public partial string CurrentFilePath { get; set; } = "";
 
+    [ObservableProperty]
+    public partial bool IsScanPaused { get; set; }
+
     // ── Events ─────────────────────────────────────────────────────────
 
     public event EventHandler? ScanCompleted;
@@ -100,7 +103,7 @@ public partial class ScanService : ObservableObject
 
     // ── Scan execution ─────────────────────────────────────────────────
 
-    public async Task StartScanAsync(string[] paths, string[] ignorePatterns)
+    public async Task StartScanAsync(string[] paths, string[] ignorePatterns, string? hashAlgorithm = null)
     {
         if (IsScanning || paths.Length == 0) return;
 
@@ -113,6 +116,14 @@ public partial class ScanService : ObservableObject
 
             _engine.SetProgressCallback((phase, current, total, messagePtr) =>
             {
+                // Pausing leaves the phase and counts as they were
+                if (phase == 4)
+                {
+                    var paused = current == 1;
+                    DispatcherQueue?.TryEnqueue(() => IsScanPaused = paused);
+                    return;
+                }
+
                 string phaseLabel;
                 double max = 1, value = 0;
                 bool indeterminate = true;
@@ -163,6 +174,8 @@ public partial class ScanService : ObservableObject
                 _engine.SetScanPaths(paths);
                 if (ignorePatterns.Length > 0)
                     _engine.SetIgnorePatterns(ignorePatterns);
+                if (!string.IsNullOrEmpty(hashAlgorithm))
+                    _engine.SetHashAlgorithm(hashAlgorithm);
                 _engine.StartScan();
             });
 
@@ -186,6 +199,7 @@ public partial class ScanService : ObservableObject
         {
             _engine.ClearProgressCallback();
             IsScanning = false;
+            IsScanPaused = false;
             ScanPhaseLabel = "";
             ScanCountLabel = "";
             ScanProgressIndeterminate = true;
@@ -207,4 +221,8 @@ public partial class ScanService : ObservableObject
     }
 
     public void CancelScan() => _engine.CancelScan();
+
+    public void PauseScan() => _engine.PauseScan();
+
+    public void ResumeScan() => _engine.ResumeScan();
 }
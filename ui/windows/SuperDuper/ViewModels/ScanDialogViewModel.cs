@@ -93,7 +93,10 @@ public partial class ScanDialogViewModel : ObservableObject
     {
         var paths = ScanPaths.ToArray();
         var patterns = IgnorePatterns.ToArray();
-        // TODO: Apply advanced options (minSize, hashAlgo, threads) to engine when FFI supports them
-        await ScanService.StartScanAsync(paths, patterns);
+        // TODO: Apply CpuThreads to engine when FFI supports it
+        _engine.SetScanFilter(
+            minSize: (ulong)Math.Max(1, MinFileSize),
+            includeHidden: IncludeHiddenFiles);
+        await ScanService.StartScanAsync(paths, patterns, SelectedHashAlgorithm);
     }
 }